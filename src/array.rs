@@ -0,0 +1,476 @@
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{bool_expr, keyword_args, Evaluator, ExprEnv};
+use crate::list::index_arg;
+use crate::math::integer_expr;
+use crate::seq;
+use std::{cell::RefCell, rc::Rc};
+
+pub struct Array {
+    pub dimensions: Vec<usize>,
+    // in row-major order
+    pub elements: Vec<Expr>,
+    pub fill_pointer: Option<usize>,
+    pub adjustable: bool,
+}
+
+pub type ArrayRef = Rc<RefCell<Array>>;
+
+// the most elements an array may hold, as ARRAY-TOTAL-SIZE-LIMIT
+const TOTAL_SIZE_LIMIT: usize = 1 << 24;
+
+// the number of elements of an array with these dimensions; zero
+// dimensions count as one in the limit so that the size of every
+// subarray fits too
+fn total_size(dimensions: &[usize]) -> Result<usize, ExprErr> {
+    let limit = dimensions.iter().try_fold(1usize, |size, dimension| {
+        size.checked_mul((*dimension).max(1))
+    });
+    match limit {
+        Some(limit) if limit <= TOTAL_SIZE_LIMIT => Ok(dimensions.iter().product()),
+        _ => Err(ExprErr::Cause(format!(
+            "array dimensions ({}) exceed the total size limit {}",
+            dimensions
+                .iter()
+                .map(|dimension| dimension.to_string())
+                .collect::<Vec<String>>()
+                .join(" "),
+            TOTAL_SIZE_LIMIT
+        ))),
+    }
+}
+
+impl Array {
+    // a simple one-dimensional array
+    pub fn vector(elements: Vec<Expr>) -> Expr {
+        Expr::Array(Rc::new(RefCell::new(Array {
+            dimensions: vec![elements.len()],
+            elements,
+            fill_pointer: None,
+            adjustable: false,
+        })))
+    }
+
+    // the elements before the fill pointer
+    pub fn active(&self) -> &[Expr] {
+        match self.fill_pointer {
+            Some(fill_pointer) => &self.elements[..fill_pointer],
+            None => &self.elements,
+        }
+    }
+
+    pub fn row_major_index(&self, subscripts: &[Expr]) -> Result<usize, ExprErr> {
+        if subscripts.len() != self.dimensions.len() {
+            return Err(ExprErr::Cause(format!(
+                "wrong number of subscripts for {}: {}",
+                self,
+                subscripts.len()
+            )));
+        }
+        let mut index = 0;
+        for (subscript, dimension) in subscripts.iter().zip(&self.dimensions) {
+            let subscript = index_arg(subscript)?;
+            if subscript >= *dimension {
+                return Err(ExprErr::Cause(format!(
+                    "index {} is out of bounds for {}",
+                    subscript, self
+                )));
+            }
+            index = index * dimension + subscript;
+        }
+        Ok(index)
+    }
+
+    fn fill_pointer(&self) -> Result<usize, ExprErr> {
+        self.fill_pointer
+            .ok_or(ExprErr::Cause(format!("{} has no fill pointer", self)))
+    }
+}
+
+// #(1 2 3), #2A((1 2) (3 4)) or #0A1
+impl Array {
+    // the printed form with each element printed by `print`
+    pub fn print(&self, print: &dyn Fn(&Expr) -> String) -> String {
+        match self.dimensions.len() {
+            0 => format!("#0A{}", print(&self.elements[0])),
+            1 => format!("#{}", nested(&self.dimensions, self.active(), print)),
+            rank => format!(
+                "#{}A{}",
+                rank,
+                nested(&self.dimensions, &self.elements, print)
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for Array {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.print(&|x| x.to_string()))
+    }
+}
+
+impl std::fmt::Debug for Array {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+fn nested(dimensions: &[usize], elements: &[Expr], print: &dyn Fn(&Expr) -> String) -> String {
+    let items = match dimensions.split_first() {
+        Some((_, [])) => elements.iter().map(print).collect(),
+        Some((count, rest)) => {
+            let size = rest.iter().product::<usize>();
+            (0..*count)
+                .map(|i| nested(rest, &elements[i * size..(i + 1) * size], print))
+                .collect()
+        }
+        None => vec![],
+    };
+    format!("({})", items.join(" "))
+}
+
+pub fn array_arg(expr: &Expr) -> Result<ArrayRef, ExprErr> {
+    match expr {
+        Expr::Array(array) => Ok(array.clone()),
+        _ => Err(ExprErr::Cause(format!("{} is not array", expr))),
+    }
+}
+
+fn vector_arg(expr: &Expr) -> Result<ArrayRef, ExprErr> {
+    match expr {
+        Expr::Array(array) if array.borrow().dimensions.len() == 1 => Ok(array.clone()),
+        _ => Err(ExprErr::Cause(format!("{} is not vector", expr))),
+    }
+}
+
+// flatten nested sequences of contents into row-major order
+fn flatten_contents(
+    contents: &Expr,
+    dimensions: &[usize],
+    elements: &mut Vec<Expr>,
+) -> Result<(), ExprErr> {
+    let Some((count, rest)) = dimensions.split_first() else {
+        elements.push(contents.clone());
+        return Ok(());
+    };
+    let (_, items) = seq::elements(contents)?;
+    if items.len() != *count {
+        return Err(ExprErr::Cause(format!(
+            "{} does not match the array dimensions",
+            contents
+        )));
+    }
+    for item in &items {
+        flatten_contents(item, rest, elements)?;
+    }
+    Ok(())
+}
+
+// #nA contents: the dimensions are the lengths of the first elements
+pub fn read_array(rank: usize, contents: &Expr) -> Result<Expr, ExprErr> {
+    let mut dimensions = vec![];
+    let mut item = contents.clone();
+    for _ in 0..rank {
+        let (_, items) = seq::elements(&item)?;
+        dimensions.push(items.len());
+        item = items.first().cloned().unwrap_or(Expr::Nil);
+    }
+    let mut elements = vec![];
+    flatten_contents(contents, &dimensions, &mut elements)?;
+    Ok(Expr::Array(Rc::new(RefCell::new(Array {
+        dimensions,
+        elements,
+        fill_pointer: None,
+        adjustable: false,
+    }))))
+}
+
+// (make-array dimensions &key initial-element initial-contents adjustable
+// fill-pointer element-type)
+pub fn make_array(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (dimensions, options) = args
+        .split_first()
+        .ok_or(ExprErr::Cause("make-array expects dimensions".to_string()))?;
+    let options = keyword_args(
+        "make-array",
+        options,
+        &[
+            "INITIAL-ELEMENT",
+            "INITIAL-CONTENTS",
+            "ADJUSTABLE",
+            "FILL-POINTER",
+            "ELEMENT-TYPE",
+        ],
+    )?;
+    let dimensions = match dimensions {
+        Expr::Number(_) => vec![index_arg(dimensions)?],
+        _ => dimensions
+            .to_vec()?
+            .iter()
+            .map(index_arg)
+            .collect::<Result<Vec<usize>, ExprErr>>()?,
+    };
+    let size = total_size(&dimensions)?;
+
+    let elements = match (
+        options.get("INITIAL-ELEMENT"),
+        options.get("INITIAL-CONTENTS"),
+    ) {
+        (Some(_), Some(_)) => {
+            return Err(ExprErr::Cause(
+                "cannot supply both :initial-element and :initial-contents".to_string(),
+            ))
+        }
+        (_, Some(contents)) => {
+            let mut elements = vec![];
+            flatten_contents(contents, &dimensions, &mut elements)?;
+            elements
+        }
+        (element, None) => vec![element.cloned().cloned().unwrap_or(Expr::Nil); size],
+    };
+    let fill_pointer = match options.get("FILL-POINTER") {
+        None | Some(Expr::Nil) => None,
+        Some(_) if dimensions.len() != 1 => {
+            return Err(ExprErr::Cause(
+                "fill pointer requires a one-dimensional array".to_string(),
+            ))
+        }
+        Some(Expr::True) => Some(size),
+        Some(fill_pointer) => match index_arg(fill_pointer)? {
+            fill_pointer if fill_pointer <= size => Some(fill_pointer),
+            _ => {
+                return Err(ExprErr::Cause(format!(
+                    "fill pointer {} is larger than the array",
+                    fill_pointer
+                )))
+            }
+        },
+    };
+    Ok(Expr::Array(Rc::new(RefCell::new(Array {
+        dimensions,
+        elements,
+        fill_pointer,
+        adjustable: options.get("ADJUSTABLE").is_some_and(|x| !x.is_nil()),
+    }))))
+}
+
+pub fn vector(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    Ok(Array::vector(args.to_vec()))
+}
+
+// (aref array subscript*) ignores the fill pointer
+pub fn aref(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (array, subscripts) = args
+        .split_first()
+        .ok_or(ExprErr::Cause("aref expects an array".to_string()))?;
+    let array = array_arg(array)?;
+    let array = array.borrow();
+    let index = array.row_major_index(subscripts)?;
+    Ok(array.elements[index].clone())
+}
+
+// (vector-push element vector) is NIL when the vector is full
+pub fn vector_push(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [element, vector] = args else {
+        return Err(ExprErr::Cause(
+            "vector-push expects exactly two args".to_string(),
+        ));
+    };
+    let vector = vector_arg(vector)?;
+    let mut vector = vector.borrow_mut();
+    let fill_pointer = vector.fill_pointer()?;
+    if fill_pointer == vector.elements.len() {
+        return Ok(Expr::Nil);
+    }
+    vector.elements[fill_pointer] = element.clone();
+    vector.fill_pointer = Some(fill_pointer + 1);
+    Ok(integer_expr(fill_pointer as i64))
+}
+
+// (vector-push-extend element vector [extension]) grows an adjustable
+// vector when it is full
+pub fn vector_push_extend(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (element, vector, extension) = match args {
+        [element, vector] => (element, vector, 1),
+        [element, vector, extension] => (element, vector, index_arg(extension)?.max(1)),
+        _ => {
+            return Err(ExprErr::Cause(
+                "vector-push-extend expects 2 or 3 args".to_string(),
+            ))
+        }
+    };
+    let vector = vector_arg(vector)?;
+    let mut vector = vector.borrow_mut();
+    let fill_pointer = vector.fill_pointer()?;
+    if fill_pointer == vector.elements.len() {
+        if !vector.adjustable {
+            return Err(ExprErr::Cause(format!("{} is not adjustable", vector)));
+        }
+        // grow by at least the extension, doubling for amortized O(1)
+        let size = vector.elements.len() + extension.max(vector.elements.len());
+        if size > TOTAL_SIZE_LIMIT {
+            return Err(ExprErr::Cause(format!(
+                "{} cannot grow beyond the total size limit {}",
+                vector, TOTAL_SIZE_LIMIT
+            )));
+        }
+        vector.elements.resize(size, Expr::Nil);
+        vector.dimensions[0] = size;
+    }
+    vector.elements[fill_pointer] = element.clone();
+    vector.fill_pointer = Some(fill_pointer + 1);
+    Ok(integer_expr(fill_pointer as i64))
+}
+
+pub fn vector_pop(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [vector] = args else {
+        return Err(ExprErr::Cause(
+            "vector-pop expects exactly one arg".to_string(),
+        ));
+    };
+    let vector = vector_arg(vector)?;
+    let mut vector = vector.borrow_mut();
+    match vector.fill_pointer()? {
+        0 => Err(ExprErr::Cause(format!("{} has no elements to pop", vector))),
+        fill_pointer => {
+            vector.fill_pointer = Some(fill_pointer - 1);
+            Ok(vector.elements[fill_pointer - 1].clone())
+        }
+    }
+}
+
+pub fn fill_pointer(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [vector] = args else {
+        return Err(ExprErr::Cause(
+            "fill-pointer expects exactly one arg".to_string(),
+        ));
+    };
+    let fill_pointer = vector_arg(vector)?.borrow().fill_pointer()?;
+    Ok(integer_expr(fill_pointer as i64))
+}
+
+// store into the fill pointer of a vector, as (setf (fill-pointer v) n)
+pub fn set_fill_pointer(vector: &ArrayRef, value: &Expr) -> Result<(), ExprErr> {
+    let mut vector = vector.borrow_mut();
+    vector.fill_pointer()?;
+    let fill_pointer = index_arg(value)?;
+    if fill_pointer > vector.elements.len() {
+        return Err(ExprErr::Cause(format!(
+            "fill pointer {} is larger than the array",
+            fill_pointer
+        )));
+    }
+    vector.fill_pointer = Some(fill_pointer);
+    Ok(())
+}
+
+pub fn array_dimensions(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let [array] = args else {
+        return Err(ExprErr::Cause(
+            "array-dimensions expects exactly one arg".to_string(),
+        ));
+    };
+    let array = array_arg(array)?;
+    let dimensions = array
+        .borrow()
+        .dimensions
+        .iter()
+        .map(|dimension| integer_expr(*dimension as i64))
+        .collect();
+    Ok(Expr::list(dimensions))
+}
+
+pub fn array_dimension(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [array, axis] = args else {
+        return Err(ExprErr::Cause(
+            "array-dimension expects exactly two args".to_string(),
+        ));
+    };
+    let array = array_arg(array)?;
+    let array = array.borrow();
+    match array.dimensions.get(index_arg(axis)?) {
+        Some(dimension) => Ok(integer_expr(*dimension as i64)),
+        None => Err(ExprErr::Cause(format!(
+            "axis {} is out of bounds for {}",
+            axis, array
+        ))),
+    }
+}
+
+pub fn array_rank(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [array] = args else {
+        return Err(ExprErr::Cause(
+            "array-rank expects exactly one arg".to_string(),
+        ));
+    };
+    let rank = array_arg(array)?.borrow().dimensions.len();
+    Ok(integer_expr(rank as i64))
+}
+
+pub fn array_total_size(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let [array] = args else {
+        return Err(ExprErr::Cause(
+            "array-total-size expects exactly one arg".to_string(),
+        ));
+    };
+    let size = array_arg(array)?.borrow().elements.len();
+    Ok(integer_expr(size as i64))
+}
+
+pub fn arrayp(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [object] => Ok(bool_expr(matches!(
+            object,
+            Expr::Array(_) | Expr::String(_)
+        ))),
+        _ => Err(ExprErr::Cause("arrayp expects exactly one arg".to_string())),
+    }
+}
+
+pub fn vectorp(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [object] => Ok(bool_expr(
+            matches!(object, Expr::String(_)) || vector_arg(object).is_ok(),
+        )),
+        _ => Err(ExprErr::Cause(
+            "vectorp expects exactly one arg".to_string(),
+        )),
+    }
+}
+
+pub fn adjustable_array_p(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    match args {
+        [array] => Ok(bool_expr(array_arg(array)?.borrow().adjustable)),
+        _ => Err(ExprErr::Cause(
+            "adjustable-array-p expects exactly one arg".to_string(),
+        )),
+    }
+}
+
+pub fn array_has_fill_pointer_p(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    match args {
+        [array] => Ok(bool_expr(array_arg(array)?.borrow().fill_pointer.is_some())),
+        _ => Err(ExprErr::Cause(
+            "array-has-fill-pointer-p expects exactly one arg".to_string(),
+        )),
+    }
+}
@@ -1,59 +1,233 @@
-use std::rc::Rc;
+use crate::array::ArrayRef;
+use crate::clos::{ClassRef, GenericRef, InstanceRef};
+use crate::eval::{Evaluator, ExprEnv};
+use crate::hash::HashTableRef;
+use crate::list::{self, Cons, ConsRef};
+use crate::number::Number;
+use crate::package::PackageRef;
+use crate::pathname::Pathname;
+use crate::printer::{write_to_string, PrintOptions};
+use crate::stream::StreamRef;
+use crate::structure::StructRef;
+use crate::symbol::SymbolRef;
+use std::{cell::RefCell, rc::Rc};
 
 #[derive(Debug)]
 pub enum ExprErr {
     Cause(String),
+    // non-local exit from (block name ...) carrying the returned value
+    ReturnFrom(String, Expr),
 }
 
-impl std::string::ToString for ExprErr {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for ExprErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            ExprErr::Cause(s) => String::from(s),
+            ExprErr::Cause(s) => write!(f, "{}", s),
+            ExprErr::ReturnFrom(name, _) => write!(f, "return from unknown block: {}", name),
         }
     }
 }
 
-#[derive(Clone)]
+// optional or keyword parameter: (var [default [supplied-p]])
+#[derive(Clone, Debug)]
+pub struct Param {
+    pub var: SymbolRef,
+    pub default: Expr,
+    pub supplied: Option<SymbolRef>,
+}
+
+// (a b &optional c &rest r &key ((:name n) 1) &allow-other-keys &aux x)
+#[derive(Clone, Debug, Default)]
+pub struct LambdaList {
+    pub required: Vec<SymbolRef>,
+    pub optional: Vec<Param>,
+    pub rest: Option<SymbolRef>,
+    pub key: bool,
+    // keyword naming each parameter in the call
+    pub keys: Vec<(SymbolRef, Param)>,
+    pub allow_other_keys: bool,
+    pub aux: Vec<(SymbolRef, Expr)>,
+}
+
+#[derive(Clone, Debug)]
 pub struct Lambda {
-    pub args: Vec<String>,
-    pub body: Rc<Expr>,
+    pub args: Rc<LambdaList>,
+    pub body: Rc<Vec<Expr>>,
+    pub env: ExprEnv,
 }
 
-#[derive(Clone)]
+// Strings are shared like conses and arrays, so destructive functions
+// change them for every holder and EQ tells two of them apart.
+pub type StringRef = Rc<RefCell<String>>;
+
+#[derive(Clone, Debug)]
 pub enum Expr {
-    Number(f64),
-    String(String),
-    Symbol(String),
-    List(Vec<Expr>),
+    Number(Number),
+    // real and imaginary parts
+    Complex(Number, Number),
+    Char(char),
+    String(StringRef),
+    Symbol(SymbolRef),
+    Cons(ConsRef),
     True,
     Nil,
-    Func(fn(&[Expr]) -> Result<Expr, ExprErr>),
+    Func(fn(&mut Evaluator, &[Expr], &mut ExprEnv) -> Result<Expr, ExprErr>),
     Lambda(Lambda),
+    Package(PackageRef),
+    HashTable(HashTableRef),
+    Struct(StructRef),
+    Array(ArrayRef),
+    Instance(InstanceRef),
+    Class(ClassRef),
+    Generic(GenericRef),
+    Stream(StreamRef),
+    Pathname(Pathname),
 }
 
 impl PartialEq for Expr {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (Expr::Symbol(a), Expr::Symbol(b)) => a == b,
+            (Expr::Symbol(a), Expr::Symbol(b)) => Rc::ptr_eq(a, b),
             (Expr::Number(a), Expr::Number(b)) => a == b,
-            (Expr::String(a), Expr::String(b)) => a == b,
-            (Expr::List(a), Expr::List(b)) => a == b,
+            (Expr::Complex(a, b), Expr::Complex(c, d)) => a == c && b == d,
+            (Expr::Char(a), Expr::Char(b)) => a == b,
+            (Expr::String(a), Expr::String(b)) => *a.borrow() == *b.borrow(),
+            (Expr::Cons(a), Expr::Cons(b)) => list::same_list(a, b, |a, b| a == b),
             (Expr::Nil, Expr::Nil) => true,
+            (Expr::True, Expr::True) => true,
+            (Expr::HashTable(a), Expr::HashTable(b)) => Rc::ptr_eq(a, b),
+            (Expr::Struct(a), Expr::Struct(b)) => Rc::ptr_eq(a, b),
+            (Expr::Package(a), Expr::Package(b)) => Rc::ptr_eq(a, b),
+            (Expr::Array(a), Expr::Array(b)) => Rc::ptr_eq(a, b),
+            (Expr::Instance(a), Expr::Instance(b)) => Rc::ptr_eq(a, b),
+            (Expr::Class(a), Expr::Class(b)) => Rc::ptr_eq(a, b),
+            (Expr::Generic(a), Expr::Generic(b)) => Rc::ptr_eq(a, b),
+            (Expr::Stream(a), Expr::Stream(b)) => Rc::ptr_eq(a, b),
+            (Expr::Pathname(a), Expr::Pathname(b)) => a == b,
             _ => false,
         }
     }
 }
 
+impl Expr {
+    pub fn string(s: impl Into<String>) -> Expr {
+        Expr::String(Rc::new(RefCell::new(s.into())))
+    }
+
+    pub fn cons(car: Expr, cdr: Expr) -> Expr {
+        Expr::Cons(Rc::new(RefCell::new(Cons { car, cdr })))
+    }
+
+    // build a list, normalizing the empty list to NIL
+    pub fn list(exprs: Vec<Expr>) -> Expr {
+        Expr::list_with_tail(exprs, Expr::Nil)
+    }
+
+    // a list of exprs ending in tail instead of NIL
+    pub fn list_with_tail(exprs: Vec<Expr>, tail: Expr) -> Expr {
+        exprs
+            .into_iter()
+            .rev()
+            .fold(tail, |cdr, car| Expr::cons(car, cdr))
+    }
+
+    // elements of a proper list; a circular list is not one
+    pub fn to_vec(&self) -> Result<Vec<Expr>, ExprErr> {
+        let mut elements = vec![];
+        let mut slow = self.clone();
+        let mut next = self.clone();
+        loop {
+            let cell = match &next {
+                Expr::Nil => return Ok(elements),
+                Expr::Cons(cell) => cell.clone(),
+                _ => return Err(ExprErr::Cause(format!("{} is not list", self))),
+            };
+            let cell = cell.borrow();
+            elements.push(cell.car.clone());
+            next = cell.cdr.clone();
+            if elements.len() % 2 == 0 {
+                slow = slow.cdr()?;
+                if let (Expr::Cons(a), Expr::Cons(b)) = (&slow, &next) {
+                    if Rc::ptr_eq(a, b) {
+                        return Err(ExprErr::Cause(format!("{} is not list", self)));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn car(&self) -> Result<Expr, ExprErr> {
+        match self {
+            Expr::Nil => Ok(Expr::Nil),
+            Expr::Cons(cell) => Ok(cell.borrow().car.clone()),
+            _ => Err(ExprErr::Cause(format!("{} is not list", self))),
+        }
+    }
+
+    pub fn cdr(&self) -> Result<Expr, ExprErr> {
+        match self {
+            Expr::Nil => Ok(Expr::Nil),
+            Expr::Cons(cell) => Ok(cell.borrow().cdr.clone()),
+            _ => Err(ExprErr::Cause(format!("{} is not list", self))),
+        }
+    }
+
+    pub fn is_nil(&self) -> bool {
+        matches!(self, Expr::Nil)
+    }
+}
+
+const CHAR_NAMES: [(&str, char); 9] = [
+    ("Space", ' '),
+    ("Newline", '\n'),
+    ("Tab", '\t'),
+    ("Return", '\r'),
+    ("Linefeed", '\n'),
+    ("Page", '\u{c}'),
+    ("Backspace", '\u{8}'),
+    ("Rubout", '\u{7f}'),
+    ("Nul", '\0'),
+];
+
+// name of a character written as #\Name
+pub fn char_name(ch: char) -> Option<&'static str> {
+    CHAR_NAMES
+        .iter()
+        .find(|(_, named)| *named == ch)
+        .map(|(name, _)| *name)
+}
+
+// character of a name, ignoring case
+pub fn name_char(name: &str) -> Option<char> {
+    CHAR_NAMES
+        .iter()
+        .find(|(named, _)| named.eq_ignore_ascii_case(name))
+        .map(|(_, ch)| *ch)
+}
+
 impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let s = match self {
-            Expr::List(exprs) => {
-                let xs = exprs.iter().map(|x| x.to_string()).collect::<Vec<String>>();
-                format!("({})", xs.join(" "))
-            }
             Expr::Number(num) => num.to_string(),
-            Expr::String(s) => s.to_string(),
-            Expr::Symbol(sym) => sym.to_string(),
+            Expr::Complex(re, im) => format!("#C({} {})", re, im),
+            Expr::Char(ch) => match char_name(*ch) {
+                Some(name) => format!("#\\{}", name),
+                None => format!("#\\{}", ch),
+            },
+            Expr::String(s) => s.borrow().to_string(),
+            Expr::Symbol(sym) => sym.qualified_name(),
+            Expr::Package(package) => format!("#<PACKAGE {}>", package.name),
+            Expr::HashTable(table) => format!("{:?}", table.borrow()),
+            // through the printer, which labels circular references
+            Expr::Cons(_) | Expr::Struct(_) | Expr::Array(_) => {
+                let options = PrintOptions::default().with_escape(false);
+                write_to_string(self, &options).unwrap_or_default()
+            }
+            Expr::Instance(instance) => format!("{:?}", instance),
+            Expr::Class(class) => format!("{:?}", class),
+            Expr::Generic(generic) => format!("{:?}", generic),
+            Expr::Stream(stream) => format!("{:?}", stream.borrow()),
+            Expr::Pathname(pathname) => pathname.namestring(),
             Expr::Nil => "NIL".to_string(),
             Expr::Func(_) => "FUNCTION".to_string(),
             Expr::Lambda(_) => "LAMBDA".to_string(),
@@ -0,0 +1,1312 @@
+use crate::ast::{Expr, ExprErr, Lambda, LambdaList};
+use crate::eval::{bool_expr, parse_lambda_list, Evaluator, ExprEnv};
+use crate::hash::eql;
+use crate::number::Number;
+use crate::package;
+use crate::setf::{setf_function_name, SetfExpander};
+use crate::stream::Stream;
+use crate::structure::{self, find_struct, StructDef};
+use crate::symbol::{symbol_of, Symbol, SymbolRef};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ClassKind {
+    BuiltIn,
+    Structure,
+    Standard,
+}
+
+// slot of a standard class; the initform is evaluated in the environment
+// of its defclass
+#[derive(Clone)]
+pub struct SlotDef {
+    pub name: SymbolRef,
+    pub initargs: Vec<SymbolRef>,
+    pub initform: Option<(Expr, ExprEnv)>,
+}
+
+// A redefinition updates the class in place, so that methods specialized
+// on it and its subclasses see the new definition.
+pub struct Class {
+    pub name: SymbolRef,
+    pub kind: ClassKind,
+    supers: RefCell<Vec<ClassRef>>,
+    direct_slots: RefCell<Vec<SlotDef>>,
+}
+
+pub type ClassRef = Rc<Class>;
+
+impl Class {
+    fn new(
+        name: SymbolRef,
+        kind: ClassKind,
+        supers: Vec<ClassRef>,
+        slots: Vec<SlotDef>,
+    ) -> ClassRef {
+        Rc::new(Class {
+            name,
+            kind,
+            supers: RefCell::new(supers),
+            direct_slots: RefCell::new(slots),
+        })
+    }
+
+    // C3 linearization of the class and its superclasses
+    pub fn precedence_list(self: &Rc<Self>) -> Result<Vec<ClassRef>, ExprErr> {
+        let supers = self.supers.borrow().clone();
+        let mut lists = supers
+            .iter()
+            .map(|s| s.precedence_list())
+            .collect::<Result<Vec<Vec<ClassRef>>, ExprErr>>()?;
+        lists.push(supers);
+
+        let mut result = vec![self.clone()];
+        loop {
+            lists.retain(|list| !list.is_empty());
+            if lists.is_empty() {
+                return Ok(result);
+            }
+            // the first head which is not in the tail of any list
+            let next = lists
+                .iter()
+                .map(|list| &list[0])
+                .find(|class| {
+                    !lists
+                        .iter()
+                        .any(|list| list[1..].iter().any(|other| Rc::ptr_eq(class, other)))
+                })
+                .cloned()
+                .ok_or(ExprErr::Cause(format!(
+                    "inconsistent class precedence list for {}",
+                    self.name.name
+                )))?;
+            for list in lists.iter_mut() {
+                if Rc::ptr_eq(&list[0], &next) {
+                    list.remove(0);
+                }
+            }
+            result.push(next);
+        }
+    }
+
+    pub fn is_subclass_of(self: &Rc<Self>, other: &ClassRef) -> bool {
+        self.precedence_list()
+            .is_ok_and(|list| list.iter().any(|class| Rc::ptr_eq(class, other)))
+    }
+
+    // effective slots, inherited ones first; more specific classes add
+    // initargs and override the initform
+    pub fn slots(self: &Rc<Self>) -> Result<Vec<SlotDef>, ExprErr> {
+        let mut slots: Vec<SlotDef> = vec![];
+        for class in self.precedence_list()?.iter().rev() {
+            for slot in class.direct_slots.borrow().iter() {
+                match slots.iter_mut().find(|s| s.name == slot.name) {
+                    Some(inherited) => {
+                        for initarg in &slot.initargs {
+                            if !inherited.initargs.contains(initarg) {
+                                inherited.initargs.push(initarg.clone());
+                            }
+                        }
+                        if slot.initform.is_some() {
+                            inherited.initform = slot.initform.clone();
+                        }
+                    }
+                    None => slots.push(slot.clone()),
+                }
+            }
+        }
+        Ok(slots)
+    }
+
+    fn has_slot(self: &Rc<Self>, name: &SymbolRef) -> Result<bool, ExprErr> {
+        Ok(self.slots()?.iter().any(|slot| &slot.name == name))
+    }
+}
+
+impl std::fmt::Debug for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let kind = match self.kind {
+            ClassKind::BuiltIn => "BUILT-IN-CLASS",
+            ClassKind::Structure => "STRUCTURE-CLASS",
+            ClassKind::Standard => "STANDARD-CLASS",
+        };
+        write!(f, "#<{} {}>", kind, Expr::Symbol(self.name.clone()))
+    }
+}
+
+// instance of a standard class; unbound slots have no entry
+pub struct Instance {
+    pub class: ClassRef,
+    values: RefCell<HashMap<SymbolRef, Expr>>,
+}
+
+pub type InstanceRef = Rc<Instance>;
+
+impl Instance {
+    fn check_slot(&self, name: &SymbolRef) -> Result<(), ExprErr> {
+        if self.class.has_slot(name)? {
+            Ok(())
+        } else {
+            Err(ExprErr::Cause(format!(
+                "slot {} is missing from {:?}",
+                name.name, self
+            )))
+        }
+    }
+
+    fn is_bound(&self, name: &SymbolRef) -> bool {
+        self.values.borrow().contains_key(name)
+    }
+}
+
+impl std::fmt::Debug for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "#<{}>", Expr::Symbol(self.class.name.clone()))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Qualifier {
+    Primary,
+    Before,
+    After,
+    Around,
+}
+
+#[derive(Clone)]
+pub enum Specializer {
+    Class(ClassRef),
+    Eql(Expr),
+}
+
+impl Specializer {
+    fn same(&self, other: &Specializer) -> bool {
+        match (self, other) {
+            (Specializer::Class(a), Specializer::Class(b)) => Rc::ptr_eq(a, b),
+            (Specializer::Eql(a), Specializer::Eql(b)) => eql(a, b),
+            _ => false,
+        }
+    }
+
+    // 0 for eql specializers, then by position in the argument's classes
+    fn rank(&self, precedence: &[ClassRef]) -> usize {
+        match self {
+            Specializer::Eql(_) => 0,
+            Specializer::Class(class) => {
+                1 + precedence
+                    .iter()
+                    .position(|other| Rc::ptr_eq(class, other))
+                    .unwrap_or(precedence.len())
+            }
+        }
+    }
+
+    fn matches(&self, arg: &Expr, precedence: &[ClassRef]) -> bool {
+        match self {
+            Specializer::Class(class) => precedence.iter().any(|other| Rc::ptr_eq(class, other)),
+            Specializer::Eql(value) => eql(value, arg),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Method {
+    pub qualifier: Qualifier,
+    pub specializers: Vec<Specializer>,
+    pub function: Expr,
+    // keyword parameters, which make-instance accepts as initargs
+    keys: Vec<SymbolRef>,
+    allow_other_keys: bool,
+}
+
+pub struct Generic {
+    pub name: String,
+    lambda_list: RefCell<Option<Rc<LambdaList>>>,
+    methods: RefCell<Vec<Method>>,
+}
+
+pub type GenericRef = Rc<Generic>;
+
+impl Generic {
+    fn new(name: String) -> GenericRef {
+        Rc::new(Generic {
+            name,
+            lambda_list: RefCell::new(None),
+            methods: RefCell::new(vec![]),
+        })
+    }
+
+    // number of required parameters, once known
+    fn required(&self) -> Option<usize> {
+        match &*self.lambda_list.borrow() {
+            Some(lambda_list) => Some(lambda_list.required.len()),
+            None => self
+                .methods
+                .borrow()
+                .first()
+                .map(|method| method.specializers.len()),
+        }
+    }
+
+    // a method with the same qualifier and specializers is replaced
+    fn add_method(&self, method: Method) -> Result<(), ExprErr> {
+        if self
+            .required()
+            .is_some_and(|required| required != method.specializers.len())
+        {
+            return Err(ExprErr::Cause(format!(
+                "lambda list of method does not match generic function {}",
+                self.name
+            )));
+        }
+        let mut methods = self.methods.borrow_mut();
+        let same = methods.iter().position(|other| {
+            other.qualifier == method.qualifier
+                && other
+                    .specializers
+                    .iter()
+                    .zip(&method.specializers)
+                    .all(|(a, b)| a.same(b))
+        });
+        match same {
+            Some(index) => methods[index] = method,
+            None => methods.push(method),
+        }
+        Ok(())
+    }
+
+    // methods applicable to the arguments, most specific first
+    fn applicable_methods(&self, args: &[Expr]) -> Result<Vec<Method>, ExprErr> {
+        let required = self.required().unwrap_or(0);
+        if args.len() < required {
+            return Err(ExprErr::Cause(format!(
+                "too few args to generic function {}",
+                self.name
+            )));
+        }
+        let precedence = args[..required]
+            .iter()
+            .map(|arg| class_of(arg).precedence_list())
+            .collect::<Result<Vec<Vec<ClassRef>>, ExprErr>>()?;
+
+        let mut methods =
+            self.methods
+                .borrow()
+                .iter()
+                .filter(|method| {
+                    method.specializers.iter().zip(args).zip(&precedence).all(
+                        |((specializer, arg), precedence)| specializer.matches(arg, precedence),
+                    )
+                })
+                .cloned()
+                .collect::<Vec<Method>>();
+        // arguments are compared from left to right
+        methods.sort_by(|a, b| {
+            a.specializers
+                .iter()
+                .zip(&b.specializers)
+                .zip(&precedence)
+                .map(|((a, b), precedence)| a.rank(precedence).cmp(&b.rank(precedence)))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        Ok(methods)
+    }
+}
+
+impl std::fmt::Debug for Generic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "#<STANDARD-GENERIC-FUNCTION {}>", self.name)
+    }
+}
+
+// the applicable methods of a call sorted by the standard method
+// combination; after methods run least specific first
+pub struct Combination {
+    name: String,
+    arounds: Vec<Expr>,
+    befores: Vec<Expr>,
+    primaries: Vec<Expr>,
+    afters: Vec<Expr>,
+}
+
+// what call-next-method calls from the running method
+#[derive(Clone)]
+pub enum NextMethods {
+    None,
+    // the next around method, or the before, primary and after methods
+    Around(Rc<Combination>, usize),
+    Primary(Rc<Combination>, usize),
+}
+
+#[derive(Clone)]
+pub struct MethodCall {
+    next: NextMethods,
+    args: Vec<Expr>,
+}
+
+const BUILT_IN_CLASSES: [(&str, &[&str]); 34] = [
+    ("T", &[]),
+    ("STANDARD-OBJECT", &["T"]),
+    ("STRUCTURE-OBJECT", &["T"]),
+    ("CLASS", &["STANDARD-OBJECT"]),
+    ("BUILT-IN-CLASS", &["CLASS"]),
+    ("STRUCTURE-CLASS", &["CLASS"]),
+    ("STANDARD-CLASS", &["CLASS"]),
+    ("FUNCTION", &["T"]),
+    ("GENERIC-FUNCTION", &["FUNCTION"]),
+    ("STANDARD-GENERIC-FUNCTION", &["GENERIC-FUNCTION"]),
+    ("SYMBOL", &["T"]),
+    ("SEQUENCE", &["T"]),
+    ("LIST", &["SEQUENCE"]),
+    ("CONS", &["LIST"]),
+    ("NULL", &["SYMBOL", "LIST"]),
+    ("ARRAY", &["T"]),
+    ("VECTOR", &["ARRAY", "SEQUENCE"]),
+    ("STRING", &["VECTOR"]),
+    ("CHARACTER", &["T"]),
+    ("NUMBER", &["T"]),
+    ("REAL", &["NUMBER"]),
+    ("RATIONAL", &["REAL"]),
+    ("INTEGER", &["RATIONAL"]),
+    ("RATIO", &["RATIONAL"]),
+    ("FLOAT", &["REAL"]),
+    ("DOUBLE-FLOAT", &["FLOAT"]),
+    ("COMPLEX", &["NUMBER"]),
+    ("HASH-TABLE", &["T"]),
+    ("PACKAGE", &["T"]),
+    ("STREAM", &["T"]),
+    ("STRING-STREAM", &["STREAM"]),
+    ("FILE-STREAM", &["STREAM"]),
+    ("PATHNAME", &["T"]),
+    ("METHOD", &["STANDARD-OBJECT"]),
+];
+
+// superclasses are defined before their subclasses
+fn builtin_classes() -> Vec<ClassRef> {
+    let cl = package::common_lisp();
+    let mut classes: Vec<ClassRef> = vec![];
+    for (name, supers) in BUILT_IN_CLASSES {
+        let symbol = cl.intern(name);
+        _ = cl.export(&symbol);
+        let supers = supers
+            .iter()
+            .map(|name| {
+                let name = cl.intern(name);
+                classes.iter().find(|class| class.name == name).cloned()
+            })
+            .collect::<Option<Vec<ClassRef>>>()
+            .expect("superclass defined first");
+        let kind = match name {
+            "STANDARD-OBJECT" | "CLASS" | "BUILT-IN-CLASS" | "STRUCTURE-CLASS"
+            | "STANDARD-CLASS" | "METHOD" => ClassKind::Standard,
+            "STRUCTURE-OBJECT" => ClassKind::Structure,
+            _ => ClassKind::BuiltIn,
+        };
+        classes.push(Class::new(symbol, kind, supers, vec![]));
+    }
+    classes
+}
+
+thread_local! {
+    static CLASSES: RefCell<HashMap<SymbolRef, ClassRef>> = RefCell::new(
+        builtin_classes()
+            .into_iter()
+            .map(|class| (class.name.clone(), class))
+            .collect()
+    );
+}
+
+// define the built-in classes, so that their names are external in CL
+// before any code is read
+pub fn init_classes() {
+    CLASSES.with(|_| ());
+}
+
+fn lookup_class(name: &SymbolRef) -> Option<ClassRef> {
+    CLASSES.with(|classes| classes.borrow().get(name).cloned())
+}
+
+fn builtin_class(name: &str) -> ClassRef {
+    lookup_class(&Symbol::cl(name)).expect("built-in class")
+}
+
+// the class of a structure type, made when it is first asked for
+fn struct_class(def: &Rc<StructDef>) -> ClassRef {
+    let supers = vec![match &def.include {
+        Some(include) => struct_class(include),
+        None => builtin_class("STRUCTURE-OBJECT"),
+    }];
+    match lookup_class(&def.name) {
+        Some(class) if class.kind == ClassKind::Structure => {
+            *class.supers.borrow_mut() = supers;
+            class
+        }
+        _ => {
+            let class = Class::new(def.name.clone(), ClassKind::Structure, supers, vec![]);
+            CLASSES.with(|classes| classes.borrow_mut().insert(def.name.clone(), class.clone()));
+            class
+        }
+    }
+}
+
+pub fn find_class(name: &SymbolRef) -> Option<ClassRef> {
+    match find_struct(name) {
+        Some(def) if !def.list => Some(struct_class(&def)),
+        _ => lookup_class(name),
+    }
+}
+
+pub fn class_of(expr: &Expr) -> ClassRef {
+    let name = match expr {
+        Expr::Number(Number::Integer(_)) => "INTEGER",
+        Expr::Number(Number::Ratio(..)) => "RATIO",
+        Expr::Number(Number::Float(_)) => "DOUBLE-FLOAT",
+        Expr::Complex(..) => "COMPLEX",
+        Expr::Char(_) => "CHARACTER",
+        Expr::String(_) => "STRING",
+        Expr::Symbol(_) | Expr::True => "SYMBOL",
+        Expr::Nil => "NULL",
+        Expr::Cons(_) => "CONS",
+        Expr::Array(array) if array.borrow().dimensions.len() == 1 => "VECTOR",
+        Expr::Array(_) => "ARRAY",
+        Expr::Func(_) | Expr::Lambda(_) => "FUNCTION",
+        Expr::Generic(_) => "STANDARD-GENERIC-FUNCTION",
+        Expr::HashTable(_) => "HASH-TABLE",
+        Expr::Package(_) => "PACKAGE",
+        Expr::Pathname(_) => "PATHNAME",
+        Expr::Stream(stream) => match &*stream.borrow() {
+            Stream::StringOutput(_) | Stream::StringInput { .. } => "STRING-STREAM",
+            Stream::File(_) => "FILE-STREAM",
+            _ => "STREAM",
+        },
+        Expr::Struct(structure) => return struct_class(&structure.borrow().def),
+        Expr::Instance(instance) => return instance.class.clone(),
+        Expr::Class(class) => match class.kind {
+            ClassKind::BuiltIn => "BUILT-IN-CLASS",
+            ClassKind::Structure => "STRUCTURE-CLASS",
+            ClassKind::Standard => "STANDARD-CLASS",
+        },
+    };
+    builtin_class(name)
+}
+
+// a class or the name of one
+fn class_designator(expr: &Expr) -> Result<ClassRef, ExprErr> {
+    match expr {
+        Expr::Class(class) => Ok(class.clone()),
+        Expr::True => Ok(builtin_class("T")),
+        _ => {
+            let name = symbol_of(expr)?;
+            find_class(&name).ok_or(ExprErr::Cause(format!("undefined class: {}", name.name)))
+        }
+    }
+}
+
+pub fn slot_value(object: &Expr, name: &SymbolRef) -> Result<Expr, ExprErr> {
+    match object {
+        Expr::Instance(instance) => {
+            instance.check_slot(name)?;
+            instance
+                .values
+                .borrow()
+                .get(name)
+                .cloned()
+                .ok_or(ExprErr::Cause(format!(
+                    "slot {} is unbound in {}",
+                    name.name, object
+                )))
+        }
+        Expr::Struct(structure) => {
+            let index = struct_slot_index(object, name)?;
+            Ok(structure.borrow().values[index].clone())
+        }
+        _ => Err(ExprErr::Cause(format!("{} has no slots", object))),
+    }
+}
+
+pub fn set_slot_value(object: &Expr, name: &SymbolRef, value: Expr) -> Result<(), ExprErr> {
+    match object {
+        Expr::Instance(instance) => {
+            instance.check_slot(name)?;
+            instance.values.borrow_mut().insert(name.clone(), value);
+            Ok(())
+        }
+        Expr::Struct(structure) => {
+            let index = struct_slot_index(object, name)?;
+            structure::set_slot(structure, index, value)
+        }
+        _ => Err(ExprErr::Cause(format!("{} has no slots", object))),
+    }
+}
+
+fn struct_slot_index(object: &Expr, name: &SymbolRef) -> Result<usize, ExprErr> {
+    let Expr::Struct(structure) = object else {
+        return Err(ExprErr::Cause(format!("{} is not structure", object)));
+    };
+    let position = structure
+        .borrow()
+        .def
+        .slots
+        .iter()
+        .position(|slot| &slot.name == name);
+    position.ok_or(ExprErr::Cause(format!(
+        "slot {} is missing from {}",
+        name.name, object
+    )))
+}
+
+fn instance_arg(expr: &Expr) -> Result<InstanceRef, ExprErr> {
+    match expr {
+        Expr::Instance(instance) => Ok(instance.clone()),
+        _ => Err(ExprErr::Cause(format!("{} is not standard object", expr))),
+    }
+}
+
+// (find-class name [errorp])
+pub fn find_class_fn(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (name, errorp) = match args {
+        [name] => (name, true),
+        [name, errorp] | [name, errorp, _] => (name, !errorp.is_nil()),
+        _ => return Err(ExprErr::Cause("find-class expects 1 to 3 args".to_string())),
+    };
+    match class_designator(name) {
+        Ok(class) => Ok(Expr::Class(class)),
+        Err(_) if !errorp => Ok(Expr::Nil),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn class_of_fn(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [object] => Ok(Expr::Class(class_of(object))),
+        _ => Err(ExprErr::Cause(
+            "class-of expects exactly one arg".to_string(),
+        )),
+    }
+}
+
+pub fn class_name(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [Expr::Class(class)] => Ok(crate::symbol::symbol_expr(class.name.clone())),
+        [other] => Err(ExprErr::Cause(format!("{} is not class", other))),
+        _ => Err(ExprErr::Cause(
+            "class-name expects exactly one arg".to_string(),
+        )),
+    }
+}
+
+// (slot-value object slot-name)
+pub fn slot_value_fn(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [object, name] => slot_value(object, &symbol_of(name)?),
+        _ => Err(ExprErr::Cause(
+            "slot-value expects exactly two args".to_string(),
+        )),
+    }
+}
+
+pub fn slot_boundp(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [object, name] => {
+            let name = symbol_of(name)?;
+            match object {
+                Expr::Struct(_) => struct_slot_index(object, &name).map(|_| Expr::True),
+                _ => {
+                    let instance = instance_arg(object)?;
+                    instance.check_slot(&name)?;
+                    Ok(bool_expr(instance.is_bound(&name)))
+                }
+            }
+        }
+        _ => Err(ExprErr::Cause(
+            "slot-boundp expects exactly two args".to_string(),
+        )),
+    }
+}
+
+pub fn slot_makunbound(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [object, name] => {
+            let name = symbol_of(name)?;
+            let instance = instance_arg(object)?;
+            instance.check_slot(&name)?;
+            instance.values.borrow_mut().remove(&name);
+            Ok(object.clone())
+        }
+        _ => Err(ExprErr::Cause(
+            "slot-makunbound expects exactly two args".to_string(),
+        )),
+    }
+}
+
+// (make-instance class initarg*) allocates an instance and passes it to
+// initialize-instance with the initargs
+pub fn make_instance(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (class, initargs) = args
+        .split_first()
+        .ok_or(ExprErr::Cause("make-instance expects a class".to_string()))?;
+    let class = class_designator(class)?;
+    if class.kind != ClassKind::Standard {
+        return Err(ExprErr::Cause(format!(
+            "cannot make an instance of {:?}",
+            class
+        )));
+    }
+    if !initargs.len().is_multiple_of(2) {
+        return Err(ExprErr::Cause(
+            "odd number of initargs to make-instance".to_string(),
+        ));
+    }
+    let instance = Expr::Instance(Rc::new(Instance {
+        class: class.clone(),
+        values: RefCell::new(HashMap::new()),
+    }));
+
+    let initialize = Symbol::cl("INITIALIZE-INSTANCE")
+        .function()
+        .ok_or(ExprErr::Cause(
+            "undefined function: INITIALIZE-INSTANCE".to_string(),
+        ))?;
+
+    // initargs are those of the slots and the keyword parameters of the
+    // applicable initialize-instance methods
+    let allow_other_keys = Symbol::keyword("ALLOW-OTHER-KEYS");
+    let mut valid = vec![allow_other_keys.clone()];
+    let mut any = initargs.chunks(2).any(|pair| {
+        matches!(&pair[0], Expr::Symbol(key) if key == &allow_other_keys) && !pair[1].is_nil()
+    });
+    for slot in class.slots()? {
+        valid.extend(slot.initargs);
+    }
+    if let Expr::Generic(generic) = &initialize {
+        for method in generic.applicable_methods(std::slice::from_ref(&instance))? {
+            valid.extend(method.keys);
+            any |= method.allow_other_keys;
+        }
+    }
+    if !any {
+        for pair in initargs.chunks(2) {
+            if !matches!(&pair[0], Expr::Symbol(key) if valid.contains(key)) {
+                return Err(ExprErr::Cause(format!(
+                    "invalid initarg to make-instance of {}: {}",
+                    class.name.name, pair[0]
+                )));
+            }
+        }
+    }
+
+    let mut call = vec![instance.clone()];
+    call.extend(initargs.iter().cloned());
+    evaluator.apply(&initialize, &call, env)?;
+    Ok(instance)
+}
+
+// the primary initialize-instance method of standard objects fills slots
+// from the initargs, then unbound slots from their initforms
+fn initialize_instance(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (object, initargs) = args.split_first().ok_or(ExprErr::Cause(
+        "initialize-instance expects an instance".to_string(),
+    ))?;
+    let instance = instance_arg(object)?;
+    for slot in instance.class.slots()? {
+        // the leftmost occurrence wins
+        let supplied = initargs
+            .chunks_exact(2)
+            .find(|pair| matches!(&pair[0], Expr::Symbol(key) if slot.initargs.contains(key)));
+        let value = match (supplied, &slot.initform) {
+            (Some(pair), _) => pair[1].clone(),
+            (None, Some((form, env))) if !instance.is_bound(&slot.name) => {
+                evaluator.eval(form, &mut env.clone())?
+            }
+            _ => continue,
+        };
+        instance
+            .values
+            .borrow_mut()
+            .insert(slot.name.clone(), value);
+    }
+    Ok(object.clone())
+}
+
+pub fn initialize_instance_generic() -> Expr {
+    let generic = Generic::new("INITIALIZE-INSTANCE".to_string());
+    generic.methods.borrow_mut().push(Method {
+        qualifier: Qualifier::Primary,
+        specializers: vec![Specializer::Class(builtin_class("STANDARD-OBJECT"))],
+        function: Expr::Func(initialize_instance),
+        keys: vec![],
+        allow_other_keys: false,
+    });
+    Expr::Generic(generic)
+}
+
+// (call-next-method arg*) with the arguments of the method by default
+pub fn call_next_method(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let call = evaluator
+        .method_calls
+        .last()
+        .cloned()
+        .ok_or(ExprErr::Cause(
+            "call-next-method called outside of a method".to_string(),
+        ))?;
+    let args = if args.is_empty() {
+        call.args
+    } else {
+        args.to_vec()
+    };
+    let result = match call.next {
+        NextMethods::Around(combination, index) => {
+            evaluator.run_methods(combination, index, &args, env)?
+        }
+        NextMethods::Primary(combination, index) => match combination.primaries.get(index) {
+            Some(method) => {
+                let next = NextMethods::Primary(combination.clone(), index + 1);
+                evaluator.invoke_method(method, next, &args, env)?
+            }
+            None => {
+                return Err(ExprErr::Cause(format!(
+                    "no next method for {}",
+                    combination.name
+                )))
+            }
+        },
+        NextMethods::None => {
+            return Err(ExprErr::Cause(
+                "call-next-method is not allowed in before and after methods".to_string(),
+            ))
+        }
+    };
+    let values = evaluator.take_values(result);
+    Ok(evaluator.multiple_values(values))
+}
+
+pub fn next_method_p(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    if !args.is_empty() {
+        return Err(ExprErr::Cause("next-method-p expects no args".to_string()));
+    }
+    match evaluator.method_calls.last() {
+        Some(call) => Ok(bool_expr(match &call.next {
+            NextMethods::Around(..) => true,
+            NextMethods::Primary(combination, index) => *index < combination.primaries.len(),
+            NextMethods::None => false,
+        })),
+        None => Err(ExprErr::Cause(
+            "next-method-p called outside of a method".to_string(),
+        )),
+    }
+}
+
+// a global function name or (setf name)
+enum FunctionName {
+    Global(SymbolRef),
+    Setf(SymbolRef),
+}
+
+impl FunctionName {
+    fn parse(expr: &Expr) -> Result<FunctionName, ExprErr> {
+        match expr {
+            Expr::Symbol(symbol) => Ok(FunctionName::Global(symbol.clone())),
+            _ => match setf_function_name(expr) {
+                Some(symbol) => Ok(FunctionName::Setf(symbol)),
+                None => Err(ExprErr::Cause(format!("invalid function name: {}", expr))),
+            },
+        }
+    }
+
+    fn to_expr(&self) -> Expr {
+        match self {
+            FunctionName::Global(symbol) => Expr::Symbol(symbol.clone()),
+            FunctionName::Setf(symbol) => Expr::list(vec![
+                Expr::Symbol(Symbol::cl("SETF")),
+                Expr::Symbol(symbol.clone()),
+            ]),
+        }
+    }
+}
+
+fn quote(expr: Expr) -> Expr {
+    Expr::list(vec![Expr::Symbol(Symbol::cl("QUOTE")), expr])
+}
+
+impl Evaluator {
+    // dispatch on the classes of the required arguments
+    pub fn apply_generic(
+        &mut self,
+        generic: &GenericRef,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        let mut combination = Combination {
+            name: generic.name.clone(),
+            arounds: vec![],
+            befores: vec![],
+            primaries: vec![],
+            afters: vec![],
+        };
+        for method in generic.applicable_methods(args)? {
+            let methods = match method.qualifier {
+                Qualifier::Around => &mut combination.arounds,
+                Qualifier::Before => &mut combination.befores,
+                Qualifier::Primary => &mut combination.primaries,
+                Qualifier::After => &mut combination.afters,
+            };
+            methods.push(method.function);
+        }
+        if combination.primaries.is_empty() {
+            return Err(ExprErr::Cause(format!(
+                "no applicable method for {} with args {}",
+                generic.name,
+                Expr::list(args.to_vec())
+            )));
+        }
+        combination.afters.reverse();
+        self.run_methods(Rc::new(combination), 0, args, env)
+    }
+
+    // the around methods from `index` on, then the before, primary and
+    // after methods
+    fn run_methods(
+        &mut self,
+        combination: Rc<Combination>,
+        index: usize,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        if let Some(around) = combination.arounds.get(index) {
+            let next = NextMethods::Around(combination.clone(), index + 1);
+            return self.invoke_method(around, next, args, env);
+        }
+        for before in &combination.befores {
+            self.invoke_method(before, NextMethods::None, args, env)?;
+        }
+        let next = NextMethods::Primary(combination.clone(), 1);
+        let primary = self.invoke_method(&combination.primaries[0], next, args, env)?;
+        let values = self.take_values(primary);
+        for after in &combination.afters {
+            self.invoke_method(after, NextMethods::None, args, env)?;
+        }
+        Ok(self.multiple_values(values))
+    }
+
+    fn invoke_method(
+        &mut self,
+        function: &Expr,
+        next: NextMethods,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        self.method_calls.push(MethodCall {
+            next,
+            args: args.to_vec(),
+        });
+        let result = self.apply(function, args, env);
+        self.method_calls.pop();
+        result
+    }
+
+    // the generic function named `name`, made if there is none
+    fn ensure_generic(&mut self, name: &FunctionName) -> Result<GenericRef, ExprErr> {
+        let existing = match name {
+            FunctionName::Global(symbol) => symbol.function(),
+            FunctionName::Setf(symbol) => match self.setf_expanders.get(symbol) {
+                Some(SetfExpander::Function(function)) => Some(function.clone()),
+                _ => None,
+            },
+        };
+        match (existing, name) {
+            (Some(Expr::Generic(generic)), _) => Ok(generic),
+            (Some(_), FunctionName::Global(symbol)) => Err(ExprErr::Cause(format!(
+                "{} already names an ordinary function",
+                symbol.name
+            ))),
+            (_, FunctionName::Global(symbol)) => {
+                let generic = Generic::new(name.to_expr().to_string());
+                symbol.set_function(Some(Expr::Generic(generic.clone())));
+                Ok(generic)
+            }
+            (_, FunctionName::Setf(symbol)) => {
+                let generic = Generic::new(name.to_expr().to_string());
+                self.setf_expanders.insert(
+                    symbol.clone(),
+                    SetfExpander::Function(Expr::Generic(generic.clone())),
+                );
+                Ok(generic)
+            }
+        }
+    }
+
+    // (defclass name (superclass*) (slot-spec*) class-option*)
+    pub fn eval_defclass(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let [name, supers, slot_specs, options @ ..] = args else {
+            return Err(ExprErr::Cause(
+                "defclass expects a name, superclasses and slots".to_string(),
+            ));
+        };
+        let Expr::Symbol(name) = name else {
+            return Err(ExprErr::Cause(format!("invalid class name: {}", name)));
+        };
+        for option in options {
+            match option.to_vec()?.first() {
+                Some(Expr::Symbol(key)) if key.is_keyword() && key.name == "DOCUMENTATION" => {}
+                _ => {
+                    return Err(ExprErr::Cause(format!(
+                        "unknown defclass option: {}",
+                        option
+                    )))
+                }
+            }
+        }
+
+        let mut superclasses = vec![];
+        for designator in supers.to_vec()? {
+            let class = class_designator(&designator)?;
+            if class.kind != ClassKind::Standard {
+                return Err(ExprErr::Cause(format!(
+                    "{} cannot inherit from {:?}",
+                    name.name, class
+                )));
+            }
+            superclasses.push(class);
+        }
+        if superclasses.is_empty() {
+            superclasses.push(builtin_class("STANDARD-OBJECT"));
+        }
+
+        let mut slots: Vec<SlotDef> = vec![];
+        let mut accessors = vec![];
+        for spec in slot_specs.to_vec()? {
+            let (slot, readers, writers) = parse_slot_spec(&spec, env)?;
+            if slots.iter().any(|other| other.name == slot.name) {
+                return Err(ExprErr::Cause(format!(
+                    "duplicate slot name: {}",
+                    slot.name.name
+                )));
+            }
+            accessors.push((slot.name.clone(), readers, writers));
+            slots.push(slot);
+        }
+
+        let class = Class::new(name.clone(), ClassKind::Standard, superclasses, slots);
+        let precedence = class.precedence_list()?;
+        let class = match lookup_class(name) {
+            Some(existing) if existing.kind == ClassKind::Standard => {
+                if precedence[1..]
+                    .iter()
+                    .any(|other| Rc::ptr_eq(other, &existing))
+                {
+                    return Err(ExprErr::Cause(format!(
+                        "circular superclasses for {}",
+                        name.name
+                    )));
+                }
+                *existing.supers.borrow_mut() = class.supers.take();
+                *existing.direct_slots.borrow_mut() = class.direct_slots.take();
+                existing
+            }
+            Some(existing) => {
+                return Err(ExprErr::Cause(format!("cannot redefine {:?}", existing)));
+            }
+            None => {
+                CLASSES.with(|classes| classes.borrow_mut().insert(name.clone(), class.clone()));
+                class
+            }
+        };
+
+        for (slot, readers, writers) in accessors {
+            self.define_accessors(&class, &slot, &readers, &writers)?;
+        }
+        Ok(Expr::Class(class))
+    }
+
+    // reader and writer methods of a slot
+    fn define_accessors(
+        &mut self,
+        class: &ClassRef,
+        slot: &SymbolRef,
+        readers: &[FunctionName],
+        writers: &[FunctionName],
+    ) -> Result<(), ExprErr> {
+        let object = Expr::Symbol(Symbol::uninterned("OBJECT"));
+        let value = Expr::Symbol(Symbol::uninterned("VALUE"));
+        let place = Expr::list(vec![
+            Expr::Symbol(Symbol::cl("SLOT-VALUE")),
+            object.clone(),
+            quote(Expr::Symbol(slot.clone())),
+        ]);
+        let method = |params: Vec<Expr>,
+                      body: Expr,
+                      specializers: Vec<Specializer>|
+         -> Result<Method, ExprErr> {
+            Ok(Method {
+                qualifier: Qualifier::Primary,
+                specializers,
+                function: Expr::Lambda(Lambda {
+                    args: Rc::new(parse_lambda_list(&Expr::list(params))?),
+                    body: Rc::new(vec![body]),
+                    env: ExprEnv::new(),
+                }),
+                keys: vec![],
+                allow_other_keys: false,
+            })
+        };
+
+        for reader in readers {
+            let method = method(
+                vec![object.clone()],
+                place.clone(),
+                vec![Specializer::Class(class.clone())],
+            )?;
+            self.ensure_generic(reader)?.add_method(method)?;
+        }
+        for writer in writers {
+            let body = Expr::list(vec![
+                Expr::Symbol(Symbol::cl("SETF")),
+                place.clone(),
+                value.clone(),
+            ]);
+            let method = method(
+                vec![value.clone(), object.clone()],
+                body,
+                vec![
+                    Specializer::Class(builtin_class("T")),
+                    Specializer::Class(class.clone()),
+                ],
+            )?;
+            self.ensure_generic(writer)?.add_method(method)?;
+        }
+        Ok(())
+    }
+
+    // (defgeneric name lambda-list option*)
+    pub fn eval_defgeneric(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let [name, lambda_list, options @ ..] = args else {
+            return Err(ExprErr::Cause(
+                "defgeneric expects a name and lambda list".to_string(),
+            ));
+        };
+        let name = FunctionName::parse(name)?;
+        let lambda_list = Rc::new(parse_lambda_list(lambda_list)?);
+        let generic = self.ensure_generic(&name)?;
+        if generic
+            .methods
+            .borrow()
+            .iter()
+            .any(|method| method.specializers.len() != lambda_list.required.len())
+        {
+            return Err(ExprErr::Cause(format!(
+                "lambda list of {} does not match its methods",
+                generic.name
+            )));
+        }
+        *generic.lambda_list.borrow_mut() = Some(lambda_list);
+
+        for option in options {
+            let option = option.to_vec()?;
+            match option.split_first() {
+                Some((Expr::Symbol(key), _)) if key.name == "DOCUMENTATION" => {}
+                Some((Expr::Symbol(key), method)) if key.name == "METHOD" => {
+                    self.define_method(&generic, method, env)?;
+                }
+                Some((Expr::Symbol(key), [Expr::Symbol(kind)]))
+                    if key.name == "METHOD-COMBINATION" && kind.name == "STANDARD" => {}
+                _ => {
+                    return Err(ExprErr::Cause(format!(
+                        "unknown defgeneric option: {}",
+                        Expr::list(option)
+                    )))
+                }
+            }
+        }
+        Ok(Expr::Generic(generic))
+    }
+
+    // (defmethod name qualifier* specialized-lambda-list body*). We have
+    // no method objects, so the generic function is returned.
+    pub fn eval_defmethod(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let (name, method) = args
+            .split_first()
+            .ok_or(ExprErr::Cause("defmethod expects a name".to_string()))?;
+        let generic = self.ensure_generic(&FunctionName::parse(name)?)?;
+        self.define_method(&generic, method, env)?;
+        Ok(Expr::Generic(generic))
+    }
+
+    fn define_method(
+        &mut self,
+        generic: &GenericRef,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<(), ExprErr> {
+        let count = args
+            .iter()
+            .take_while(|arg| matches!(arg, Expr::Symbol(symbol) if symbol.is_keyword()))
+            .count();
+        let (qualifiers, rest) = args.split_at(count);
+        let qualifier = match qualifiers {
+            [] => Qualifier::Primary,
+            [Expr::Symbol(q)] if q.name == "BEFORE" => Qualifier::Before,
+            [Expr::Symbol(q)] if q.name == "AFTER" => Qualifier::After,
+            [Expr::Symbol(q)] if q.name == "AROUND" => Qualifier::Around,
+            _ => {
+                return Err(ExprErr::Cause(format!(
+                    "unsupported method qualifiers: {}",
+                    Expr::list(qualifiers.to_vec())
+                )))
+            }
+        };
+        let (params, body) = rest
+            .split_first()
+            .ok_or(ExprErr::Cause("expected method lambda list".to_string()))?;
+
+        // (var class) and (var (eql form)) in the required parameters
+        let mut plain = vec![];
+        let mut specializers = vec![];
+        let mut required = true;
+        for param in params.to_vec()? {
+            match &param {
+                Expr::Symbol(symbol) if symbol.name.starts_with('&') => {
+                    required = false;
+                    plain.push(param);
+                }
+                _ if !required => plain.push(param),
+                Expr::Symbol(_) => {
+                    plain.push(param);
+                    specializers.push(Specializer::Class(builtin_class("T")));
+                }
+                Expr::Cons(_) => match param.to_vec()?.as_slice() {
+                    [var, specializer] => {
+                        plain.push(var.clone());
+                        specializers.push(self.parse_specializer(specializer, env)?);
+                    }
+                    _ => {
+                        return Err(ExprErr::Cause(format!(
+                            "invalid specialized parameter: {}",
+                            param
+                        )))
+                    }
+                },
+                _ => {
+                    return Err(ExprErr::Cause(format!(
+                        "invalid specialized parameter: {}",
+                        param
+                    )))
+                }
+            }
+        }
+
+        // methods accept the keyword arguments of the other methods
+        let mut lambda_list = parse_lambda_list(&Expr::list(plain))?;
+        let keys = lambda_list
+            .keys
+            .iter()
+            .map(|(key, _)| key.clone())
+            .collect();
+        let allow_other_keys = lambda_list.allow_other_keys;
+        if lambda_list.key {
+            lambda_list.allow_other_keys = true;
+        }
+
+        // skip the docstring
+        let mut body = body;
+        if let [Expr::String(_), rest @ ..] = body {
+            if !rest.is_empty() {
+                body = rest;
+            }
+        }
+        generic.add_method(Method {
+            qualifier,
+            specializers,
+            function: Expr::Lambda(Lambda {
+                args: Rc::new(lambda_list),
+                body: Rc::new(body.to_vec()),
+                env: env.clone(),
+            }),
+            keys,
+            allow_other_keys,
+        })
+    }
+
+    fn parse_specializer(
+        &mut self,
+        specializer: &Expr,
+        env: &mut ExprEnv,
+    ) -> Result<Specializer, ExprErr> {
+        match specializer {
+            Expr::Cons(_) => match specializer.to_vec()?.as_slice() {
+                [Expr::Symbol(eql), form] if eql.name == "EQL" => {
+                    Ok(Specializer::Eql(self.eval(form, env)?))
+                }
+                _ => Err(ExprErr::Cause(format!(
+                    "invalid specializer: {}",
+                    specializer
+                ))),
+            },
+            _ => Ok(Specializer::Class(class_designator(specializer)?)),
+        }
+    }
+}
+
+// name or (name [:initarg key] [:initform form] [:reader r] [:writer w]
+// [:accessor a] [:type type] [:documentation doc])
+fn parse_slot_spec(
+    spec: &Expr,
+    env: &ExprEnv,
+) -> Result<(SlotDef, Vec<FunctionName>, Vec<FunctionName>), ExprErr> {
+    let (name, options) = match spec {
+        Expr::Symbol(name) => (name.clone(), vec![]),
+        Expr::Cons(_) => match spec.to_vec()?.split_first() {
+            Some((Expr::Symbol(name), options)) => (name.clone(), options.to_vec()),
+            _ => return Err(ExprErr::Cause(format!("invalid slot spec: {}", spec))),
+        },
+        _ => return Err(ExprErr::Cause(format!("invalid slot spec: {}", spec))),
+    };
+    if !options.len().is_multiple_of(2) {
+        return Err(ExprErr::Cause(format!("invalid slot spec: {}", spec)));
+    }
+
+    let mut slot = SlotDef {
+        name,
+        initargs: vec![],
+        initform: None,
+    };
+    let mut readers = vec![];
+    let mut writers = vec![];
+    for pair in options.chunks(2) {
+        let key = match &pair[0] {
+            Expr::Symbol(key) if key.is_keyword() => key.name.as_str(),
+            key => return Err(ExprErr::Cause(format!("unknown slot option: {}", key))),
+        };
+        match key {
+            "INITARG" => slot.initargs.push(symbol_of(&pair[1])?),
+            "INITFORM" => slot.initform = Some((pair[1].clone(), env.clone())),
+            "READER" => readers.push(FunctionName::parse(&pair[1])?),
+            "WRITER" => writers.push(FunctionName::parse(&pair[1])?),
+            "ACCESSOR" => {
+                let accessor = symbol_of(&pair[1])?;
+                readers.push(FunctionName::Global(accessor.clone()));
+                writers.push(FunctionName::Setf(accessor));
+            }
+            "ALLOCATION" if matches!(&pair[1], Expr::Symbol(a) if a.name == "INSTANCE") => {}
+            "TYPE" | "DOCUMENTATION" => {}
+            _ => return Err(ExprErr::Cause(format!("unknown slot option: {}", pair[0]))),
+        }
+    }
+    Ok((slot, readers, writers))
+}
@@ -1,19 +1,124 @@
-use crate::ast::{Expr, ExprErr, Lambda};
-use std::{collections::HashMap, rc::Rc};
+use crate::array;
+use crate::ast::{Expr, ExprErr, Lambda, LambdaList, Param};
+use crate::clos::{self, MethodCall};
+use crate::file;
+use crate::format;
+use crate::hash;
+use crate::list;
+use crate::math;
+use crate::number::Number;
+use crate::package;
+use crate::pathname;
+use crate::printer;
+use crate::reader;
+use crate::seq;
+use crate::setf::{setf_function_name, SetfExpander};
+use crate::stream;
+use crate::string;
+use crate::structure;
+use crate::symbol::{self, Symbol, SymbolRef};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-pub type ExprEnv = HashMap<String, Expr>;
-pub struct Evaluator {}
+// Lexical environment. Frames are shared so that closures see later
+// assignments; global values and functions live in the symbols themselves.
+#[derive(Clone, Default)]
+pub struct ExprEnv(Rc<RefCell<Frame>>);
 
-fn parse_list_of_floats(args: &[Expr]) -> Result<Vec<f64>, ExprErr> {
-    args.iter()
-        .map(|x| match x {
-            Expr::Number(num) => Ok(*num),
-            _ => Err(ExprErr::Cause(format!("{} is not number", x))),
-        })
-        .collect()
+#[derive(Default)]
+struct Frame {
+    vars: HashMap<SymbolRef, Expr>,
+    parent: Option<ExprEnv>,
+}
+
+impl ExprEnv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // new scope whose lookups fall back to this one
+    pub fn extend(&self) -> Self {
+        ExprEnv(Rc::new(RefCell::new(Frame {
+            vars: HashMap::new(),
+            parent: Some(self.clone()),
+        })))
+    }
+
+    pub fn get(&self, symbol: &SymbolRef) -> Option<Expr> {
+        let frame = self.0.borrow();
+        match frame.vars.get(symbol) {
+            Some(value) => Some(value.clone()),
+            None => frame.parent.as_ref().and_then(|parent| parent.get(symbol)),
+        }
+    }
+
+    // bind in this frame
+    pub fn define(&self, symbol: &SymbolRef, value: Expr) {
+        self.0.borrow_mut().vars.insert(symbol.clone(), value);
+    }
+
+    // assign the nearest binding; false if the variable is not lexically bound
+    pub fn set(&self, symbol: &SymbolRef, value: Expr) -> bool {
+        let mut frame = self.0.borrow_mut();
+        if let Some(slot) = frame.vars.get_mut(symbol) {
+            *slot = value;
+            return true;
+        }
+        match &frame.parent {
+            Some(parent) => parent.set(symbol, value),
+            None => false,
+        }
+    }
+
+    // the outermost scope, where top-level forms are evaluated
+    pub fn global(&self) -> ExprEnv {
+        match &self.0.borrow().parent {
+            Some(parent) => parent.global(),
+            None => self.clone(),
+        }
+    }
+
+    fn names(&self) -> Vec<String> {
+        let frame = self.0.borrow();
+        let mut names = frame
+            .vars
+            .keys()
+            .map(|symbol| symbol.name.clone())
+            .collect::<Vec<String>>();
+        if let Some(parent) = &frame.parent {
+            names.extend(parent.names());
+        }
+        names
+    }
 }
 
-fn parse_list_of_symbols(args: &[Expr]) -> Result<Vec<String>, ExprErr> {
+impl std::fmt::Debug for ExprEnv {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ExprEnv({})", self.names().join(" "))
+    }
+}
+
+pub type Builtin = fn(&mut Evaluator, &[Expr], &mut ExprEnv) -> Result<Expr, ExprErr>;
+
+// global values of special variables replaced by a binding form,
+// restored by `unbind` when the form exits
+pub type Shadowed = Vec<(SymbolRef, Option<Expr>)>;
+
+pub struct Evaluator {
+    // all values of the last form when it returned multiple values
+    values: Option<Vec<Expr>>,
+    // values a builtin returns, claimed by `apply` when it returns
+    pending_values: Option<Vec<Expr>>,
+    // with-hash-table-iterator names and their remaining entries
+    pub iterators: Vec<(SymbolRef, Vec<(Expr, Expr)>)>,
+    // defsetf and define-setf-expander definitions by accessor name
+    pub setf_expanders: HashMap<SymbolRef, SetfExpander>,
+    // generic function methods being run, innermost last
+    pub method_calls: Vec<MethodCall>,
+    // special operators by their COMMON-LISP symbol
+    special_forms: HashMap<SymbolRef, SpecialForm>,
+}
+
+fn parse_list_of_symbols(args: &[Expr]) -> Result<Vec<SymbolRef>, ExprErr> {
     args.iter()
         .map(|x| match x {
             Expr::Symbol(symbol) => Ok(symbol.clone()),
@@ -22,67 +127,762 @@ fn parse_list_of_symbols(args: &[Expr]) -> Result<Vec<String>, ExprErr> {
         .collect()
 }
 
-macro_rules! basic_op {
-    ($fn: expr) => {
-        |args: &[Expr]| -> Result<Expr, ExprErr> {
-            let floats = parse_list_of_floats(args)?;
-            let (first, rest) = floats
-                .split_first()
-                .ok_or_else(|| ExprErr::Cause("expected at least one number".to_string()))?;
-            Ok(Expr::Number(rest.iter().fold(*first, $fn)))
+pub fn default_env() -> ExprEnv {
+    let mut env: HashMap<String, Expr> = HashMap::new();
+    env.insert("+".to_string(), Expr::Func(math::add));
+    env.insert("-".to_string(), Expr::Func(math::subtract));
+    env.insert("*".to_string(), Expr::Func(math::multiply));
+    env.insert("/".to_string(), Expr::Func(math::divide));
+    env.insert("=".to_string(), Expr::Func(math::EQUAL));
+    env.insert("/=".to_string(), Expr::Func(math::NOT_EQUAL));
+    for (name, func) in math::COMPARE_OPS
+        .into_iter()
+        .chain(math::UNARY_OPS)
+        .chain(math::DIVISION_OPS)
+        .chain(math::BITWISE_OPS)
+        .chain(math::NUMBER_PREDICATES)
+    {
+        env.insert(name.to_string(), Expr::Func(func));
+    }
+    env.insert("MOD".to_string(), Expr::Func(math::modulo));
+    env.insert("REM".to_string(), Expr::Func(math::rem));
+    env.insert("MIN".to_string(), Expr::Func(math::MIN));
+    env.insert("MAX".to_string(), Expr::Func(math::MAX));
+    env.insert("GCD".to_string(), Expr::Func(math::gcd));
+    env.insert("LCM".to_string(), Expr::Func(math::lcm));
+    env.insert("EXPT".to_string(), Expr::Func(math::expt));
+    env.insert("ISQRT".to_string(), Expr::Func(math::isqrt));
+    env.insert("LOG".to_string(), Expr::Func(math::log));
+    env.insert("ATAN".to_string(), Expr::Func(math::atan));
+    env.insert("LOGNOT".to_string(), Expr::Func(math::lognot));
+    env.insert("ASH".to_string(), Expr::Func(math::ash));
+    env.insert("LOGCOUNT".to_string(), Expr::Func(math::logcount));
+    env.insert("COMPLEX".to_string(), Expr::Func(math::complex));
+    env.insert("REALPART".to_string(), Expr::Func(math::realpart));
+    env.insert("IMAGPART".to_string(), Expr::Func(math::imagpart));
+    env.insert("CONJUGATE".to_string(), Expr::Func(math::conjugate));
+    env.insert("PHASE".to_string(), Expr::Func(math::phase));
+    env.insert("FLOAT".to_string(), Expr::Func(math::float));
+    env.insert("NUMERATOR".to_string(), Expr::Func(math::numerator));
+    env.insert("DENOMINATOR".to_string(), Expr::Func(math::denominator));
+    env.insert("RANDOM".to_string(), Expr::Func(math::random));
+    env.insert(
+        "MAKE-RANDOM-STATE".to_string(),
+        Expr::Func(math::make_random_state),
+    );
+    env.insert(
+        "RANDOM-STATE-P".to_string(),
+        Expr::Func(math::random_state_p),
+    );
+    math::init_random_state();
+    env.insert(
+        "LIST".to_string(),
+        Expr::Func(|_, args, _| Ok(Expr::list(args.to_vec()))),
+    );
+    env.insert("CAR".to_string(), Expr::Func(list::car));
+    env.insert("CDR".to_string(), Expr::Func(list::cdr));
+    env.insert("FIRST".to_string(), Expr::Func(list::car));
+    env.insert("REST".to_string(), Expr::Func(list::cdr));
+    env.insert("NTH".to_string(), Expr::Func(list::nth));
+    env.insert("CONS".to_string(), Expr::Func(list::cons));
+    env.insert("RPLACA".to_string(), Expr::Func(list::rplaca));
+    env.insert("RPLACD".to_string(), Expr::Func(list::rplacd));
+    env.insert(
+        "VALUES".to_string(),
+        Expr::Func(|evaluator, args, _| Ok(evaluator.multiple_values(args.to_vec()))),
+    );
+    env.insert(
+        "VALUES-LIST".to_string(),
+        Expr::Func(|evaluator, args, _| match args {
+            [list] => Ok(evaluator.multiple_values(list.to_vec()?)),
+            _ => Err(ExprErr::Cause(
+                "values-list expects exactly one arg".to_string(),
+            )),
+        }),
+    );
+    env.insert("EQ".to_string(), Expr::Func(eq));
+    env.insert("EQL".to_string(), Expr::Func(hash::eql_fn));
+    env.insert("EQUAL".to_string(), Expr::Func(hash::equal_fn));
+    env.insert("EQUALP".to_string(), Expr::Func(hash::equalp_fn));
+    env.insert(
+        "MAKE-HASH-TABLE".to_string(),
+        Expr::Func(hash::make_hash_table),
+    );
+    env.insert("GETHASH".to_string(), Expr::Func(hash::gethash));
+    env.insert("REMHASH".to_string(), Expr::Func(hash::remhash));
+    env.insert("CLRHASH".to_string(), Expr::Func(hash::clrhash));
+    env.insert("MAPHASH".to_string(), Expr::Func(hash::maphash));
+    env.insert(
+        "HASH-TABLE-COUNT".to_string(),
+        Expr::Func(hash::hash_table_count),
+    );
+    env.insert("HASH-TABLE-P".to_string(), Expr::Func(hash::hash_table_p));
+    env.insert("FUNCALL".to_string(), Expr::Func(funcall));
+    env.insert("APPLY".to_string(), Expr::Func(apply));
+    env.insert(
+        "SYMBOL-FUNCTION".to_string(),
+        Expr::Func(symbol::symbol_function),
+    );
+    env.insert("FBOUNDP".to_string(), Expr::Func(symbol::fboundp));
+    env.insert("FMAKUNBOUND".to_string(), Expr::Func(symbol::fmakunbound));
+    env.insert("SYMBOL-VALUE".to_string(), Expr::Func(symbol::symbol_value));
+    env.insert("BOUNDP".to_string(), Expr::Func(symbol::boundp));
+    env.insert("MAKUNBOUND".to_string(), Expr::Func(symbol::makunbound));
+    env.insert("INTERN".to_string(), Expr::Func(package::intern));
+    env.insert("FIND-SYMBOL".to_string(), Expr::Func(package::find_symbol));
+    env.insert(
+        "FIND-PACKAGE".to_string(),
+        Expr::Func(package::find_package_fn),
+    );
+    env.insert(
+        "PACKAGE-NAME".to_string(),
+        Expr::Func(package::package_name),
+    );
+    env.insert("USE-PACKAGE".to_string(), Expr::Func(package::use_package));
+    env.insert("EXPORT".to_string(), Expr::Func(package::export));
+    env.insert("IMPORT".to_string(), Expr::Func(package::import));
+    env.insert("SHADOW".to_string(), Expr::Func(package::shadow));
+    env.insert("SYMBOL-NAME".to_string(), Expr::Func(symbol::symbol_name));
+    env.insert(
+        "SYMBOL-PACKAGE".to_string(),
+        Expr::Func(symbol::symbol_package),
+    );
+    env.insert("SYMBOL-PLIST".to_string(), Expr::Func(symbol::symbol_plist));
+    env.insert("GET".to_string(), Expr::Func(symbol::get));
+    env.insert("GETF".to_string(), Expr::Func(symbol::getf));
+    env.insert("KEYWORDP".to_string(), Expr::Func(symbol::keywordp));
+    env.insert("MAKE-SYMBOL".to_string(), Expr::Func(symbol::make_symbol));
+    env.insert("GENSYM".to_string(), Expr::Func(symbol::gensym));
+    env.insert("MAKE-ARRAY".to_string(), Expr::Func(array::make_array));
+    env.insert("VECTOR".to_string(), Expr::Func(array::vector));
+    env.insert("AREF".to_string(), Expr::Func(array::aref));
+    env.insert("VECTOR-PUSH".to_string(), Expr::Func(array::vector_push));
+    env.insert(
+        "VECTOR-PUSH-EXTEND".to_string(),
+        Expr::Func(array::vector_push_extend),
+    );
+    env.insert("VECTOR-POP".to_string(), Expr::Func(array::vector_pop));
+    env.insert("FILL-POINTER".to_string(), Expr::Func(array::fill_pointer));
+    env.insert(
+        "ARRAY-DIMENSIONS".to_string(),
+        Expr::Func(array::array_dimensions),
+    );
+    env.insert(
+        "ARRAY-DIMENSION".to_string(),
+        Expr::Func(array::array_dimension),
+    );
+    env.insert("ARRAY-RANK".to_string(), Expr::Func(array::array_rank));
+    env.insert(
+        "ARRAY-TOTAL-SIZE".to_string(),
+        Expr::Func(array::array_total_size),
+    );
+    env.insert("ARRAYP".to_string(), Expr::Func(array::arrayp));
+    env.insert("VECTORP".to_string(), Expr::Func(array::vectorp));
+    env.insert(
+        "ADJUSTABLE-ARRAY-P".to_string(),
+        Expr::Func(array::adjustable_array_p),
+    );
+    env.insert(
+        "ARRAY-HAS-FILL-POINTER-P".to_string(),
+        Expr::Func(array::array_has_fill_pointer_p),
+    );
+    env.insert("LENGTH".to_string(), Expr::Func(seq::length));
+    env.insert("ELT".to_string(), Expr::Func(seq::elt));
+    env.insert("SUBSEQ".to_string(), Expr::Func(seq::subseq));
+    env.insert("CONCATENATE".to_string(), Expr::Func(seq::concatenate));
+    env.insert("MAP".to_string(), Expr::Func(seq::map));
+    env.insert("REDUCE".to_string(), Expr::Func(seq::reduce));
+    env.insert("FIND".to_string(), Expr::Func(seq::find));
+    env.insert("FIND-IF".to_string(), Expr::Func(seq::find_if));
+    env.insert("FIND-IF-NOT".to_string(), Expr::Func(seq::find_if_not));
+    env.insert("POSITION".to_string(), Expr::Func(seq::position));
+    env.insert("POSITION-IF".to_string(), Expr::Func(seq::position_if));
+    env.insert(
+        "POSITION-IF-NOT".to_string(),
+        Expr::Func(seq::position_if_not),
+    );
+    env.insert("COUNT".to_string(), Expr::Func(seq::count));
+    env.insert("COUNT-IF".to_string(), Expr::Func(seq::count_if));
+    env.insert("COUNT-IF-NOT".to_string(), Expr::Func(seq::count_if_not));
+    env.insert("REMOVE".to_string(), Expr::Func(seq::remove));
+    env.insert("REMOVE-IF".to_string(), Expr::Func(seq::remove_if));
+    env.insert("REMOVE-IF-NOT".to_string(), Expr::Func(seq::remove_if_not));
+    env.insert("DELETE".to_string(), Expr::Func(seq::delete));
+    env.insert("DELETE-IF".to_string(), Expr::Func(seq::delete_if));
+    env.insert("SUBSTITUTE".to_string(), Expr::Func(seq::substitute));
+    env.insert("SUBSTITUTE-IF".to_string(), Expr::Func(seq::substitute_if));
+    env.insert("SEARCH".to_string(), Expr::Func(seq::search));
+    env.insert("MISMATCH".to_string(), Expr::Func(seq::mismatch));
+    env.insert("SORT".to_string(), Expr::Func(seq::sort));
+    env.insert("STABLE-SORT".to_string(), Expr::Func(seq::sort));
+    env.insert("REVERSE".to_string(), Expr::Func(seq::reverse));
+    env.insert("NREVERSE".to_string(), Expr::Func(seq::nreverse));
+    env.insert("FILL".to_string(), Expr::Func(seq::fill));
+    env.insert("REPLACE".to_string(), Expr::Func(seq::replace));
+    env.insert(
+        "REMOVE-DUPLICATES".to_string(),
+        Expr::Func(seq::remove_duplicates),
+    );
+    env.insert("STRINGP".to_string(), Expr::Func(string::stringp));
+    env.insert("CHARACTERP".to_string(), Expr::Func(string::characterp));
+    env.insert("CHAR-CODE".to_string(), Expr::Func(string::char_code));
+    env.insert("CODE-CHAR".to_string(), Expr::Func(string::code_char));
+    env.insert("CHAR-UPCASE".to_string(), Expr::Func(string::char_upcase));
+    env.insert(
+        "CHAR-DOWNCASE".to_string(),
+        Expr::Func(string::char_downcase),
+    );
+    env.insert("DIGIT-CHAR-P".to_string(), Expr::Func(string::digit_char_p));
+    env.insert("STRING".to_string(), Expr::Func(string::string));
+    env.insert("CHAR".to_string(), Expr::Func(string::char));
+    env.insert("SCHAR".to_string(), Expr::Func(string::char));
+    env.insert("MAKE-STRING".to_string(), Expr::Func(string::make_string));
+    env.insert(
+        "STRING-UPCASE".to_string(),
+        Expr::Func(string::string_upcase),
+    );
+    env.insert(
+        "STRING-DOWNCASE".to_string(),
+        Expr::Func(string::string_downcase),
+    );
+    env.insert(
+        "STRING-CAPITALIZE".to_string(),
+        Expr::Func(string::string_capitalize),
+    );
+    env.insert("STRING-TRIM".to_string(), Expr::Func(string::string_trim));
+    env.insert(
+        "STRING-LEFT-TRIM".to_string(),
+        Expr::Func(string::string_left_trim),
+    );
+    env.insert(
+        "STRING-RIGHT-TRIM".to_string(),
+        Expr::Func(string::string_right_trim),
+    );
+    env.insert("STRING-SPLIT".to_string(), Expr::Func(string::string_split));
+    env.insert(
+        "PARSE-INTEGER".to_string(),
+        Expr::Func(string::parse_integer),
+    );
+    env.insert("PARSE-FLOAT".to_string(), Expr::Func(string::parse_float));
+    for (name, func) in string::STRING_COMPARISONS
+        .into_iter()
+        .chain(string::CHAR_COMPARISONS)
+        .chain(string::CHAR_PREDICATES)
+    {
+        env.insert(name.to_string(), Expr::Func(func));
+    }
+    env.insert("WRITE-STRING".to_string(), Expr::Func(stream::write_string));
+    env.insert("FORMAT".to_string(), Expr::Func(format::format));
+    env.insert("WRITE-CHAR".to_string(), Expr::Func(stream::write_char));
+    env.insert("WRITE-LINE".to_string(), Expr::Func(stream::write_line));
+    env.insert("TERPRI".to_string(), Expr::Func(stream::terpri));
+    env.insert("FRESH-LINE".to_string(), Expr::Func(stream::fresh_line));
+    env.insert("READ-LINE".to_string(), Expr::Func(stream::read_line));
+    env.insert("READ-CHAR".to_string(), Expr::Func(stream::read_char));
+    env.insert("PEEK-CHAR".to_string(), Expr::Func(stream::peek_char));
+    env.insert("UNREAD-CHAR".to_string(), Expr::Func(stream::unread_char));
+    env.insert(
+        "MAKE-STRING-INPUT-STREAM".to_string(),
+        Expr::Func(stream::make_string_input_stream),
+    );
+    env.insert(
+        "MAKE-STRING-OUTPUT-STREAM".to_string(),
+        Expr::Func(stream::make_string_output_stream),
+    );
+    env.insert(
+        "GET-OUTPUT-STREAM-STRING".to_string(),
+        Expr::Func(stream::get_output_stream_string),
+    );
+    env.insert("READ".to_string(), Expr::Func(reader::read));
+    env.insert(
+        "READ-FROM-STRING".to_string(),
+        Expr::Func(reader::read_from_string),
+    );
+    env.insert("EVAL".to_string(), Expr::Func(reader::eval));
+    env.insert("LOAD".to_string(), Expr::Func(reader::load));
+    env.insert("COMPILE".to_string(), Expr::Func(reader::compile));
+    env.insert("COMPILE-FILE".to_string(), Expr::Func(reader::compile_file));
+    env.insert("OPEN".to_string(), Expr::Func(file::open));
+    env.insert("CLOSE".to_string(), Expr::Func(file::close));
+    env.insert("FILE-LENGTH".to_string(), Expr::Func(file::file_length));
+    env.insert("FILE-POSITION".to_string(), Expr::Func(file::file_position));
+    env.insert("READ-BYTE".to_string(), Expr::Func(file::read_byte));
+    env.insert("WRITE-BYTE".to_string(), Expr::Func(file::write_byte));
+    env.insert("PATHNAME".to_string(), Expr::Func(pathname::pathname));
+    env.insert("PATHNAMEP".to_string(), Expr::Func(pathname::pathnamep));
+    env.insert("NAMESTRING".to_string(), Expr::Func(pathname::namestring));
+    env.insert(
+        "PATHNAME-DIRECTORY".to_string(),
+        Expr::Func(pathname::pathname_directory),
+    );
+    env.insert(
+        "PATHNAME-NAME".to_string(),
+        Expr::Func(pathname::pathname_name),
+    );
+    env.insert(
+        "PATHNAME-TYPE".to_string(),
+        Expr::Func(pathname::pathname_type),
+    );
+    env.insert(
+        "MAKE-PATHNAME".to_string(),
+        Expr::Func(pathname::make_pathname),
+    );
+    env.insert(
+        "MERGE-PATHNAMES".to_string(),
+        Expr::Func(pathname::merge_pathnames),
+    );
+    env.insert("PROBE-FILE".to_string(), Expr::Func(pathname::probe_file));
+    env.insert("DELETE-FILE".to_string(), Expr::Func(pathname::delete_file));
+    env.insert("RENAME-FILE".to_string(), Expr::Func(pathname::rename_file));
+    env.insert(
+        "ENSURE-DIRECTORIES-EXIST".to_string(),
+        Expr::Func(pathname::ensure_directories_exist),
+    );
+    env.insert("DIRECTORY".to_string(), Expr::Func(pathname::directory));
+    printer::init_print_variables();
+    stream::init_streams();
+    pathname::init_pathname_variables();
+    reader::init_reader_variables();
+    env.insert("PRIN1".to_string(), Expr::Func(printer::prin1));
+    env.insert("PRINC".to_string(), Expr::Func(printer::princ));
+    env.insert("PRINT".to_string(), Expr::Func(printer::print_fn));
+    env.insert("PPRINT".to_string(), Expr::Func(printer::pprint));
+    env.insert("WRITE".to_string(), Expr::Func(printer::write));
+    env.insert(
+        "WRITE-TO-STRING".to_string(),
+        Expr::Func(printer::write_to_string_fn),
+    );
+    env.insert(
+        "PRIN1-TO-STRING".to_string(),
+        Expr::Func(printer::prin1_to_string),
+    );
+    env.insert(
+        "PRINC-TO-STRING".to_string(),
+        Expr::Func(printer::princ_to_string),
+    );
+    clos::init_classes();
+    env.insert("FIND-CLASS".to_string(), Expr::Func(clos::find_class_fn));
+    env.insert("CLASS-OF".to_string(), Expr::Func(clos::class_of_fn));
+    env.insert("CLASS-NAME".to_string(), Expr::Func(clos::class_name));
+    env.insert("MAKE-INSTANCE".to_string(), Expr::Func(clos::make_instance));
+    env.insert(
+        "INITIALIZE-INSTANCE".to_string(),
+        clos::initialize_instance_generic(),
+    );
+    env.insert("SLOT-VALUE".to_string(), Expr::Func(clos::slot_value_fn));
+    env.insert("SLOT-BOUNDP".to_string(), Expr::Func(clos::slot_boundp));
+    env.insert(
+        "SLOT-MAKUNBOUND".to_string(),
+        Expr::Func(clos::slot_makunbound),
+    );
+    env.insert(
+        "CALL-NEXT-METHOD".to_string(),
+        Expr::Func(clos::call_next_method),
+    );
+    env.insert("NEXT-METHOD-P".to_string(), Expr::Func(clos::next_method_p));
+
+    let cl = package::common_lisp();
+    for (name, func) in env {
+        let symbol = cl.intern(&name);
+        symbol.set_function(Some(func));
+        _ = cl.export(&symbol);
+    }
+
+    // internal functions called by generated code
+    let system: [(&str, Builtin); 4] = [
+        ("%MAKE-STRUCT", structure::make_struct_fn),
+        ("%STRUCT-REF", structure::struct_ref),
+        ("%STRUCT-TYPEP", structure::struct_typep),
+        ("%COPY-STRUCT", structure::copy_struct),
+    ];
+    for (name, func) in system {
+        Symbol::sys(name).set_function(Some(Expr::Func(func)));
+    }
+    ExprEnv::new()
+}
+
+pub fn single_arg<'a>(name: &str, args: &'a [Expr]) -> Result<&'a Expr, ExprErr> {
+    match args {
+        [arg] => Ok(arg),
+        _ => Err(ExprErr::Cause(format!("{} expects exactly one arg", name))),
+    }
+}
+
+// keyword arguments of a builtin by keyword name
+pub fn keyword_args<'a>(
+    name: &str,
+    args: &'a [Expr],
+    allowed: &[&str],
+) -> Result<HashMap<String, &'a Expr>, ExprErr> {
+    if !args.len().is_multiple_of(2) {
+        return Err(ExprErr::Cause(format!(
+            "odd number of keyword args to {}",
+            name
+        )));
+    }
+    let mut options = HashMap::new();
+    for pair in args.chunks(2) {
+        match &pair[0] {
+            Expr::Symbol(key) if key.is_keyword() && allowed.contains(&key.name.as_str()) => {
+                // the leftmost occurrence wins
+                options.entry(key.name.clone()).or_insert(&pair[1]);
+            }
+            key => {
+                return Err(ExprErr::Cause(format!(
+                    "unknown keyword arg to {}: {}",
+                    name, key
+                )))
+            }
         }
-    };
+    }
+    Ok(options)
 }
 
-pub fn default_env() -> ExprEnv {
-    let mut env: ExprEnv = HashMap::new();
-    env.insert("+".to_string(), Expr::Func(basic_op!(|sum, x| sum + x)));
-    env.insert("-".to_string(), Expr::Func(basic_op!(|sum, x| sum - x)));
-    env.insert("*".to_string(), Expr::Func(basic_op!(|sum, x| sum * x)));
-    env.insert("/".to_string(), Expr::Func(basic_op!(|sum, x| sum / x)));
-    env
+pub fn bool_expr(b: bool) -> Expr {
+    if b {
+        Expr::True
+    } else {
+        Expr::Nil
+    }
+}
+
+// symbols are compared by identity
+fn eq(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [Expr::Symbol(a), Expr::Symbol(b)] => Ok(bool_expr(Rc::ptr_eq(a, b))),
+        [a, b] => Ok(bool_expr(hash::eql(a, b))),
+        _ => Err(ExprErr::Cause("eq expects exactly two args".to_string())),
+    }
+}
+
+// (funcall f arg*)
+fn funcall(evaluator: &mut Evaluator, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (func, args) = args
+        .split_first()
+        .ok_or(ExprErr::Cause("funcall expects a function".to_string()))?;
+    let func = evaluator.function_designator(func)?;
+    evaluator.apply_values(&func, args, env)
+}
+
+// (apply f arg* list)
+fn apply(evaluator: &mut Evaluator, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (func, args) = args
+        .split_first()
+        .ok_or(ExprErr::Cause("apply expects a function".to_string()))?;
+    let func = evaluator.function_designator(func)?;
+    let (last, args) = args
+        .split_last()
+        .ok_or(ExprErr::Cause("apply expects a list of args".to_string()))?;
+    let mut args = args.to_vec();
+    args.extend(last.to_vec()?);
+    evaluator.apply_values(&func, &args, env)
+}
+
+// lambda-list keywords split the parameters into sections
+pub fn parse_lambda_list(params: &Expr) -> Result<LambdaList, ExprErr> {
+    let mut list = LambdaList::default();
+    let mut section = "&REQUIRED";
+    for param in params.to_vec()? {
+        if let Expr::Symbol(symbol) = &param {
+            match symbol.name.as_str() {
+                "&OPTIONAL" | "&REST" | "&KEY" | "&AUX" => {
+                    section = match symbol.name.as_str() {
+                        "&OPTIONAL" => "&OPTIONAL",
+                        "&REST" => "&REST",
+                        "&KEY" => "&KEY",
+                        _ => "&AUX",
+                    };
+                    list.key |= section == "&KEY";
+                    continue;
+                }
+                "&ALLOW-OTHER-KEYS" => {
+                    list.allow_other_keys = true;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        match section {
+            "&REQUIRED" => list.required.push(parse_param_var(&param)?),
+            "&OPTIONAL" => list.optional.push(parse_param(&param)?),
+            "&REST" => {
+                if list.rest.is_some() {
+                    return Err(ExprErr::Cause(format!("invalid lambda list: {}", params)));
+                }
+                list.rest = Some(parse_param_var(&param)?);
+            }
+            "&KEY" => list.keys.push(parse_key_param(&param)?),
+            _ => {
+                let param = parse_param(&param)?;
+                list.aux.push((param.var, param.default));
+            }
+        }
+    }
+    Ok(list)
 }
 
+fn parse_param_var(param: &Expr) -> Result<SymbolRef, ExprErr> {
+    match param {
+        Expr::Symbol(symbol) if !symbol.is_keyword() => Ok(symbol.clone()),
+        _ => Err(ExprErr::Cause(format!("{} is not symbol", param))),
+    }
+}
+
+// a keyword parameter is named by the keyword of its variable
+// unless written ((:keyword var) default supplied-p)
+fn parse_key_param(param: &Expr) -> Result<(SymbolRef, Param), ExprErr> {
+    if let Expr::Cons(_) = param {
+        let mut spec = param.to_vec()?;
+        if let Expr::Cons(_) = spec[0] {
+            return match spec[0].to_vec()?.as_slice() {
+                [Expr::Symbol(keyword), var] => {
+                    spec[0] = var.clone();
+                    Ok((keyword.clone(), parse_param(&Expr::list(spec))?))
+                }
+                _ => Err(ExprErr::Cause(format!(
+                    "invalid keyword parameter: {}",
+                    param
+                ))),
+            };
+        }
+    }
+    let param = parse_param(param)?;
+    Ok((Symbol::keyword(&param.var.name), param))
+}
+
+// var or (var [default [supplied-p]])
+fn parse_param(param: &Expr) -> Result<Param, ExprErr> {
+    match param {
+        Expr::Cons(_) => match param.to_vec()?.as_slice() {
+            [var] => Ok(Param {
+                var: parse_param_var(var)?,
+                default: Expr::Nil,
+                supplied: None,
+            }),
+            [var, default] => Ok(Param {
+                var: parse_param_var(var)?,
+                default: default.clone(),
+                supplied: None,
+            }),
+            [var, default, supplied] => Ok(Param {
+                var: parse_param_var(var)?,
+                default: default.clone(),
+                supplied: Some(parse_param_var(supplied)?),
+            }),
+            _ => Err(ExprErr::Cause(format!("invalid parameter: {}", param))),
+        },
+        _ => Ok(Param {
+            var: parse_param_var(param)?,
+            default: Expr::Nil,
+            supplied: None,
+        }),
+    }
+}
+
+// closure over `env` from a lambda list and body
+pub fn make_lambda(args: &[Expr], env: &ExprEnv) -> Result<Lambda, ExprErr> {
+    let (params, body) = args
+        .split_first()
+        .ok_or(ExprErr::Cause("cannot get lambda args".to_string()))?;
+    Ok(Lambda {
+        args: Rc::new(parse_lambda_list(params)?),
+        body: Rc::new(body.to_vec()),
+        env: env.clone(),
+    })
+}
+
+// leading (declare ...) forms of a body and the names they declare special
+fn parse_declarations(body: &[Expr]) -> Result<(Vec<SymbolRef>, &[Expr]), ExprErr> {
+    let mut specials = vec![];
+    let mut rest = body;
+    while let Some((form @ Expr::Cons(_), tail)) = rest.split_first() {
+        match form.to_vec()?.split_first() {
+            Some((Expr::Symbol(head), specs)) if head.name == "DECLARE" => {
+                for spec in specs {
+                    if let Some((Expr::Symbol(kind), names)) = spec.to_vec()?.split_first() {
+                        if kind.name == "SPECIAL" {
+                            specials.extend(parse_list_of_symbols(names)?);
+                        }
+                    }
+                }
+                rest = tail;
+            }
+            _ => break,
+        }
+    }
+    Ok((specials, rest))
+}
+
+// special forms returning the values of the last form they evaluate
+const VALUES_FORMS: [&str; 8] = [
+    "PROGN",
+    "LET",
+    "LET*",
+    "BLOCK",
+    "LOOP",
+    "MULTIPLE-VALUE-BIND",
+    "MULTIPLE-VALUE-CALL",
+    "WITH-HASH-TABLE-ITERATOR",
+];
+
+// how a special operator is evaluated, and whether it leaves the values
+// of the last form it evaluated
+#[derive(Clone, Copy)]
+struct SpecialForm {
+    eval: Builtin,
+    values: bool,
+}
+
+// special operators of the COMMON-LISP package; they are looked up by
+// symbol, so a package shadowing one of the names calls its own function
+const SPECIAL_FORMS: [(&str, Builtin); 42] = [
+    ("SETQ", Evaluator::eval_setq),
+    ("DEFUN", Evaluator::eval_defun),
+    ("QUOTE", |evaluator, args, _| evaluator.eval_quote(args)),
+    ("FUNCTION", |evaluator, args, env| match args {
+        [name] => evaluator.eval_function(name, env),
+        _ => Err(ExprErr::Cause(
+            "function expects exactly one arg".to_string(),
+        )),
+    }),
+    ("BLOCK", Evaluator::eval_block),
+    ("RETURN-FROM", Evaluator::eval_return_from),
+    ("RETURN", Evaluator::eval_return),
+    ("LOOP", Evaluator::eval_loop),
+    ("PROGN", Evaluator::eval_progn),
+    ("LET", |evaluator, args, env| {
+        evaluator.eval_let(args, env, false)
+    }),
+    ("LET*", |evaluator, args, env| {
+        evaluator.eval_let(args, env, true)
+    }),
+    ("LAMBDA", Evaluator::eval_lambda_form),
+    ("DEFVAR", |evaluator, args, env| {
+        evaluator.eval_defvar(args, env, false)
+    }),
+    ("DEFPARAMETER", |evaluator, args, env| {
+        evaluator.eval_defvar(args, env, true)
+    }),
+    ("DEFCONSTANT", Evaluator::eval_defconstant),
+    ("DEFPACKAGE", Evaluator::eval_defpackage),
+    ("IN-PACKAGE", Evaluator::eval_in_package),
+    ("SETF", Evaluator::eval_setf),
+    ("DEFSTRUCT", Evaluator::eval_defstruct),
+    ("DEFCLASS", Evaluator::eval_defclass),
+    ("DEFGENERIC", Evaluator::eval_defgeneric),
+    ("DEFMETHOD", Evaluator::eval_defmethod),
+    ("PSETF", Evaluator::eval_psetf),
+    ("INCF", |evaluator, args, env| {
+        evaluator.eval_incf(args, env, Number::add)
+    }),
+    ("DECF", |evaluator, args, env| {
+        evaluator.eval_incf(args, env, Number::sub)
+    }),
+    ("PUSH", Evaluator::eval_push),
+    ("PUSHNEW", Evaluator::eval_pushnew),
+    ("POP", Evaluator::eval_pop),
+    ("ROTATEF", Evaluator::eval_rotatef),
+    ("SHIFTF", Evaluator::eval_shiftf),
+    ("DEFSETF", Evaluator::eval_defsetf),
+    ("DEFINE-SETF-EXPANDER", Evaluator::eval_define_setf_expander),
+    ("MULTIPLE-VALUE-LIST", Evaluator::eval_multiple_value_list),
+    ("MULTIPLE-VALUE-BIND", Evaluator::eval_multiple_value_bind),
+    ("MULTIPLE-VALUE-CALL", Evaluator::eval_multiple_value_call),
+    ("MULTIPLE-VALUE-PROG1", Evaluator::eval_multiple_value_prog1),
+    ("NTH-VALUE", Evaluator::eval_nth_value),
+    (
+        "WITH-HASH-TABLE-ITERATOR",
+        Evaluator::eval_with_hash_table_iterator,
+    ),
+    (
+        "WITH-OUTPUT-TO-STRING",
+        Evaluator::eval_with_output_to_string,
+    ),
+    (
+        "WITH-INPUT-FROM-STRING",
+        Evaluator::eval_with_input_from_string,
+    ),
+    ("WITH-OPEN-FILE", Evaluator::eval_with_open_file),
+    ("DECLARE", |_, _, _| {
+        Err(ExprErr::Cause("declare is not allowed here".to_string()))
+    }),
+];
+
 impl Evaluator {
     pub fn new() -> Self {
-        Evaluator {}
+        Evaluator {
+            values: None,
+            pending_values: None,
+            iterators: vec![],
+            setf_expanders: HashMap::new(),
+            method_calls: vec![],
+            special_forms: SPECIAL_FORMS
+                .iter()
+                .map(|(name, eval)| {
+                    let special = SpecialForm {
+                        eval: *eval,
+                        values: VALUES_FORMS.contains(name),
+                    };
+                    (Symbol::cl(name), special)
+                })
+                .collect(),
+        }
     }
 
-    fn print_env(&mut self, env: ExprEnv) -> String {
-        env.clone()
-            .iter()
-            .map(|x| format!("{}={}", x.0, x.1))
-            .collect::<Vec<String>>()
-            .join(" ")
+    // Builtins and special forms return their primary value and record
+    // the rest here. Special forms in VALUES_FORMS instead leave the values
+    // of the last form they evaluated.
+    pub fn multiple_values(&mut self, values: Vec<Expr>) -> Expr {
+        let primary = values.first().cloned().unwrap_or(Expr::Nil);
+        self.pending_values = Some(values);
+        primary
+    }
+
+    // all values of the form that just returned `primary`
+    pub fn take_values(&mut self, primary: Expr) -> Vec<Expr> {
+        self.values.take().unwrap_or_else(|| vec![primary])
+    }
+
+    // forget the values of the last form, for special forms in
+    // VALUES_FORMS that return a single value of their own
+    pub fn clear_values(&mut self) {
+        self.values = None;
     }
 
     pub fn eval(&mut self, expr: &Expr, env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        self.values = None;
         match expr {
-            Expr::String(_) => Ok(expr.clone()),
-            Expr::Number(_) => Ok(expr.clone()),
+            Expr::String(_) | Expr::Char(_) | Expr::Pathname(_) => Ok(expr.clone()),
+            Expr::HashTable(_) | Expr::Package(_) | Expr::Struct(_) => Ok(expr.clone()),
+            Expr::Array(_) => Ok(expr.clone()),
+            Expr::Instance(_) | Expr::Class(_) | Expr::Generic(_) | Expr::Stream(_) => {
+                Ok(expr.clone())
+            }
+            Expr::Number(_) | Expr::Complex(..) => Ok(expr.clone()),
             Expr::Nil => Ok(expr.clone()),
             Expr::True => Ok(expr.clone()),
-            Expr::Symbol(sym) => match env.get(sym) {
-                Some(expr) => Ok(expr.clone()),
-                None => Err(ExprErr::Cause(format!(
-                    "not found symbol: {}, env: {}",
-                    sym,
-                    self.print_env(env.clone()),
-                ))),
-            },
-            Expr::List(list) => {
+            Expr::Symbol(sym) => {
+                let value = if sym.is_special() {
+                    sym.value()
+                } else {
+                    env.get(sym).or_else(|| sym.value())
+                };
+                match value {
+                    Some(expr) => Ok(expr),
+                    None => Err(ExprErr::Cause(format!("not found symbol: {}", sym.name))),
+                }
+            }
+            Expr::Cons(_) => {
+                let list = expr.to_vec()?;
                 let (first, rest) = list
                     .split_first()
                     .ok_or_else(|| ExprErr::Cause("expected at least one number".to_string()))?;
                 match self.eval_builtin(first, rest, env) {
                     Some(expr) => expr,
                     None => {
-                        let expr = self.eval(first, env)?;
-                        match expr {
-                            Expr::Func(f) => f(self.eval_args(rest, env)?.as_slice()),
-                            Expr::Lambda(lambda) => self.eval_lambda(lambda, rest, env),
-                            _ => unreachable!(),
-                        }
+                        let func = self.eval_function(first, env)?;
+                        let args = self.eval_args(rest, env)?;
+                        self.apply(&func, &args, env)
                     }
                 }
             }
@@ -94,57 +894,278 @@ impl Evaluator {
         args.iter().map(|x| self.eval(x, env)).collect()
     }
 
-    pub fn eval_builtin(
+    // function named in call position or by (function name): only the
+    // function namespace is consulted
+    pub fn eval_function(&mut self, expr: &Expr, env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        match expr {
+            Expr::Symbol(symbol) => symbol.function().ok_or(ExprErr::Cause(format!(
+                "undefined function: {}",
+                symbol.name
+            ))),
+            Expr::Cons(cell) if matches!(&cell.borrow().car, Expr::Symbol(head) if head.name == "LAMBDA") => {
+                self.eval_lambda_form(&expr.cdr()?.to_vec()?, env)
+            }
+            _ => match setf_function_name(expr).map(|name| self.setf_expanders.get(&name)) {
+                Some(Some(SetfExpander::Function(function))) => Ok(function.clone()),
+                Some(_) => Err(ExprErr::Cause(format!("undefined function: {}", expr))),
+                None => Err(ExprErr::Cause(format!("illegal function call: {}", expr))),
+            },
+        }
+    }
+
+    // a function object, or a symbol naming a global function
+    pub fn function_designator(&mut self, expr: &Expr) -> Result<Expr, ExprErr> {
+        match expr {
+            Expr::Func(_) | Expr::Lambda(_) | Expr::Generic(_) => Ok(expr.clone()),
+            _ => {
+                let symbol = symbol::symbol_of(expr)?;
+                symbol.function().ok_or(ExprErr::Cause(format!(
+                    "undefined function: {}",
+                    symbol.name
+                )))
+            }
+        }
+    }
+
+    // call an evaluated function object with evaluated arguments
+    pub fn apply(
         &mut self,
-        first: &Expr,
+        func: &Expr,
         args: &[Expr],
         env: &mut ExprEnv,
-    ) -> Option<Result<Expr, ExprErr>> {
-        match first {
-            Expr::Symbol(symbol) => match symbol.as_str() {
-                "SETQ" => Some(self.eval_setq(args, env)),
-                "DEFUN" => Some(self.eval_defun(args, env)),
-                _ => None,
-            },
-            _ => None,
+    ) -> Result<Expr, ExprErr> {
+        match func {
+            Expr::Func(f) => {
+                let result = f(self, args, env);
+                self.values = self.pending_values.take();
+                result
+            }
+            Expr::Lambda(lambda) => self.eval_lambda(lambda.clone(), args),
+            Expr::Generic(generic) => {
+                let result = self.apply_generic(generic, args, env);
+                if let Some(values) = self.pending_values.take() {
+                    self.values = Some(values);
+                }
+                result
+            }
+            _ => Err(ExprErr::Cause(format!("{} is not function", func))),
         }
     }
 
-    pub fn eval_lambda(
+    // call a function from a builtin that returns all its values
+    pub fn apply_values(
         &mut self,
-        lambda: Lambda,
-        outer_args: &[Expr],
+        func: &Expr,
+        args: &[Expr],
         env: &mut ExprEnv,
     ) -> Result<Expr, ExprErr> {
-        if lambda.args.len() != outer_args.len() {
+        let primary = self.apply(func, args, env)?;
+        let values = self.take_values(primary);
+        Ok(self.multiple_values(values))
+    }
+
+    // a special form, or a call of the next function of a
+    // with-hash-table-iterator; None for ordinary function calls
+    fn eval_builtin(
+        &mut self,
+        first: &Expr,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Option<Result<Expr, ExprErr>> {
+        let Expr::Symbol(symbol) = first else {
+            return None;
+        };
+        let result = match self.special_forms.get(symbol).copied() {
+            Some(special) if special.values => return Some((special.eval)(self, args, env)),
+            Some(special) => (special.eval)(self, args, env),
+            None if args.is_empty() && !self.iterators.is_empty() => {
+                Ok(self.next_hash_table_entry(symbol)?)
+            }
+            None => return None,
+        };
+        if result.is_ok() {
+            self.values = self.pending_values.take();
+        }
+        Some(result)
+    }
+
+    pub fn eval_lambda(&mut self, lambda: Lambda, args: &[Expr]) -> Result<Expr, ExprErr> {
+        let params = &lambda.args;
+        let positional = params.required.len() + params.optional.len();
+        if args.len() < params.required.len()
+            || (args.len() > positional && params.rest.is_none() && !params.key)
+        {
             return Err(ExprErr::Cause(
                 "number of args and lambda's arg is not same".to_string(),
             ));
         }
 
-        let mut args = Vec::<Expr>::new();
-        for arg in outer_args {
-            args.push(self.eval(arg, env)?);
+        let (specials, body) = parse_declarations(&lambda.body)?;
+        let mut local_env = lambda.env.extend();
+        let mut shadowed = Shadowed::new();
+        let result = self.eval_lambda_body(
+            &lambda.args,
+            args,
+            &specials,
+            body,
+            &mut local_env,
+            &mut shadowed,
+        );
+        self.unbind(shadowed);
+        result
+    }
+
+    // defaults are evaluated in order with the earlier parameters in scope
+    fn eval_lambda_body(
+        &mut self,
+        params: &LambdaList,
+        args: &[Expr],
+        specials: &[SymbolRef],
+        body: &[Expr],
+        env: &mut ExprEnv,
+        shadowed: &mut Shadowed,
+    ) -> Result<Expr, ExprErr> {
+        let mut args = args.iter();
+        for name in &params.required {
+            let value = args.next().cloned().unwrap_or(Expr::Nil);
+            self.bind(env, name, value, specials.contains(name), shadowed)?;
+        }
+        for param in &params.optional {
+            let value = args.next().cloned();
+            self.bind_param(param, value, specials, env, shadowed)?;
+        }
+
+        let rest = args.as_slice();
+        if let Some(name) = &params.rest {
+            let value = Expr::list(rest.to_vec());
+            self.bind(env, name, value, specials.contains(name), shadowed)?;
+        }
+        if params.key {
+            if !rest.len().is_multiple_of(2) {
+                return Err(ExprErr::Cause("odd number of keyword args".to_string()));
+            }
+            let allow_other_keys = params.allow_other_keys
+                || rest.chunks(2).any(|pair| {
+                    matches!(&pair[0], Expr::Symbol(key) if key.name == "ALLOW-OTHER-KEYS" && key.is_keyword())
+                        && !pair[1].is_nil()
+                });
+            for pair in rest.chunks(2) {
+                let known = params
+                    .keys
+                    .iter()
+                    .any(|(keyword, _)| pair[0] == Expr::Symbol(keyword.clone()));
+                if !known && !allow_other_keys {
+                    return Err(ExprErr::Cause(format!("unknown keyword arg: {}", pair[0])));
+                }
+            }
+            for (keyword, param) in &params.keys {
+                let keyword = Expr::Symbol(keyword.clone());
+                let value = rest
+                    .chunks(2)
+                    .find(|pair| pair[0] == keyword)
+                    .map(|pair| pair[1].clone());
+                self.bind_param(param, value, specials, env, shadowed)?;
+            }
+        }
+        for (name, form) in &params.aux {
+            let value = self.eval(form, env)?;
+            self.bind(env, name, value, specials.contains(name), shadowed)?;
+        }
+        self.eval_progn(body, env)
+    }
+
+    // bind an optional or keyword parameter, evaluating its default if not supplied
+    fn bind_param(
+        &mut self,
+        param: &Param,
+        value: Option<Expr>,
+        specials: &[SymbolRef],
+        env: &mut ExprEnv,
+        shadowed: &mut Shadowed,
+    ) -> Result<(), ExprErr> {
+        let supplied = value.is_some();
+        let value = match value {
+            Some(value) => value,
+            None => self.eval(&param.default, env)?,
+        };
+        self.bind(
+            env,
+            &param.var,
+            value,
+            specials.contains(&param.var),
+            shadowed,
+        )?;
+        if let Some(name) = &param.supplied {
+            self.bind(
+                env,
+                name,
+                bool_expr(supplied),
+                specials.contains(name),
+                shadowed,
+            )?;
         }
+        Ok(())
+    }
 
-        let mut local_env = env.clone();
+    // bind a variable in the new frame `env`; special variables are bound
+    // dynamically by replacing their global value until `unbind`
+    pub fn bind(
+        &mut self,
+        env: &ExprEnv,
+        symbol: &SymbolRef,
+        value: Expr,
+        special: bool,
+        shadowed: &mut Shadowed,
+    ) -> Result<(), ExprErr> {
+        if symbol.is_constant() {
+            return Err(ExprErr::Cause(format!(
+                "cannot bind constant: {}",
+                symbol.name
+            )));
+        }
+        if special || symbol.is_special() {
+            let old = symbol.set_value(Some(value));
+            shadowed.push((symbol.clone(), old));
+        } else {
+            env.define(symbol, value);
+        }
+        Ok(())
+    }
 
-        for (i, k) in lambda.args.iter().enumerate() {
-            let value = args
-                .get(i)
-                .ok_or(ExprErr::Cause("not found value from env".to_string()))?;
-            local_env.insert(k.clone(), value.clone());
+    pub fn unbind(&mut self, shadowed: Shadowed) {
+        for (symbol, old) in shadowed.into_iter().rev() {
+            symbol.set_value(old);
         }
+    }
 
-        let result = self.eval(&lambda.body, &mut local_env)?;
+    // assign a variable as setq does
+    pub fn set_var(
+        &mut self,
+        env: &ExprEnv,
+        symbol: &SymbolRef,
+        value: Expr,
+    ) -> Result<(), ExprErr> {
+        if symbol.is_constant() {
+            return Err(ExprErr::Cause(format!(
+                "cannot set constant: {}",
+                symbol.name
+            )));
+        }
+        if symbol.is_special() || !env.set(symbol, value.clone()) {
+            symbol.set_value(Some(value));
+        }
+        Ok(())
+    }
 
-        Ok(result)
+    // (lambda (a b) body*)
+    pub fn eval_lambda_form(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        Ok(Expr::Lambda(make_lambda(args, env)?))
     }
 
     // parse defun and store to env
     // (defun add (a b) (+ a b))
     pub fn eval_defun(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
-        if args.len() != 3 {
+        if args.len() < 3 {
             return Err(ExprErr::Cause("unexpected function definition".to_string()));
         }
 
@@ -153,50 +1174,364 @@ impl Evaluator {
             .next()
             .ok_or(ExprErr::Cause("cannot get function name".to_string()))?;
 
-        let name = match symbol {
-            Expr::Symbol(symbol) => Ok(symbol),
-            _ => Err(ExprErr::Cause(format!("invalid symbol: {}", symbol))),
-        }?;
+        let setf_name = setf_function_name(symbol);
+        if !matches!(symbol, Expr::Symbol(_)) && setf_name.is_none() {
+            return Err(ExprErr::Cause(format!("invalid symbol: {}", symbol)));
+        }
 
         let args_expr = itr
             .next()
             .ok_or(ExprErr::Cause("cannot get function args".to_string()))?;
 
-        let args = match args_expr {
-            Expr::List(list) => Ok(list),
-            _ => Err(ExprErr::Cause(format!("invalid list: {}", args_expr))),
-        }?;
-        let args = parse_list_of_symbols(args)?;
+        let args = Rc::new(parse_lambda_list(args_expr)?);
 
-        let body = itr
-            .next()
-            .ok_or(ExprErr::Cause("cannot get function body".to_string()))?;
+        // skip the docstring
+        let mut body = itr.as_slice();
+        if let [Expr::String(_), rest @ ..] = body {
+            if !rest.is_empty() {
+                body = rest;
+            }
+        }
 
         let lambda = Expr::Lambda(Lambda {
             args,
-            body: Rc::new(body.clone()),
+            body: Rc::new(body.to_vec()),
+            env: env.clone(),
         });
-        env.insert(name.clone(), lambda);
+        match (symbol, setf_name) {
+            (Expr::Symbol(name), _) => name.set_function(Some(lambda)),
+            // (defun (setf name) (value arg*) ...)
+            (_, Some(name)) => {
+                self.setf_expanders
+                    .insert(name, SetfExpander::Function(lambda));
+            }
+            _ => unreachable!("checked above"),
+        }
 
-        Ok(Expr::String(name.clone()))
+        Ok(symbol.clone())
     }
 
+    // (setq a 1 b 2)
     pub fn eval_setq(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
-        let first = args
-            .first()
-            .ok_or(ExprErr::Cause("expected first arg".to_string()))?;
-        let key = match first {
-            Expr::Symbol(s) => Ok(s.clone()),
-            _ => Err(ExprErr::Cause("first arg must be symbol".to_string())),
-        }?;
-
-        let second = args
-            .get(1)
-            .ok_or(ExprErr::Cause("expected second arg".to_string()))?;
-        let value = self.eval(second, env)?;
-
-        env.insert(key, value.clone());
-
-        Ok(value.clone())
+        if args.is_empty() || !args.len().is_multiple_of(2) {
+            return Err(ExprErr::Cause(
+                "expected pairs of symbol and value".to_string(),
+            ));
+        }
+
+        let mut value = Expr::Nil;
+        for pair in args.chunks(2) {
+            let key = match &pair[0] {
+                Expr::Symbol(s) => Ok(s.clone()),
+                _ => Err(ExprErr::Cause("first arg must be symbol".to_string())),
+            }?;
+            value = self.eval(&pair[1], env)?;
+            self.set_var(env, &key, value.clone())?;
+        }
+
+        Ok(value)
+    }
+
+    // (progn form*)
+    pub fn eval_progn(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let mut result = Expr::Nil;
+        for form in args {
+            result = self.eval(form, env)?;
+        }
+        Ok(result)
+    }
+
+    // (let ((a 1) (b 2) c) (declare (special a)) body*)
+    // let* evaluates each init form with the previous bindings in scope
+    pub fn eval_let(
+        &mut self,
+        args: &[Expr],
+        env: &mut ExprEnv,
+        sequential: bool,
+    ) -> Result<Expr, ExprErr> {
+        let (bindings, body) = args
+            .split_first()
+            .ok_or(ExprErr::Cause("expected let bindings".to_string()))?;
+        let bindings = bindings
+            .to_vec()?
+            .iter()
+            .map(|binding| match binding {
+                Expr::Symbol(name) => Ok((name.clone(), Expr::Nil)),
+                Expr::Cons(_) => match binding.to_vec()?.as_slice() {
+                    [Expr::Symbol(name)] => Ok((name.clone(), Expr::Nil)),
+                    [Expr::Symbol(name), form] => Ok((name.clone(), form.clone())),
+                    _ => Err(ExprErr::Cause(format!("invalid let binding: {}", binding))),
+                },
+                _ => Err(ExprErr::Cause(format!("invalid let binding: {}", binding))),
+            })
+            .collect::<Result<Vec<(SymbolRef, Expr)>, ExprErr>>()?;
+        let (specials, body) = parse_declarations(body)?;
+
+        let mut local_env = env.extend();
+        let mut shadowed = Shadowed::new();
+        let result = self.eval_let_body(
+            &bindings,
+            &specials,
+            body,
+            env,
+            &mut local_env,
+            sequential,
+            &mut shadowed,
+        );
+        self.unbind(shadowed);
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn eval_let_body(
+        &mut self,
+        bindings: &[(SymbolRef, Expr)],
+        specials: &[SymbolRef],
+        body: &[Expr],
+        env: &mut ExprEnv,
+        local_env: &mut ExprEnv,
+        sequential: bool,
+        shadowed: &mut Shadowed,
+    ) -> Result<Expr, ExprErr> {
+        if sequential {
+            for (name, form) in bindings {
+                let value = self.eval(form, local_env)?;
+                self.bind(local_env, name, value, specials.contains(name), shadowed)?;
+            }
+        } else {
+            let values = bindings
+                .iter()
+                .map(|(_, form)| self.eval(form, env))
+                .collect::<Result<Vec<Expr>, ExprErr>>()?;
+            for ((name, _), value) in bindings.iter().zip(values) {
+                self.bind(local_env, name, value, specials.contains(name), shadowed)?;
+            }
+        }
+        self.eval_progn(body, local_env)
+    }
+
+    // (defvar name [value [doc]]) assigns only when unbound,
+    // (defparameter name value [doc]) always assigns
+    pub fn eval_defvar(
+        &mut self,
+        args: &[Expr],
+        env: &mut ExprEnv,
+        always_assign: bool,
+    ) -> Result<Expr, ExprErr> {
+        let (name, rest) = args
+            .split_first()
+            .ok_or(ExprErr::Cause("expected variable name".to_string()))?;
+        let name = match name {
+            Expr::Symbol(name) => name.clone(),
+            _ => return Err(ExprErr::Cause(format!("invalid symbol: {}", name))),
+        };
+        if name.is_constant() {
+            return Err(ExprErr::Cause(format!(
+                "cannot redefine constant: {}",
+                name.name
+            )));
+        }
+        if always_assign && rest.is_empty() {
+            return Err(ExprErr::Cause(format!("expected value for {}", name.name)));
+        }
+
+        name.proclaim_special();
+        if let Some(form) = rest.first() {
+            if always_assign || name.value().is_none() {
+                let value = self.eval(form, env)?;
+                name.set_value(Some(value));
+            }
+        }
+
+        Ok(Expr::Symbol(name))
+    }
+
+    // (defconstant name value [doc])
+    pub fn eval_defconstant(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let (name, form) = match args {
+            [Expr::Symbol(name), form, ..] => (name.clone(), form),
+            _ => {
+                return Err(ExprErr::Cause(
+                    "expected constant name and value".to_string(),
+                ))
+            }
+        };
+        let value = self.eval(form, env)?;
+        if name.is_constant() && name.value() != Some(value.clone()) {
+            return Err(ExprErr::Cause(format!(
+                "cannot redefine constant: {}",
+                name.name
+            )));
+        }
+
+        name.proclaim_constant();
+        name.set_value(Some(value));
+
+        Ok(Expr::Symbol(name))
+    }
+
+    // (quote a)
+    pub fn eval_quote(&mut self, args: &[Expr]) -> Result<Expr, ExprErr> {
+        match args {
+            [expr] => Ok(expr.clone()),
+            _ => Err(ExprErr::Cause("quote expects exactly one arg".to_string())),
+        }
+    }
+
+    // (multiple-value-list form)
+    pub fn eval_multiple_value_list(
+        &mut self,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        match args {
+            [form] => {
+                let primary = self.eval(form, env)?;
+                Ok(Expr::list(self.take_values(primary)))
+            }
+            _ => Err(ExprErr::Cause(
+                "multiple-value-list expects exactly one arg".to_string(),
+            )),
+        }
+    }
+
+    // (multiple-value-bind (var*) form body*) binds the vars to the values
+    // of form, NIL for the missing ones
+    pub fn eval_multiple_value_bind(
+        &mut self,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        let [vars, form, body @ ..] = args else {
+            return Err(ExprErr::Cause(
+                "multiple-value-bind expects variables and a form".to_string(),
+            ));
+        };
+        let vars = vars
+            .to_vec()?
+            .iter()
+            .map(symbol::symbol_of)
+            .collect::<Result<Vec<SymbolRef>, ExprErr>>()?;
+        let primary = self.eval(form, env)?;
+        let mut values = self.take_values(primary).into_iter();
+        let (specials, body) = parse_declarations(body)?;
+        let mut local_env = env.extend();
+        let mut shadowed = Shadowed::new();
+        let result = vars
+            .iter()
+            .try_for_each(|var| {
+                let value = values.next().unwrap_or(Expr::Nil);
+                self.bind(
+                    &local_env,
+                    var,
+                    value,
+                    specials.contains(var),
+                    &mut shadowed,
+                )
+            })
+            .and_then(|_| self.eval_progn(body, &mut local_env));
+        self.unbind(shadowed);
+        result
+    }
+
+    // (multiple-value-call function form*) calls function with all the
+    // values of the forms
+    pub fn eval_multiple_value_call(
+        &mut self,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        let (function, forms) = args.split_first().ok_or(ExprErr::Cause(
+            "multiple-value-call expects a function".to_string(),
+        ))?;
+        let function = self.eval(function, env)?;
+        let function = self.function_designator(&function)?;
+        let mut call_args = vec![];
+        for form in forms {
+            let primary = self.eval(form, env)?;
+            call_args.extend(self.take_values(primary));
+        }
+        self.apply(&function, &call_args, env)
+    }
+
+    // (multiple-value-prog1 first-form form*) returns the values of the
+    // first form
+    pub fn eval_multiple_value_prog1(
+        &mut self,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        let (first, rest) = args.split_first().ok_or(ExprErr::Cause(
+            "multiple-value-prog1 expects a form".to_string(),
+        ))?;
+        let primary = self.eval(first, env)?;
+        let values = self.take_values(primary);
+        self.eval_progn(rest, env)?;
+        Ok(self.multiple_values(values))
+    }
+
+    // (nth-value n form)
+    pub fn eval_nth_value(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let [n, form] = args else {
+            return Err(ExprErr::Cause(
+                "nth-value expects an index and a form".to_string(),
+            ));
+        };
+        let n = list::index_arg(&self.eval(n, env)?)?;
+        let primary = self.eval(form, env)?;
+        let values = self.take_values(primary);
+        Ok(values.get(n).cloned().unwrap_or(Expr::Nil))
+    }
+
+    // (block name form*)
+    pub fn eval_block(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let (name, body) = args
+            .split_first()
+            .ok_or(ExprErr::Cause("expected block name".to_string()))?;
+        let name = block_name(name)?;
+        self.eval_in_block(&name, body, env)
+    }
+
+    // evaluate forms as a progn, catching (return-from name ...) for this block
+    pub fn eval_in_block(
+        &mut self,
+        name: &str,
+        body: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        match self.eval_progn(body, env) {
+            Err(ExprErr::ReturnFrom(block, value)) if block == name => Ok(value),
+            result => result,
+        }
+    }
+
+    // (return-from name [value])
+    pub fn eval_return_from(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let (name, rest) = args
+            .split_first()
+            .ok_or(ExprErr::Cause("expected block name".to_string()))?;
+        let name = block_name(name)?;
+        let value = match rest.first() {
+            Some(form) => self.eval(form, env)?,
+            None => Expr::Nil,
+        };
+        Err(ExprErr::ReturnFrom(name, value))
+    }
+
+    // (return [value]) is (return-from nil [value])
+    pub fn eval_return(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let value = match args.first() {
+            Some(form) => self.eval(form, env)?,
+            None => Expr::Nil,
+        };
+        Err(ExprErr::ReturnFrom("NIL".to_string(), value))
+    }
+}
+
+fn block_name(expr: &Expr) -> Result<String, ExprErr> {
+    match expr {
+        Expr::Symbol(symbol) => Ok(symbol.name.clone()),
+        Expr::Nil => Ok("NIL".to_string()),
+        _ => Err(ExprErr::Cause(format!("invalid block name: {}", expr))),
     }
 }
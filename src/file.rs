@@ -0,0 +1,436 @@
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{keyword_args, Evaluator, ExprEnv};
+use crate::number::Number;
+use crate::pathname::{file_error, file_name, merged_pathname, Pathname};
+use crate::stream::{column_after, Stream, StreamRef};
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::rc::Rc;
+
+// A stream reading or writing a file, of characters in UTF-8 or of
+// (unsigned-byte 8) bytes
+pub struct FileStream {
+    pub pathname: Pathname,
+    // None once closed
+    file: Option<BufReader<File>>,
+    input: bool,
+    output: bool,
+    binary: bool,
+    // whether opening the file created it, so that aborting deletes it
+    created: bool,
+    column: usize,
+    // the character last read, and the one put back by unread-char
+    last: Option<char>,
+    unread: Option<char>,
+}
+
+impl FileStream {
+    fn file(&mut self) -> Result<&mut BufReader<File>, ExprErr> {
+        let closed = || ExprErr::Cause(format!("{} is closed", self.pathname.namestring()));
+        match &mut self.file {
+            Some(file) => Ok(file),
+            None => Err(closed()),
+        }
+    }
+
+    fn check(&self, output: bool, binary: bool) -> Result<(), ExprErr> {
+        let name = self.pathname.namestring();
+        if output && !self.output {
+            return Err(ExprErr::Cause(format!("{} is not output stream", name)));
+        }
+        if !output && !self.input {
+            return Err(ExprErr::Cause(format!("{} is not input stream", name)));
+        }
+        match (binary, self.binary) {
+            (true, false) => Err(ExprErr::Cause(format!("{} is not binary stream", name))),
+            (false, true) => Err(ExprErr::Cause(format!("{} is not character stream", name))),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), ExprErr> {
+        let name = self.pathname.namestring();
+        let file = self.file()?;
+        // drop what was read ahead so that the write goes at the position
+        if !file.buffer().is_empty() {
+            let position = file
+                .stream_position()
+                .and_then(|position| file.seek(SeekFrom::Start(position)));
+            position.map_err(|err| file_error("write", &name, err))?;
+        }
+        file.get_mut()
+            .write_all(bytes)
+            .map_err(|err| file_error("write", &name, err))
+    }
+
+    pub fn write_str(&mut self, s: &str) -> Result<(), ExprErr> {
+        self.check(true, false)?;
+        self.write_bytes(s.as_bytes())?;
+        self.column = column_after(self.column, s);
+        Ok(())
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, ExprErr> {
+        let name = self.pathname.namestring();
+        let file = self.file()?;
+        let buffer = file
+            .fill_buf()
+            .map_err(|err| file_error("read", &name, err))?;
+        let Some(&byte) = buffer.first() else {
+            return Ok(None);
+        };
+        file.consume(1);
+        Ok(Some(byte))
+    }
+
+    // the next character decoded from UTF-8, or None at end of file
+    pub fn read_char(&mut self) -> Result<Option<char>, ExprErr> {
+        self.check(false, false)?;
+        if let Some(ch) = self.unread.take() {
+            self.last = Some(ch);
+            return Ok(Some(ch));
+        }
+        let Some(first) = self.read_byte()? else {
+            return Ok(None);
+        };
+        let width = match first.leading_ones() {
+            2..=4 => first.leading_ones() as usize,
+            _ => 1,
+        };
+        let mut bytes = vec![first; width];
+        for byte in bytes.iter_mut().skip(1) {
+            *byte = self.read_byte()?.unwrap_or_default();
+        }
+        let ch = std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+        self.last = Some(ch);
+        Ok(Some(ch))
+    }
+
+    pub fn unread_char(&mut self) {
+        self.unread = self.last.take();
+    }
+
+    pub fn unread_str(&mut self, text: &str) {
+        self.unread = None;
+        self.last = None;
+        if let Some(file) = &mut self.file {
+            _ = file.seek_relative(-(text.len() as i64));
+        }
+    }
+
+    fn position(&mut self) -> Result<u64, ExprErr> {
+        let name = self.pathname.namestring();
+        let unread = self.unread.map_or(0, |ch| ch.len_utf8() as u64);
+        let position = self
+            .file()?
+            .stream_position()
+            .map_err(|err| file_error("get position of", &name, err))?;
+        Ok(position - unread)
+    }
+
+    fn set_position(&mut self, position: SeekFrom) -> Result<(), ExprErr> {
+        let name = self.pathname.namestring();
+        self.unread = None;
+        self.last = None;
+        self.file()?
+            .seek(position)
+            .map_err(|err| file_error("set position of", &name, err))?;
+        Ok(())
+    }
+
+    fn length(&mut self) -> Result<u64, ExprErr> {
+        let name = self.pathname.namestring();
+        let metadata = self.file()?.get_ref().metadata();
+        Ok(metadata
+            .map_err(|err| file_error("get length of", &name, err))?
+            .len())
+    }
+
+    // close the file, deleting it when aborting after creating it
+    fn close(&mut self, abort: bool) {
+        if self.file.take().is_some() && abort && self.created {
+            _ = fs::remove_file(self.pathname.namestring());
+        }
+    }
+}
+
+fn file_stream_arg<'a>(
+    name: &str,
+    stream: &'a Expr,
+) -> Result<std::cell::RefMut<'a, Stream>, ExprErr> {
+    match stream {
+        Expr::Stream(file) if matches!(&*file.borrow(), Stream::File(_)) => Ok(file.borrow_mut()),
+        _ => Err(ExprErr::Cause(format!(
+            "{}: {} is not file stream",
+            name, stream
+        ))),
+    }
+}
+
+// the file stream in a RefMut from file_stream_arg
+fn file_stream(stream: &mut Stream) -> &mut FileStream {
+    match stream {
+        Stream::File(file) => file,
+        _ => unreachable!("checked by file_stream_arg"),
+    }
+}
+
+fn keyword_option(options: &std::collections::HashMap<String, &Expr>, key: &str) -> Option<String> {
+    options.get(key).map(|value| match value {
+        Expr::Symbol(symbol) => symbol.name.clone(),
+        Expr::Nil => "NIL".to_string(),
+        value => value.to_string(),
+    })
+}
+
+fn open_file(args: &[Expr]) -> Result<Expr, ExprErr> {
+    let Some((filespec, options)) = args.split_first() else {
+        return Err(ExprErr::Cause("open expects a pathname".to_string()));
+    };
+    let options = keyword_args(
+        "open",
+        options,
+        &[
+            "DIRECTION",
+            "ELEMENT-TYPE",
+            "IF-EXISTS",
+            "IF-DOES-NOT-EXIST",
+            "EXTERNAL-FORMAT",
+        ],
+    )?;
+    let direction = keyword_option(&options, "DIRECTION").unwrap_or("INPUT".to_string());
+    let (input, output) = match direction.as_str() {
+        "INPUT" | "PROBE" => (true, false),
+        "OUTPUT" => (false, true),
+        "IO" => (true, true),
+        _ => return Err(ExprErr::Cause(format!("invalid direction: {}", direction))),
+    };
+    let binary = match options.get("ELEMENT-TYPE") {
+        None => false,
+        Some(typ @ Expr::Cons(_)) => match typ.to_vec()?.as_slice() {
+            [Expr::Symbol(name), Expr::Number(Number::Integer(8))]
+                if name.name == "UNSIGNED-BYTE" =>
+            {
+                true
+            }
+            _ => return Err(ExprErr::Cause(format!("unsupported element type: {}", typ))),
+        },
+        Some(Expr::Symbol(name))
+            if ["CHARACTER", "BASE-CHAR", "DEFAULT"].contains(&name.name.as_str()) =>
+        {
+            false
+        }
+        Some(typ) => return Err(ExprErr::Cause(format!("unsupported element type: {}", typ))),
+    };
+    let pathname = merged_pathname(filespec)?;
+    let name = file_name(&Expr::Pathname(pathname.clone()))?;
+    let exists = fs::metadata(&name).is_ok();
+    let if_exists = keyword_option(&options, "IF-EXISTS").unwrap_or("ERROR".to_string());
+    let if_does_not_exist = keyword_option(&options, "IF-DOES-NOT-EXIST").unwrap_or(
+        match (output, if_exists.as_str()) {
+            (true, "OVERWRITE" | "APPEND") | (false, _) => "ERROR".to_string(),
+            (true, _) => "CREATE".to_string(),
+        },
+    );
+    let mut open_options = OpenOptions::new();
+    open_options.read(input).write(output);
+    let mut created = false;
+    if exists && output {
+        match if_exists.as_str() {
+            "ERROR" => return Err(ExprErr::Cause(format!("file already exists: {}", name))),
+            "NIL" => return Ok(Expr::Nil),
+            "SUPERSEDE" | "NEW-VERSION" | "RENAME-AND-DELETE" => {
+                open_options.truncate(true);
+            }
+            "RENAME" => {
+                let backup = format!("{}.bak", name);
+                fs::rename(&name, &backup).map_err(|err| file_error("rename", &name, err))?;
+                open_options.create(true);
+            }
+            "OVERWRITE" => {}
+            "APPEND" => {
+                open_options.append(true);
+            }
+            _ => return Err(ExprErr::Cause(format!("invalid if-exists: {}", if_exists))),
+        }
+    } else if !exists {
+        match if_does_not_exist.as_str() {
+            "ERROR" => return Err(ExprErr::Cause(format!("file does not exist: {}", name))),
+            "NIL" => return Ok(Expr::Nil),
+            // write(true) is needed to create a file opened for input
+            "CREATE" => {
+                open_options.write(true).create(true);
+                created = true;
+            }
+            _ => {
+                return Err(ExprErr::Cause(format!(
+                    "invalid if-does-not-exist: {}",
+                    if_does_not_exist
+                )))
+            }
+        }
+    }
+    let file = open_options
+        .open(&name)
+        .map_err(|err| file_error("open", &name, err))?;
+    let stream = FileStream {
+        pathname,
+        // a probe stream is returned closed
+        file: Some(BufReader::new(file)).filter(|_| direction != "PROBE"),
+        input,
+        output,
+        binary,
+        created,
+        column: 0,
+        last: None,
+        unread: None,
+    };
+    Ok(Expr::Stream(Rc::new(RefCell::new(Stream::File(stream)))))
+}
+
+// (open filespec &key direction element-type if-exists if-does-not-exist
+// external-format)
+pub fn open(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    open_file(args)
+}
+
+fn close_stream(stream: &StreamRef, abort: bool) {
+    if let Stream::File(file) = &mut *stream.borrow_mut() {
+        file.close(abort);
+    }
+}
+
+// (close stream &key abort)
+pub fn close(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [Expr::Stream(stream), options @ ..] => {
+            let options = keyword_args("close", options, &["ABORT"])?;
+            let abort = options.get("ABORT").is_some_and(|abort| !abort.is_nil());
+            close_stream(stream, abort);
+            Ok(Expr::True)
+        }
+        _ => Err(ExprErr::Cause("close expects a stream".to_string())),
+    }
+}
+
+// (file-length stream) in bytes
+pub fn file_length(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [stream] => {
+            let mut stream = file_stream_arg("file-length", stream)?;
+            let length = file_stream(&mut stream).length()?;
+            Ok(Expr::Number(Number::Integer(length as i64)))
+        }
+        _ => Err(ExprErr::Cause("file-length expects a stream".to_string())),
+    }
+}
+
+// (file-position stream [position]) is the position in bytes, or sets it to
+// an index, :start or :end
+pub fn file_position(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (stream, position) = match args {
+        [stream] => (stream, None),
+        [stream, position] => (stream, Some(position)),
+        _ => {
+            return Err(ExprErr::Cause(
+                "file-position expects a stream and a position".to_string(),
+            ))
+        }
+    };
+    let mut stream = file_stream_arg("file-position", stream)?;
+    let file = file_stream(&mut stream);
+    let position = match position {
+        None => return Ok(Expr::Number(Number::Integer(file.position()? as i64))),
+        Some(Expr::Number(Number::Integer(n))) if *n >= 0 => SeekFrom::Start(*n as u64),
+        Some(Expr::Symbol(symbol)) if symbol.is_keyword() && symbol.name == "START" => {
+            SeekFrom::Start(0)
+        }
+        Some(Expr::Symbol(symbol)) if symbol.is_keyword() && symbol.name == "END" => {
+            SeekFrom::End(0)
+        }
+        Some(position) => return Err(ExprErr::Cause(format!("{} is not file position", position))),
+    };
+    file.set_position(position)?;
+    Ok(Expr::True)
+}
+
+// (read-byte stream [eof-error-p [eof-value]])
+pub fn read_byte(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (stream, eof_error, eof_value) = match args {
+        [stream, rest @ ..] if rest.len() <= 2 => (
+            stream,
+            !matches!(rest.first(), Some(Expr::Nil)),
+            rest.get(1).cloned().unwrap_or(Expr::Nil),
+        ),
+        _ => return Err(ExprErr::Cause("read-byte expects a stream".to_string())),
+    };
+    let mut stream = file_stream_arg("read-byte", stream)?;
+    let file = file_stream(&mut stream);
+    file.check(false, true)?;
+    match file.read_byte()? {
+        Some(byte) => Ok(Expr::Number(Number::Integer(byte as i64))),
+        None if eof_error => Err(ExprErr::Cause(format!(
+            "end of file on {}",
+            file.pathname.namestring()
+        ))),
+        None => Ok(eof_value),
+    }
+}
+
+// (write-byte byte stream)
+pub fn write_byte(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [byte @ Expr::Number(Number::Integer(n)), stream] if (0..=255).contains(n) => {
+            let mut stream = file_stream_arg("write-byte", stream)?;
+            let file = file_stream(&mut stream);
+            file.check(true, true)?;
+            file.write_bytes(&[*n as u8])?;
+            Ok(byte.clone())
+        }
+        _ => Err(ExprErr::Cause(
+            "write-byte expects a byte and a stream".to_string(),
+        )),
+    }
+}
+
+impl Evaluator {
+    // (with-open-file (var filespec options*) body*) evaluates body with var
+    // bound to the open file, which is closed afterwards
+    pub fn eval_with_open_file(
+        &mut self,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        let (spec, body) = args
+            .split_first()
+            .ok_or(ExprErr::Cause("expected stream variable".to_string()))?;
+        let (var, stream) = match spec.to_vec()?.as_slice() {
+            [Expr::Symbol(var), open_args @ ..] if !open_args.is_empty() => {
+                let open_args = open_args
+                    .iter()
+                    .map(|arg| self.eval(arg, env))
+                    .collect::<Result<Vec<Expr>, ExprErr>>()?;
+                (var.clone(), open_file(&open_args)?)
+            }
+            _ => {
+                return Err(ExprErr::Cause(format!(
+                    "invalid stream variable spec: {}",
+                    spec
+                )))
+            }
+        };
+        let result = self.eval_with_stream(&var, stream.clone(), body, env);
+        if let Expr::Stream(stream) = &stream {
+            close_stream(stream, result.is_err());
+        }
+        result
+    }
+}
@@ -0,0 +1,1045 @@
+use crate::ast::{char_name, Expr, ExprErr};
+use crate::eval::{Evaluator, ExprEnv};
+use crate::hash::eql;
+use crate::math::integer_expr;
+use crate::number::Number;
+use crate::printer::print_object;
+use crate::stream::{column_after, column_of, write_to};
+
+// a prefix parameter of a directive
+#[derive(Clone)]
+enum Param {
+    Default,
+    Value(Expr),
+    // V takes the parameter from the next argument
+    Arg,
+    // # is the number of remaining arguments
+    Remaining,
+}
+
+struct Directive {
+    params: Vec<Param>,
+    colon: bool,
+    at: bool,
+    ch: char,
+}
+
+enum Item {
+    Text(String),
+    Directive(Directive),
+    // ~{ body ~}, where ~:} runs the body at least once
+    Iteration(Directive, Vec<Item>, bool),
+    // ~[ clause ~; clause ~], where ~:; makes the last clause the default
+    Conditional(Directive, Vec<Vec<Item>>, bool),
+}
+
+fn format_error(message: &str) -> ExprErr {
+    ExprErr::Cause(format!("format: {}", message))
+}
+
+struct ControlParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl ControlParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    // items up to one of the `terminators` directives, which is returned;
+    // None at the end of the control string
+    fn parse_items(
+        &mut self,
+        terminators: &[char],
+    ) -> Result<(Vec<Item>, Option<Directive>), ExprErr> {
+        let mut items = vec![];
+        let mut text = String::new();
+        while let Some(ch) = self.peek() {
+            self.pos += 1;
+            if ch != '~' {
+                text.push(ch);
+                continue;
+            }
+            let directive = self.parse_directive()?;
+            if directive.ch == '\n' {
+                // ~newline skips the newline and the whitespace after it;
+                // ~:newline keeps the whitespace, ~@newline the newline
+                if directive.at {
+                    text.push('\n');
+                }
+                if !directive.colon {
+                    while self
+                        .peek()
+                        .is_some_and(|ch| ch.is_whitespace() && ch != '\n')
+                    {
+                        self.pos += 1;
+                    }
+                }
+                continue;
+            }
+            if !text.is_empty() {
+                items.push(Item::Text(std::mem::take(&mut text)));
+            }
+            if terminators.contains(&directive.ch) {
+                return Ok((items, Some(directive)));
+            }
+            match directive.ch {
+                '{' => {
+                    let (body, end) = self.parse_items(&['}'])?;
+                    let end = end.ok_or(format_error("unterminated ~{"))?;
+                    items.push(Item::Iteration(directive, body, end.colon));
+                }
+                '[' => {
+                    let mut clauses = vec![];
+                    let mut has_default = false;
+                    loop {
+                        let (clause, end) = self.parse_items(&[';', ']'])?;
+                        let end = end.ok_or(format_error("unterminated ~["))?;
+                        clauses.push(clause);
+                        if end.ch == ']' {
+                            break;
+                        }
+                        has_default = end.colon;
+                    }
+                    items.push(Item::Conditional(directive, clauses, has_default));
+                }
+                '}' | ']' | ';' => {
+                    return Err(format_error(&format!("unexpected ~{}", directive.ch)))
+                }
+                _ => items.push(Item::Directive(directive)),
+            }
+        }
+        if !text.is_empty() {
+            items.push(Item::Text(text));
+        }
+        Ok((items, None))
+    }
+
+    // the parameters, modifiers and character after a tilde
+    fn parse_directive(&mut self) -> Result<Directive, ExprErr> {
+        let mut params = vec![];
+        loop {
+            let param = match self.peek() {
+                Some('\'') => {
+                    self.pos += 1;
+                    let ch = self
+                        .peek()
+                        .ok_or(format_error("missing parameter character"))?;
+                    self.pos += 1;
+                    Some(Param::Value(Expr::Char(ch)))
+                }
+                Some('v' | 'V') => {
+                    self.pos += 1;
+                    Some(Param::Arg)
+                }
+                Some('#') => {
+                    self.pos += 1;
+                    Some(Param::Remaining)
+                }
+                Some(ch) if ch.is_ascii_digit() || ch == '-' || ch == '+' => {
+                    let start = self.pos;
+                    self.pos += 1;
+                    while self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+                        self.pos += 1;
+                    }
+                    let text = self.chars[start..self.pos].iter().collect::<String>();
+                    let num = text
+                        .parse::<i64>()
+                        .map_err(|_| format_error(&format!("invalid parameter {}", text)))?;
+                    Some(Param::Value(integer_expr(num)))
+                }
+                _ => None,
+            };
+            if self.peek() == Some(',') {
+                self.pos += 1;
+                params.push(param.unwrap_or(Param::Default));
+            } else {
+                params.extend(param);
+                break;
+            }
+        }
+        let (mut colon, mut at) = (false, false);
+        loop {
+            match self.peek() {
+                Some(':') => colon = true,
+                Some('@') => at = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let ch = self
+            .peek()
+            .ok_or(format_error("control string ends within a directive"))?;
+        self.pos += 1;
+        Ok(Directive {
+            params,
+            colon,
+            at,
+            ch: ch.to_ascii_uppercase(),
+        })
+    }
+}
+
+fn parse_control(control: &str) -> Result<Vec<Item>, ExprErr> {
+    let mut parser = ControlParser {
+        chars: control.chars().collect(),
+        pos: 0,
+    };
+    Ok(parser.parse_items(&[])?.0)
+}
+
+// the arguments consumed by directives
+struct Args {
+    items: Vec<Expr>,
+    pos: usize,
+}
+
+impl Args {
+    fn new(items: Vec<Expr>) -> Args {
+        Args { items, pos: 0 }
+    }
+
+    fn next(&mut self) -> Result<Expr, ExprErr> {
+        let arg = self
+            .items
+            .get(self.pos)
+            .cloned()
+            .ok_or(format_error("no more arguments"))?;
+        self.pos += 1;
+        Ok(arg)
+    }
+
+    fn peek(&self) -> Result<&Expr, ExprErr> {
+        self.items
+            .get(self.pos)
+            .ok_or(format_error("no more arguments"))
+    }
+
+    fn remaining(&self) -> usize {
+        self.items.len().saturating_sub(self.pos)
+    }
+
+    fn goto(&mut self, pos: isize) -> Result<(), ExprErr> {
+        if pos < 0 || pos as usize > self.items.len() {
+            return Err(format_error("argument index out of range"));
+        }
+        self.pos = pos as usize;
+        Ok(())
+    }
+}
+
+// how running a list of items ended
+#[derive(PartialEq)]
+enum Flow {
+    Done,
+    // ~^ ends the enclosing iteration step or the whole format
+    Escape,
+    // ~:^ ends the whole ~:{ iteration
+    EscapeAll,
+}
+
+// resolved parameters of a directive
+struct Params(Vec<Option<Expr>>);
+
+impl Params {
+    fn int(&self, i: usize, default: i64) -> Result<i64, ExprErr> {
+        match self.0.get(i) {
+            Some(Some(Expr::Number(Number::Integer(n)))) => Ok(*n),
+            Some(Some(param)) => Err(format_error(&format!("{} is not integer", param))),
+            _ => Ok(default),
+        }
+    }
+
+    fn count(&self, i: usize, default: usize) -> Result<usize, ExprErr> {
+        Ok(self.int(i, default as i64)?.max(0) as usize)
+    }
+
+    fn optional(&self, i: usize) -> Result<Option<usize>, ExprErr> {
+        match self.0.get(i) {
+            Some(Some(_)) => Ok(Some(self.count(i, 0)?)),
+            _ => Ok(None),
+        }
+    }
+
+    fn char(&self, i: usize, default: char) -> Result<char, ExprErr> {
+        match self.0.get(i) {
+            Some(Some(Expr::Char(ch))) => Ok(*ch),
+            Some(Some(param)) => Err(format_error(&format!("{} is not character", param))),
+            _ => Ok(default),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.iter().all(Option::is_none)
+    }
+}
+
+fn integer_of(expr: &Expr) -> Option<i64> {
+    match expr {
+        Expr::Number(Number::Integer(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+// pad `s` to at least `mincol` columns: `minpad` pad characters, then
+// `colinc` at a time, on the left with `left`
+fn pad(s: &str, mincol: usize, colinc: usize, minpad: usize, padchar: char, left: bool) -> String {
+    let mut len = s.chars().count() + minpad;
+    let mut padding = padchar.to_string().repeat(minpad);
+    while len < mincol {
+        let step = colinc.max(1);
+        padding.push_str(&padchar.to_string().repeat(step));
+        len += step;
+    }
+    if left {
+        padding + s
+    } else {
+        s.to_string() + &padding
+    }
+}
+
+fn radix_digits(mut n: u64, radix: u32) -> String {
+    if n == 0 {
+        return "0".to_string();
+    }
+    let mut digits = vec![];
+    while n > 0 {
+        let digit = std::char::from_digit((n % radix as u64) as u32, radix).unwrap_or('?');
+        digits.push(digit.to_ascii_uppercase());
+        n /= radix as u64;
+    }
+    digits.iter().rev().collect()
+}
+
+fn group_digits(digits: &str, comma: char, interval: usize) -> String {
+    let chars = digits.chars().collect::<Vec<char>>();
+    let mut result = String::new();
+    for (i, ch) in chars.iter().enumerate() {
+        if i > 0 && (chars.len() - i) % interval.max(1) == 0 {
+            result.push(comma);
+        }
+        result.push(*ch);
+    }
+    result
+}
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+const SCALES: [&str; 7] = [
+    "",
+    "thousand",
+    "million",
+    "billion",
+    "trillion",
+    "quadrillion",
+    "quintillion",
+];
+
+fn below_thousand(n: u64) -> String {
+    let mut words = vec![];
+    if n >= 100 {
+        words.push(format!("{} hundred", ONES[(n / 100) as usize]));
+    }
+    match n % 100 {
+        0 => {}
+        rest if rest < 20 => words.push(ONES[rest as usize].to_string()),
+        rest if rest % 10 == 0 => words.push(TENS[(rest / 10) as usize].to_string()),
+        rest => words.push(format!(
+            "{}-{}",
+            TENS[(rest / 10) as usize],
+            ONES[(rest % 10) as usize]
+        )),
+    }
+    words.join(" ")
+}
+
+// ~R: one thousand two hundred thirty-four
+fn cardinal(n: i64) -> String {
+    if n < 0 {
+        return format!("negative {}", cardinal_of(n.unsigned_abs()));
+    }
+    cardinal_of(n as u64)
+}
+
+fn cardinal_of(mut n: u64) -> String {
+    if n == 0 {
+        return ONES[0].to_string();
+    }
+    let mut groups = vec![];
+    let mut scale = 0;
+    while n > 0 {
+        let group = n % 1000;
+        if group > 0 {
+            let words = below_thousand(group);
+            groups.push(match SCALES[scale] {
+                "" => words,
+                name => format!("{} {}", words, name),
+            });
+        }
+        n /= 1000;
+        scale += 1;
+    }
+    groups.reverse();
+    groups.join(" ")
+}
+
+// ~:R: one thousand two hundred thirty-fourth
+fn ordinal(n: i64) -> String {
+    let words = cardinal(n);
+    let split = words.rfind([' ', '-']).map_or(0, |i| i + 1);
+    let (head, last) = words.split_at(split);
+    let last = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        word if word.ends_with('y') => format!("{}ieth", &word[..word.len() - 1]),
+        word => format!("{}th", word),
+    };
+    format!("{}{}", head, last)
+}
+
+// ~@R and, with old-style fours and nines, ~:@R
+fn roman(n: i64, old: bool) -> Result<String, ExprErr> {
+    if !(1..=3999).contains(&n) {
+        return Err(format_error(&format!(
+            "{} cannot be printed in Roman numerals",
+            n
+        )));
+    }
+    let numerals: &[(i64, &str)] = if old {
+        &[
+            (1000, "M"),
+            (500, "D"),
+            (100, "C"),
+            (50, "L"),
+            (10, "X"),
+            (5, "V"),
+            (1, "I"),
+        ]
+    } else {
+        &[
+            (1000, "M"),
+            (900, "CM"),
+            (500, "D"),
+            (400, "CD"),
+            (100, "C"),
+            (90, "XC"),
+            (50, "L"),
+            (40, "XL"),
+            (10, "X"),
+            (9, "IX"),
+            (5, "V"),
+            (4, "IV"),
+            (1, "I"),
+        ]
+    };
+    let mut n = n;
+    let mut result = String::new();
+    for (value, numeral) in numerals {
+        while n >= *value {
+            result.push_str(numeral);
+            n -= value;
+        }
+    }
+    Ok(result)
+}
+
+fn sign_of(x: f64, always: bool) -> &'static str {
+    if x.is_sign_negative() && x != 0.0 {
+        "-"
+    } else if always {
+        "+"
+    } else {
+        ""
+    }
+}
+
+// the digits of a fixed-format number without its sign
+fn fixed_digits(x: f64, width: Option<usize>, digits: Option<usize>, sign_len: usize) -> String {
+    let x = x.abs();
+    match (width, digits) {
+        (_, Some(digits)) => point_digits(x, digits),
+        (Some(width), None) => {
+            let int_len = format!("{:.0}", x.trunc()).len();
+            let digits = width.saturating_sub(int_len + 1 + sign_len);
+            let s = point_digits(x, digits);
+            // drop trailing zeros beyond the first decimal
+            let trimmed = s.trim_end_matches('0');
+            match trimmed.ends_with('.') {
+                true if digits > 0 => format!("{}0", trimmed),
+                _ => trimmed.to_string(),
+            }
+        }
+        (None, None) => {
+            let s = x.to_string();
+            match s.contains('.') {
+                true => s,
+                false => format!("{}.0", s),
+            }
+        }
+    }
+}
+
+// x with `digits` digits after the point, which is printed even when
+// there are none, rounding halves away from zero as ~F does
+fn point_digits(x: f64, digits: usize) -> String {
+    match digits {
+        0 => format!("{}.", x.round()),
+        _ => format!("{:.*}", digits, x),
+    }
+}
+
+// the digits and exponent of an exponential-format number without its
+// sign, with `k` digits before the point
+fn exponential_digits(x: f64, digits: Option<usize>, k: i64) -> (String, i64) {
+    let x = x.abs();
+    let significant = digits.map(|d| {
+        if k > 0 {
+            d + 1
+        } else {
+            (d as i64 + k).max(1) as usize
+        }
+    });
+    let formatted = match significant {
+        Some(n) => format!("{:.*e}", n - 1, x),
+        None => format!("{:e}", x),
+    };
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent = exponent.parse::<i64>().unwrap_or(0);
+    let mut figures = mantissa.replace('.', "");
+    let mantissa = if k > 0 {
+        let k = k as usize;
+        while figures.len() < k {
+            figures.push('0');
+        }
+        let (int, frac) = figures.split_at(k);
+        match (frac.is_empty(), digits) {
+            (true, None) => format!("{}.0", int),
+            _ => format!("{}.{}", int, frac),
+        }
+    } else {
+        format!("0.{}{}", "0".repeat((-k) as usize), figures)
+    };
+    (mantissa, exponent - (k - 1))
+}
+
+fn is_integer_directive(ch: char) -> bool {
+    matches!(ch, 'D' | 'B' | 'O' | 'X' | 'R')
+}
+
+struct Formatter {
+    out: String,
+    // column of the destination before the output
+    start_column: usize,
+    // sublists left in the innermost ~:{ iteration, for ~:^
+    sublists_left: usize,
+}
+
+impl Formatter {
+    fn column(&self) -> usize {
+        column_after(self.start_column, &self.out)
+    }
+
+    fn fresh_line(&mut self) {
+        if self.column() != 0 {
+            self.out.push('\n');
+        }
+    }
+
+    fn params(&self, directive: &Directive, args: &mut Args) -> Result<Params, ExprErr> {
+        let mut params = vec![];
+        for param in &directive.params {
+            params.push(match param {
+                Param::Default => None,
+                Param::Value(value) => Some(value.clone()),
+                Param::Arg => Some(args.next()?).filter(|arg| !arg.is_nil()),
+                Param::Remaining => Some(integer_expr(args.remaining() as i64)),
+            });
+        }
+        Ok(Params(params))
+    }
+
+    fn run(&mut self, items: &[Item], args: &mut Args) -> Result<Flow, ExprErr> {
+        for item in items {
+            let flow = match item {
+                Item::Text(text) => {
+                    self.out.push_str(text);
+                    Flow::Done
+                }
+                Item::Directive(directive) => self.directive(directive, args)?,
+                Item::Iteration(directive, body, at_least_once) => {
+                    self.iteration(directive, body, *at_least_once, args)?;
+                    Flow::Done
+                }
+                Item::Conditional(directive, clauses, has_default) => {
+                    self.conditional(directive, clauses, *has_default, args)?
+                }
+            };
+            if flow != Flow::Done {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Done)
+    }
+
+    fn directive(&mut self, directive: &Directive, args: &mut Args) -> Result<Flow, ExprErr> {
+        let params = self.params(directive, args)?;
+        match directive.ch {
+            'A' | 'S' => {
+                let arg = args.next()?;
+                let s = match arg {
+                    Expr::Nil if directive.colon => "()".to_string(),
+                    _ => print_object(&arg, directive.ch == 'S')?,
+                };
+                self.out.push_str(&pad(
+                    &s,
+                    params.count(0, 0)?,
+                    params.count(1, 1)?,
+                    params.count(2, 0)?,
+                    params.char(3, ' ')?,
+                    directive.at,
+                ));
+            }
+            ch if is_integer_directive(ch) => self.integer(directive, &params, args)?,
+            'C' => {
+                let arg = args.next()?;
+                let ch = match arg {
+                    Expr::Char(ch) => ch,
+                    _ => return Err(format_error(&format!("{} is not character", arg))),
+                };
+                match (directive.colon, directive.at) {
+                    (_, true) => self.out.push_str(&print_object(&arg, true)?),
+                    (true, false) => self.out.push_str(char_name(ch).unwrap_or(&ch.to_string())),
+                    (false, false) => self.out.push(ch),
+                }
+            }
+            'F' => self.fixed(directive, &params, args)?,
+            'E' => self.exponential(directive, &params, args)?,
+            '$' => self.monetary(directive, &params, args)?,
+            'P' => {
+                if directive.colon {
+                    args.goto(args.pos as isize - 1)?;
+                }
+                let plural = !eql(&args.next()?, &integer_expr(1));
+                self.out.push_str(match (directive.at, plural) {
+                    (false, false) => "",
+                    (false, true) => "s",
+                    (true, false) => "y",
+                    (true, true) => "ies",
+                });
+            }
+            '%' => self.out.push_str(&"\n".repeat(params.count(0, 1)?)),
+            '&' => {
+                let count = params.count(0, 1)?;
+                if count > 0 {
+                    self.fresh_line();
+                    self.out.push_str(&"\n".repeat(count - 1));
+                }
+            }
+            '|' => self.out.push_str(&"\x0c".repeat(params.count(0, 1)?)),
+            '~' => self.out.push_str(&"~".repeat(params.count(0, 1)?)),
+            'T' => {
+                let column = self.column();
+                let target = if directive.at {
+                    let target = column + params.count(0, 1)?;
+                    let colinc = params.count(1, 1)?;
+                    target.div_ceil(colinc.max(1)) * colinc.max(1)
+                } else {
+                    let colnum = params.count(0, 1)?;
+                    let colinc = params.count(1, 1)?;
+                    match (column < colnum, colinc) {
+                        (true, _) => colnum,
+                        (false, 0) => column,
+                        (false, colinc) => colnum + (column - colnum) / colinc * colinc + colinc,
+                    }
+                };
+                self.out
+                    .push_str(&" ".repeat(target.saturating_sub(column)));
+            }
+            '*' => match (directive.colon, directive.at) {
+                (_, true) => args.goto(params.int(0, 0)? as isize)?,
+                (true, false) => args.goto(args.pos as isize - params.int(0, 1)? as isize)?,
+                (false, false) => args.goto(args.pos as isize + params.int(0, 1)? as isize)?,
+            },
+            '?' => {
+                let control = self.control_arg(args)?;
+                if directive.at {
+                    return self.run(&control, args);
+                }
+                let mut sub_args = Args::new(args.next()?.to_vec()?);
+                self.run(&control, &mut sub_args)?;
+            }
+            '^' => {
+                let stop = match params.0.as_slice() {
+                    [] | [None] => match directive.colon {
+                        true => self.sublists_left == 0,
+                        false => args.remaining() == 0,
+                    },
+                    [_] => params.int(0, 0)? == 0,
+                    [_, _] => params.int(0, 0)? == params.int(1, 0)?,
+                    _ => {
+                        let (a, b, c) = (params.int(0, 0)?, params.int(1, 0)?, params.int(2, 0)?);
+                        a <= b && b <= c
+                    }
+                };
+                if stop {
+                    return Ok(if directive.colon {
+                        Flow::EscapeAll
+                    } else {
+                        Flow::Escape
+                    });
+                }
+            }
+            ch => return Err(format_error(&format!("unknown directive ~{}", ch))),
+        }
+        Ok(Flow::Done)
+    }
+
+    fn control_arg(&self, args: &mut Args) -> Result<Vec<Item>, ExprErr> {
+        match args.next()? {
+            Expr::String(control) => parse_control(&control.borrow()),
+            arg => Err(format_error(&format!("{} is not control string", arg))),
+        }
+    }
+
+    // ~mincol,padchar,commachar,comma-intervalD and ~B ~O ~X, and ~radix,...R
+    fn integer(
+        &mut self,
+        directive: &Directive,
+        params: &Params,
+        args: &mut Args,
+    ) -> Result<(), ExprErr> {
+        let (radix, params) = match directive.ch {
+            'D' => (10, Params(params.0.clone())),
+            'B' => (2, Params(params.0.clone())),
+            'O' => (8, Params(params.0.clone())),
+            'X' => (16, Params(params.0.clone())),
+            _ => {
+                let arg = args.next()?;
+                if params.is_empty() {
+                    let n =
+                        integer_of(&arg).ok_or(format_error(&format!("{} is not integer", arg)))?;
+                    let words = match (directive.colon, directive.at) {
+                        (false, false) => cardinal(n),
+                        (true, false) => ordinal(n),
+                        (false, true) => roman(n, false)?,
+                        (true, true) => roman(n, true)?,
+                    };
+                    self.out.push_str(&words);
+                    return Ok(());
+                }
+                args.goto(args.pos as isize - 1)?;
+                let radix = params.count(0, 10)?;
+                if !(2..=36).contains(&radix) {
+                    return Err(format_error(&format!("invalid radix {}", radix)));
+                }
+                (
+                    radix as u32,
+                    Params(params.0.iter().skip(1).cloned().collect()),
+                )
+            }
+        };
+        let arg = args.next()?;
+        let mincol = params.count(0, 0)?;
+        let padchar = params.char(1, ' ')?;
+        let s = match integer_of(&arg) {
+            Some(n) => {
+                let mut digits = radix_digits(n.unsigned_abs(), radix);
+                if directive.colon {
+                    digits = group_digits(&digits, params.char(2, ',')?, params.count(3, 3)?);
+                }
+                let sign = if n < 0 {
+                    "-"
+                } else if directive.at {
+                    "+"
+                } else {
+                    ""
+                };
+                format!("{}{}", sign, digits)
+            }
+            None => print_object(&arg, false)?,
+        };
+        self.out.push_str(&pad(&s, mincol, 1, 0, padchar, true));
+        Ok(())
+    }
+
+    // ~w,d,k,overflowchar,padcharF
+    fn fixed(
+        &mut self,
+        directive: &Directive,
+        params: &Params,
+        args: &mut Args,
+    ) -> Result<(), ExprErr> {
+        let arg = args.next()?;
+        let width = params.optional(0)?;
+        let x = match arg {
+            Expr::Number(x) => x.to_f64() * 10f64.powi(params.int(2, 0)? as i32),
+            _ => {
+                let s = print_object(&arg, false)?;
+                self.out
+                    .push_str(&pad(&s, width.unwrap_or(0), 1, 0, ' ', true));
+                return Ok(());
+            }
+        };
+        let sign = sign_of(x, directive.at);
+        let digits = fixed_digits(x, width, params.optional(1)?, sign.len());
+        let s = format!("{}{}", sign, digits);
+        self.out
+            .push_str(&self.fit(&s, width, params.0.get(3), params.char(4, ' ')?)?);
+        Ok(())
+    }
+
+    // ~w,d,e,k,overflowchar,padchar,exptcharE
+    fn exponential(
+        &mut self,
+        directive: &Directive,
+        params: &Params,
+        args: &mut Args,
+    ) -> Result<(), ExprErr> {
+        let arg = args.next()?;
+        let width = params.optional(0)?;
+        let x = match arg {
+            Expr::Number(x) => x.to_f64(),
+            _ => {
+                let s = print_object(&arg, false)?;
+                self.out
+                    .push_str(&pad(&s, width.unwrap_or(0), 1, 0, ' ', true));
+                return Ok(());
+            }
+        };
+        let (mantissa, exponent) = exponential_digits(x, params.optional(1)?, params.int(3, 1)?);
+        let exponent_digits = format!("{:0>1$}", exponent.unsigned_abs(), params.count(2, 1)?);
+        let s = format!(
+            "{}{}{}{}{}",
+            sign_of(x, directive.at),
+            mantissa,
+            params.char(6, 'e')?,
+            if exponent < 0 { '-' } else { '+' },
+            exponent_digits
+        );
+        self.out
+            .push_str(&self.fit(&s, width, params.0.get(4), params.char(5, ' ')?)?);
+        Ok(())
+    }
+
+    // pad a number on the left to `width`, or fill the width with the
+    // overflow character if it does not fit
+    fn fit(
+        &self,
+        s: &str,
+        width: Option<usize>,
+        overflow: Option<&Option<Expr>>,
+        padchar: char,
+    ) -> Result<String, ExprErr> {
+        let Some(width) = width else {
+            return Ok(s.to_string());
+        };
+        match overflow {
+            Some(Some(Expr::Char(ch))) if s.chars().count() > width => {
+                Ok(ch.to_string().repeat(width))
+            }
+            _ => Ok(pad(s, width, 1, 0, padchar, true)),
+        }
+    }
+
+    // ~d,n,w,padchar$ with the sign before the padding if ~:$
+    fn monetary(
+        &mut self,
+        directive: &Directive,
+        params: &Params,
+        args: &mut Args,
+    ) -> Result<(), ExprErr> {
+        let arg = args.next()?;
+        let x = match arg {
+            Expr::Number(x) => x.to_f64(),
+            _ => {
+                self.out.push_str(&print_object(&arg, false)?);
+                return Ok(());
+            }
+        };
+        let digits = format!("{:.*}", params.count(0, 2)?, x.abs());
+        let int_len = digits.find('.').unwrap_or(digits.len());
+        let digits = format!(
+            "{}{}",
+            "0".repeat(params.count(1, 1)?.saturating_sub(int_len)),
+            digits
+        );
+        let sign = sign_of(x, directive.at);
+        let width = params.count(2, 0)?;
+        let padchar = params.char(3, ' ')?;
+        let s = if directive.colon {
+            let padded = pad(
+                &digits,
+                width.saturating_sub(sign.len()),
+                1,
+                0,
+                padchar,
+                true,
+            );
+            format!("{}{}", sign, padded)
+        } else {
+            pad(&format!("{}{}", sign, digits), width, 1, 0, padchar, true)
+        };
+        self.out.push_str(&s);
+        Ok(())
+    }
+
+    // ~max{ body ~} over a list argument; ~:{ over sublists, ~@{ over the
+    // remaining arguments and ~:@{ over remaining sublist arguments
+    fn iteration(
+        &mut self,
+        directive: &Directive,
+        body: &[Item],
+        at_least_once: bool,
+        args: &mut Args,
+    ) -> Result<(), ExprErr> {
+        let params = self.params(directive, args)?;
+        let max = params.optional(0)?;
+        let control;
+        let body = if body.is_empty() {
+            control = self.control_arg(args)?;
+            &control
+        } else {
+            body
+        };
+        let mut list_args;
+        let items = if directive.at {
+            args
+        } else {
+            list_args = Args::new(args.next()?.to_vec()?);
+            &mut list_args
+        };
+
+        let mut count = 0;
+        loop {
+            let first = count == 0;
+            if max.is_some_and(|max| count >= max) {
+                break;
+            }
+            if items.remaining() == 0 && !(first && at_least_once) {
+                break;
+            }
+            count += 1;
+            if directive.colon {
+                let sublist = match items.remaining() {
+                    0 => vec![],
+                    _ => items.next()?.to_vec()?,
+                };
+                let saved = std::mem::replace(&mut self.sublists_left, items.remaining());
+                let flow = self.run(body, &mut Args::new(sublist));
+                self.sublists_left = saved;
+                if flow? == Flow::EscapeAll {
+                    break;
+                }
+            } else if self.run(body, items)? != Flow::Done {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // ~[ clauses selected by number, ~:[ false ~; true ~] and ~@[ if the
+    // argument is true ~]
+    fn conditional(
+        &mut self,
+        directive: &Directive,
+        clauses: &[Vec<Item>],
+        has_default: bool,
+        args: &mut Args,
+    ) -> Result<Flow, ExprErr> {
+        let params = self.params(directive, args)?;
+        let clause = if directive.at {
+            if args.peek()?.is_nil() {
+                args.next()?;
+                return Ok(Flow::Done);
+            }
+            clauses.first()
+        } else if directive.colon {
+            let index = if args.next()?.is_nil() { 0 } else { 1 };
+            clauses.get(index)
+        } else {
+            let index = match params.0.first() {
+                Some(Some(_)) => params.int(0, 0)?,
+                _ => {
+                    let arg = args.next()?;
+                    integer_of(&arg).ok_or(format_error(&format!("{} is not integer", arg)))?
+                }
+            };
+            let choices = clauses.len() - usize::from(has_default);
+            match usize::try_from(index) {
+                Ok(index) if index < choices => clauses.get(index),
+                _ if has_default => clauses.last(),
+                _ => None,
+            }
+        };
+        match clause {
+            Some(clause) => self.run(clause, args),
+            None => Ok(Flow::Done),
+        }
+    }
+}
+
+// (format destination control-string arg*) writes to standard output if
+// the destination is T, to a stream, or returns a string if it is NIL
+pub fn format(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [destination, control, args @ ..] = args else {
+        return Err(ExprErr::Cause(
+            "format expects a destination and a control string".to_string(),
+        ));
+    };
+    let control = match control {
+        Expr::String(control) => parse_control(&control.borrow())?,
+        _ => return Err(format_error(&format!("{} is not control string", control))),
+    };
+    // T designates *standard-output* here, which write_to finds for NIL
+    let standard_output = Expr::Nil;
+    let stream = match destination {
+        Expr::Nil => None,
+        Expr::True => Some(&standard_output),
+        _ => Some(destination),
+    };
+    let mut formatter = Formatter {
+        out: String::new(),
+        start_column: match stream {
+            Some(stream) => column_of(Some(stream))?,
+            None => 0,
+        },
+        sublists_left: 0,
+    };
+    formatter.run(&control, &mut Args::new(args.to_vec()))?;
+    match stream {
+        Some(stream) => {
+            write_to(Some(stream), &formatter.out)?;
+            Ok(Expr::Nil)
+        }
+        None => Ok(Expr::string(formatter.out)),
+    }
+}
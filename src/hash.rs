@@ -0,0 +1,424 @@
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{bool_expr, keyword_args, Evaluator, ExprEnv};
+use crate::list;
+use crate::math::integer_expr;
+use crate::number::Number;
+use crate::symbol::{symbol_of, Symbol};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Test {
+    Eq,
+    Eql,
+    Equal,
+    Equalp,
+}
+
+impl Test {
+    fn name(self) -> &'static str {
+        match self {
+            Test::Eq => "EQ",
+            Test::Eql => "EQL",
+            Test::Equal => "EQUAL",
+            Test::Equalp => "EQUALP",
+        }
+    }
+}
+
+// Keys are hashed with the same notion of sameness as the table's test,
+// so EQUALP tables fold the case of strings for both.
+struct Key {
+    expr: Expr,
+    test: Test,
+}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_expr(&self.expr, self.test, HASH_DEPTH, state)
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        match self.test {
+            Test::Equalp => equalp(&self.expr, &other.expr),
+            Test::Equal => equal(&self.expr, &other.expr),
+            Test::Eq | Test::Eql => eql(&self.expr, &other.expr),
+        }
+    }
+}
+
+impl Eq for Key {}
+
+// Like SXHASH, only the first elements of lists, structures and arrays
+// down to a fixed depth are hashed, so circular keys hash in finite time.
+const HASH_LENGTH: usize = 16;
+const HASH_DEPTH: usize = 4;
+
+fn hash_expr<H: Hasher>(expr: &Expr, test: Test, depth: usize, state: &mut H) {
+    let fold_case = test == Test::Equalp;
+    std::mem::discriminant(expr).hash(state);
+    if depth == 0 {
+        return;
+    }
+    match expr {
+        // equalp compares numbers with =, so they hash by value
+        Expr::Number(num) if fold_case => match num.to_f64() {
+            0.0 => 0u64.hash(state),
+            x => x.to_bits().hash(state),
+        },
+        Expr::Number(Number::Integer(n)) => n.hash(state),
+        Expr::Number(Number::Ratio(numerator, denominator)) => (numerator, denominator).hash(state),
+        // 0.0 and -0.0 are the same number
+        Expr::Number(Number::Float(x)) if *x == 0.0 => 0u64.hash(state),
+        Expr::Number(Number::Float(x)) => x.to_bits().hash(state),
+        Expr::Complex(re, im) => {
+            hash_expr(&Expr::Number(*re), test, depth, state);
+            hash_expr(&Expr::Number(*im), test, depth, state);
+        }
+        Expr::Char(ch) if fold_case => ch.to_lowercase().collect::<String>().hash(state),
+        Expr::Char(ch) => ch.hash(state),
+        Expr::String(s) if fold_case => s.borrow().to_lowercase().hash(state),
+        // eq and eql tables tell strings apart by identity
+        Expr::String(s) if matches!(test, Test::Eq | Test::Eql) => Rc::as_ptr(s).hash(state),
+        Expr::String(s) => s.borrow().hash(state),
+        Expr::Symbol(symbol) => symbol.hash(state),
+        // eq and eql tables tell conses apart by identity
+        Expr::Cons(cell) if matches!(test, Test::Eq | Test::Eql) => Rc::as_ptr(cell).hash(state),
+        Expr::Cons(_) => {
+            for cell in list::cells(expr).take(HASH_LENGTH) {
+                hash_expr(&cell.borrow().car, test, depth - 1, state);
+            }
+        }
+        Expr::HashTable(table) => Rc::as_ptr(table).hash(state),
+        // equalp compares structures by their slots
+        Expr::Struct(structure) if fold_case => {
+            let structure = structure.borrow();
+            structure.def.name.hash(state);
+            for value in structure.values.iter().take(HASH_LENGTH) {
+                hash_expr(value, test, depth - 1, state);
+            }
+        }
+        Expr::Struct(structure) => Rc::as_ptr(structure).hash(state),
+        Expr::Package(package) => Rc::as_ptr(package).hash(state),
+        Expr::Array(array) if fold_case => {
+            let array = array.borrow();
+            array.dimensions.hash(state);
+            for element in array.active().iter().take(HASH_LENGTH) {
+                hash_expr(element, test, depth - 1, state);
+            }
+        }
+        Expr::Array(array) => Rc::as_ptr(array).hash(state),
+        Expr::Instance(instance) => Rc::as_ptr(instance).hash(state),
+        Expr::Class(class) => Rc::as_ptr(class).hash(state),
+        Expr::Generic(generic) => Rc::as_ptr(generic).hash(state),
+        Expr::Stream(stream) => Rc::as_ptr(stream).hash(state),
+        Expr::Pathname(pathname) => pathname.namestring().hash(state),
+        _ => {}
+    }
+}
+
+pub fn eql(a: &Expr, b: &Expr) -> bool {
+    match (a, b) {
+        (Expr::Cons(a), Expr::Cons(b)) => Rc::ptr_eq(a, b),
+        (Expr::String(a), Expr::String(b)) => Rc::ptr_eq(a, b),
+        _ => a == b,
+    }
+}
+
+pub fn equal(a: &Expr, b: &Expr) -> bool {
+    a == b
+}
+
+// like equal, but numbers are compared with =, strings and characters ignore
+// case, and structures and arrays are compared by their elements
+pub fn equalp(a: &Expr, b: &Expr) -> bool {
+    let same = |a: &Number, b: &Number| a.compare(*b) == Some(Ordering::Equal);
+    match (a, b) {
+        (Expr::Number(a), Expr::Number(b)) => same(a, b),
+        (Expr::Complex(a, b), Expr::Complex(c, d)) => same(a, c) && same(b, d),
+        (Expr::Struct(a), Expr::Struct(b)) => {
+            let (a, b) = (a.borrow(), b.borrow());
+            Rc::ptr_eq(&a.def, &b.def) && a.values.iter().zip(&b.values).all(|(a, b)| equalp(a, b))
+        }
+        (Expr::Array(a), Expr::Array(b)) => {
+            let (a, b) = (a.borrow(), b.borrow());
+            a.dimensions == b.dimensions
+                && a.active().len() == b.active().len()
+                && a.active().iter().zip(b.active()).all(|(a, b)| equalp(a, b))
+        }
+        (Expr::String(a), Expr::String(b)) => {
+            a.borrow().to_lowercase() == b.borrow().to_lowercase()
+        }
+        (Expr::Char(a), Expr::Char(b)) => a.to_lowercase().eq(b.to_lowercase()),
+        (Expr::Cons(a), Expr::Cons(b)) => list::same_list(a, b, equalp),
+        _ => a == b,
+    }
+}
+
+// entries remember their insertion order so that iteration is stable
+pub struct HashTable {
+    pub test: Test,
+    entries: HashMap<Key, (u64, Expr)>,
+    inserted: u64,
+}
+
+pub type HashTableRef = Rc<RefCell<HashTable>>;
+
+impl HashTable {
+    pub fn new(test: Test) -> Self {
+        HashTable {
+            test,
+            entries: HashMap::new(),
+            inserted: 0,
+        }
+    }
+
+    fn key(&self, expr: &Expr) -> Key {
+        Key {
+            expr: expr.clone(),
+            test: self.test,
+        }
+    }
+
+    pub fn get(&self, key: &Expr) -> Option<Expr> {
+        self.entries
+            .get(&self.key(key))
+            .map(|(_, value)| value.clone())
+    }
+
+    pub fn insert(&mut self, key: &Expr, value: Expr) {
+        let order = match self.entries.get(&self.key(key)) {
+            Some((order, _)) => *order,
+            None => {
+                self.inserted += 1;
+                self.inserted
+            }
+        };
+        self.entries.insert(self.key(key), (order, value));
+    }
+
+    pub fn remove(&mut self, key: &Expr) -> bool {
+        self.entries.remove(&self.key(key)).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    // (key value) pairs in insertion order
+    pub fn entries(&self) -> Vec<(Expr, Expr)> {
+        let mut entries = self
+            .entries
+            .iter()
+            .map(|(key, (order, value))| (*order, key.expr.clone(), value.clone()))
+            .collect::<Vec<(u64, Expr, Expr)>>();
+        entries.sort_by_key(|(order, _, _)| *order);
+        entries
+            .into_iter()
+            .map(|(_, key, value)| (key, value))
+            .collect()
+    }
+}
+
+impl std::fmt::Debug for HashTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "#<HASH-TABLE :TEST {} :COUNT {}>",
+            self.test.name(),
+            self.len()
+        )
+    }
+}
+
+pub fn hash_table_arg(expr: &Expr) -> Result<HashTableRef, ExprErr> {
+    match expr {
+        Expr::HashTable(table) => Ok(table.clone()),
+        _ => Err(ExprErr::Cause(format!("{} is not hash table", expr))),
+    }
+}
+
+// a test is named by a symbol or given as one of the predicate functions
+fn test_designator(expr: &Expr) -> Result<Test, ExprErr> {
+    for test in [Test::Eq, Test::Eql, Test::Equal, Test::Equalp] {
+        let symbol = Symbol::cl(test.name());
+        let same = match expr {
+            Expr::Func(f) => {
+                matches!(symbol.function(), Some(Expr::Func(g)) if std::ptr::fn_addr_eq(*f, g))
+            }
+            _ => symbol_of(expr).is_ok_and(|s| s == symbol),
+        };
+        if same {
+            return Ok(test);
+        }
+    }
+    Err(ExprErr::Cause(format!("invalid hash table test: {}", expr)))
+}
+
+pub fn eql_fn(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [a, b] => Ok(bool_expr(eql(a, b))),
+        _ => Err(ExprErr::Cause("eql expects exactly two args".to_string())),
+    }
+}
+
+pub fn equal_fn(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [a, b] => Ok(bool_expr(equal(a, b))),
+        _ => Err(ExprErr::Cause("equal expects exactly two args".to_string())),
+    }
+}
+
+pub fn equalp_fn(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [a, b] => Ok(bool_expr(equalp(a, b))),
+        _ => Err(ExprErr::Cause(
+            "equalp expects exactly two args".to_string(),
+        )),
+    }
+}
+
+// (make-hash-table &key test size)
+pub fn make_hash_table(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let options = keyword_args(
+        "make-hash-table",
+        args,
+        &["TEST", "SIZE", "REHASH-SIZE", "REHASH-THRESHOLD"],
+    )?;
+    let test = match options.get("TEST") {
+        Some(test) => test_designator(test)?,
+        None => Test::Eql,
+    };
+    Ok(Expr::HashTable(Rc::new(RefCell::new(HashTable::new(test)))))
+}
+
+// (gethash key table [default]) returns the value and whether it was found
+pub fn gethash(evaluator: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (key, table, default) = match args {
+        [key, table] => (key, table, Expr::Nil),
+        [key, table, default] => (key, table, default.clone()),
+        _ => return Err(ExprErr::Cause("gethash expects 2 or 3 args".to_string())),
+    };
+    let values = match hash_table_arg(table)?.borrow().get(key) {
+        Some(value) => vec![value, Expr::True],
+        None => vec![default, Expr::Nil],
+    };
+    Ok(evaluator.multiple_values(values))
+}
+
+// (remhash key table) is T if there was an entry
+pub fn remhash(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [key, table] => Ok(bool_expr(hash_table_arg(table)?.borrow_mut().remove(key))),
+        _ => Err(ExprErr::Cause(
+            "remhash expects exactly two args".to_string(),
+        )),
+    }
+}
+
+pub fn clrhash(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [table] => {
+            hash_table_arg(table)?.borrow_mut().clear();
+            Ok(table.clone())
+        }
+        _ => Err(ExprErr::Cause(
+            "clrhash expects exactly one arg".to_string(),
+        )),
+    }
+}
+
+// (maphash function table) calls function with each key and value
+pub fn maphash(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (func, table) = match args {
+        [func, table] => (evaluator.function_designator(func)?, hash_table_arg(table)?),
+        _ => {
+            return Err(ExprErr::Cause(
+                "maphash expects exactly two args".to_string(),
+            ))
+        }
+    };
+    // the function may modify the table while we iterate
+    let entries = table.borrow().entries();
+    for (key, value) in entries {
+        evaluator.apply(&func, &[key, value], env)?;
+    }
+    Ok(Expr::Nil)
+}
+
+pub fn hash_table_count(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    match args {
+        [table] => Ok(integer_expr(hash_table_arg(table)?.borrow().len() as i64)),
+        _ => Err(ExprErr::Cause(
+            "hash-table-count expects exactly one arg".to_string(),
+        )),
+    }
+}
+
+pub fn hash_table_p(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [table] => Ok(bool_expr(matches!(table, Expr::HashTable(_)))),
+        _ => Err(ExprErr::Cause(
+            "hash-table-p expects exactly one arg".to_string(),
+        )),
+    }
+}
+
+impl Evaluator {
+    // (with-hash-table-iterator (name table) body*): within body, (name)
+    // returns whether an entry was left, its key and its value
+    pub fn eval_with_hash_table_iterator(
+        &mut self,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        let (spec, body) = args.split_first().ok_or(ExprErr::Cause(
+            "expected iterator name and table".to_string(),
+        ))?;
+        let (name, table) = match spec.to_vec()?.as_slice() {
+            [Expr::Symbol(name), table] => (name.clone(), table.clone()),
+            _ => return Err(ExprErr::Cause(format!("invalid iterator spec: {}", spec))),
+        };
+        let table = hash_table_arg(&self.eval(&table, env)?)?;
+        let mut entries = table.borrow().entries();
+        entries.reverse();
+
+        self.iterators.push((name, entries));
+        let result = self.eval_progn(body, env);
+        self.iterators.pop();
+        result
+    }
+
+    // (name) for an iterator of an enclosing with-hash-table-iterator
+    pub fn next_hash_table_entry(&mut self, name: &Symbol) -> Option<Expr> {
+        let (_, entries) = self
+            .iterators
+            .iter_mut()
+            .rev()
+            .find(|(iterator, _)| iterator.as_ref() == name)?;
+        let values = match entries.pop() {
+            Some((key, value)) => vec![Expr::True, key, value],
+            None => vec![Expr::Nil],
+        };
+        Some(self.multiple_values(values))
+    }
+}
@@ -1,21 +1,20 @@
+use super::ast::name_char;
+use super::number::{self, Number};
 use super::token::Token;
 
 #[derive(Debug)]
 pub struct Lexer {
     ch: char,
-    input: String,
-    length: usize,
+    input: Vec<char>,
     read_position: usize,
     position: usize,
 }
 
 impl Lexer {
     pub fn new(input: String) -> Lexer {
-        let length = input.len() - 1;
         let mut lexer = Self {
             ch: '\0',
-            input,
-            length,
+            input: input.chars().collect(),
             read_position: 0,
             position: 0,
         };
@@ -24,109 +23,365 @@ impl Lexer {
     }
 
     pub fn next_token(&mut self) -> Token {
-        while self.ch.is_whitespace() {
-            self.read();
+        let token = self.read_token();
+        self.read();
+        token
+    }
+
+    // the token starting at the current character, which is left at the
+    // last character of the token
+    fn read_token(&mut self) -> Token {
+        loop {
+            while self.ch.is_whitespace() {
+                self.read();
+            }
+            if self.ch != ';' {
+                break;
+            }
+            // a comment runs to the end of the line
+            while self.ch != '\n' && self.ch != '\0' {
+                self.read();
+            }
         }
-        let token = match self.ch {
+        match self.ch {
             '(' => Token::Lparen,
             ')' => Token::Rparen,
-            '*' => Token::Asterfisk,
-            '/' => Token::Slash,
-            '+' => match self.peek() {
-                '0'..='9' => self.read_as_number(),
-                _ => Token::Plus,
-            },
-            '-' => match self.peek() {
-                '0'..='9' => self.read_as_number(),
-                _ => Token::Minus,
-            },
-            '0'..='9' => self.read_as_number(),
+            '\'' => Token::Quote,
+            '#' => self.read_as_dispatch(),
             '"' => self.read_as_string(),
-            'a'..='z' | 'A'..='Z' => self.read_as_literal(),
+            ':' => self.read_as_keyword(),
             '\0' => Token::Eof,
+            ch if is_constituent(ch) || ch == '|' || ch == '\\' => self.read_as_atom(),
             _ => Token::Illegal(self.ch.to_string()),
-        };
+        }
+    }
+
+    // read a run of constituent characters and classify it as number or
+    // symbol; characters escaped with \ or between bars keep their case and
+    // make the token a symbol
+    fn read_as_atom(&mut self) -> Token {
+        let mut chars = vec![];
+        loop {
+            match self.ch {
+                '\\' => {
+                    self.read();
+                    if self.at_end() {
+                        return Token::Illegal(String::from("\\"));
+                    }
+                    chars.push((self.ch, true));
+                }
+                '|' => loop {
+                    self.read();
+                    match self.ch {
+                        _ if self.at_end() => return Token::Illegal(String::from("|")),
+                        '|' => break,
+                        '\\' => {
+                            self.read();
+                            chars.push((self.ch, true));
+                        }
+                        ch => chars.push((ch, true)),
+                    }
+                },
+                ch => chars.push((ch, false)),
+            }
+            if is_symbol_char(self.peek()) {
+                self.read();
+            } else {
+                break;
+            }
+        }
+        let s = chars.iter().map(|(ch, _)| ch).collect::<String>();
+        let escaped = chars.iter().any(|(_, escaped)| *escaped);
+
+        // pkg:name or pkg::name
+        if let Some(colon) = chars
+            .iter()
+            .position(|&(ch, escaped)| ch == ':' && !escaped)
+        {
+            let (package, name) = (&chars[..colon], &chars[colon + 1..]);
+            let (name, internal) = match name.first() {
+                Some((':', false)) => (&name[1..], true),
+                _ => (name, false),
+            };
+            if package.is_empty()
+                || name.is_empty()
+                || name.iter().any(|&(ch, escaped)| ch == ':' && !escaped)
+            {
+                return Token::Illegal(s);
+            }
+            return Token::Qualified(upcase(package), upcase(name), internal);
+        }
+
+        if !escaped {
+            match s.as_str() {
+                "+" => return Token::Plus,
+                "-" => return Token::Minus,
+                "*" => return Token::Asterfisk,
+                "/" => return Token::Slash,
+                "." => return Token::Dot,
+                _ => {}
+            }
+
+            match number::parse(&s) {
+                Some(Ok(number)) => return Token::Number(number),
+                Some(Err(_)) => return Token::Illegal(s),
+                None => {}
+            }
+        }
+
+        match upcase(&chars).as_str() {
+            "NIL" => Token::Nil,
+            "T" => Token::True,
+            name => Token::Literal(name.to_string()),
+        }
+    }
+
+    // :name
+    fn read_as_keyword(&mut self) -> Token {
+        if !is_symbol_char(self.peek()) || self.peek() == ':' {
+            return Token::Illegal(self.ch.to_string());
+        }
         self.read();
+        match self.read_as_atom() {
+            Token::Literal(name) => Token::Keyword(name),
+            Token::Illegal(s) => Token::Illegal(format!(":{}", s)),
+            token @ Token::Qualified(..) => Token::Illegal(format!(":{}", token)),
+            token => Token::Keyword(token.to_string()),
+        }
+    }
 
-        token
+    // # followed by a sub-character from DISPATCH_MACROS or by digits
+    fn read_as_dispatch(&mut self) -> Token {
+        let sub_char = self.peek();
+        if sub_char.is_ascii_digit() {
+            return self.read_as_numbered();
+        }
+        let sub_char = sub_char.to_ascii_uppercase();
+        match DISPATCH_MACROS.iter().find(|(ch, _)| *ch == sub_char) {
+            Some((_, read_macro)) => {
+                self.read();
+                read_macro(self)
+            }
+            None => match self.input.get(self.read_position) {
+                Some(ch) => Token::Illegal(format!("#{ch}")),
+                None => Token::Illegal(String::from("#")),
+            },
+        }
     }
 
-    fn read_as_literal(&mut self) -> Token {
-        if self.ch == 't' && self.peek() == '\0' {
-            return Token::True;
+    // #:name
+    fn read_as_uninterned(&mut self) -> Token {
+        if !is_symbol_char(self.peek()) || self.peek() == ':' {
+            return Token::Illegal(String::from("#:"));
         }
-        let mut s = String::from("");
-        loop {
-            s.push(self.ch);
-            match self.peek() {
-                'a'..='z' | 'A'..='Z' => self.read(),
-                _ => break,
+        self.read();
+        match self.read_as_atom() {
+            Token::Literal(name) => Token::Uninterned(name),
+            token @ (Token::Nil
+            | Token::True
+            | Token::Plus
+            | Token::Minus
+            | Token::Asterfisk
+            | Token::Slash) => Token::Uninterned(token.to_string()),
+            Token::Illegal(s) => Token::Illegal(format!("#:{}", s)),
+            token => Token::Illegal(format!("#:{}", token)),
+        }
+    }
+
+    // #\c or #\Name
+    fn read_as_char(&mut self) -> Token {
+        self.read();
+        let mut name = self.ch.to_string();
+        if self.ch.is_alphabetic() {
+            while is_constituent(self.peek()) {
+                self.read();
+                name.push(self.ch);
             }
         }
+        if name.chars().count() == 1 {
+            return Token::Char(self.ch);
+        }
+        match name_char(&name) {
+            Some(ch) => Token::Char(ch),
+            None => Token::Illegal(format!("#\\{}", name)),
+        }
+    }
 
-        if s.to_uppercase() == "NIL" {
-            return Token::Nil;
+    // #| comment |#, which may nest, is skipped like whitespace
+    fn skip_block_comment(&mut self) -> Token {
+        let mut depth = 1;
+        while depth > 0 {
+            self.read();
+            match (self.ch, self.peek()) {
+                _ if self.at_end() => return Token::Illegal(String::from("#|")),
+                ('|', '#') => {
+                    self.read();
+                    depth -= 1;
+                }
+                ('#', '|') => {
+                    self.read();
+                    depth += 1;
+                }
+                _ => {}
+            }
         }
+        self.read();
+        self.read_token()
+    }
 
-        Token::Literal(s.to_uppercase())
+    // #x, #o and #b followed by a rational in that radix
+    fn read_as_radix(&mut self, radix: u32) -> Token {
+        let prefix = format!("#{}", self.ch);
+        self.read_rational(radix, prefix)
     }
 
-    fn read_as_string(&mut self) -> Token {
-        let mut s = String::from("");
-        loop {
+    // [sign]digits[/digits] in radix after the dispatch prefix
+    fn read_rational(&mut self, radix: u32, prefix: String) -> Token {
+        let mut s = String::new();
+        while is_constituent(self.peek()) {
             self.read();
             s.push(self.ch);
-            if self.peek() == '"' {
+        }
+        let digits = |s: &str| !s.is_empty() && s.chars().all(|ch| ch.is_digit(radix));
+        let (numerator, denominator) = s.split_once('/').unwrap_or((&s, "1"));
+        let unsigned = numerator.strip_prefix(['+', '-']).unwrap_or(numerator);
+        if !digits(unsigned) || !digits(denominator) {
+            return Token::Illegal(format!("{}{}", prefix, s));
+        }
+        let number = match (
+            i128::from_str_radix(numerator, radix),
+            i128::from_str_radix(denominator, radix),
+        ) {
+            (Ok(numerator), Ok(denominator)) => Number::ratio(numerator, denominator).ok(),
+            _ => None,
+        };
+        match number {
+            Some(number) => Token::Number(number),
+            None => Token::Illegal(format!("{}{}", prefix, s)),
+        }
+    }
+
+    // #nA, #n=, #n# and #nR
+    fn read_as_numbered(&mut self) -> Token {
+        let mut digits = String::new();
+        while self.peek().is_ascii_digit() {
+            self.read();
+            digits.push(self.ch);
+        }
+        let n = digits.parse().unwrap_or(usize::MAX);
+        match self.peek() {
+            'A' | 'a' => {
                 self.read();
-                break;
+                Token::Array(n)
             }
+            '=' => {
+                self.read();
+                Token::Label(n)
+            }
+            'R' | 'r' if (2..=36).contains(&n) => {
+                self.read();
+                self.read_rational(n as u32, format!("#{}{}", digits, self.ch))
+            }
+            '#' => {
+                self.read();
+                Token::Reference(n)
+            }
+            _ => Token::Illegal(format!("#{}", digits)),
         }
-
-        Token::String(s)
     }
 
-    fn read_as_number(&mut self) -> Token {
-        let mut chars = Vec::<char>::new();
-
+    // read up to the closing quote; a backslash escapes the next character
+    fn read_as_string(&mut self) -> Token {
+        let mut s = String::from("");
         loop {
-            chars.push(self.ch);
-            match self.peek() {
-                '0'..='9' | '.' => {
+            self.read();
+            match self.ch {
+                '"' => break,
+                '\\' => {
                     self.read();
+                    s.push(self.ch);
                 }
-                _ => {
-                    break;
-                }
+                _ => s.push(self.ch),
+            }
+            if self.read_position >= self.input.len() {
+                return Token::Illegal(format!("\"{}", s));
             }
         }
 
-        let s: String = chars.iter().collect();
-        Token::Number(s.parse::<f64>().unwrap())
+        Token::String(s)
+    }
+
+    // the number of characters before the current one, which is the
+    // first after the last token read
+    pub fn position(&self) -> usize {
+        self.position.min(self.input.len())
+    }
+
+    // whether the current character is past the end of the input
+    fn at_end(&self) -> bool {
+        self.position >= self.input.len()
     }
 
     fn read(&mut self) {
-        self.ch = if self.read_position > self.length {
-            '\0'
-        } else {
-            self.input.chars().nth(self.read_position).unwrap()
-        };
+        self.ch = self.peek();
         self.position = self.read_position;
         self.read_position += 1;
     }
 
     fn peek(&mut self) -> char {
-        if self.read_position > self.length {
-            '\0'
+        self.input.get(self.read_position).copied().unwrap_or('\0')
+    }
+}
+
+// reads the token after a # sub-character, which is the current character
+type DispatchMacro = fn(&mut Lexer) -> Token;
+
+// the sub-characters of #, upcased, and their readers
+const DISPATCH_MACROS: [(char, DispatchMacro); 14] = [
+    ('\'', |_| Token::Function),
+    ('(', |_| Token::Vector),
+    ('\\', Lexer::read_as_char),
+    ('S', |_| Token::Struct),
+    ('C', |_| Token::Complex),
+    ('P', |_| Token::Pathname),
+    ('+', |_| Token::Feature(true)),
+    ('-', |_| Token::Feature(false)),
+    ('.', |_| Token::ReadEval),
+    (':', Lexer::read_as_uninterned),
+    ('X', |lexer| lexer.read_as_radix(16)),
+    ('O', |lexer| lexer.read_as_radix(8)),
+    ('B', |lexer| lexer.read_as_radix(2)),
+    ('|', Lexer::skip_block_comment),
+];
+
+pub fn is_constituent(ch: char) -> bool {
+    ch.is_alphanumeric() || "+-*/<>=!?%&_.$@~".contains(ch)
+}
+
+// characters continuing a symbol: constituents, package markers and escapes
+fn is_symbol_char(ch: char) -> bool {
+    is_constituent(ch) || ch == ':' || ch == '|' || ch == '\\'
+}
+
+// the name of a symbol token, with the unescaped characters upcased
+fn upcase(chars: &[(char, bool)]) -> String {
+    let mut name = String::new();
+    for &(ch, escaped) in chars {
+        if escaped {
+            name.push(ch);
         } else {
-            self.input.chars().nth(self.read_position).unwrap()
+            name.extend(ch.to_uppercase());
         }
     }
+    name
 }
 
+// [+-]digits[.digits]
 #[cfg(test)]
 mod test {
     use super::Token;
     use super::*;
+    use crate::number::Number;
 
     #[test]
     fn read_invalid_token() {
@@ -138,6 +393,13 @@ mod test {
     fn read_string() {
         let mut lexer = Lexer::new(String::from(r#""hello""#));
         assert_eq!(lexer.next_token(), Token::String(String::from("hello")));
+        let mut lexer = Lexer::new(String::from(r#"("" "a\"b\\")"#));
+        assert_eq!(lexer.next_token(), Token::Lparen);
+        assert_eq!(lexer.next_token(), Token::String(String::from("")));
+        assert_eq!(lexer.next_token(), Token::String(String::from(r#"a"b\"#)));
+        assert_eq!(lexer.next_token(), Token::Rparen);
+        let mut lexer = Lexer::new(String::from(r#""abc"#));
+        assert_eq!(lexer.next_token(), Token::Illegal(String::from(r#""abc"#)));
     }
 
     #[test]
@@ -146,7 +408,7 @@ mod test {
         assert_eq!(lexer.next_token(), Token::Lparen);
         assert_eq!(lexer.next_token(), Token::Literal(String::from("SETQ")));
         assert_eq!(lexer.next_token(), Token::Literal(String::from("A")));
-        assert_eq!(lexer.next_token(), Token::Number(2.0));
+        assert_eq!(lexer.next_token(), Token::Number(Number::Integer(2)));
         assert_eq!(lexer.next_token(), Token::Rparen);
     }
 
@@ -156,17 +418,195 @@ mod test {
         assert_eq!(lexer.next_token(), Token::Lparen);
         assert_eq!(lexer.next_token(), Token::Plus);
         assert_eq!(lexer.next_token(), Token::Literal(String::from("A")));
-        assert_eq!(lexer.next_token(), Token::Number(2.0));
+        assert_eq!(lexer.next_token(), Token::Number(Number::Integer(2)));
         assert_eq!(lexer.next_token(), Token::Literal(String::from("A")));
         assert_eq!(lexer.next_token(), Token::Rparen);
     }
 
+    #[test]
+    fn read_quote_and_symbols() {
+        let mut lexer = Lexer::new(String::from("'(hash-keys <= t nil 1+)"));
+        let wants = vec![
+            Token::Quote,
+            Token::Lparen,
+            Token::Literal(String::from("HASH-KEYS")),
+            Token::Literal(String::from("<=")),
+            Token::True,
+            Token::Nil,
+            Token::Literal(String::from("1+")),
+            Token::Rparen,
+            Token::Eof,
+        ];
+        for want in wants {
+            assert_eq!(lexer.next_token(), want);
+        }
+    }
+
+    #[test]
+    fn read_function() {
+        let mut lexer = Lexer::new(String::from("#'car #"));
+        assert_eq!(lexer.next_token(), Token::Function);
+        assert_eq!(lexer.next_token(), Token::Literal(String::from("CAR")));
+        assert_eq!(lexer.next_token(), Token::Illegal(String::from("#")));
+    }
+
+    #[test]
+    fn read_keyword() {
+        let mut lexer = Lexer::new(String::from("(:test :key) :1+ :"));
+        assert_eq!(lexer.next_token(), Token::Lparen);
+        assert_eq!(lexer.next_token(), Token::Keyword(String::from("TEST")));
+        assert_eq!(lexer.next_token(), Token::Keyword(String::from("KEY")));
+        assert_eq!(lexer.next_token(), Token::Rparen);
+        assert_eq!(lexer.next_token(), Token::Keyword(String::from("1+")));
+        assert_eq!(lexer.next_token(), Token::Illegal(String::from(":")));
+    }
+
+    #[test]
+    fn read_qualified_symbol() {
+        let mut lexer = Lexer::new(String::from("(cl:car util::helper a:)"));
+        assert_eq!(lexer.next_token(), Token::Lparen);
+        assert_eq!(
+            lexer.next_token(),
+            Token::Qualified(String::from("CL"), String::from("CAR"), false)
+        );
+        assert_eq!(
+            lexer.next_token(),
+            Token::Qualified(String::from("UTIL"), String::from("HELPER"), true)
+        );
+        assert_eq!(lexer.next_token(), Token::Illegal(String::from("a:")));
+    }
+
+    #[test]
+    fn read_struct() {
+        let mut lexer = Lexer::new(String::from("#S(point :x 1)"));
+        assert_eq!(lexer.next_token(), Token::Struct);
+        assert_eq!(lexer.next_token(), Token::Lparen);
+        assert_eq!(lexer.next_token(), Token::Literal(String::from("POINT")));
+    }
+
+    #[test]
+    fn read_complex() {
+        let mut lexer = Lexer::new(String::from("#c(1 -2)"));
+        assert_eq!(lexer.next_token(), Token::Complex);
+        assert_eq!(lexer.next_token(), Token::Lparen);
+        assert_eq!(lexer.next_token(), Token::Number(Number::Integer(1)));
+        assert_eq!(lexer.next_token(), Token::Number(Number::Integer(-2)));
+        assert_eq!(lexer.next_token(), Token::Rparen);
+    }
+
+    #[test]
+    fn read_comment() {
+        let mut lexer = Lexer::new(String::from("; comment\n1 ; more\n;; last"));
+        assert_eq!(lexer.next_token(), Token::Number(Number::Integer(1)));
+        assert_eq!(lexer.next_token(), Token::Eof);
+        let mut lexer = Lexer::new(String::new());
+        assert_eq!(lexer.next_token(), Token::Eof);
+    }
+
+    #[test]
+    fn read_pathname() {
+        let mut lexer = Lexer::new(String::from("#P\"/tmp/a.log\""));
+        assert_eq!(lexer.next_token(), Token::Pathname);
+        assert_eq!(
+            lexer.next_token(),
+            Token::String(String::from("/tmp/a.log"))
+        );
+    }
+
+    #[test]
+    fn read_radix_and_block_comment() {
+        let mut lexer = Lexer::new(String::from(
+            "#xFF #b-101 #o17 #3r12 #X1/2 #| a #| b |# |# 1 #x1G #|",
+        ));
+        assert_eq!(lexer.next_token(), Token::Number(Number::Integer(255)));
+        assert_eq!(lexer.next_token(), Token::Number(Number::Integer(-5)));
+        assert_eq!(lexer.next_token(), Token::Number(Number::Integer(15)));
+        assert_eq!(lexer.next_token(), Token::Number(Number::Integer(5)));
+        assert_eq!(lexer.next_token(), Token::Number(Number::Ratio(1, 2)));
+        assert_eq!(lexer.next_token(), Token::Number(Number::Integer(1)));
+        assert_eq!(lexer.next_token(), Token::Illegal(String::from("#x1G")));
+        assert_eq!(lexer.next_token(), Token::Illegal(String::from("#|")));
+    }
+
+    #[test]
+    fn read_escaped_symbol() {
+        let mut lexer = Lexer::new(String::from(r"|a b|c a\bc |1| pkg::|x| :|k| #:g \"));
+        assert_eq!(lexer.next_token(), Token::Literal(String::from("a bC")));
+        assert_eq!(lexer.next_token(), Token::Literal(String::from("AbC")));
+        assert_eq!(lexer.next_token(), Token::Literal(String::from("1")));
+        assert_eq!(
+            lexer.next_token(),
+            Token::Qualified(String::from("PKG"), String::from("x"), true)
+        );
+        assert_eq!(lexer.next_token(), Token::Keyword(String::from("k")));
+        assert_eq!(lexer.next_token(), Token::Uninterned(String::from("G")));
+        assert_eq!(lexer.next_token(), Token::Illegal(String::from(r"\")));
+    }
+
+    #[test]
+    fn read_dispatch_macro() {
+        let mut lexer = Lexer::new(String::from("#+risp #-(or a) #.x #$"));
+        assert_eq!(lexer.next_token(), Token::Feature(true));
+        assert_eq!(lexer.next_token(), Token::Literal(String::from("RISP")));
+        assert_eq!(lexer.next_token(), Token::Feature(false));
+        assert_eq!(lexer.next_token(), Token::Lparen);
+        lexer.next_token();
+        lexer.next_token();
+        assert_eq!(lexer.next_token(), Token::Rparen);
+        assert_eq!(lexer.next_token(), Token::ReadEval);
+        lexer.next_token();
+        assert_eq!(lexer.next_token(), Token::Illegal(String::from("#$")));
+    }
+
+    #[test]
+    fn read_array() {
+        let mut lexer = Lexer::new(String::from("#(1) #2A((1)) #2"));
+        assert_eq!(lexer.next_token(), Token::Vector);
+        assert_eq!(lexer.next_token(), Token::Number(Number::Integer(1)));
+        assert_eq!(lexer.next_token(), Token::Rparen);
+        assert_eq!(lexer.next_token(), Token::Array(2));
+        assert_eq!(lexer.next_token(), Token::Lparen);
+        lexer.next_token();
+        lexer.next_token();
+        lexer.next_token();
+        lexer.next_token();
+        assert_eq!(lexer.next_token(), Token::Illegal(String::from("#2")));
+    }
+
+    #[test]
+    fn read_label() {
+        let mut lexer = Lexer::new(String::from("#1=(a #1#) #12#"));
+        assert_eq!(lexer.next_token(), Token::Label(1));
+        assert_eq!(lexer.next_token(), Token::Lparen);
+        assert_eq!(lexer.next_token(), Token::Literal(String::from("A")));
+        assert_eq!(lexer.next_token(), Token::Reference(1));
+        assert_eq!(lexer.next_token(), Token::Rparen);
+        assert_eq!(lexer.next_token(), Token::Reference(12));
+    }
+
+    #[test]
+    fn read_char() {
+        let mut lexer = Lexer::new(String::from("#\\a #\\( #\\space #\\Newline #\\bogus"));
+        assert_eq!(lexer.next_token(), Token::Char('a'));
+        assert_eq!(lexer.next_token(), Token::Char('('));
+        assert_eq!(lexer.next_token(), Token::Char(' '));
+        assert_eq!(lexer.next_token(), Token::Char('\n'));
+        assert_eq!(lexer.next_token(), Token::Illegal(String::from("#\\bogus")));
+    }
+
     #[test]
     fn read_number() {
         let tests = vec![
-            ("1", Token::Number(1.0)),
-            ("1.5", Token::Number(1.5)),
-            ("2.345", Token::Number(2.345)),
+            ("1", Token::Number(Number::Integer(1))),
+            ("-12.", Token::Number(Number::Integer(-12))),
+            ("1.5", Token::Number(Number::Float(1.5))),
+            ("2.345", Token::Number(Number::Float(2.345))),
+            ("1.0d0", Token::Number(Number::Float(1.0))),
+            ("-.5e2", Token::Number(Number::Float(-50.0))),
+            ("10/4", Token::Number(Number::Ratio(5, 2))),
+            ("-6/3", Token::Number(Number::Integer(-2))),
+            ("1/0", Token::Illegal(String::from("1/0"))),
+            ("1e", Token::Literal(String::from("1E"))),
         ];
         for test in tests {
             let mut lexer = Lexer::new(test.0.to_string());
@@ -179,8 +619,8 @@ mod test {
         let mut lexer = Lexer::new(String::from("(+ 1 2)"));
         assert_eq!(lexer.next_token(), Token::Lparen);
         assert_eq!(lexer.next_token(), Token::Plus);
-        assert_eq!(lexer.next_token(), Token::Number(1.0));
-        assert_eq!(lexer.next_token(), Token::Number(2.0));
+        assert_eq!(lexer.next_token(), Token::Number(Number::Integer(1)));
+        assert_eq!(lexer.next_token(), Token::Number(Number::Integer(2)));
         assert_eq!(lexer.next_token(), Token::Rparen);
         assert_eq!(lexer.next_token(), Token::Eof);
     }
@@ -193,17 +633,17 @@ mod test {
             Token::Plus,
             Token::Lparen,
             Token::Minus,
-            Token::Number(30.0),
-            Token::Number(2.0),
+            Token::Number(Number::Integer(30)),
+            Token::Number(Number::Integer(2)),
             Token::Rparen,
             Token::Lparen,
             Token::Asterfisk,
             Token::Lparen,
             Token::Slash,
-            Token::Number(4.0),
-            Token::Number(2.0),
+            Token::Number(Number::Integer(4)),
+            Token::Number(Number::Integer(2)),
             Token::Rparen,
-            Token::Number(3.0),
+            Token::Number(Number::Integer(3)),
             Token::Rparen,
             Token::Rparen,
             Token::Eof,
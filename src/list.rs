@@ -0,0 +1,154 @@
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{single_arg, Evaluator, ExprEnv};
+use crate::number::Number;
+use std::{cell::RefCell, rc::Rc};
+
+// A cons cell. Lists are chains of cells shared by every value holding
+// them, so storing into a cell is seen through all of them.
+pub struct Cons {
+    pub car: Expr,
+    pub cdr: Expr,
+}
+
+pub type ConsRef = Rc<RefCell<Cons>>;
+
+// the cdr chain is unlinked a cell at a time so that dropping a long
+// list does not recurse once per element
+impl Drop for Cons {
+    fn drop(&mut self) {
+        let mut next = std::mem::replace(&mut self.cdr, Expr::Nil);
+        while let Expr::Cons(cell) = next {
+            match Rc::try_unwrap(cell) {
+                Ok(cell) => next = std::mem::replace(&mut cell.into_inner().cdr, Expr::Nil),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Cons {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "({:?} . {:?})", self.car, self.cdr)
+    }
+}
+
+// the cells of a list, ending at its last cell
+pub struct Cells {
+    next: Expr,
+}
+
+impl Iterator for Cells {
+    type Item = ConsRef;
+
+    fn next(&mut self) -> Option<ConsRef> {
+        match std::mem::replace(&mut self.next, Expr::Nil) {
+            Expr::Cons(cell) => {
+                self.next = cell.borrow().cdr.clone();
+                Some(cell)
+            }
+            _ => None,
+        }
+    }
+}
+
+pub fn cells(list: &Expr) -> Cells {
+    Cells { next: list.clone() }
+}
+
+// whether two lists have the same elements under `same`, walking the cdr
+// chain iteratively
+pub fn same_list(a: &ConsRef, b: &ConsRef, same: fn(&Expr, &Expr) -> bool) -> bool {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    loop {
+        if Rc::ptr_eq(&a, &b) {
+            return true;
+        }
+        let (next_a, next_b) = {
+            let (x, y) = (a.borrow(), b.borrow());
+            if !same(&x.car, &y.car) {
+                return false;
+            }
+            match (&x.cdr, &y.cdr) {
+                (Expr::Cons(x), Expr::Cons(y)) => (x.clone(), y.clone()),
+                (x, y) => return same(x, y),
+            }
+        };
+        a = next_a;
+        b = next_b;
+    }
+}
+
+// a non-negative integer
+pub fn index_arg(expr: &Expr) -> Result<usize, ExprErr> {
+    match expr {
+        Expr::Number(Number::Integer(n)) if *n >= 0 => Ok(*n as usize),
+        _ => Err(ExprErr::Cause(format!("{} is not index", expr))),
+    }
+}
+
+// the cell of a list holding the element at index, if the list is that long
+pub fn nth_cell(list: &Expr, index: usize) -> Result<Option<ConsRef>, ExprErr> {
+    match list {
+        Expr::Nil | Expr::Cons(_) => Ok(cells(list).nth(index)),
+        _ => Err(ExprErr::Cause(format!("{} is not list", list))),
+    }
+}
+
+fn cons_arg(expr: &Expr) -> Result<ConsRef, ExprErr> {
+    match expr {
+        Expr::Cons(cell) => Ok(cell.clone()),
+        _ => Err(ExprErr::Cause(format!("{} is not cons", expr))),
+    }
+}
+
+pub fn car(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    single_arg("car", args)?.car()
+}
+
+pub fn cdr(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    single_arg("cdr", args)?.cdr()
+}
+
+// (cons car cdr)
+pub fn cons(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [car, cdr] => Ok(Expr::cons(car.clone(), cdr.clone())),
+        _ => Err(ExprErr::Cause("cons expects exactly two args".to_string())),
+    }
+}
+
+// (rplaca cons object) and (rplacd cons object) return the cons
+pub fn rplaca(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [cell, value] => {
+            cons_arg(cell)?.borrow_mut().car = value.clone();
+            Ok(cell.clone())
+        }
+        _ => Err(ExprErr::Cause(
+            "rplaca expects exactly two args".to_string(),
+        )),
+    }
+}
+
+pub fn rplacd(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [cell, value] => {
+            cons_arg(cell)?.borrow_mut().cdr = value.clone();
+            Ok(cell.clone())
+        }
+        _ => Err(ExprErr::Cause(
+            "rplacd expects exactly two args".to_string(),
+        )),
+    }
+}
+
+// (nth index list)
+pub fn nth(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [index, list] => Ok(match nth_cell(list, index_arg(index)?)? {
+            Some(cell) => cell.borrow().car.clone(),
+            None => Expr::Nil,
+        }),
+        _ => Err(ExprErr::Cause("nth expects exactly two args".to_string())),
+    }
+}
@@ -0,0 +1,1142 @@
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{Evaluator, ExprEnv, Shadowed};
+use crate::hash::hash_table_arg;
+use crate::list::{self, ConsRef};
+use crate::math::integer_expr;
+use crate::number::Number;
+use crate::seq::{self, Kind};
+use std::cmp::Ordering;
+
+// The extended LOOP is parsed into clauses first and then interpreted
+// directly instead of being expanded into other special forms.
+//
+// (loop for x in '(1 2 3) when (> x 1) collect x finally (return 0))
+
+struct LoopSpec {
+    name: String,
+    variables: Vec<VarClause>,
+    initially: Vec<Expr>,
+    finally: Vec<Expr>,
+    body: Vec<Clause>,
+    // accumulation kind of the unnamed accumulator, if any
+    default_accumulation: Option<Accumulation>,
+    // named accumulators introduced by `into var`
+    into_vars: Vec<(Expr, Accumulation)>,
+    // `always` and `never` make a loop that runs to completion return T
+    returns_true: bool,
+}
+
+enum VarClause {
+    With(Expr, Option<Expr>),
+    For(Driver),
+}
+
+enum Driver {
+    In {
+        var: Expr,
+        list: Expr,
+        by: Option<Expr>,
+    },
+    On {
+        var: Expr,
+        list: Expr,
+        by: Option<Expr>,
+    },
+    Arithmetic {
+        var: Expr,
+        from: Option<Expr>,
+        limit: Option<(Expr, bool)>,
+        by: Option<Expr>,
+        down: bool,
+    },
+    Equals {
+        var: Expr,
+        init: Expr,
+        then: Option<Expr>,
+    },
+    Across {
+        var: Expr,
+        vector: Expr,
+    },
+    // for var being the hash-keys of table [using (hash-value other)], or
+    // the hash-values with (hash-key other)
+    Hash {
+        var: Expr,
+        table: Expr,
+        values: bool,
+        using: Option<Expr>,
+    },
+    Repeat(Expr),
+}
+
+enum DriverState {
+    // `for x in list` without `by`, `across` and hash table iteration walk
+    // the elements by index, the latter binding the other half of each
+    // entry to its `using` variable
+    Items {
+        var: Expr,
+        items: Vec<Expr>,
+        index: usize,
+        using: Option<(Expr, Vec<Expr>)>,
+    },
+    // `for x on list` and `for x in list by fn` step through successive tails
+    Tails {
+        var: Expr,
+        rest: Expr,
+        by: Option<Expr>,
+        on: bool,
+    },
+    Number {
+        var: Expr,
+        current: Number,
+        limit: Option<(Number, bool)>,
+        by: Number,
+        down: bool,
+    },
+    Equals {
+        var: Expr,
+        init: Expr,
+        then: Option<Expr>,
+    },
+    Repeat(f64),
+}
+
+enum Clause {
+    Do(Vec<Expr>),
+    Return(Expr),
+    // `slot` is the index of the accumulator: 0 for the unnamed one and
+    // i + 1 for the ith `into` variable
+    Accumulate {
+        kind: Accumulation,
+        form: Expr,
+        into: Option<Expr>,
+        slot: usize,
+    },
+    Conditional {
+        test: Expr,
+        negate: bool,
+        then: Vec<Clause>,
+        otherwise: Vec<Clause>,
+    },
+    While(Expr),
+    Until(Expr),
+    Always(Expr),
+    Never(Expr),
+    Thereis(Expr),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Accumulation {
+    Collect,
+    Append,
+    Nconc,
+    Sum,
+    Count,
+    Maximize,
+    Minimize,
+}
+
+enum Flow {
+    Next,
+    Terminate,
+    Return(Expr),
+}
+
+impl Accumulation {
+    fn from_keyword(keyword: &str) -> Option<Accumulation> {
+        match keyword {
+            "COLLECT" | "COLLECTING" => Some(Accumulation::Collect),
+            "APPEND" | "APPENDING" => Some(Accumulation::Append),
+            "NCONC" | "NCONCING" => Some(Accumulation::Nconc),
+            "SUM" | "SUMMING" => Some(Accumulation::Sum),
+            "COUNT" | "COUNTING" => Some(Accumulation::Count),
+            "MAXIMIZE" | "MAXIMIZING" => Some(Accumulation::Maximize),
+            "MINIMIZE" | "MINIMIZING" => Some(Accumulation::Minimize),
+            _ => None,
+        }
+    }
+
+    fn initial(&self) -> Expr {
+        match self {
+            Accumulation::Sum | Accumulation::Count => integer_expr(0),
+            _ => Expr::Nil,
+        }
+    }
+
+    // accumulations sharing one variable must agree on its type
+    fn is_compatible(&self, other: Accumulation) -> bool {
+        use Accumulation::*;
+        let group = |kind: Accumulation| match kind {
+            Collect | Append | Nconc => 0,
+            Sum | Count => 1,
+            Maximize | Minimize => 2,
+        };
+        group(*self) == group(other)
+    }
+
+    // the numeric accumulations; lists are extended by an Accumulator
+    fn accumulate(&self, acc: Expr, value: Expr) -> Result<Expr, ExprErr> {
+        match self {
+            Accumulation::Collect | Accumulation::Append | Accumulation::Nconc => {
+                unreachable!("lists are accumulated at their last cell")
+            }
+            Accumulation::Sum => Ok(Expr::Number(number(&acc)?.add(number(&value)?)?)),
+            Accumulation::Count => {
+                let count = number(&acc)?;
+                Ok(Expr::Number(if value.is_nil() {
+                    count
+                } else {
+                    count.add(Number::Integer(1))?
+                }))
+            }
+            Accumulation::Maximize | Accumulation::Minimize => {
+                let value_num = number(&value)?;
+                if acc.is_nil() {
+                    return Ok(value);
+                }
+                let acc_num = number(&acc)?;
+                let replace = match self {
+                    Accumulation::Maximize => value_num.compare(acc_num) == Some(Ordering::Greater),
+                    _ => value_num.compare(acc_num) == Some(Ordering::Less),
+                };
+                Ok(if replace { value } else { acc })
+            }
+        }
+    }
+}
+
+// The value of an accumulation. Lists keep their last cell so that each
+// element is added in constant time.
+struct Accumulator {
+    value: Expr,
+    last: Option<ConsRef>,
+}
+
+impl Accumulator {
+    fn new(kind: Accumulation) -> Self {
+        Accumulator {
+            value: kind.initial(),
+            last: None,
+        }
+    }
+
+    fn add(&mut self, kind: Accumulation, value: Expr) -> Result<(), ExprErr> {
+        if matches!(kind, Accumulation::Append | Accumulation::Nconc)
+            && !matches!(value, Expr::Nil | Expr::Cons(_))
+        {
+            return Err(ExprErr::Cause(format!("{} is not list", value)));
+        }
+        match kind {
+            Accumulation::Collect => self.link(Expr::cons(value, Expr::Nil)),
+            // append copies the list, nconc links it in
+            Accumulation::Append => self.link(Expr::list(value.to_vec()?)),
+            Accumulation::Nconc => self.link(value),
+            _ => {
+                let acc = std::mem::replace(&mut self.value, Expr::Nil);
+                self.value = kind.accumulate(acc, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn link(&mut self, list: Expr) {
+        let Some(last) = list::cells(&list).last() else {
+            return;
+        };
+        match &self.last {
+            Some(cell) => cell.borrow_mut().cdr = list,
+            None => self.value = list,
+        }
+        self.last = Some(last);
+    }
+}
+
+fn number(expr: &Expr) -> Result<Number, ExprErr> {
+    match expr {
+        Expr::Number(num) => Ok(*num),
+        _ => Err(ExprErr::Cause(format!("LOOP: {} is not number", expr))),
+    }
+}
+
+fn keyword(expr: Option<&Expr>) -> Option<&str> {
+    match expr {
+        Some(Expr::Symbol(symbol)) => Some(symbol.name.as_str()),
+        _ => None,
+    }
+}
+
+fn is_compound(expr: &Expr) -> bool {
+    matches!(expr, Expr::Cons(_))
+}
+
+fn unsupported(expr: &Expr) -> ExprErr {
+    ExprErr::Cause(format!("LOOP: unsupported clause: {}", expr))
+}
+
+struct LoopParser<'a> {
+    forms: &'a [Expr],
+    pos: usize,
+    spec: LoopSpec,
+}
+
+impl<'a> LoopParser<'a> {
+    fn new(forms: &'a [Expr]) -> Self {
+        Self {
+            forms,
+            pos: 0,
+            spec: LoopSpec {
+                name: "NIL".to_string(),
+                variables: vec![],
+                initially: vec![],
+                finally: vec![],
+                body: vec![],
+                default_accumulation: None,
+                into_vars: vec![],
+                returns_true: false,
+            },
+        }
+    }
+
+    fn peek(&self) -> Option<&'a Expr> {
+        self.forms.get(self.pos)
+    }
+
+    fn peek_keyword(&self) -> Option<&'a str> {
+        keyword(self.peek())
+    }
+
+    fn next(&mut self) -> Result<&'a Expr, ExprErr> {
+        let expr = self
+            .forms
+            .get(self.pos)
+            .ok_or_else(|| ExprErr::Cause("LOOP: unexpected end of clauses".to_string()))?;
+        self.pos += 1;
+        Ok(expr)
+    }
+
+    fn next_keyword(&mut self) -> Result<&'a str, ExprErr> {
+        let expr = self.next()?;
+        keyword(Some(expr))
+            .ok_or_else(|| ExprErr::Cause(format!("LOOP: expected keyword but got {}", expr)))
+    }
+
+    fn compound_forms(&mut self, clause: &str) -> Result<Vec<Expr>, ExprErr> {
+        let mut forms = vec![];
+        while let Some(form) = self.peek() {
+            if !is_compound(form) {
+                break;
+            }
+            forms.push(form.clone());
+            self.pos += 1;
+        }
+        if forms.is_empty() {
+            return Err(ExprErr::Cause(format!(
+                "LOOP: {} expects at least one compound form",
+                clause
+            )));
+        }
+        Ok(forms)
+    }
+
+    fn parse(mut self) -> Result<LoopSpec, ExprErr> {
+        if self.peek_keyword() == Some("NAMED") {
+            self.pos += 1;
+            self.spec.name = match self.next()? {
+                Expr::Symbol(name) => name.name.clone(),
+                expr => return Err(ExprErr::Cause(format!("LOOP: invalid name: {}", expr))),
+            };
+        }
+
+        while let Some(expr) = self.peek() {
+            let keyword = keyword(Some(expr)).ok_or_else(|| {
+                ExprErr::Cause(format!("LOOP: expected clause keyword but got {}", expr))
+            })?;
+            match keyword {
+                "WITH" => {
+                    self.pos += 1;
+                    self.parse_with()?;
+                }
+                "FOR" | "AS" => {
+                    self.pos += 1;
+                    self.parse_for()?;
+                }
+                "REPEAT" => {
+                    self.pos += 1;
+                    let count = self.next()?.clone();
+                    self.spec
+                        .variables
+                        .push(VarClause::For(Driver::Repeat(count)));
+                }
+                "INITIALLY" => {
+                    self.pos += 1;
+                    let forms = self.compound_forms(keyword)?;
+                    self.spec.initially.extend(forms);
+                }
+                "FINALLY" => {
+                    self.pos += 1;
+                    let forms = self.compound_forms(keyword)?;
+                    self.spec.finally.extend(forms);
+                }
+                _ => {
+                    let clause = self.parse_main_clause()?;
+                    self.spec.body.push(clause);
+                }
+            }
+        }
+
+        Ok(self.spec)
+    }
+
+    fn parse_var(&mut self) -> Result<Expr, ExprErr> {
+        let var = self.next()?.clone();
+        check_pattern(&var)?;
+        match self.peek_keyword() {
+            Some("OF-TYPE") => {
+                self.pos += 2;
+            }
+            Some("FIXNUM" | "FLOAT" | "INTEGER" | "NUMBER") => {
+                self.pos += 1;
+            }
+            _ => {}
+        }
+        Ok(var)
+    }
+
+    // with var [= form] {and var [= form]}*
+    fn parse_with(&mut self) -> Result<(), ExprErr> {
+        loop {
+            let var = self.parse_var()?;
+            let form = if self.peek_keyword() == Some("=") {
+                self.pos += 1;
+                Some(self.next()?.clone())
+            } else {
+                None
+            };
+            self.spec.variables.push(VarClause::With(var, form));
+            if self.peek_keyword() != Some("AND") {
+                return Ok(());
+            }
+            self.pos += 1;
+        }
+    }
+
+    // for var driver {and var driver}*
+    fn parse_for(&mut self) -> Result<(), ExprErr> {
+        loop {
+            let var = self.parse_var()?;
+            let driver = self.parse_driver(var)?;
+            self.spec.variables.push(VarClause::For(driver));
+            if self.peek_keyword() != Some("AND") {
+                return Ok(());
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn parse_driver(&mut self, var: Expr) -> Result<Driver, ExprErr> {
+        let expr = self
+            .peek()
+            .ok_or_else(|| ExprErr::Cause(format!("LOOP: missing iteration clause for {}", var)))?;
+        match keyword(Some(expr)) {
+            Some("IN") | Some("ON") => {
+                let on = self.next_keyword()? == "ON";
+                let list = self.next()?.clone();
+                let by = self.parse_by()?;
+                if on {
+                    Ok(Driver::On { var, list, by })
+                } else {
+                    Ok(Driver::In { var, list, by })
+                }
+            }
+            Some("=") => {
+                self.pos += 1;
+                let init = self.next()?.clone();
+                let then = if self.peek_keyword() == Some("THEN") {
+                    self.pos += 1;
+                    Some(self.next()?.clone())
+                } else {
+                    None
+                };
+                Ok(Driver::Equals { var, init, then })
+            }
+            Some("ACROSS") => {
+                self.pos += 1;
+                let vector = self.next()?.clone();
+                Ok(Driver::Across { var, vector })
+            }
+            Some("BEING") => {
+                self.pos += 1;
+                self.parse_hash(var)
+            }
+            Some(
+                "FROM" | "UPFROM" | "DOWNFROM" | "TO" | "UPTO" | "BELOW" | "DOWNTO" | "ABOVE"
+                | "BY",
+            ) => self.parse_arithmetic(var),
+            _ => Err(unsupported(expr)),
+        }
+    }
+
+    // being {the|each} {hash-key|hash-keys|hash-value|hash-values}
+    // {of|in} table [using ({hash-value|hash-key} other)]
+    fn parse_hash(&mut self, var: Expr) -> Result<Driver, ExprErr> {
+        if matches!(self.peek_keyword(), Some("THE" | "EACH")) {
+            self.pos += 1;
+        }
+        let values = match self.next_keyword()? {
+            "HASH-KEY" | "HASH-KEYS" => false,
+            "HASH-VALUE" | "HASH-VALUES" => true,
+            _ => return Err(unsupported(&self.forms[self.pos - 1])),
+        };
+        if !matches!(self.next_keyword()?, "OF" | "IN") {
+            return Err(unsupported(&self.forms[self.pos - 1]));
+        }
+        let table = self.next()?.clone();
+        let using = if self.peek_keyword() == Some("USING") {
+            self.pos += 1;
+            let spec = self.next()?;
+            let other = if values { "HASH-KEY" } else { "HASH-VALUE" };
+            match spec.to_vec().as_deref() {
+                Ok([name, other_var]) if keyword(Some(name)) == Some(other) => {
+                    check_pattern(other_var)?;
+                    Some(other_var.clone())
+                }
+                _ => return Err(unsupported(spec)),
+            }
+        } else {
+            None
+        };
+        Ok(Driver::Hash {
+            var,
+            table,
+            values,
+            using,
+        })
+    }
+
+    fn parse_by(&mut self) -> Result<Option<Expr>, ExprErr> {
+        if self.peek_keyword() == Some("BY") {
+            self.pos += 1;
+            Ok(Some(self.next()?.clone()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // for var {from | upfrom | downfrom} a {to | upto | below | downto | above} b [by step]
+    fn parse_arithmetic(&mut self, var: Expr) -> Result<Driver, ExprErr> {
+        let mut from = None;
+        let mut limit = None;
+        let mut by = None;
+        let mut down = false;
+        while let Some(keyword) = self.peek_keyword() {
+            match keyword {
+                "FROM" | "UPFROM" | "DOWNFROM" if from.is_none() => {
+                    self.pos += 1;
+                    down |= keyword == "DOWNFROM";
+                    from = Some(self.next()?.clone());
+                }
+                "TO" | "UPTO" | "BELOW" | "DOWNTO" | "ABOVE" if limit.is_none() => {
+                    self.pos += 1;
+                    down |= keyword == "DOWNTO" || keyword == "ABOVE";
+                    let inclusive = !(keyword == "BELOW" || keyword == "ABOVE");
+                    limit = Some((self.next()?.clone(), inclusive));
+                }
+                "BY" if by.is_none() => {
+                    self.pos += 1;
+                    by = Some(self.next()?.clone());
+                }
+                _ => break,
+            }
+        }
+        if down && from.is_none() {
+            return Err(ExprErr::Cause(format!(
+                "LOOP: counting down for {} requires a starting value",
+                var
+            )));
+        }
+        Ok(Driver::Arithmetic {
+            var,
+            from,
+            limit,
+            by,
+            down,
+        })
+    }
+
+    fn parse_main_clause(&mut self) -> Result<Clause, ExprErr> {
+        let expr = self.next()?;
+        let keyword = keyword(Some(expr)).unwrap_or_default();
+        match keyword {
+            "WHILE" => Ok(Clause::While(self.next()?.clone())),
+            "UNTIL" => Ok(Clause::Until(self.next()?.clone())),
+            "ALWAYS" => {
+                self.spec.returns_true = true;
+                Ok(Clause::Always(self.next()?.clone()))
+            }
+            "NEVER" => {
+                self.spec.returns_true = true;
+                Ok(Clause::Never(self.next()?.clone()))
+            }
+            "THEREIS" => Ok(Clause::Thereis(self.next()?.clone())),
+            _ => {
+                self.pos -= 1;
+                self.parse_selectable_clause()
+            }
+        }
+    }
+
+    // clauses allowed inside when/if/unless
+    fn parse_selectable_clause(&mut self) -> Result<Clause, ExprErr> {
+        let expr = self.next()?;
+        let keyword = keyword(Some(expr)).unwrap_or_default();
+        if let Some(kind) = Accumulation::from_keyword(keyword) {
+            let form = self.next()?.clone();
+            let into = if self.peek_keyword() == Some("INTO") {
+                self.pos += 1;
+                let var = self.next()?.clone();
+                if !matches!(var, Expr::Symbol(_)) {
+                    return Err(ExprErr::Cause(format!("LOOP: invalid into var: {}", var)));
+                }
+                Some(var)
+            } else {
+                None
+            };
+            let slot = self.register_accumulation(kind, into.as_ref())?;
+            return Ok(Clause::Accumulate {
+                kind,
+                form,
+                into,
+                slot,
+            });
+        }
+
+        match keyword {
+            "DO" | "DOING" => Ok(Clause::Do(self.compound_forms(keyword)?)),
+            "RETURN" => Ok(Clause::Return(self.next()?.clone())),
+            "WHEN" | "IF" | "UNLESS" => {
+                let test = self.next()?.clone();
+                let then = self.parse_conjunction()?;
+                let otherwise = if self.peek_keyword() == Some("ELSE") {
+                    self.pos += 1;
+                    self.parse_conjunction()?
+                } else {
+                    vec![]
+                };
+                if self.peek_keyword() == Some("END") {
+                    self.pos += 1;
+                }
+                Ok(Clause::Conditional {
+                    test,
+                    negate: keyword == "UNLESS",
+                    then,
+                    otherwise,
+                })
+            }
+            _ => Err(unsupported(expr)),
+        }
+    }
+
+    // clause {and clause}*
+    fn parse_conjunction(&mut self) -> Result<Vec<Clause>, ExprErr> {
+        let mut clauses = vec![self.parse_selectable_clause()?];
+        while self.peek_keyword() == Some("AND") {
+            self.pos += 1;
+            clauses.push(self.parse_selectable_clause()?);
+        }
+        Ok(clauses)
+    }
+
+    // the accumulator slot of the clause
+    fn register_accumulation(
+        &mut self,
+        kind: Accumulation,
+        into: Option<&Expr>,
+    ) -> Result<usize, ExprErr> {
+        let (slot, existing) = match into {
+            Some(var) => match self.spec.into_vars.iter().position(|(v, _)| v == var) {
+                Some(index) => (index + 1, Some(self.spec.into_vars[index].1)),
+                None => {
+                    self.spec.into_vars.push((var.clone(), kind));
+                    (self.spec.into_vars.len(), None)
+                }
+            },
+            None => (0, self.spec.default_accumulation.replace(kind)),
+        };
+        match existing {
+            Some(existing) if !existing.is_compatible(kind) => Err(ExprErr::Cause(
+                "LOOP: incompatible accumulations into the same variable".to_string(),
+            )),
+            _ => Ok(slot),
+        }
+    }
+}
+
+// a loop variable is a symbol, NIL (ignored) or a destructuring list of
+// those, possibly dotted
+fn check_pattern(pattern: &Expr) -> Result<(), ExprErr> {
+    match pattern {
+        Expr::Symbol(_) | Expr::Nil => Ok(()),
+        Expr::Cons(cell) => {
+            let cell = cell.borrow();
+            check_pattern(&cell.car)?;
+            check_pattern(&cell.cdr)
+        }
+        _ => Err(ExprErr::Cause(format!(
+            "LOOP: invalid variable: {}",
+            pattern
+        ))),
+    }
+}
+
+impl Evaluator {
+    pub fn eval_loop(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        // simple loop: (loop form*) repeats until an explicit return
+        if args.iter().all(is_compound) {
+            loop {
+                for form in args {
+                    match self.eval(form, env) {
+                        Err(ExprErr::ReturnFrom(name, value)) if name == "NIL" => return Ok(value),
+                        Err(e) => return Err(e),
+                        Ok(_) => {}
+                    }
+                }
+            }
+        }
+
+        let spec = LoopParser::new(args).parse()?;
+        let mut loop_env = env.extend();
+        let mut shadowed = Shadowed::new();
+        let result = self.run_loop(&spec, &mut loop_env, &mut shadowed);
+        self.unbind(shadowed);
+        match result {
+            Err(ExprErr::ReturnFrom(name, value)) if name == spec.name => Ok(value),
+            result => result,
+        }
+    }
+
+    // introduce the variables of a pattern in the loop's frame
+    fn declare_pattern(
+        &mut self,
+        pattern: &Expr,
+        env: &ExprEnv,
+        shadowed: &mut Shadowed,
+    ) -> Result<(), ExprErr> {
+        match pattern {
+            Expr::Symbol(name) => self.bind(env, name, Expr::Nil, false, shadowed),
+            Expr::Cons(cell) => {
+                let (car, cdr) = (cell.borrow().car.clone(), cell.borrow().cdr.clone());
+                self.declare_pattern(&car, env, shadowed)?;
+                self.declare_pattern(&cdr, env, shadowed)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn assign_pattern(
+        &mut self,
+        pattern: &Expr,
+        value: Expr,
+        env: &ExprEnv,
+    ) -> Result<(), ExprErr> {
+        match pattern {
+            Expr::Symbol(name) => self.set_var(env, name, value),
+            Expr::Cons(cell) => {
+                let (car, cdr) = (cell.borrow().car.clone(), cell.borrow().cdr.clone());
+                self.assign_pattern(&car, value.car()?, env)?;
+                self.assign_pattern(&cdr, value.cdr()?, env)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn run_loop(
+        &mut self,
+        spec: &LoopSpec,
+        env: &mut ExprEnv,
+        shadowed: &mut Shadowed,
+    ) -> Result<Expr, ExprErr> {
+        let mut accumulators = vec![Accumulator::new(
+            spec.default_accumulation.unwrap_or(Accumulation::Collect),
+        )];
+        for (var, kind) in &spec.into_vars {
+            self.declare_pattern(var, env, shadowed)?;
+            self.assign_pattern(var, kind.initial(), env)?;
+            accumulators.push(Accumulator::new(*kind));
+        }
+
+        let mut drivers = vec![];
+        for clause in &spec.variables {
+            match clause {
+                VarClause::With(var, form) => {
+                    let value = match form {
+                        Some(form) => self.eval(form, env)?,
+                        None => Expr::Nil,
+                    };
+                    self.declare_pattern(var, env, shadowed)?;
+                    self.assign_pattern(var, value, env)?;
+                }
+                VarClause::For(driver) => {
+                    let state = self.init_driver(driver, env, shadowed)?;
+                    drivers.push(state);
+                }
+            }
+        }
+
+        for form in &spec.initially {
+            self.eval(form, env)?;
+        }
+
+        let mut first = true;
+        'iteration: loop {
+            for state in drivers.iter_mut() {
+                if !self.step_driver(state, first, env)? {
+                    break 'iteration;
+                }
+            }
+            first = false;
+
+            for clause in &spec.body {
+                match self.run_clause(clause, &mut accumulators, None, env)? {
+                    Flow::Next => {}
+                    Flow::Terminate => break 'iteration,
+                    Flow::Return(value) => return Ok(value),
+                }
+            }
+        }
+
+        for form in &spec.finally {
+            self.eval(form, env)?;
+        }
+        // only a return passes on multiple values
+        self.clear_values();
+
+        if spec.default_accumulation.is_some() {
+            Ok(accumulators.swap_remove(0).value)
+        } else if spec.returns_true {
+            Ok(Expr::True)
+        } else {
+            Ok(Expr::Nil)
+        }
+    }
+
+    fn eval_number(&mut self, form: &Expr, env: &mut ExprEnv) -> Result<Number, ExprErr> {
+        number(&self.eval(form, env)?)
+    }
+
+    fn init_driver(
+        &mut self,
+        driver: &Driver,
+        env: &mut ExprEnv,
+        shadowed: &mut Shadowed,
+    ) -> Result<DriverState, ExprErr> {
+        let state = match driver {
+            Driver::In {
+                var,
+                list,
+                by: None,
+            } => DriverState::Items {
+                var: var.clone(),
+                items: self.eval(list, env)?.to_vec()?,
+                index: 0,
+                using: None,
+            },
+            Driver::Across { var, vector } => {
+                let vector = self.eval(vector, env)?;
+                match seq::elements(&vector)? {
+                    (Kind::Vector | Kind::String, items) => DriverState::Items {
+                        var: var.clone(),
+                        items,
+                        index: 0,
+                        using: None,
+                    },
+                    (Kind::List, _) => {
+                        return Err(ExprErr::Cause(format!("LOOP: {} is not vector", vector)))
+                    }
+                }
+            }
+            Driver::Hash {
+                var,
+                table,
+                values,
+                using,
+            } => {
+                let entries = hash_table_arg(&self.eval(table, env)?)?.borrow().entries();
+                let (keys, vals): (Vec<Expr>, Vec<Expr>) = entries.into_iter().unzip();
+                let (items, others) = if *values { (vals, keys) } else { (keys, vals) };
+                if let Some(other) = using {
+                    self.declare_pattern(other, env, shadowed)?;
+                }
+                DriverState::Items {
+                    var: var.clone(),
+                    items,
+                    index: 0,
+                    using: using.clone().map(|other| (other, others)),
+                }
+            }
+            Driver::In { var, list, by } | Driver::On { var, list, by } => DriverState::Tails {
+                var: var.clone(),
+                rest: self.eval(list, env)?,
+                by: match by {
+                    Some(by) => {
+                        let by = self.eval(by, env)?;
+                        Some(self.function_designator(&by)?)
+                    }
+                    None => None,
+                },
+                on: matches!(driver, Driver::On { .. }),
+            },
+            Driver::Arithmetic {
+                var,
+                from,
+                limit,
+                by,
+                down,
+            } => {
+                let current = match from {
+                    Some(from) => self.eval_number(from, env)?,
+                    None => Number::Integer(0),
+                };
+                let limit = match limit {
+                    Some((form, inclusive)) => Some((self.eval_number(form, env)?, *inclusive)),
+                    None => None,
+                };
+                let by = match by {
+                    Some(by) => self.eval_number(by, env)?,
+                    None => Number::Integer(1),
+                };
+                if by.compare(Number::Integer(0)) != Some(Ordering::Greater) {
+                    return Err(ExprErr::Cause(format!(
+                        "LOOP: BY value must be positive: {}",
+                        by
+                    )));
+                }
+                DriverState::Number {
+                    var: var.clone(),
+                    current,
+                    limit,
+                    by,
+                    down: *down,
+                }
+            }
+            Driver::Equals { var, init, then } => match then {
+                Some(then) => {
+                    let value = self.eval(init, env)?;
+                    self.declare_pattern(var, env, shadowed)?;
+                    self.assign_pattern(var, value, env)?;
+                    DriverState::Equals {
+                        var: var.clone(),
+                        init: init.clone(),
+                        then: Some(then.clone()),
+                    }
+                }
+                None => DriverState::Equals {
+                    var: var.clone(),
+                    init: init.clone(),
+                    then: None,
+                },
+            },
+            Driver::Repeat(count) => DriverState::Repeat(self.eval_number(count, env)?.to_f64()),
+        };
+
+        match &state {
+            DriverState::Items { var, .. }
+            | DriverState::Tails { var, .. }
+            | DriverState::Number { var, .. } => self.declare_pattern(var, env, shadowed)?,
+            DriverState::Equals {
+                var, then: None, ..
+            } => self.declare_pattern(var, env, shadowed)?,
+            _ => {}
+        }
+        if let DriverState::Number { var, current, .. } = &state {
+            self.assign_pattern(var, Expr::Number(*current), env)?;
+        }
+        Ok(state)
+    }
+
+    // advance one driver; false means the loop is finished
+    fn step_driver(
+        &mut self,
+        state: &mut DriverState,
+        first: bool,
+        env: &mut ExprEnv,
+    ) -> Result<bool, ExprErr> {
+        match state {
+            DriverState::Items {
+                var,
+                items,
+                index,
+                using,
+            } => match items.get(*index) {
+                Some(item) => {
+                    self.assign_pattern(var, item.clone(), env)?;
+                    if let Some((other, others)) = using {
+                        self.assign_pattern(other, others[*index].clone(), env)?;
+                    }
+                    *index += 1;
+                    Ok(true)
+                }
+                None => Ok(false),
+            },
+            DriverState::Tails { var, rest, by, on } => {
+                if !first {
+                    *rest = match by {
+                        Some(by) => {
+                            let by = by.clone();
+                            self.apply(&by, std::slice::from_ref(rest), env)?
+                        }
+                        None => rest.cdr()?,
+                    };
+                }
+                match rest {
+                    Expr::Cons(cell) => {
+                        let value = if *on {
+                            rest.clone()
+                        } else {
+                            cell.borrow().car.clone()
+                        };
+                        self.assign_pattern(var, value, env)?;
+                        Ok(true)
+                    }
+                    Expr::Nil => Ok(false),
+                    _ => Err(ExprErr::Cause(format!("{} is not list", rest))),
+                }
+            }
+            DriverState::Number {
+                var,
+                current,
+                limit,
+                by,
+                down,
+            } => {
+                if !first {
+                    *current = if *down {
+                        current.sub(*by)?
+                    } else {
+                        current.add(*by)?
+                    };
+                    self.assign_pattern(var, Expr::Number(*current), env)?;
+                }
+                let ordering = limit.map(|(limit, inclusive)| (current.compare(limit), inclusive));
+                Ok(match ordering {
+                    Some((Some(Ordering::Equal), inclusive)) => inclusive,
+                    Some((Some(ordering), _)) => ordering.is_lt() != *down,
+                    Some((None, _)) => false,
+                    None => true,
+                })
+            }
+            DriverState::Equals { var, init, then } => {
+                match then {
+                    Some(_) if first => {}
+                    Some(then) => {
+                        let value = self.eval(then, env)?;
+                        self.assign_pattern(var, value, env)?;
+                    }
+                    None => {
+                        let value = self.eval(init, env)?;
+                        self.assign_pattern(var, value, env)?;
+                    }
+                }
+                Ok(true)
+            }
+            DriverState::Repeat(count) => {
+                *count -= 1.0;
+                Ok(*count >= 0.0)
+            }
+        }
+    }
+
+    // `it` is the value of the test of the innermost enclosing conditional
+    fn eval_clause_form(
+        &mut self,
+        form: &Expr,
+        it: Option<&Expr>,
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        match (form, it) {
+            (Expr::Symbol(symbol), Some(it)) if symbol.name == "IT" => Ok(it.clone()),
+            _ => self.eval(form, env),
+        }
+    }
+
+    fn run_clause(
+        &mut self,
+        clause: &Clause,
+        accumulators: &mut [Accumulator],
+        it: Option<&Expr>,
+        env: &mut ExprEnv,
+    ) -> Result<Flow, ExprErr> {
+        match clause {
+            Clause::Do(forms) => {
+                for form in forms {
+                    self.eval(form, env)?;
+                }
+            }
+            Clause::Return(form) => {
+                return Ok(Flow::Return(self.eval_clause_form(form, it, env)?));
+            }
+            Clause::Accumulate {
+                kind,
+                form,
+                into,
+                slot,
+            } => {
+                let value = self.eval_clause_form(form, it, env)?;
+                let accumulator = &mut accumulators[*slot];
+                accumulator.add(*kind, value)?;
+                if let Some(var) = into {
+                    let value = accumulator.value.clone();
+                    self.assign_pattern(var, value, env)?;
+                }
+            }
+            Clause::Conditional {
+                test,
+                negate,
+                then,
+                otherwise,
+            } => {
+                let value = self.eval(test, env)?;
+                let clauses = if value.is_nil() == *negate {
+                    then
+                } else {
+                    otherwise
+                };
+                for clause in clauses {
+                    match self.run_clause(clause, accumulators, Some(&value), env)? {
+                        Flow::Next => {}
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            Clause::While(form) => {
+                if self.eval(form, env)?.is_nil() {
+                    return Ok(Flow::Terminate);
+                }
+            }
+            Clause::Until(form) => {
+                if !self.eval(form, env)?.is_nil() {
+                    return Ok(Flow::Terminate);
+                }
+            }
+            Clause::Always(form) => {
+                if self.eval(form, env)?.is_nil() {
+                    self.clear_values();
+                    return Ok(Flow::Return(Expr::Nil));
+                }
+            }
+            Clause::Never(form) => {
+                if !self.eval(form, env)?.is_nil() {
+                    self.clear_values();
+                    return Ok(Flow::Return(Expr::Nil));
+                }
+            }
+            Clause::Thereis(form) => {
+                let value = self.eval(form, env)?;
+                if !value.is_nil() {
+                    self.clear_values();
+                    return Ok(Flow::Return(value));
+                }
+            }
+        }
+        Ok(Flow::Next)
+    }
+}
@@ -1,16 +1,39 @@
 use eval::{Evaluator, ExprEnv};
+mod array;
 mod ast;
+mod clos;
 mod eval;
+mod file;
+mod format;
+mod hash;
 mod lexer;
+mod list;
+mod loops;
+mod math;
+mod number;
+mod package;
 mod parser;
+mod pathname;
+mod pretty;
+mod printer;
+mod reader;
+mod seq;
+mod setf;
+mod stream;
+mod string;
+mod structure;
+mod symbol;
 mod token;
 
 fn eval(evaluator: &mut Evaluator, env: &mut ExprEnv, line: &str) -> String {
     let l = lexer::Lexer::new(line.into());
-    let mut p = parser::Parser::new(l);
+    let mut p = parser::Parser::new(l).with_evaluator(evaluator);
     match p.parse() {
-        Ok(expr) => match evaluator.eval(&expr, env) {
-            Ok(result) => result.to_string(),
+        Ok(expr) => match evaluator
+            .eval(&expr, env)
+            .and_then(|result| printer::print_object(&result, true))
+        {
+            Ok(printed) => printed,
             Err(e) => e.to_string(),
         },
         Err(e) => e.to_string(),
@@ -36,7 +59,7 @@ fn repl(evaluator: &mut Evaluator, env: &mut ExprEnv) {
                     Ok(line) => {
                         rl.add_history_entry(line.as_str());
                         let result = eval(evaluator, env, &line);
-                        println!("{}", result);
+                        stream::echo(&result);
                     }
                     Err(ReadlineError::Interrupted) => {
                         break;
@@ -57,7 +80,7 @@ fn repl(evaluator: &mut Evaluator, env: &mut ExprEnv) {
                 let file = File::open(filename).unwrap();
                 for line in io::BufReader::new(file).lines() {
                     let result = eval(evaluator, env, &line.unwrap());
-                    println!("{}", result);
+                    stream::echo(&result);
                 }
             }
         }
@@ -70,7 +93,7 @@ fn repl(evaluator: &mut Evaluator, env: &mut ExprEnv) {
     let stdin = io::stdin();
     for line in stdin.lines() {
         let result = eval(evaluator, env, &line.unwrap());
-        println!("{}", result);
+        stream::echo(&result);
     }
 }
 
@@ -105,6 +128,20 @@ mod tests {
         }
     }
 
+    // like `test`, but errors are compared by their message
+    fn test_eval(tests: Vec<(&str, &str)>) {
+        let mut evaluator = eval::Evaluator::new();
+        let mut env = eval::default_env();
+        for (i, test) in tests.iter().enumerate() {
+            let result = eval(&mut evaluator, &mut env, test.0);
+            assert_eq!(
+                result, test.1,
+                "test[{}] fail: got={}, want={}",
+                i, result, test.1
+            );
+        }
+    }
+
     #[test]
     fn eval_basic_atom() {
         test(vec![
@@ -165,4 +202,1350 @@ mod tests {
             ("(medium 2 4)", "3"),
         ])
     }
+
+    #[test]
+    fn eval_loop() {
+        test(vec![
+            ("(loop for i from 1 to 5 collect i)", "(1 2 3 4 5)"),
+            ("(loop for x in '(1 2 3 4) sum x)", "10"),
+            ("(loop for x in '(1 2 3 4) when (> x 2) collect x)", "(3 4)"),
+            ("(loop for i from 10 downto 7 collect i)", "(10 9 8 7)"),
+            ("(loop for i below 6 by 2 collect i)", "(0 2 4)"),
+            ("(loop for x on '(1 2 3) collect x)", "((1 2 3) (2 3) (3))"),
+            ("(loop for (a b) in '((1 2) (3 4)) collect (+ a b))", "(3 7)"),
+            ("(loop for x = 1 then (* x 2) repeat 5 collect x)", "(1 2 4 8 16)"),
+            ("(loop with total = 0 for x in '(1 2 3) do (setq total (+ total x)) finally (return total))", "6"),
+            ("(loop for x in '(3 1 4 1 5) maximize x)", "5"),
+            ("(loop for x in '(1 2 3) count (> x 1))", "2"),
+            ("(loop for x in '(1 2 3) if (> x 1) collect x into big else collect x into small finally (return (list small big)))", "((1) (2 3))"),
+            ("(loop for x in '(1 2 3 4) while (< x 3) collect x)", "(1 2)"),
+            ("(loop for x in '(1 2 3) thereis (> x 2))", "T"),
+            ("(loop for x in '(1 2 3) always (> x 0))", "T"),
+            ("(loop named outer for x in '(1 2 3) do (loop for y in '(4 5) do (return-from outer (list x y))))", "(1 4)"),
+            ("(loop for x in '(a b) append (list x x))", "(A A B B)"),
+            ("(loop for x in '(1 2) nconc (list x (* x 10)))", "(1 10 2 20)"),
+            ("(loop for x in '(1 2) append (list x) into l finally (return l))", "(1 2)"),
+            ("(length (loop for i below 100000 collect i))", "100000"),
+            ("(loop for x on (loop for i below 100000 collect i) count t)", "100000"),
+            ("(loop for x across (vector 1 2 3) collect (* x x))", "(1 4 9)"),
+            ("(loop for c across \"abc\" collect c)", "(a b c)"),
+            ("(setq h (make-hash-table))", "#<HASH-TABLE :TEST EQL :COUNT 0>"),
+            ("(setf (gethash 'a h) 1 (gethash 'b h) 2)", "2"),
+            ("(loop for k being the hash-keys of h using (hash-value v) collect (list k v))", "((A 1) (B 2))"),
+            ("(loop for v being each hash-value in h sum v)", "3"),
+            ("(setq n 0)", "0"),
+            ("(block done (loop (setq n (+ n 1)) (loop repeat 1 when (= n 3) do (return-from done n))))", "3"),
+        ]);
+    }
+
+    #[test]
+    fn eval_special_variables() {
+        test_eval(vec![
+            ("(defvar *x* 10)", "*X*"),
+            ("(defun get-x () *x*)", "GET-X"),
+            ("(let ((*x* 20)) (get-x))", "20"),
+            ("(get-x)", "10"),
+            ("(defvar *x* 30)", "*X*"),
+            ("*x*", "10"),
+            ("(defparameter *x* 40)", "*X*"),
+            ("(get-x)", "40"),
+            ("(let* ((*x* 1) (y (get-x))) y)", "1"),
+            ("(block b (let ((*x* 2)) (return-from b (get-x))))", "2"),
+            ("(get-x)", "40"),
+            (
+                "(let ((*x* 3)) (undefined-function))",
+                "undefined function: UNDEFINED-FUNCTION",
+            ),
+            ("(get-x)", "40"),
+            ("(defun get-z () z)", "GET-Z"),
+            ("(let ((z 5)) (declare (special z)) (get-z))", "5"),
+            ("(defconstant +limit+ 100)", "+LIMIT+"),
+            ("(setq +limit+ 1)", "cannot set constant: +LIMIT+"),
+            (
+                "(let ((+limit+ 1)) +limit+)",
+                "cannot bind constant: +LIMIT+",
+            ),
+            ("+limit+", "100"),
+        ]);
+    }
+
+    #[test]
+    fn eval_lexical_variables() {
+        test_eval(vec![
+            ("(defun get-y () y)", "GET-Y"),
+            ("(let ((y 1)) (get-y))", "not found symbol: Y"),
+            (
+                "(let ((n 0)) (defun counter () (setq n (+ n 1))))",
+                "COUNTER",
+            ),
+            ("(counter)", "1"),
+            ("(counter)", "2"),
+            ("((lambda (a b) (+ a b)) 1 2)", "3"),
+            ("(let ((a 1)) (let ((a 2) (b a)) (list a b)))", "(2 1)"),
+        ]);
+    }
+
+    #[test]
+    fn eval_function_namespace() {
+        test_eval(vec![
+            ("(setq list 1)", "1"),
+            ("(list list 2)", "(1 2)"),
+            ("(defun double (x) (* x 2))", "DOUBLE"),
+            ("(let ((double 3)) (double double))", "6"),
+            ("(funcall #'double 4)", "8"),
+            ("(funcall 'double 5)", "10"),
+            ("(apply #'+ 1 2 '(3 4))", "10"),
+            ("(funcall (lambda (x) (+ x 1)) 1)", "2"),
+            ("(setq f #'double)", "LAMBDA"),
+            ("(f 1)", "undefined function: F"),
+            ("(funcall f 1)", "2"),
+            ("(fboundp 'double)", "T"),
+            ("(boundp 'double)", "NIL"),
+            ("(boundp 'list)", "T"),
+            ("(symbol-value 'list)", "1"),
+            ("(funcall (symbol-function 'double) 6)", "12"),
+            ("(fmakunbound 'double)", "DOUBLE"),
+            ("(fboundp 'double)", "NIL"),
+            ("(makunbound 'list)", "LIST"),
+            ("(boundp 'list)", "NIL"),
+            ("(let ((x 1)) (boundp 'x))", "NIL"),
+        ]);
+    }
+
+    #[test]
+    fn eval_symbols() {
+        test_eval(vec![
+            ("(eq 'foo 'foo)", "T"),
+            ("(eq 'foo 'bar)", "NIL"),
+            ("(eq (intern \"FOO\") 'foo)", "T"),
+            ("(find-symbol \"NEVER-READ-BEFORE\")", "NIL"),
+            ("(find-symbol \"FOO\")", "FOO"),
+            ("(symbol-name 'foo)", "\"FOO\""),
+            ("(symbol-package 'foo)", "#<PACKAGE COMMON-LISP-USER>"),
+            ("(setq s (make-symbol \"FOO\"))", "#:FOO"),
+            ("(eq s 'foo)", "NIL"),
+            ("(symbol-package s)", "NIL"),
+            ("(eq (gensym) (gensym))", "NIL"),
+            ("(symbol-name (gensym \"TMP\"))", "\"TMP3\""),
+            ("(symbol-plist 'foo)", "NIL"),
+            ("(get 'foo 'color 'red)", "RED"),
+            ("(eq (intern \"NIL\") nil)", "T"),
+        ]);
+    }
+
+    #[test]
+    fn eval_keywords() {
+        test_eval(vec![
+            (":test", ":TEST"),
+            ("'(:a 1 :b 2)", "(:A 1 :B 2)"),
+            ("(eq :key ':key)", "T"),
+            ("(keywordp :key)", "T"),
+            ("(keywordp 'key)", "NIL"),
+            ("(eq :key 'key)", "NIL"),
+            ("(symbol-package :key)", "#<PACKAGE KEYWORD>"),
+            ("(setq :key 1)", "cannot set constant: KEY"),
+            ("(getf '(:a 1 :b 2) :b)", "2"),
+            ("(getf '(:a 1) :c 'none)", "NONE"),
+            (
+                "(defun opt (a &optional (b 2 b-p) c) (list a b b-p c))",
+                "OPT",
+            ),
+            ("(opt 1)", "(1 2 NIL NIL)"),
+            ("(opt 1 5 6)", "(1 5 T 6)"),
+            ("(opt)", "number of args and lambda's arg is not same"),
+            ("(defun rest (a &rest more) (list a more))", "REST"),
+            ("(rest 1 2 3)", "(1 (2 3))"),
+            (
+                "(defun kw (&key (start 0) end ((:by step) 1)) (list start end step))",
+                "KW",
+            ),
+            ("(kw)", "(0 NIL 1)"),
+            ("(kw :end 10 :by 2)", "(0 10 2)"),
+            ("(kw :stop 1)", "unknown keyword arg: :STOP"),
+            ("(kw :stop 1 :allow-other-keys t)", "(0 NIL 1)"),
+            ("(kw :end)", "odd number of keyword args"),
+            (
+                "(funcall (lambda (x &key (y (* x 2))) (list x y)) 3)",
+                "(3 6)",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn eval_packages() {
+        test_eval(vec![
+            ("*package*", "#<PACKAGE COMMON-LISP-USER>"),
+            ("(symbol-package 'list)", "#<PACKAGE COMMON-LISP>"),
+            ("(eq 'cl:list 'list)", "T"),
+            ("(cl-user::foo)", "undefined function: FOO"),
+            (
+                "(defpackage :util (:frobnicate))",
+                "unknown defpackage option: FROBNICATE",
+            ),
+            ("(find-package :util)", "NIL"),
+            (
+                "(defpackage :util (:use :cl) (:export :helper :shared))",
+                "#<PACKAGE UTIL>",
+            ),
+            ("(in-package :util)", "#<PACKAGE UTIL>"),
+            ("(defun helper (x) (list x 'secret))", "HELPER"),
+            ("(defun shared () 'util)", "SHARED"),
+            ("(in-package :cl-user)", "#<PACKAGE COMMON-LISP-USER>"),
+            ("(util:helper 1)", "(1 UTIL::SECRET)"),
+            ("(util:secret)", "symbol SECRET is not external in UTIL"),
+            ("(find-symbol \"HELPER\")", "NIL"),
+            ("(defun shared () 'mine)", "SHARED"),
+            (
+                "(use-package :util)",
+                "name conflict: SHARED is already accessible in COMMON-LISP-USER",
+            ),
+            ("(shadow 'shared)", "T"),
+            ("(use-package :util)", "T"),
+            ("(helper 2)", "(2 UTIL::SECRET)"),
+            ("(shared)", "MINE"),
+            ("'util:shared", "UTIL:SHARED"),
+            ("(import 'util::secret)", "T"),
+            ("(helper 3)", "(3 SECRET)"),
+            (
+                "(defpackage :foo (:nicknames \"COMMON-LISP\"))",
+                "package name conflict: COMMON-LISP already names package COMMON-LISP",
+            ),
+            (
+                "(defpackage :cl-user (:nicknames \"KEYWORD\"))",
+                "package name conflict: KEYWORD already names package KEYWORD",
+            ),
+            ("(find-package :foo)", "NIL"),
+            ("(list :a 'car)", "(:A CAR)"),
+            ("(defpackage :foo (:nicknames :f))", "#<PACKAGE FOO>"),
+            ("(defpackage :foo (:nicknames :f))", "#<PACKAGE FOO>"),
+            ("(find-package :f)", "#<PACKAGE FOO>"),
+            ("(defpackage :other (:use))", "#<PACKAGE OTHER>"),
+            ("(in-package :other)", "#<PACKAGE OTHER>"),
+            ("(list 1)", "undefined function: LIST"),
+            ("(cl:list 1 'a)", "(1 A)"),
+            ("(cl:in-package :cl-user)", "#<PACKAGE COMMON-LISP-USER>"),
+            ("(package-name (find-package :cl))", "\"COMMON-LISP\""),
+            ("(find-package :missing)", "NIL"),
+            ("(export 'local)", "T"),
+            ("(find-symbol \"LOCAL\" :cl-user)", "LOCAL"),
+            (
+                "(multiple-value-list (find-symbol \"CAR\" :cl))",
+                "(CAR :EXTERNAL)",
+            ),
+            (
+                "(multiple-value-list (find-symbol \"CAR\"))",
+                "(CAR :INHERITED)",
+            ),
+            (
+                "(multiple-value-list (find-symbol \"SHARED\"))",
+                "(SHARED :INTERNAL)",
+            ),
+            (
+                "(multiple-value-list (find-symbol \"LOCAL\"))",
+                "(LOCAL :EXTERNAL)",
+            ),
+            (
+                "(multiple-value-list (find-symbol \"ABSENT-NAME\"))",
+                "(NIL NIL)",
+            ),
+            (
+                "(multiple-value-list (intern \"FRESH-NAME\"))",
+                "(FRESH-NAME NIL)",
+            ),
+            (
+                "(multiple-value-list (intern \"FRESH-NAME\"))",
+                "(FRESH-NAME :INTERNAL)",
+            ),
+            (
+                "(multiple-value-list (intern \"LIST\"))",
+                "(LIST :INHERITED)",
+            ),
+            (
+                "(defpackage :mine (:use :cl) (:shadow \"LOOP\" \"PUSH\"))",
+                "#<PACKAGE MINE>",
+            ),
+            ("(in-package :mine)", "#<PACKAGE MINE>"),
+            ("(defun loop (x) (* x 2))", "LOOP"),
+            ("(defun push (a b) (+ a b))", "PUSH"),
+            (
+                "(list (loop 5) (push 1 2) (cl:loop for i from 1 to 2 collect i))",
+                "(10 3 (1 2))",
+            ),
+            ("(in-package :cl-user)", "#<PACKAGE COMMON-LISP-USER>"),
+            ("(loop for i from 1 to 2 collect i)", "(1 2)"),
+        ]);
+    }
+
+    #[test]
+    fn eval_hash_tables() {
+        test_eval(vec![
+            ("(setq h (make-hash-table))", "#<HASH-TABLE :TEST EQL :COUNT 0>"),
+            ("(setf (gethash 'a h) 1 (gethash 'b h) 2)", "2"),
+            ("(gethash 'a h)", "1"),
+            ("(multiple-value-list (gethash 'b h))", "(2 T)"),
+            ("(multiple-value-list (gethash 'c h))", "(NIL NIL)"),
+            ("(gethash 'c h 0)", "0"),
+            ("(setf (gethash 'a h) 10)", "10"),
+            ("(hash-table-count h)", "2"),
+            ("(setq keys nil)", "NIL"),
+            ("(maphash (lambda (k v) (setq keys (list k v keys))) h)", "NIL"),
+            ("keys", "(B 2 (A 10 NIL))"),
+            ("(remhash 'a h)", "T"),
+            ("(remhash 'a h)", "NIL"),
+            ("(hash-table-count h)", "1"),
+            ("(clrhash h)", "#<HASH-TABLE :TEST EQL :COUNT 0>"),
+            ("(setq s (make-hash-table :test #'equal))", "#<HASH-TABLE :TEST EQUAL :COUNT 0>"),
+            ("(setf (gethash '(1 \"x\") s) 'found)", "FOUND"),
+            ("(gethash (list 1 \"x\") s)", "FOUND"),
+            ("(gethash (list 1 \"X\") s)", "NIL"),
+            ("(list (eq \"x\" \"x\") (eql \"x\" \"x\") (equal \"x\" \"x\") (let ((x \"x\")) (eql x x)))", "(NIL NIL T T)"),
+            ("(setq k \"key\" e (make-hash-table))", "#<HASH-TABLE :TEST EQL :COUNT 0>"),
+            ("(setf (gethash k e) 1)", "1"),
+            ("(list (gethash k e) (gethash \"key\" e))", "(1 NIL)"),
+            ("(setq c '#1=(a . #1#))", "#1=(A . #1#)"),
+            ("(setf (gethash c s) 'circular)", "CIRCULAR"),
+            ("(gethash c s)", "CIRCULAR"),
+            ("(setq p (make-hash-table :test 'equalp))", "#<HASH-TABLE :TEST EQUALP :COUNT 0>"),
+            ("(setf (gethash \"Key\" p) 1)", "1"),
+            ("(gethash \"KEY\" p)", "1"),
+            ("(make-hash-table :test 'car)", "invalid hash table test: CAR"),
+            ("(make-hash-table :weakness t)", "unknown keyword arg to make-hash-table: :WEAKNESS"),
+            ("(setf (gethash 1 p) 'one (gethash 2 p) 'two)", "TWO"),
+            (
+                "(with-hash-table-iterator (next p) (list (multiple-value-list (next)) (multiple-value-list (next)) (multiple-value-list (next)) (multiple-value-list (next))))",
+                "((T \"Key\" 1) (T 1 ONE) (T 2 TWO) (NIL))",
+            ),
+            ("(equalp \"abc\" \"ABC\")", "T"),
+            ("(equal \"abc\" \"ABC\")", "NIL"),
+            ("(list (equalp 1 1.0) (equalp 1/2 0.5) (equal 1 1.0) (equalp 1 2))", "(T T NIL NIL)"),
+            ("(setf (gethash 1.0 p) 'one-float)", "ONE-FLOAT"),
+            ("(list (gethash 1 p) (gethash 2.0 p) (hash-table-count p))", "(ONE-FLOAT TWO 3)"),
+            ("(eql 1 1)", "T"),
+        ]);
+    }
+
+    #[test]
+    fn eval_places() {
+        test_eval(vec![
+            ("(setq x (list 1 2 3))", "(1 2 3)"),
+            ("(setf (car x) 10)", "10"),
+            ("(setf (nth 2 x) 30 (cdr (cdr x)) (list 3 4))", "(3 4)"),
+            ("x", "(10 2 3 4)"),
+            ("(setf (nth 9 x) 1)", "index 9 is out of range"),
+            ("(incf (car x))", "11"),
+            ("(decf (nth 1 x) 5)", "-3"),
+            ("(push 0 x)", "(0 11 -3 3 4)"),
+            ("(pop x)", "0"),
+            ("(pop (cdr x))", "-3"),
+            ("x", "(11 3 4)"),
+            ("(pushnew 3 x)", "(11 3 4)"),
+            ("(pushnew 5 x)", "(5 11 3 4)"),
+            ("(setq names (list \"a\"))", "(\"a\")"),
+            ("(pushnew \"A\" names :test #'equalp)", "(\"a\")"),
+            ("(setq a 1 b 2 c 3)", "3"),
+            ("(rotatef a b c)", "NIL"),
+            ("(list a b c)", "(2 3 1)"),
+            ("(shiftf a b c 4)", "2"),
+            ("(list a b c)", "(3 1 4)"),
+            ("(psetf a b b a)", "NIL"),
+            ("(list a b)", "(1 3)"),
+            ("(setq plist nil)", "NIL"),
+            ("(setf (getf plist :a) 1)", "1"),
+            ("(incf (getf plist :a))", "2"),
+            ("(setf (getf plist :b) 3)", "3"),
+            ("plist", "(:B 3 :A 2)"),
+            ("(setq h (make-hash-table))", "#<HASH-TABLE :TEST EQL :COUNT 0>"),
+            ("(incf (gethash 'n h 0) 5)", "5"),
+            ("(push 'x (gethash 'l h))", "(X)"),
+            ("(gethash 'n h)", "5"),
+            ("(defvar *level* 1)", "*LEVEL*"),
+            ("(setf (symbol-value '*level*) 2)", "2"),
+            ("*level*", "2"),
+            ("(setf (get 'widget 'color) 'red)", "RED"),
+            ("(get 'widget 'color)", "RED"),
+            ("(setq registry (make-hash-table))", "#<HASH-TABLE :TEST EQL :COUNT 0>"),
+            ("(defun lookup (k) (gethash k registry))", "LOOKUP"),
+            ("(defun register (k v) (setf (gethash k registry) v))", "REGISTER"),
+            ("(defsetf lookup register)", "LOOKUP"),
+            ("(setf (lookup 'a) 1)", "1"),
+            ("(incf (lookup 'a))", "2"),
+            ("(gethash 'a registry)", "2"),
+            ("(defun entry (k) (lookup k))", "ENTRY"),
+            ("(defsetf entry (k) (v) (list 'register k (list 'list v)))", "ENTRY"),
+            ("(setf (entry 'b) 3)", "3"),
+            ("(entry 'b)", "(3)"),
+            ("(setq pair (list 1 2))", "(1 2)"),
+            (
+                "(define-setf-expander head (var) (let ((store (gensym))) (values nil nil (list store) (list 'rplaca var store) (list 'car var))))",
+                "HEAD",
+            ),
+            ("(setf (head pair) 9)", "9"),
+            ("pair", "(9 2)"),
+            ("(incf (head pair))", "10"),
+            ("pair", "(10 2)"),
+            ("(setf (foo x) 1)", "invalid place: (FOO X)"),
+        ]);
+    }
+
+    #[test]
+    fn eval_conses() {
+        test_eval(vec![
+            ("(cons 1 2)", "(1 . 2)"),
+            ("'(a b . c)", "(A B . C)"),
+            ("(cdr '(a . (b . (c))))", "(B C)"),
+            ("'(. a)", "unexpected ."),
+            ("'(a . b c)", "unexpected C"),
+            ("(setq x (list 1 2 3))", "(1 2 3)"),
+            ("(rplacd (cdr x) 'end)", "(2 . END)"),
+            ("x", "(1 2 . END)"),
+            ("(rplaca 1 2)", "1 is not cons"),
+            ("(length x)", "(1 2 . END) is not list"),
+            ("(defun set-first (l v) (setf (car l) v))", "SET-FIRST"),
+            ("(setq x (list 1 2 3))", "(1 2 3)"),
+            ("(set-first x 'one)", "ONE"),
+            ("x", "(ONE 2 3)"),
+            ("(defun put-second (l v) (setf (nth 1 l) v))", "PUT-SECOND"),
+            ("(defun second-of (l) (nth 1 l))", "SECOND-OF"),
+            ("(defsetf second-of put-second)", "SECOND-OF"),
+            ("(setf (second-of x) 'two)", "TWO"),
+            ("x", "(ONE TWO 3)"),
+            ("(setq outer (list (list 1 2) 3))", "((1 2) 3)"),
+            ("(setq inner (car outer))", "(1 2)"),
+            ("(setf (car inner) 'changed)", "CHANGED"),
+            ("outer", "((CHANGED 2) 3)"),
+            ("(eq inner (car outer))", "T"),
+            (
+                "(list (eq (list 1) (list 1)) (eql (list 1) (list 1)) (equal (list 1) (list 1)))",
+                "(NIL NIL T)",
+            ),
+            ("(setq y (list 3 1 2))", "(3 1 2)"),
+            ("(sort y #'<)", "(1 2 3)"),
+            ("y", "(1 2 3)"),
+            ("(nreverse y)", "(3 2 1)"),
+            ("y", "(3 2 1)"),
+            ("(delete 2 y)", "(3 1)"),
+            ("y", "(3 1)"),
+            ("(reverse y)", "(1 3)"),
+            ("y", "(3 1)"),
+            (
+                "(setq h (make-hash-table))",
+                "#<HASH-TABLE :TEST EQL :COUNT 0>",
+            ),
+            ("(setf (gethash y h) 'found)", "FOUND"),
+            ("(list (gethash y h) (gethash (list 3 1) h))", "(FOUND NIL)"),
+            (
+                "(let ((l nil)) (loop for i below 100000 do (push i l)) (list (length l) (car l)))",
+                "(100000 99999)",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn eval_structures() {
+        test_eval(vec![
+            ("(defstruct point (x 0 :type number) (y 0))", "POINT"),
+            ("(setq p (make-point :x 1))", "#S(POINT :X 1 :Y 0)"),
+            ("(point-x p)", "1"),
+            ("(setf (point-y p) 5)", "5"),
+            ("(incf (point-x p) 10)", "11"),
+            ("p", "#S(POINT :X 11 :Y 5)"),
+            ("(point-p p)", "T"),
+            ("(point-p 1)", "NIL"),
+            ("(setq q (copy-point p))", "#S(POINT :X 11 :Y 5)"),
+            ("(setf (point-x q) 0)", "0"),
+            ("(list (point-x p) (eq p q) (equalp p (copy-point p)))", "(11 NIL T)"),
+            ("(make-point :x \"a\")", "a is not of type NUMBER for slot X"),
+            ("(make-point :z 1)", "unknown keyword arg: :Z"),
+            ("(point-x 1)", "1 is not POINT"),
+            (
+                "(defstruct (point3 (:include point (y 1)) (:conc-name p3-)) (z 0 :read-only t))",
+                "POINT3",
+            ),
+            ("(setq r (make-point3 :z 2))", "#S(POINT3 :X 0 :Y 1 :Z 2)"),
+            ("(list (point-p r) (point3-p p) (p3-z r) (point-x r))", "(T NIL 2 0)"),
+            ("(setf (p3-z r) 3)", "slot Z of POINT3 is read-only"),
+            ("'#S(point :y 2)", "#S(POINT :X 0 :Y 2)"),
+            ("(point-y #S(point :x 1 :y 7))", "7"),
+            ("(defvar *count* 0)", "*COUNT*"),
+            ("(defstruct (node (:constructor new-node) (:predicate is-node)) (id (incf *count*)))", "NODE"),
+            ("(list (new-node) (new-node))", "(#S(NODE :ID 1) #S(NODE :ID 2))"),
+            ("(is-node (new-node :id 9))", "T"),
+            (
+                "(defstruct (p (:constructor make-p (x y)) (:constructor make-p2 (x &optional y &key (z (* x 10)) &aux (w (+ x z))))) x (y 5) z w (v 'v))",
+                "P",
+            ),
+            ("(make-p 1 2)", "#S(P :X 1 :Y 2 :Z NIL :W NIL :V V)"),
+            ("(list (make-p2 1) (make-p2 1 2 :z 3))", "(#S(P :X 1 :Y 5 :Z 10 :W 11 :V V) #S(P :X 1 :Y 2 :Z 3 :W 4 :V V))"),
+            ("(make-p 1)", "number of args and lambda's arg is not same"),
+            ("(defstruct (pair (:type list) :named) left right)", "PAIR"),
+            ("(setq pr (make-pair :left 1 :right 2))", "(PAIR 1 2)"),
+            ("(setf (pair-right pr) 3)", "3"),
+            ("(list pr (pair-left pr) (pair-p pr) (pair-p '(1 2)))", "((PAIR 1 3) 1 T NIL)"),
+        ]);
+    }
+
+    #[test]
+    fn eval_clos() {
+        test_eval(vec![
+            (
+                "(defclass shape () ((name :initarg :name :initform \"shape\" :accessor shape-name)))",
+                "#<STANDARD-CLASS SHAPE>",
+            ),
+            (
+                "(defclass circle (shape) ((radius :initarg :radius :reader radius)))",
+                "#<STANDARD-CLASS CIRCLE>",
+            ),
+            ("(setq c (make-instance 'circle :radius 2))", "#<CIRCLE>"),
+            ("(list (shape-name c) (radius c))", "(\"shape\" 2)"),
+            ("(setf (shape-name c) \"disc\")", "\"disc\""),
+            ("(list (slot-value c 'name) (slot-boundp c 'radius))", "(\"disc\" T)"),
+            ("(setf (slot-value c 'radius) 3)", "3"),
+            ("(radius c)", "3"),
+            ("(slot-boundp (make-instance 'circle) 'radius)", "NIL"),
+            ("(radius (make-instance 'circle))", "slot RADIUS is unbound in #<CIRCLE>"),
+            ("(slot-value c 'x)", "slot X is missing from #<CIRCLE>"),
+            ("(make-instance 'circle :size 1)", "invalid initarg to make-instance of CIRCLE: :SIZE"),
+            ("(list (class-of c) (class-name (find-class 'shape)))", "(#<STANDARD-CLASS CIRCLE> SHAPE)"),
+            ("(class-of 1)", "#<BUILT-IN-CLASS INTEGER>"),
+            ("(defgeneric area (shape))", "#<STANDARD-GENERIC-FUNCTION AREA>"),
+            ("(defmethod area ((c circle)) (* 3 (radius c) (radius c)))", "#<STANDARD-GENERIC-FUNCTION AREA>"),
+            ("(area c)", "27"),
+            ("(area 1)", "no applicable method for AREA with args (1)"),
+            ("(defmethod area ((c circle) extra) 0)", "lambda list of method does not match generic function AREA"),
+            // dispatch on built-in classes, eql specializers and several arguments
+            ("(defmethod describe-it ((x t)) 'object)", "#<STANDARD-GENERIC-FUNCTION DESCRIBE-IT>"),
+            ("(defmethod describe-it ((x number)) 'number)", "#<STANDARD-GENERIC-FUNCTION DESCRIBE-IT>"),
+            ("(defmethod describe-it ((x integer)) (list 'integer (call-next-method)))", "#<STANDARD-GENERIC-FUNCTION DESCRIBE-IT>"),
+            ("(defmethod describe-it ((x (eql 0))) (list 'zero (call-next-method)))", "#<STANDARD-GENERIC-FUNCTION DESCRIBE-IT>"),
+            ("(defmethod describe-it ((x string)) 'string)", "#<STANDARD-GENERIC-FUNCTION DESCRIBE-IT>"),
+            ("(defmethod describe-it ((x null)) 'null)", "#<STANDARD-GENERIC-FUNCTION DESCRIBE-IT>"),
+            (
+                "(list (describe-it 0) (describe-it 1.5) (describe-it \"a\") (describe-it nil) (describe-it 'a) (describe-it c))",
+                "((ZERO (INTEGER NUMBER)) NUMBER STRING NULL OBJECT OBJECT)",
+            ),
+            ("(defmethod collide ((a shape) (b circle)) 'shape-circle)", "#<STANDARD-GENERIC-FUNCTION COLLIDE>"),
+            ("(defmethod collide ((a circle) (b shape)) 'circle-shape)", "#<STANDARD-GENERIC-FUNCTION COLLIDE>"),
+            ("(collide c c)", "CIRCLE-SHAPE"),
+            ("(collide (make-instance 'shape) c)", "SHAPE-CIRCLE"),
+            // standard method combination
+            ("(defvar *log* nil)", "*LOG*"),
+            ("(defmethod area :before ((s shape)) (push 'before-shape *log*))", "#<STANDARD-GENERIC-FUNCTION AREA>"),
+            ("(defmethod area :before ((c circle)) (push 'before-circle *log*))", "#<STANDARD-GENERIC-FUNCTION AREA>"),
+            ("(defmethod area :after ((s shape)) (push 'after-shape *log*))", "#<STANDARD-GENERIC-FUNCTION AREA>"),
+            ("(defmethod area :after ((c circle)) (push 'after-circle *log*))", "#<STANDARD-GENERIC-FUNCTION AREA>"),
+            (
+                "(defmethod area :around ((c circle)) (push 'around *log*) (list (next-method-p) (call-next-method)))",
+                "#<STANDARD-GENERIC-FUNCTION AREA>",
+            ),
+            ("(area c)", "(T 27)"),
+            ("*log*", "(AFTER-CIRCLE AFTER-SHAPE BEFORE-SHAPE BEFORE-CIRCLE AROUND)"),
+            ("(defmethod area ((s shape)) 0)", "#<STANDARD-GENERIC-FUNCTION AREA>"),
+            ("(defmethod area ((c circle)) (+ 1 (call-next-method)))", "#<STANDARD-GENERIC-FUNCTION AREA>"),
+            ("(area c)", "(T 1)"),
+            ("(call-next-method)", "call-next-method called outside of a method"),
+            // initialize-instance, setf functions and redefinition
+            ("(defclass counter () ((count :initform 0 :accessor counter-count)))", "#<STANDARD-CLASS COUNTER>"),
+            (
+                "(defmethod initialize-instance :after ((c counter) &key start) (setf (counter-count c) (+ start 1)))",
+                "#<STANDARD-GENERIC-FUNCTION INITIALIZE-INSTANCE>",
+            ),
+            ("(counter-count (make-instance 'counter :start 4))", "5"),
+            ("(defun (setf counter-total) (value c) (setf (counter-count c) (* value 2)))", "(SETF COUNTER-TOTAL)"),
+            ("(defun counter-total (c) (counter-count c))", "COUNTER-TOTAL"),
+            ("(setq k (make-instance 'counter :start 0))", "#<COUNTER>"),
+            ("(setf (counter-total k) 5)", "5"),
+            ("(incf (counter-count k))", "11"),
+            ("(defclass counter () ((count :initform 0 :accessor counter-count) (step :initform 2)))", "#<STANDARD-CLASS COUNTER>"),
+            ("(slot-value (make-instance 'counter :start 0) 'step)", "2"),
+            ("(defun plain (x) x)", "PLAIN"),
+            ("(defmethod plain ((x t)) x)", "PLAIN already names an ordinary function"),
+        ]);
+    }
+
+    #[test]
+    fn eval_class_precedence() {
+        test_eval(vec![
+            ("(defclass a () ())", "#<STANDARD-CLASS A>"),
+            ("(defclass b (a) ())", "#<STANDARD-CLASS B>"),
+            ("(defclass c (a) ())", "#<STANDARD-CLASS C>"),
+            ("(defclass d (b c) ())", "#<STANDARD-CLASS D>"),
+            (
+                "(defmethod who ((x a)) '(a))",
+                "#<STANDARD-GENERIC-FUNCTION WHO>",
+            ),
+            (
+                "(defmethod who ((x b)) (cons-up 'b (call-next-method)))",
+                "#<STANDARD-GENERIC-FUNCTION WHO>",
+            ),
+            (
+                "(defmethod who ((x c)) (cons-up 'c (call-next-method)))",
+                "#<STANDARD-GENERIC-FUNCTION WHO>",
+            ),
+            (
+                "(defmethod who ((x d)) (cons-up 'd (call-next-method)))",
+                "#<STANDARD-GENERIC-FUNCTION WHO>",
+            ),
+            ("(defun cons-up (x list) (push x list))", "CONS-UP"),
+            ("(who (make-instance 'd))", "(D B C A)"),
+            (
+                "(defclass e (a b) ())",
+                "inconsistent class precedence list for E",
+            ),
+            ("(defclass a (d) ())", "circular superclasses for A"),
+            (
+                "(defclass f (integer) ())",
+                "F cannot inherit from #<BUILT-IN-CLASS INTEGER>",
+            ),
+            ("(defstruct s1 x)", "S1"),
+            ("(defstruct (s2 (:include s1)) y)", "S2"),
+            (
+                "(defmethod who ((x s1)) (list 's1 (slot-value x 'x)))",
+                "#<STANDARD-GENERIC-FUNCTION WHO>",
+            ),
+            ("(who (make-s2 :x 1))", "(S1 1)"),
+            ("(class-of (make-s2))", "#<STRUCTURE-CLASS S2>"),
+        ]);
+    }
+
+    #[test]
+    fn eval_arrays() {
+        test_eval(vec![
+            ("#(1 2 3)", "#(1 2 3)"),
+            ("(setq v #(1 (+ 1 1) c))", "#(1 (+ 1 1) C)"),
+            ("(aref v 1)", "(+ 1 1)"),
+            ("(setf (aref v 0) 10)", "10"),
+            ("(incf (aref v 0))", "11"),
+            ("v", "#(11 (+ 1 1) C)"),
+            ("(aref v 3)", "index 3 is out of bounds for #(11 (+ 1 1) C)"),
+            ("(aref v 0 0)", "wrong number of subscripts for #(11 (+ 1 1) C): 2"),
+            ("(make-array 3 :initial-element 0)", "#(0 0 0)"),
+            ("(setq m (make-array '(2 3) :initial-element 0))", "#2A((0 0 0) (0 0 0))"),
+            ("(setf (aref m 1 2) 5)", "5"),
+            ("(list (aref m 1 2) (array-dimensions m) (array-rank m) (array-total-size m))", "(5 (2 3) 2 6)"),
+            ("(make-array '(2 2) :initial-contents '((1 2) #(3 4)))", "#2A((1 2) (3 4))"),
+            ("(make-array '(2 2) :initial-contents '((1 2)))", "((1 2)) does not match the array dimensions"),
+            ("(aref #2A((1 2) (3 4)) 1 0)", "3"),
+            ("(make-array nil :initial-element 7)", "#0A7"),
+            ("(make-array '(4294967296 4294967296))", "array dimensions (4294967296 4294967296) exceed the total size limit 16777216"),
+            ("(make-array '(0 4294967296 4294967296))", "array dimensions (0 4294967296 4294967296) exceed the total size limit 16777216"),
+            ("(make-array 100000000000000)", "array dimensions (100000000000000) exceed the total size limit 16777216"),
+            ("(make-array '(0 2))", "#2A()"),
+            ("(setq s (make-array 2 :fill-pointer 0 :adjustable t))", "#()"),
+            ("(list (vector-push-extend 'a s) (vector-push-extend 'b s) (vector-push-extend 'c s))", "(0 1 2)"),
+            ("(list s (fill-pointer s) (array-dimension s 0) (adjustable-array-p s))", "(#(A B C) 3 4 T)"),
+            ("(vector-pop s)", "C"),
+            ("(setf (fill-pointer s) 1)", "1"),
+            ("(list s (aref s 1))", "(#(A) B)"),
+            ("(setq f (make-array 1 :fill-pointer t))", "#(NIL)"),
+            ("(list (vector-push 1 f) (array-has-fill-pointer-p f))", "(NIL T)"),
+            ("(vector-push-extend 1 f)", "#(NIL) is not adjustable"),
+            ("(vector-pop (vector 1))", "#(1) has no fill pointer"),
+            ("(list (equalp #(1 \"a\") (vector 1 \"A\")) (equal #(1) #(1)) (vectorp m) (arrayp m))", "(T NIL NIL T)"),
+            ("(defmethod kind ((x vector)) 'vector)", "#<STANDARD-GENERIC-FUNCTION KIND>"),
+            ("(defmethod kind ((x array)) 'array)", "#<STANDARD-GENERIC-FUNCTION KIND>"),
+            ("(list (kind v) (kind m))", "(VECTOR ARRAY)"),
+        ]);
+    }
+
+    #[test]
+    fn eval_sequences() {
+        test_eval(vec![
+            ("#\\a", "#\\a"),
+            ("(list (length '(1 2 3)) (length #(1 2)) (length \"abcd\"))", "(3 2 4)"),
+            ("(list (elt '(a b c) 1) (elt #(a b c) 2) (elt \"abc\" 0))", "(B C #\\a)"),
+            ("(elt '(a b) 2)", "index 2 is out of bounds for (A B)"),
+            ("(list (subseq '(a b c d) 1 3) (subseq #(a b c) 1) (subseq \"hello\" 1 3))", "((B C) #(B C) \"el\")"),
+            ("(subseq '(a b) 1 3)", "bounding indices 1 and 3 are bad for a sequence of length 2"),
+            ("(concatenate 'list '(a) #(b) \"c\")", "(A B #\\c)"),
+            ("(concatenate 'vector '(1 2) #(3))", "#(1 2 3)"),
+            ("(concatenate 'string \"ab\" '(#\\c))", "\"abc\""),
+            ("(concatenate 'string \"ab\" '(1))", "1 is not character"),
+            ("(map 'list #'+ '(1 2 3) #(10 20))", "(11 22)"),
+            ("(map 'string #'(lambda (c) (elt \"xyz\" (position c \"abc\"))) \"cab\")", "\"zxy\""),
+            ("(map nil #'+ '(1))", "NIL"),
+            ("(list (reduce #'+ '(1 2 3 4)) (reduce #'+ #() :initial-value 5) (reduce #'list nil))", "(10 5 NIL)"),
+            ("(reduce #'list '(1 2 3) :from-end t)", "(1 (2 3))"),
+            ("(reduce #'list '(1 2 3 4) :start 1 :end 3 :key #'(lambda (x) (* x 10)) :initial-value 0)", "((0 20) 30)"),
+            ("(list (find 3 '(1 2 3)) (find #\\b \"abc\") (find 5 #(1 2)))", "(3 #\\b NIL)"),
+            ("(find \"b\" '(\"a\" \"b\") :test #'equal)", "\"b\""),
+            ("(find 2 '((1 a) (2 b)) :key #'car)", "(2 B)"),
+            ("(list (position 2 '(1 2 3 2)) (position 2 '(1 2 3 2) :from-end t) (position 2 '(1 2 3 2) :start 2))", "(1 3 3)"),
+            ("(list (position-if #'(lambda (x) (> x 2)) #(1 3 4)) (find-if-not #'keywordp '(:a b)) (position #\\c \"abc\"))", "(1 B 2)"),
+            ("(list (count 1 '(1 2 1)) (count-if #'vectorp '(#(1) 2 #())) (count #\\a \"banana\" :test-not #'eql))", "(2 2 3)"),
+            ("(find 1 '(1) :test #'eql :test-not #'eql)", "cannot supply both :test and :test-not"),
+            ("(remove 1 '(1 2 1 3))", "(2 3)"),
+            ("(remove 1 '(1 2 1 3) :count 1 :from-end t)", "(1 2 3)"),
+            ("(list (remove-if #'(lambda (x) (> x 2)) #(1 2 3 4)) (remove-if-not #'keywordp '(:a b :c)) (delete #\\a \"banana\"))", "(#(1 2) (:A :C) \"bnn\")"),
+            ("(list (substitute 'x 1 '(1 2 1)) (substitute-if 0 #'(lambda (x) (> x 1)) #(1 2 4) :count 1) (substitute #\\o #\\a \"banana\"))", "((X 2 X) #(1 0 4) \"bonono\")"),
+            ("(list (search '(2 3) '(1 2 3 2 3)) (search '(2 3) '(1 2 3 2 3) :from-end t) (search \"na\" \"banana\") (search '(4) '(1 2)))", "(1 3 2 NIL)"),
+            ("(list (mismatch '(1 2 3) '(1 2 4)) (mismatch \"abc\" \"abc\") (mismatch '(1 2) '(1 2 3)) (mismatch '(1 2 3) '(0 2 3) :from-end t))", "(2 NIL 2 1)"),
+            ("(list (sort '(3 1 2) #'<) (sort '((b 2) (a 1)) #'< :key #'(lambda (x) (nth 1 x))))", "((1 2 3) ((A 1) (B 2)))"),
+            ("(stable-sort '((1 a) (0 b) (1 c) (0 d)) #'< :key #'car)", "((0 B) (0 D) (1 A) (1 C))"),
+            ("(setq v (vector 3 1 2))", "#(3 1 2)"),
+            ("(list (sort v #'>) v)", "(#(3 2 1) #(3 2 1))"),
+            ("(list (reverse '(1 2 3)) (reverse v) (reverse \"abc\") (nreverse '(a b)))", "((3 2 1) #(1 2 3) \"cba\" (B A))"),
+            ("(list (fill v 0 :start 1) v (fill '(1 2 3) 'x :end 2))", "(#(3 0 0) #(3 0 0) (X X 3))"),
+            ("(list (replace v '(a b c d) :start1 1) (replace \"abcd\" \"xy\" :start1 2))", "(#(3 A B) \"abxy\")"),
+            ("(list (remove-duplicates '(a b a c b)) (remove-duplicates '(a b a c b) :from-end t) (remove-duplicates \"aabbc\"))", "((A C B) (A B C) \"abc\")"),
+            ("(remove-duplicates '((1 a) (2 b) (1 c)) :key #'car :test #'=)", "((2 B) (1 C))"),
+            ("(setq l (list 1 2 3) s \"abc\")", "\"abc\""),
+            ("(setf (elt l 1) 'b (elt s 0) #\\x (elt v 0) 'z)", "Z"),
+            ("(list l s v)", "((1 B 3) \"xbc\" #(Z A B))"),
+            ("(make-array 3 :initial-contents \"abc\")", "#(#\\a #\\b #\\c)"),
+            ("(setq s \"hello\" alias s)", "\"hello\""),
+            ("(fill s #\\x :start 3)", "\"helxx\""),
+            ("s", "\"helxx\""),
+            ("(replace s \"HE\")", "\"HElxx\""),
+            ("(nreverse s)", "\"xxlEH\""),
+            ("(setf (char s 0) #\\y)", "#\\y"),
+            ("(list s alias (eq s alias))", "(\"yxlEH\" \"yxlEH\" T)"),
+        ]);
+    }
+
+    #[test]
+    fn eval_strings() {
+        test_eval(vec![
+            ("(list (string= \"abc\" \"abc\") (string= \"abc\" \"ABC\") (string-equal \"abc\" \"ABC\"))", "(T NIL T)"),
+            ("(list (string< \"abc\" \"abd\") (string< \"ab\" \"abc\") (string< \"b\" \"a\") (string>= \"abc\" \"abc\"))", "(2 2 NIL 3)"),
+            ("(list (string-lessp \"ABC\" \"abd\") (string/= \"abc\" \"abd\") (string= \"xabc\" \"abc\" :start1 1))", "(2 2 T)"),
+            ("(string= 'abc \"ABC\")", "T"),
+            ("(list (char= #\\a #\\a #\\a) (char< #\\a #\\b #\\c) (char/= #\\a #\\b #\\a) (char-equal #\\a #\\A))", "(T T NIL T)"),
+            ("(list (char-code #\\A) (code-char 97) (char-upcase #\\a) (char-downcase #\\A))", "(65 #\\a #\\A #\\a)"),
+            ("(list (upper-case-p #\\A) (alpha-char-p #\\1) (digit-char-p #\\7) (digit-char-p #\\f 16) (digit-char-p #\\x))", "(T NIL 7 15 NIL)"),
+            ("(list (stringp \"a\") (characterp #\\a) (string 'foo) (string #\\x))", "(T T \"FOO\" \"x\")"),
+            ("(list (string-upcase \"hello world\") (string-downcase \"HeLLo\") (string-upcase \"hello\" :start 1 :end 3))", "(\"HELLO WORLD\" \"hello\" \"hELlo\")"),
+            ("(string-capitalize \"hELLO wORLD, it's 3am\")", "\"Hello World, It'S 3am\""),
+            ("(list (string-trim \" \" \"  hi  \") (string-left-trim '(#\\Space #\\-) \" -hi- \") (string-right-trim \"-\" \"hi--\"))", "(\"hi\" \"hi- \" \"hi\")"),
+            ("(list (subseq \"hello\" 1 3) (char \"hello\" 1) (schar \"hello\" 4))", "(\"el\" #\\e #\\o)"),
+            ("(char \"abc\" 3)", "index 3 is out of bounds for abc"),
+            ("(setq s (make-string 3 :initial-element #\\z))", "\"zzz\""),
+            ("(setf (char s 1) #\\a)", "#\\a"),
+            ("(list s (make-string 2))", "(\"zaz\" \"  \")"),
+            ("(multiple-value-list (parse-integer \" 42 \"))", "(42 4)"),
+            ("(multiple-value-list (parse-integer \"-ff\" :radix 16))", "(-255 3)"),
+            ("(multiple-value-list (parse-integer \"12abc\" :junk-allowed t))", "(12 2)"),
+            ("(multiple-value-list (parse-integer \"abc\" :junk-allowed t))", "(NIL 0)"),
+            ("(parse-integer \"12abc\")", "junk in string \"12abc\""),
+            ("(parse-integer \"99999999999999999999\")", "integer overflow"),
+            ("(parse-integer \"-9223372036854775808\")", "-9223372036854775808"),
+            ("(parse-integer \"x12\" :start 1)", "12"),
+            ("(list (parse-float \"1.5\") (parse-float \"-.25\") (parse-float \"2e3\") (parse-float \"1.0d-1\"))", "(1.5 -0.25 2000.0 0.1)"),
+            ("(multiple-value-list (parse-float \"3.5kg\" :junk-allowed t))", "(3.5 3)"),
+            ("(parse-float \".\")", "junk in string \".\""),
+            ("(search \"lo\" \"hello\")", "3"),
+            ("(concatenate 'string \"foo\" \"-\" \"bar\")", "\"foo-bar\""),
+            ("(string-split \"a,b,,c\" #\\,)", "(\"a\" \"b\" \"\" \"c\")"),
+            ("(string-split \"one two\")", "(\"one\" \"two\")"),
+            ("(string-split \"k=v;x\" \"=;\")", "(\"k\" \"v\" \"x\")"),
+            ("(with-output-to-string (out) (write-string \"hello\" out) (write-char #\\Space out) (write-string \"world!\" out :end 5))", "\"hello world\""),
+            ("(with-output-to-string (out) (setq o out))", "\"\""),
+            ("(list o (class-name (class-of o)))", "(#<STRING-OUTPUT-STREAM> STRING-STREAM)"),
+        ]);
+    }
+
+    #[test]
+    fn eval_format() {
+        test_eval(vec![
+            ("(format nil \"Hello, ~a!\" \"world\")", "\"Hello, world!\""),
+            (
+                "(format nil \"~a ~s ~a ~s\" \"x\" \"x\" #\\y #\\y)",
+                "\"x \\\"x\\\" y #\\\\y\"",
+            ),
+            (
+                "(format nil \"~s\" '(a \"b\" #(#\\c)))",
+                "\"(A \\\"b\\\" #(#\\\\c))\"",
+            ),
+            (
+                "(format nil \"[~5a] [~5@a] [~:a] [~5,,,'*a]\" 'ab 'ab nil 'ab)",
+                "\"[AB   ] [   AB] [()] [AB***]\"",
+            ),
+            (
+                "(format nil \"~d ~5d ~5,'0d ~@d ~:d\" 42 42 42 42 1234567)",
+                "\"42    42 00042 +42 1,234,567\"",
+            ),
+            (
+                "(format nil \"~b ~o ~x ~:x ~8,'0b\" 5 8 255 -65535 5)",
+                "\"101 10 FF -F,FFF 00000101\"",
+            ),
+            ("(format nil \"~d\" 'x)", "\"X\""),
+            (
+                "(format nil \"~r, ~:r, ~@r, ~:@r\" 1234 22 1999 4)",
+                "\"one thousand two hundred thirty-four, twenty-second, MCMXCIX, IIII\"",
+            ),
+            (
+                "(format nil \"~r ~:r ~16r ~2,8,'0r\" -15 100 255 3)",
+                "\"negative fifteen one hundredth FF 00000011\"",
+            ),
+            (
+                "(format nil \"~d item~:p, ~d famil~:@p, ~d famil~:@p\" 1 2 1)",
+                "\"1 item, 2 families, 1 family\"",
+            ),
+            (
+                "(format nil \"~f ~,2f ~6,2f ~8,3,,,'*f ~@f ~f\" 3.5 3.14159 3.14159 2.5 1.0 10)",
+                "\"3.5 3.14   3.14 ***2.500 +1.0 10.0\"",
+            ),
+            (
+                "(format nil \"~4f ~3,1,,'#f ~,2,2f\" 3.14159 123.456 0.5)",
+                "\"3.14 ### 50.00\"",
+            ),
+            (
+                "(format nil \"~,0f ~,0f ~3f ~,0f\" 2.5 -0.4 123.456 10)",
+                "\"3. -0. 123. 10.\"",
+            ),
+            (
+                "(format nil \"~e ~,2e ~,3,2e ~e\" 150.0 1234.5 0.001 1)",
+                "\"1.5e+2 1.23e+3 1.000e-03 1.0e+0\"",
+            ),
+            (
+                "(format nil \"~$ ~,3$ ~2,4$ ~@$ ~,,8$ ~,,8:$\" 3.14159 2.5 7 1 -1.5 -1.5)",
+                "\"3.14 002.50 0007.00 +1.00    -1.50 -   1.50\"",
+            ),
+            ("(format nil \"a~%b~2%c~&d~&~&e\")", "\"a\nb\n\nc\nd\ne\""),
+            ("(format nil \"~~ ~3~\")", "\"~ ~~~\""),
+            ("(format nil \"ab~6tc~3,4td\")", "\"ab    c    d\""),
+            ("(format nil \"~{~a~^, ~}\" '(1 2 3))", "\"1, 2, 3\""),
+            ("(format nil \"~{~a=~a~^ ~}\" '(a 1 b 2))", "\"A=1 B=2\""),
+            (
+                "(format nil \"~:{(~a ~a)~}\" '((a 1) (b 2)))",
+                "\"(A 1)(B 2)\"",
+            ),
+            (
+                "(format nil \"~:{~a~:^, ~}\" '((a) (b) (c)))",
+                "\"A, B, C\"",
+            ),
+            ("(format nil \"~@{<~a>~}\" 1 2)", "\"<1><2>\""),
+            ("(format nil \"~2{~a~}\" '(1 2 3))", "\"12\""),
+            ("(format nil \"~{x~:}\" nil)", "\"x\""),
+            ("(format nil \"~{~}\" \"<~a>\" '(1 2))", "\"<1><2>\""),
+            (
+                "(format nil \"~[zero~;one~;two~]|~[a~;b~:;other~]|~2[a~;b~;c~]\" 1 5)",
+                "\"one|other|c\"",
+            ),
+            (
+                "(format nil \"~:[no~;yes~] ~:[no~;yes~]\" nil 3)",
+                "\"no yes\"",
+            ),
+            ("(format nil \"~@[x=~a ~]~@[y=~a~]\" nil 2)", "\"y=2\""),
+            (
+                "(format nil \"~a ~* ~a ~:* ~a ~0@* ~a\" 1 2 3)",
+                "\"1  3  3  1\"",
+            ),
+            ("(format nil \"~? ~a\" \"<~a ~a>\" '(1 2) 3)", "\"<1 2> 3\""),
+            ("(format nil \"~@? ~a\" \"<~a>\" 1 2)", "\"<1> 2\""),
+            (
+                "(format nil \"~v,,,'-a|~#[none~;one~;two~]\" 4 'x 1 2)",
+                "\"X---|two\"",
+            ),
+            ("(format nil \"a~\n    b\")", "\"ab\""),
+            (
+                "(format nil \"~c ~:c ~@c\" #\\a #\\Space #\\b)",
+                "\"a Space #\\\\b\"",
+            ),
+            ("(format nil \"~a~^ ~a\" 1)", "\"1\""),
+            ("(format nil \"~a ~a\" 1)", "format: no more arguments"),
+            ("(format nil \"~q\")", "format: unknown directive ~Q"),
+            ("(format nil \"~{~a\")", "format: unterminated ~{"),
+            (
+                "(with-output-to-string (s) (format s \"~a-~a\" 1 2) (format s \"~&x\"))",
+                "\"1-2\nx\"",
+            ),
+            ("(format t \"\")", "NIL"),
+        ]);
+    }
+
+    #[test]
+    fn eval_printer() {
+        test_eval(vec![
+            ("\"NIL\"", "\"NIL\""),
+            ("(list (prin1-to-string \"NIL\") (princ-to-string \"NIL\"))", "(\"\\\"NIL\\\"\" \"NIL\")"),
+            ("(list (intern \"foo bar\") (intern \"1\") (intern \"\") 'a.b)", "(|foo bar| |1| || A.B)"),
+            ("(list (make-symbol \"G\") :key #\\a \"a\\\"b\")", "(#:G :KEY #\\a \"a\\\"b\")"),
+            ("(list 'a\\bc '|a b| '|1| '|NIL| 'cl::|CAR| :|k| '|a\\|b|)", "(|AbC| |a b| |1| NIL CAR :|k| |a\\|b|)"),
+            ("(read-from-string \"a\\\\bc\")", "|AbC|"),
+            ("(eq (read-from-string (prin1-to-string (intern \"abc\"))) (intern \"abc\"))", "T"),
+            ("(eq (read-from-string (prin1-to-string '|x;y(z|)) '|x;y(z|)", "T"),
+            ("(symbol-name (read-from-string (prin1-to-string (make-symbol \"g x\"))))", "\"g x\""),
+            ("(list (symbol-package '#:foo) (eq '#:foo '#:foo) (symbol-name '#:foo))", "(NIL NIL \"FOO\")"),
+            ("(defpackage #:rt (:use #:cl) (:export #:helper))", "#<PACKAGE RT>"),
+            ("(read-from-string \"|abc\")", "invalid token: |"),
+            ("(princ-to-string '(\"a\" #\\b :c))", "\"(a b C)\""),
+            ("(write-to-string 255 :base 16 :radix t)", "\"#xFF\""),
+            ("(list (write-to-string 5 :base 2) (write-to-string 10 :radix t) (write-to-string 20 :base 3 :radix t))", "(\"101\" \"10.\" \"#3r202\")"),
+            ("(write-to-string '(foo-bar :baz) :case :capitalize)", "\"(Foo-Bar :Baz)\""),
+            ("(write-to-string '(1 2 3 4 5) :length 3)", "\"(1 2 3 ...)\""),
+            ("(write-to-string #(1 2 3) :length 2)", "\"#(1 2 ...)\""),
+            ("(write-to-string '(1 (2 (3 (4)))) :level 2)", "\"(1 (2 #))\""),
+            ("(write-to-string \"a\" :escape nil)", "\"a\""),
+            ("(write-to-string (make-hash-table) :readably t)", "cannot print #<HASH-TABLE :TEST EQL :COUNT 0> readably"),
+            ("(let ((*print-base* 8)) (prin1-to-string 64))", "\"100\""),
+            ("(let ((*print-case* :downcase)) (prin1-to-string '(a :b)))", "\"(a :b)\""),
+            ("(setq *print-length* 2)", "2"),
+            ("'(a b c)", "(A B ...)"),
+            ("(setq *print-length* nil)", "NIL"),
+            ("(with-output-to-string (s) (prin1 \"x\" s) (princ \"x\" s) (write 'a :stream s :escape nil))", "\"\\\"x\\\"xA\""),
+            ("(with-output-to-string (s) (print 'a s))", "\"\nA \""),
+            ("(write-to-string 1 :base 1)", "invalid *print-base*: 1"),
+        ]);
+    }
+
+    #[test]
+    fn eval_pretty_printer() {
+        test_eval(vec![
+            (
+                "(write-to-string '(defun fact (n) (cond ((< n 2) 1) (t (* n (fact (- n 1)))))) :right-margin 30)",
+                "\"(DEFUN FACT (N)\n  (COND ((< N 2) 1)\n        (T\n         (* N\n            (FACT (- N 1))))))\"",
+            ),
+            (
+                "(write-to-string '(let ((alpha 1) (beta 2)) (print alpha) (print beta)) :right-margin 20)",
+                "\"(LET ((ALPHA 1)\n      (BETA 2))\n  (PRINT ALPHA)\n  (PRINT BETA))\"",
+            ),
+            (
+                "(write-to-string '(1 2 3 4 5 6 7 8 9 10 11 12 13 14 15) :right-margin 20)",
+                "\"(1 2 3 4 5 6 7 8 9\n 10 11 12 13 14 15)\"",
+            ),
+            (
+                "(write-to-string '(list-all-things 'alpha 'beta 'gamma) :right-margin 30)",
+                "\"(LIST-ALL-THINGS 'ALPHA 'BETA\n                 'GAMMA)\"",
+            ),
+            (
+                "(write-to-string '(if (> x 1) (print \"big\") (print \"small\")) :right-margin 20)",
+                "\"(IF (> X 1)\n    (PRINT \\\"big\\\")\n    (PRINT \\\"small\\\"))\"",
+            ),
+            ("(write-to-string '(a b c d e f g h) :right-margin 10 :pretty nil)", "\"(A B C D E F G H)\""),
+            ("(write-to-string '(quote (function car)) :pretty nil)", "\"(QUOTE (FUNCTION CAR))\""),
+            ("'(quote (function car))", "'#'CAR"),
+            ("(let ((*print-right-margin* 12)) (prin1-to-string #(alpha beta gamma)))", "\"#(ALPHA BETA\n  GAMMA)\""),
+            (
+                "(let ((*print-pretty* nil)) (with-output-to-string (s) (pprint '(when a (b) (c)) s)))",
+                "\"\n(WHEN A (B) (C))\"",
+            ),
+            (
+                "(with-output-to-string (s) (princ \"abc \" s) (write '(a b c) :stream s :right-margin 10))",
+                "\"abc (A B\n       C)\"",
+            ),
+            (
+                "(write-to-string '(defun f (a) (list a (+ a 1))) :right-margin 0)",
+                "\"(DEFUN\n    F\n    (A)\n  (LIST\n   A\n   (+\n    A\n    1)))\"",
+            ),
+            ("(let ((*print-right-margin* 0)) (prin1-to-string #(a b)))", "\"#(A\n  B)\""),
+            ("(write-to-string 1 :right-margin -1)", "invalid *print-right-margin*: -1"),
+        ]);
+    }
+
+    #[test]
+    fn eval_print_circle() {
+        test_eval(vec![
+            ("(setq v (vector 1 2))", "#(1 2)"),
+            ("(setf (aref v 1) v)", "#1=#(1 #1#)"),
+            ("(car v)", "#1=#(1 #1#) is not list"),
+            ("(setq w (vector 'a))", "#(A)"),
+            ("(list w w)", "(#(A) #(A))"),
+            (
+                "(write-to-string (list w w (vector w)) :circle t)",
+                "\"(#1=#(A) #1# #(#1#))\"",
+            ),
+            ("(defstruct node value next)", "NODE"),
+            (
+                "(setq n (make-node :value 1))",
+                "#S(NODE :VALUE 1 :NEXT NIL)",
+            ),
+            ("(setf (node-next n) n)", "#1=#S(NODE :VALUE 1 :NEXT #1#)"),
+            ("(setq *print-circle* t)", "T"),
+            ("'(#1=#(a) #1# #2=#(b) #2#)", "(#1=#(A) #1# #2=#(B) #2#)"),
+            ("'#1=#(#2=#(a) #2# #1#)", "#1=#(#2=#(A) #2# #1#)"),
+            ("(let ((x '#1=#(1 #1#))) (eq x (aref x 1)))", "T"),
+            ("(let ((x '(#1=#(1) #1#))) (eq (car x) (nth 1 x)))", "T"),
+            ("'(#1=(a) #1#)", "(#1=(A) #1#)"),
+            ("(let ((x '(#1=(a) #1#))) (eq (car x) (nth 1 x)))", "T"),
+            (
+                "(write-to-string '(#1=(a) #1#) :circle nil)",
+                "\"((A) (A))\"",
+            ),
+            ("'#1=(a #1#)", "#1=(A #1#)"),
+            (
+                "(let ((x '#1=(a . #1#))) (list (eq x (cdr x)) (nth 5 x)))",
+                "(T A)",
+            ),
+            ("(length '#1=(a b . #1#))", "#1=(A B . #1#) is not list"),
+            ("(setq tail (list 'b 'c))", "(B C)"),
+            ("(list (cons 'a tail) tail)", "((A . #1=(B C)) #1#)"),
+            ("(setf (cdr (cdr tail)) tail)", "#1=(B C . #1#)"),
+            ("(setq *print-circle* nil)", "NIL"),
+            ("tail", "#1=(B C . #1#)"),
+            ("'#2#", "undefined label #2#"),
+            ("'#1=#1#", "label #1= refers only to itself"),
+            ("'#1=#2=#1#", "label #1= refers only to itself"),
+            ("'(#1=#(a) #1=#(b))", "label #1= defined twice"),
+        ]);
+    }
+
+    #[test]
+    fn eval_multiple_values() {
+        test_eval(vec![
+            ("(defun two () (values 1 2))", "TWO"),
+            ("(two)", "1"),
+            ("(multiple-value-list (two))", "(1 2)"),
+            ("(multiple-value-list (values))", "NIL"),
+            ("(multiple-value-list (values-list '(a b c)))", "(A B C)"),
+            (
+                "(multiple-value-bind (a b c) (two) (list a b c))",
+                "(1 2 NIL)",
+            ),
+            ("(multiple-value-bind (a) 1 2 3)", "3"),
+            (
+                "(multiple-value-call #'list (two) (values) (values 3 4))",
+                "(1 2 3 4)",
+            ),
+            ("(multiple-value-call 'list 1 (two))", "(1 1 2)"),
+            (
+                "(multiple-value-list (multiple-value-prog1 (two) (values 5 6)))",
+                "(1 2)",
+            ),
+            (
+                "(list (nth-value 1 (two)) (nth-value 2 (two)) (nth-value 0 (two)))",
+                "(2 NIL 1)",
+            ),
+            ("(multiple-value-list (nth-value 0 (two)))", "(1)"),
+            ("(multiple-value-list (setq x (two)))", "(1)"),
+            ("(multiple-value-list (list (two)))", "((1))"),
+            ("(multiple-value-list (let ((y 1)) (two)))", "(1 2)"),
+            (
+                "(multiple-value-list (block b (return-from b (two))))",
+                "(1 2)",
+            ),
+            ("(multiple-value-list (funcall #'two))", "(1 2)"),
+            (
+                "(multiple-value-list (loop for i from 1 to 2 do (values 7 8)))",
+                "(NIL)",
+            ),
+            (
+                "(multiple-value-list (loop for i from 1 to 2 collect (two)))",
+                "((1 1))",
+            ),
+            (
+                "(multiple-value-list (loop for i from 1 do (return (two))))",
+                "(1 2)",
+            ),
+            (
+                "(multiple-value-list (loop for i in '(nil 2) thereis (two)))",
+                "(1)",
+            ),
+            ("(multiple-value-list (apply #'two nil))", "(1 2)"),
+            ("(defvar *z* 0)", "*Z*"),
+            (
+                "(multiple-value-bind (*z* w) (two) (list (symbol-value '*z*) w))",
+                "(1 2)",
+            ),
+            ("*z*", "0"),
+            ("(multiple-value-bind a (two) a)", "A is not list"),
+        ]);
+    }
+
+    #[test]
+    fn eval_math() {
+        test_eval(vec![
+            ("(multiple-value-list (floor 7 2))", "(3 1)"),
+            ("(multiple-value-list (floor -7 2))", "(-4 1)"),
+            ("(multiple-value-list (ceiling 7 2))", "(4 -1)"),
+            ("(multiple-value-list (truncate -7 2))", "(-3 -1)"),
+            ("(list (round 5 2) (round 7 2) (round -2.5))", "(2 4 -2)"),
+            ("(multiple-value-list (floor 5.5))", "(5 0.5)"),
+            (
+                "(list (mod -7 2) (rem -7 2) (mod 7 -2) (mod 5.5 2))",
+                "(1 -1 -1 1.5)",
+            ),
+            (
+                "(list (1+ 1) (1- 1) (abs -3) (min 3 1 2) (max 3 1 2))",
+                "(2 0 3 1 3)",
+            ),
+            ("(list (gcd 12 18) (gcd) (lcm 4 6) (lcm))", "(6 0 12 1)"),
+            (
+                "(list (expt 2 10) (expt 2 -1) (expt 4 0.5) (sqrt 16) (isqrt 17))",
+                "(1024 1/2 2.0 4.0 4)",
+            ),
+            (
+                "(list (exp 0) (log 1) (log 8 2) (sin 0) (cos 0) (tanh 0))",
+                "(1.0 0.0 3.0 0.0 1.0 0.0)",
+            ),
+            (
+                "(list (atan 1 1) (atan 1))",
+                "(0.7853981633974483 0.7853981633974483)",
+            ),
+            (
+                "(list (sinh 1) (cosh 0) (asinh 1) (acosh 2) (atanh 0.5))",
+                "(1.1752011936438014 1.0 0.881373587019543 1.3169578969248166 0.5493061443340548)",
+            ),
+            (
+                "(list (acosh 0.5) (atanh 2))",
+                "(#C(0.0 1.0471975511965979) #C(0.5493061443340549 1.5707963267948966))",
+            ),
+            ("(acosh -2)", "#C(1.3169578969248166 3.141592653589793)"),
+            ("(list (- 0.0) (- 5) (- #c(1 2)) (- 3 1))", "(-0.0 -5 #C(-1 -2) 2)"),
+            ("(list (signum -5) (signum 0) (signum 2.5))", "(-1 0 1.0)"),
+            (
+                "(list (logand 12 10) (logior 12 10) (logxor 12 10) (logand) (lognot 5))",
+                "(8 14 6 -1 -6)",
+            ),
+            (
+                "(list (ash 1 10) (ash -8 -1) (logcount 7) (logcount -8))",
+                "(1024 -4 3 3)",
+            ),
+            ("(/ 2)", "1/2"),
+            ("(/ 1 0)", "division by zero"),
+            ("(floor 1 0)", "division by zero"),
+            ("(mod 1 0)", "division by zero"),
+            ("(gcd -9223372036854775808 0)", "integer overflow"),
+            ("(gcd -9223372036854775808 -1)", "1"),
+            (
+                "(list (mod -9223372036854775808 -1) (rem -9223372036854775808 -1) (mod -9 4) (rem 9 -4))",
+                "(0 0 3 1)",
+            ),
+            ("(log 0)", "division by zero"),
+            ("(asin 2)", "asin: 2 has no real value"),
+            ("(logand 1.5 1)", "1.5 is not integer"),
+            ("(isqrt -1)", "-1 is not natural number"),
+            (
+                "(setq s (make-random-state 42))",
+                "#S(RANDOM-STATE :STATE 25214903879)",
+            ),
+            (
+                "(list (random 100 s) (random 100 s) (random 0.5 s))",
+                "(72 68 0.15435972766632988)",
+            ),
+            (
+                "(setq s (make-random-state 42))",
+                "#S(RANDOM-STATE :STATE 25214903879)",
+            ),
+            (
+                "(list (random 100 s) (random 100 (make-random-state s)) (random 100 s))",
+                "(72 68 68)",
+            ),
+            ("(random-state-p *random-state*)", "T"),
+            (
+                "(let ((*random-state* (make-random-state 7))) (random 1000))",
+                "730",
+            ),
+            ("(random 0)", "0 is not positive integer or float"),
+        ]);
+    }
+
+    #[test]
+    fn eval_complex() {
+        test_eval(vec![
+            ("#c(1 2)", "#C(1 2)"),
+            ("'(#C(1.5 -2) #C(3 0))", "(#C(1.5 -2.0) 3)"),
+            ("(complex 3 4)", "#C(3 4)"),
+            ("(complex 3)", "3"),
+            (
+                "(list (complex 1.0 0) (complex 1 0.0) (complex 1.5) (complex 1/2 0))",
+                "(#C(1.0 0.0) #C(1.0 0.0) #C(1.5 0.0) 1/2)",
+            ),
+            (
+                "(list (realpart (complex 1.0 0)) (imagpart (complex 1.0 0)))",
+                "(1.0 0.0)",
+            ),
+            (
+                "(list (realpart #C(3 4)) (imagpart #C(3 4)) (imagpart 5))",
+                "(3 4 0)",
+            ),
+            ("(conjugate #C(3 4))", "#C(3 -4)"),
+            ("(abs #C(3 4))", "5.0"),
+            (
+                "(list (phase #C(0 1)) (phase -1) (phase 1))",
+                "(1.5707963267948966 3.141592653589793 0.0)",
+            ),
+            ("(sqrt -4)", "#C(0.0 2.0)"),
+            ("(sqrt #C(3 4))", "#C(2.0 1.0)"),
+            ("(+ #C(1 2) #C(3 -2))", "4"),
+            ("(- #C(1 2))", "#C(-1 -2)"),
+            ("(- 5)", "-5"),
+            ("(list (+) (*))", "(0 1)"),
+            ("(* #C(1 2) #C(3 4))", "#C(-5 10)"),
+            ("(/ #C(-5 10) #C(3 4))", "#C(1 2)"),
+            ("(* #C(0 1) #C(0 1))", "-1"),
+            ("(expt #C(0 1) 2)", "-1"),
+            ("(expt -8 2)", "64"),
+            ("(log -1)", "#C(0.0 3.141592653589793)"),
+            ("(1+ #C(1 1))", "#C(2 1)"),
+            (
+                "(list (= #C(1 2) #C(1 2)) (= #C(1 2) 1) (/= 1 #C(1 2)))",
+                "(T NIL T)",
+            ),
+            ("(< #C(1 2) 3)", "#C(1 2) is not real number"),
+            ("(list (complexp #C(1 2)) (complexp 1))", "(T NIL)"),
+            ("(class-name (class-of #C(1 2)))", "COMPLEX"),
+            ("(prin1-to-string #c(1/2 1))", "\"#C(1/2 1)\""),
+            (
+                "(gethash #C(1 2) (let ((h (make-hash-table))) (setf (gethash #C(1 2) h) 'z) h))",
+                "Z",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn eval_numbers() {
+        test_eval(vec![
+            ("(list 1 1.0 1. -0.5 10/4 -6/3)", "(1 1.0 1 -0.5 5/2 -2)"),
+            ("(list 1.0d0 1.5e3 2d-2 1e20 1.5e-7)", "(1.0 1500.0 0.02 1.0e20 1.5e-7)"),
+            ("(list (/ 10 4) (/ 10 5) (/ 10.0 4) (+ 1/2 1/3) (* 2/3 3/2))", "(5/2 2 2.5 5/6 1)"),
+            ("(list (+ 1/2 0.5) (- 1/2) (< 1/3 0.34 1/2) (= 1/2 0.5))", "(1.0 -1/2 T T)"),
+            ("(list (eql 1 1.0) (= 1 1.0) (equal 1/2 2/4))", "(NIL T T)"),
+            ("(multiple-value-list (floor 7/2))", "(3 1/2)"),
+            ("(list (round 5/2) (round 7/2) (ceiling -7/2) (mod 7/2 1))", "(2 4 -3 1/2)"),
+            ("(list (numerator 6/4) (denominator 6/4) (denominator 3))", "(3 2 1)"),
+            ("(list (float 1/4) (float 3) (sqrt 4) (expt 2/3 2) (expt 2 -2))", "(0.25 3.0 2.0 4/9 1/4)"),
+            (
+                "(list (integerp 1) (integerp 1.0) (floatp 1.0) (rationalp 1/2) (realp #C(1 2)) (numberp #C(1 2)))",
+                "(T NIL T T NIL T)",
+            ),
+            ("(list (class-name (class-of 1/2)) (class-name (class-of 1.5)))", "(RATIO DOUBLE-FLOAT)"),
+            ("(let ((*read-default-float-format* 'single-float)) (prin1-to-string (list 1.5 1e20 2)))", "\"(1.5d0 1.0d20 2)\""),
+            ("(let ((*print-base* 2) (*print-radix* t)) (prin1-to-string (list 5 1/2 0.5)))", "\"(#b101 #b1/10 0.5)\""),
+            (
+                "(list (intern \"1.0\") (intern \"1/2\") '1+ '1e)",
+                "(|1.0| |1/2| 1+ 1E)",
+            ),
+            ("(expt 2 64)", "integer overflow"),
+            ("(/ 1 0)", "division by zero"),
+            ("(loop for x from 0 to 1 by 1/4 collect x)", "(0 1/4 1/2 3/4 1)"),
+            ("(loop for x in '(1 2.5 1/2) sum x)", "4.0"),
+        ]);
+    }
+
+    #[test]
+    fn eval_streams() {
+        test_eval(vec![
+            ("(with-output-to-string (s) (write-line \"one\" s) (terpri s) (write-string \"two\" s))", "\"one\n\ntwo\""),
+            ("(with-output-to-string (s) (write-string \"a\" s) (fresh-line s) (fresh-line s) (write-string \"b\" s))", "\"a\nb\""),
+            ("(with-output-to-string (s) (list (fresh-line s) (write-char #\\x s) (fresh-line s)))", "\"x\n\""),
+            ("(with-output-to-string (*standard-output*) (write-string \"out\") (terpri))", "\"out\n\""),
+            ("(with-output-to-string (*standard-output*) (format t \"~a\" 1))", "\"1\""),
+            ("(with-input-from-string (s \"ab c\") (list (read-char s) (peek-char nil s) (read-char s) (peek-char t s) (read-char s)))", "(#\\a #\\b #\\b #\\c #\\c)"),
+            ("(with-input-from-string (s \"abc\" :start 1 :end 2) (list (read-char s) (read-char s nil :eof)))", "(#\\b :EOF)"),
+            ("(with-input-from-string (s \"x-y\") (peek-char #\\y s))", "#\\y"),
+            ("(with-input-from-string (s \"ab\") (unread-char (read-char s) s) (read-char s))", "#\\a"),
+            ("(let ((s (make-string-input-stream \"a\nb\"))) (list (multiple-value-list (read-line s)) (multiple-value-list (read-line s)) (read-line s nil 'done)))", "((\"a\" NIL) (\"b\" T) DONE)"),
+            ("(read-line (make-string-input-stream \"\"))", "end of file on #<STRING-INPUT-STREAM>"),
+            ("(read-char (make-string-input-stream \"hello\" 4))", "#\\o"),
+            ("(let ((s (make-string-output-stream))) (write-string \"x\" s) (list (get-output-stream-string s) (get-output-stream-string s)))", "(\"x\" \"\")"),
+            ("(write-char #\\a (make-string-input-stream \"\"))", "#<STRING-INPUT-STREAM> is not output stream"),
+            ("(list (class-name (class-of *standard-input*)) (class-name (class-of (make-string-input-stream \"\"))))", "(STREAM STRING-STREAM)"),
+        ]);
+    }
+
+    #[test]
+    fn eval_files() {
+        _ = std::fs::remove_dir_all("/tmp/risp-test-files");
+        test_eval(vec![
+            ("(multiple-value-list (ensure-directories-exist \"/tmp/risp-test-files/logs/\"))", "(\"/tmp/risp-test-files/logs/\" T)"),
+            ("(with-open-file (out \"/tmp/risp-test-files/logs/a.log\" :direction :output) (write-line \"one\" out) (format out \"two ~a~%\" 2))", "NIL"),
+            ("(with-open-file (in \"/tmp/risp-test-files/logs/a.log\") (list (read-line in) (read-line in) (read-line in nil :eof) (file-length in)))", "(\"one\" \"two 2\" :EOF 10)"),
+            ("(open \"/tmp/risp-test-files/logs/a.log\" :direction :output)", "file already exists: /tmp/risp-test-files/logs/a.log"),
+            ("(with-open-file (out \"/tmp/risp-test-files/logs/a.log\" :direction :output :if-exists :append) (write-string \"three\" out))", "\"three\""),
+            ("(with-open-file (in \"/tmp/risp-test-files/logs/a.log\") (read-line in) (list (file-position in) (read-char in) (file-position in :start) (read-line in)))", "(4 #\\t T \"one\")"),
+            ("(open \"/tmp/risp-test-files/none.txt\")", "file does not exist: /tmp/risp-test-files/none.txt"),
+            ("(open \"/tmp/risp-test-files/none.txt\" :if-does-not-exist nil)", "NIL"),
+            ("(with-open-file (out \"/tmp/risp-test-files/b.bin\" :direction :output :element-type '(unsigned-byte 8)) (write-byte 200 out))", "200"),
+            ("(with-open-file (in \"/tmp/risp-test-files/b.bin\" :element-type '(unsigned-byte 8)) (list (read-byte in) (read-byte in nil -1)))", "(200 -1)"),
+            ("(let ((in (open \"/tmp/risp-test-files/b.bin\"))) (close in) (read-char in))", "/tmp/risp-test-files/b.bin is closed"),
+            ("(with-open-file (out \"/tmp/risp-test-files/c.txt\" :direction :output) (car 1))", "1 is not list"),
+            ("(probe-file \"/tmp/risp-test-files/c.txt\")", "NIL"),
+            ("(directory \"/tmp/risp-test-files/**/*.log\")", "(#P\"/tmp/risp-test-files/logs/a.log\")"),
+            ("(directory \"/tmp/risp-test-files/*.*\")", "(#P\"/tmp/risp-test-files/b.bin\" #P\"/tmp/risp-test-files/logs/\")"),
+            ("(probe-file \"/tmp/risp-test-files/logs/../b.bin\")", "#P\"/tmp/risp-test-files/b.bin\""),
+            ("(nth-value 2 (rename-file \"/tmp/risp-test-files/b.bin\" \"d\"))", "#P\"/tmp/risp-test-files/d.bin\""),
+            ("(delete-file \"/tmp/risp-test-files/d.bin\")", "T"),
+            ("(directory \"/tmp/risp-test-files/*.bin\")", "NIL"),
+            ("(make-pathname :directory '(:absolute \"var\" \"log\") :name \"app\" :type \"log\")", "#P\"/var/log/app.log\""),
+            ("(make-pathname :type \"txt\" :defaults #p\"/tmp/a.log\")", "#P\"/tmp/a.txt\""),
+            ("(list (pathname-directory #p\"../log/\") (pathname-name \"/var/app.log\") (pathname-type \"app.log\") (pathname-name \"*.log\"))", "((:RELATIVE :UP \"log\") \"app\" \"log\" :WILD)"),
+            ("(list (merge-pathnames \"sub/x.txt\" #p\"/tmp/base/y.lisp\") (merge-pathnames \"../x\" \"/tmp/base/\"))", "(#P\"/tmp/base/sub/x.txt\" #P\"/tmp/x\")"),
+            ("(let ((*default-pathname-defaults* #p\"/home/\")) (merge-pathnames \"a.lisp\"))", "#P\"/home/a.lisp\""),
+            ("(list (namestring #p\"a/b.c\") (princ-to-string #p\"a/b.c\") (equal #p\"/a\" (pathname \"/a\")))", "(\"a/b.c\" \"a/b.c\" T)"),
+            ("(list (class-name (class-of #p\"a\")) (pathnamep #p\"a\") (pathnamep \"a\"))", "(PATHNAME T NIL)"),
+        ]);
+    }
+
+    #[test]
+    fn eval_load_and_read() {
+        _ = std::fs::remove_dir_all("/tmp/risp-test-load");
+        std::fs::create_dir_all("/tmp/risp-test-load/lib").unwrap();
+        std::fs::write(
+            "/tmp/risp-test-load/main.lisp",
+            "; the main file\n(defvar *loaded* (list (pathname-name *load-pathname*)))\n(load \"lib/util\") ; relative to this file\n(defun twice (x) (* 2 (helper x)))\n",
+        )
+        .unwrap();
+        std::fs::write(
+            "/tmp/risp-test-load/lib/util.lisp",
+            "(defun helper (x)\n  ;; one more\n  (+ x 1))\n(push (pathname-name *load-truename*) *loaded*)\n",
+        )
+        .unwrap();
+        std::fs::write(
+            "/tmp/risp-test-load/data.txt",
+            "(1 2\n 3) foo\n\"str\" ; comment\n",
+        )
+        .unwrap();
+        test_eval(vec![
+            ("(load \"/tmp/risp-test-load/main.lisp\")", "T"),
+            ("(list (twice 3) *loaded* *load-pathname*)", "(8 (\"util\" \"main\") NIL)"),
+            ("(load \"/tmp/risp-test-load/none\" :if-does-not-exist nil)", "NIL"),
+            ("(load \"/tmp/risp-test-load/none\")", "cannot load /tmp/risp-test-load/none: No such file or directory (os error 2)"),
+            ("(with-output-to-string (*standard-output*) (load \"/tmp/risp-test-load/lib/util\" :verbose t :print t))", "\"; loading #P\\\"/tmp/risp-test-load/lib/util.lisp\\\"\nHELPER\n(\\\"util\\\" \\\"util\\\" \\\"main\\\")\n\""),
+            ("(with-open-file (s \"/tmp/risp-test-load/data.txt\") (list (read s) (read s) (read s) (read s nil :eof)))", "((1 2 3) FOO \"str\" :EOF)"),
+            ("(with-open-file (s \"/tmp/risp-test-load/data.txt\") (list (read s) (read-line s)))", "((1 2 3) \" foo\")"),
+            ("(with-input-from-string (s \"1 (2) x\") (list (read s) (read s) (read s) (read s nil 'done)))", "(1 (2) X DONE)"),
+            ("(read (make-string-input-stream \" \"))", "end of file on #<STRING-INPUT-STREAM>"),
+            ("(defvar *n* 0)", "*N*"),
+            (
+                "(let ((s (make-string-input-stream (format nil \"(a #.(setq *n* (+ *n* 1))~%b ; )~%\\\"c)\\\" #|)|# d)~%e\")))) (list (read s) (read s) *n*))",
+                "((A 1 B \"c)\" D) E 1)",
+            ),
+            ("(multiple-value-list (read-from-string \"abc def\"))", "(ABC 4)"),
+            ("(multiple-value-list (read-from-string \"(a b) c\"))", "((A B) 5)"),
+            ("(multiple-value-list (read-from-string \"abc def\" t nil :start 4))", "(DEF 7)"),
+            ("(multiple-value-list (read-from-string \"abc def\" t nil :preserve-whitespace t))", "(ABC 3)"),
+            ("(read-from-string \"abc\" t nil :end 2)", "AB"),
+            ("(read-from-string \" ; nothing\" nil :none)", ":NONE"),
+            ("(read-from-string \"\")", "end of file on #<STRING-INPUT-STREAM>"),
+            ("(read-from-string \"(a b\")", "unexpected EOF"),
+            ("(eval (read-from-string \"(list 1 'a \\\"s\\\")\"))", "(1 A \"s\")"),
+            ("(multiple-value-list (eval '(values 1 2)))", "(1 2)"),
+            ("(multiple-value-list (eval '(floor 7 2)))", "(3 1)"),
+            ("(let ((x 1)) (eval '(boundp 'x)))", "NIL"),
+            ("(multiple-value-list (compile 'square '(lambda (x) (* x x))))", "(SQUARE NIL NIL)"),
+            ("(list (square 5) (funcall (compile nil '(lambda (x) (+ x 1))) 1))", "(25 2)"),
+            ("(multiple-value-list (compile-file \"/tmp/risp-test-load/lib/util\"))", "(#P\"/tmp/risp-test-load/lib/util.fasl\" NIL NIL)"),
+            ("(load \"/tmp/risp-test-load/lib/util.fasl\")", "T"),
+        ]);
+    }
+
+    #[test]
+    fn eval_reader_conditionals() {
+        test_eval(vec![
+            ("(list 1 #+risp 2 #-risp 3 4)", "(1 2 4)"),
+            ("(list #+sbcl (sb-ext:quit) 5)", "(5)"),
+            (
+                "(list #+(or sbcl risp) 1 #+(and risp (not sbcl)) 2 #-(and) 3)",
+                "(1 2)",
+            ),
+            ("(car *features*)", ":RISP"),
+            ("'(#+sbcl #+risp a b c)", "(C)"),
+            ("#.(+ 1 2)", "3"),
+            ("'(a #.(* 2 3))", "(A 6)"),
+            ("(read-from-string \"#.(list 1 2)\")", "(1 2)"),
+            (
+                "(let ((*read-eval* nil)) (read-from-string \"#.(+ 1 2)\"))",
+                "cannot read #. while *read-eval* is NIL",
+            ),
+            ("(read-from-string \"#+sbcl x\" nil :none)", ":NONE"),
+            ("(read-from-string \"#$x\")", "invalid token: #$"),
+            (
+                "(list #x1F #b-101 #o17 #3r12 #x1/2 #| a #| nested |# b |# 5)",
+                "(31 -5 15 5 1/2 5)",
+            ),
+            ("(read-from-string \"#36rZZ\")", "1295"),
+            ("(read-from-string \"#b102\")", "invalid token: #b102"),
+            ("(read-from-string \"#| open\")", "invalid token: #|"),
+        ]);
+    }
 }
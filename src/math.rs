@@ -0,0 +1,873 @@
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{bool_expr, single_arg, Builtin, Evaluator, ExprEnv};
+use crate::number::{division_by_zero, Number};
+use crate::package;
+use crate::structure::{define_struct, make_struct, Slot, StructDef, StructRef};
+use crate::symbol::Symbol;
+use std::cmp::Ordering;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn real_arg(expr: &Expr) -> Result<Number, ExprErr> {
+    match expr {
+        Expr::Number(num) => Ok(*num),
+        Expr::Complex(..) => Err(ExprErr::Cause(format!("{} is not real number", expr))),
+        _ => Err(ExprErr::Cause(format!("{} is not number", expr))),
+    }
+}
+
+// a real number as a float
+pub fn number_arg(expr: &Expr) -> Result<f64, ExprErr> {
+    real_arg(expr).map(Number::to_f64)
+}
+
+pub fn integer_arg(expr: &Expr) -> Result<i64, ExprErr> {
+    match expr {
+        Expr::Number(Number::Integer(n)) => Ok(*n),
+        _ => Err(ExprErr::Cause(format!("{} is not integer", expr))),
+    }
+}
+
+pub fn integer_expr(n: i64) -> Expr {
+    Expr::Number(Number::Integer(n))
+}
+
+pub fn float_expr(x: f64) -> Expr {
+    Expr::Number(Number::Float(x))
+}
+
+// a number as its real and imaginary parts
+type Complex = (Number, Number);
+
+pub fn complex_arg(expr: &Expr) -> Result<Complex, ExprErr> {
+    match expr {
+        Expr::Number(num) => Ok((*num, Number::Integer(0))),
+        Expr::Complex(re, im) => Ok((*re, *im)),
+        _ => Err(ExprErr::Cause(format!("{} is not number", expr))),
+    }
+}
+
+// a complex number with an exact zero imaginary part is rational, and
+// the parts of other complex numbers are both rational or both floats
+pub fn complex_expr(re: Number, im: Number) -> Expr {
+    match (re, im) {
+        (_, Number::Integer(0)) => Expr::Number(re),
+        _ if re.is_float() || im.is_float() => Expr::Complex(re.float(), im.float()),
+        _ => Expr::Complex(re, im),
+    }
+}
+
+fn number((re, im): Complex) -> Expr {
+    complex_expr(re, im)
+}
+
+fn complex_args(args: &[Expr]) -> Result<Vec<Complex>, ExprErr> {
+    args.iter().map(complex_arg).collect()
+}
+
+fn is_zero((re, im): Complex) -> bool {
+    re.is_zero() && im.is_zero()
+}
+
+fn is_real((_, im): Complex) -> bool {
+    im == Number::Integer(0)
+}
+
+fn sum((a, b): Complex, (c, d): Complex) -> Result<Complex, ExprErr> {
+    Ok((a.add(c)?, b.add(d)?))
+}
+
+fn difference((a, b): Complex, (c, d): Complex) -> Result<Complex, ExprErr> {
+    Ok((a.sub(c)?, b.sub(d)?))
+}
+
+// reals are multiplied and divided as reals, so that infinite parts do
+// not make NaN imaginary parts
+fn product(x: Complex, y: Complex) -> Result<Complex, ExprErr> {
+    let ((a, b), (c, d)) = (x, y);
+    if is_real(x) && is_real(y) {
+        return Ok((a.mul(c)?, b));
+    }
+    Ok((a.mul(c)?.sub(b.mul(d)?)?, a.mul(d)?.add(b.mul(c)?)?))
+}
+
+fn quotient(x: Complex, y: Complex) -> Result<Complex, ExprErr> {
+    let ((a, b), (c, d)) = (x, y);
+    if is_zero(y) {
+        return Err(division_by_zero());
+    }
+    if is_real(x) && is_real(y) {
+        return Ok((a.div(c)?, b));
+    }
+    let norm = c.mul(c)?.add(d.mul(d)?)?;
+    let re = a.mul(c)?.add(b.mul(d)?)?.div(norm)?;
+    let im = b.mul(c)?.sub(a.mul(d)?)?.div(norm)?;
+    Ok((re, im))
+}
+
+// the result of a function with no real value for x, like (asin 2)
+fn real(name: &str, x: f64, result: f64) -> Result<Expr, ExprErr> {
+    if result.is_nan() && !x.is_nan() {
+        return Err(ExprErr::Cause(format!("{}: {} has no real value", name, x)));
+    }
+    Ok(float_expr(result))
+}
+
+// Functions of complex numbers with no exact value are computed with
+// float parts.
+type Floats = (f64, f64);
+
+fn floats((re, im): Complex) -> Floats {
+    (re.to_f64(), im.to_f64())
+}
+
+fn float_complex((re, im): Floats) -> Expr {
+    Expr::Complex(Number::Float(re), Number::Float(im))
+}
+
+fn magnitude((re, im): Floats) -> f64 {
+    re.hypot(im)
+}
+
+// the principal square root, with a non-negative real part
+fn complex_sqrt((re, im): Floats) -> Floats {
+    let r = magnitude((re, im));
+    let root = ((r - re) / 2.0).sqrt().copysign(im);
+    (((r + re) / 2.0).sqrt(), root)
+}
+
+fn complex_exp((re, im): Floats) -> Floats {
+    let scale = re.exp();
+    (scale * im.cos(), scale * im.sin())
+}
+
+// the principal logarithm, with an imaginary part in (-pi, pi]
+fn complex_ln(z: Floats) -> Floats {
+    (magnitude(z).ln(), z.1.atan2(z.0))
+}
+
+fn float_sum((a, b): Floats, (c, d): Floats) -> Floats {
+    (a + c, b + d)
+}
+
+// the inverse hyperbolic functions as logarithms, with the branch cuts
+// of CLtL2
+fn complex_asinh(z: Floats) -> Floats {
+    let root = complex_sqrt(float_sum(float_product(z, z), (1.0, 0.0)));
+    complex_ln(float_sum(z, root))
+}
+
+fn complex_acosh(z: Floats) -> Floats {
+    let above = complex_sqrt(float_quotient(float_sum(z, (1.0, 0.0)), (2.0, 0.0)));
+    let below = complex_sqrt(float_quotient(float_sum(z, (-1.0, 0.0)), (2.0, 0.0)));
+    float_product((2.0, 0.0), complex_ln(float_sum(above, below)))
+}
+
+// 1 + z and 1 - z keep the sign of the imaginary part, so that reals
+// above 1 take the upper side of the cut
+fn complex_atanh(z: Floats) -> Floats {
+    let above = complex_ln((1.0 + z.0, z.1));
+    let below = complex_ln((1.0 - z.0, -z.1));
+    ((above.0 - below.0) / 2.0, (above.1 - below.1) / 2.0)
+}
+
+fn float_product((a, b): Floats, (c, d): Floats) -> Floats {
+    (a * c - b * d, a * d + b * c)
+}
+
+fn float_quotient((a, b): Floats, (c, d): Floats) -> Floats {
+    if d == 0.0 {
+        return (a / c, b / c);
+    }
+    let norm = c * c + d * d;
+    ((a * c + b * d) / norm, (b * c - a * d) / norm)
+}
+
+pub fn add(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let zero = (Number::Integer(0), Number::Integer(0));
+    Ok(number(complex_args(args)?.into_iter().try_fold(zero, sum)?))
+}
+
+pub fn multiply(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let one = (Number::Integer(1), Number::Integer(0));
+    Ok(number(
+        complex_args(args)?.into_iter().try_fold(one, product)?,
+    ))
+}
+
+// (- number) is the negation, (- number subtrahend*) the difference
+pub fn subtract(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match complex_args(args)?.as_slice() {
+        [] => Err(ExprErr::Cause("expected at least one number".to_string())),
+        [(re, im)] => Ok(number((re.neg()?, im.neg()?))),
+        [first, rest @ ..] => Ok(number(rest.iter().copied().try_fold(*first, difference)?)),
+    }
+}
+
+// (/ number) is the reciprocal, (/ number divisor*) the quotient
+pub fn divide(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let one = (Number::Integer(1), Number::Integer(0));
+    match complex_args(args)?.as_slice() {
+        [] => Err(ExprErr::Cause("expected at least one number".to_string())),
+        [x] => Ok(number(quotient(one, *x)?)),
+        [first, rest @ ..] => Ok(number(rest.iter().copied().try_fold(*first, quotient)?)),
+    }
+}
+
+fn numeric_eq((a, b): Complex, (c, d): Complex) -> bool {
+    a.compare(c) == Some(Ordering::Equal) && b.compare(d) == Some(Ordering::Equal)
+}
+
+macro_rules! equality_op {
+    ($fn: expr) => {
+        |_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv| -> Result<Expr, ExprErr> {
+            let numbers = complex_args(args)?;
+            if numbers.is_empty() {
+                return Err(ExprErr::Cause("expected at least one number".to_string()));
+            }
+            Ok(bool_expr(numbers.windows(2).all(|w| $fn(w[0], w[1]))))
+        }
+    };
+}
+
+pub const EQUAL: Builtin = equality_op!(numeric_eq);
+pub const NOT_EQUAL: Builtin = equality_op!(|a, b| !numeric_eq(a, b));
+
+macro_rules! compare_op {
+    ($fn: expr) => {
+        |_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv| -> Result<Expr, ExprErr> {
+            let numbers = args
+                .iter()
+                .map(real_arg)
+                .collect::<Result<Vec<Number>, ExprErr>>()?;
+            if numbers.is_empty() {
+                return Err(ExprErr::Cause("expected at least one number".to_string()));
+            }
+            let ordered = |a: Number, b: Number| a.compare(b).is_some_and($fn);
+            Ok(bool_expr(numbers.windows(2).all(|w| ordered(w[0], w[1]))))
+        }
+    };
+}
+
+pub const COMPARE_OPS: [(&str, Builtin); 4] = [
+    ("<", compare_op!(Ordering::is_lt)),
+    (">", compare_op!(Ordering::is_gt)),
+    ("<=", compare_op!(Ordering::is_le)),
+    (">=", compare_op!(Ordering::is_ge)),
+];
+
+macro_rules! unary_op {
+    ($name: expr, $fn: expr) => {
+        |_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv| -> Result<Expr, ExprErr> {
+            let x = number_arg(single_arg($name, args)?)?;
+            real($name, x, $fn(x))
+        }
+    };
+}
+
+// a function of reals extended to complex numbers: the complex function
+// applies to complex arguments and to reals without a real result, as in
+// (sqrt -1)
+macro_rules! complex_op {
+    ($name: expr, $real: expr, $complex: expr) => {
+        |_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv| -> Result<Expr, ExprErr> {
+            let z = floats(complex_arg(single_arg($name, args)?)?);
+            let real: f64 = $real(z.0);
+            if z.1 == 0.0 && (!real.is_nan() || z.0.is_nan()) {
+                return Ok(float_expr(real));
+            }
+            Ok(float_complex($complex(z)))
+        }
+    };
+}
+
+fn one_plus(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let z = complex_arg(single_arg("1+", args)?)?;
+    Ok(number(sum(z, (Number::Integer(1), Number::Integer(0)))?))
+}
+
+fn one_minus(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let z = complex_arg(single_arg("1-", args)?)?;
+    Ok(number(difference(
+        z,
+        (Number::Integer(1), Number::Integer(0)),
+    )?))
+}
+
+// the magnitude of a complex number
+fn abs(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match single_arg("abs", args)? {
+        Expr::Complex(re, im) => Ok(float_expr(magnitude(floats((*re, *im))))),
+        x => Ok(Expr::Number(real_arg(x)?.abs()?)),
+    }
+}
+
+// -1, 0 or 1 of the kind of a real number, or the complex number of
+// magnitude 1 with the same phase
+fn signum(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match single_arg("signum", args)? {
+        Expr::Complex(re, im) => {
+            let z = floats((*re, *im));
+            Ok(float_complex(float_quotient(z, (magnitude(z), 0.0))))
+        }
+        x => match real_arg(x)? {
+            Number::Float(x) if x == 0.0 || x.is_nan() => Ok(float_expr(x)),
+            Number::Float(x) => Ok(float_expr(x.signum())),
+            x => match x.compare(Number::Integer(0)) {
+                Some(ordering) => Ok(integer_expr(ordering as i64)),
+                None => Ok(Expr::Number(x)),
+            },
+        },
+    }
+}
+
+pub const UNARY_OPS: [(&str, Builtin); 17] = [
+    ("1+", one_plus),
+    ("1-", one_minus),
+    ("ABS", abs),
+    ("SIGNUM", signum),
+    ("SQRT", complex_op!("sqrt", f64::sqrt, complex_sqrt)),
+    ("EXP", complex_op!("exp", f64::exp, complex_exp)),
+    ("SIN", unary_op!("sin", f64::sin)),
+    ("COS", unary_op!("cos", f64::cos)),
+    ("TAN", unary_op!("tan", f64::tan)),
+    ("ASIN", unary_op!("asin", f64::asin)),
+    ("ACOS", unary_op!("acos", f64::acos)),
+    ("SINH", unary_op!("sinh", f64::sinh)),
+    ("COSH", unary_op!("cosh", f64::cosh)),
+    ("TANH", unary_op!("tanh", f64::tanh)),
+    ("ASINH", complex_op!("asinh", f64::asinh, complex_asinh)),
+    ("ACOSH", complex_op!("acosh", f64::acosh, complex_acosh)),
+    ("ATANH", complex_op!("atanh", f64::atanh, complex_atanh)),
+];
+
+// number and divisor of the integer division functions
+fn division_args(name: &str, args: &[Expr]) -> Result<(Number, Number), ExprErr> {
+    let (number, divisor) = match args {
+        [number] => (real_arg(number)?, Number::Integer(1)),
+        [number, divisor] => (real_arg(number)?, real_arg(divisor)?),
+        _ => {
+            return Err(ExprErr::Cause(format!(
+                "{} expects a number and a divisor",
+                name
+            )))
+        }
+    };
+    if divisor.is_zero() {
+        return Err(division_by_zero());
+    }
+    Ok((number, divisor))
+}
+
+// the quotient rounded to an integer and the remainder
+fn divide_rounded(
+    number: Number,
+    divisor: Number,
+    round: fn(Number) -> Result<Number, ExprErr>,
+) -> Result<(Number, Number), ExprErr> {
+    let quotient = round(number.div(divisor)?)?;
+    let remainder = number.sub(quotient.mul(divisor)?)?;
+    Ok((quotient, remainder))
+}
+
+// the quotient and the remainder as two values
+macro_rules! division_op {
+    ($name: expr, $round: expr) => {
+        |evaluator: &mut Evaluator, args: &[Expr], _: &mut ExprEnv| -> Result<Expr, ExprErr> {
+            let (number, divisor) = division_args($name, args)?;
+            let (quotient, remainder) = divide_rounded(number, divisor, $round)?;
+            Ok(evaluator.multiple_values(vec![Expr::Number(quotient), Expr::Number(remainder)]))
+        }
+    };
+}
+
+pub const DIVISION_OPS: [(&str, Builtin); 4] = [
+    ("FLOOR", division_op!("floor", Number::floor)),
+    ("CEILING", division_op!("ceiling", Number::ceiling)),
+    ("TRUNCATE", division_op!("truncate", Number::truncate)),
+    ("ROUND", division_op!("round", Number::round)),
+];
+
+// (mod number divisor) has the sign of the divisor; the remainder of two
+// integers is computed directly since the quotient may not fit
+pub fn modulo(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match division_args("mod", args)? {
+        (Number::Integer(number), Number::Integer(divisor)) => {
+            let remainder = number.wrapping_rem(divisor);
+            if remainder != 0 && (remainder < 0) != (divisor < 0) {
+                Ok(integer_expr(remainder + divisor))
+            } else {
+                Ok(integer_expr(remainder))
+            }
+        }
+        (number, divisor) => Ok(Expr::Number(
+            divide_rounded(number, divisor, Number::floor)?.1,
+        )),
+    }
+}
+
+// (rem number divisor) has the sign of the number
+pub fn rem(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match division_args("rem", args)? {
+        (Number::Integer(number), Number::Integer(divisor)) => {
+            Ok(integer_expr(number.wrapping_rem(divisor)))
+        }
+        (number, divisor) => Ok(Expr::Number(
+            divide_rounded(number, divisor, Number::truncate)?.1,
+        )),
+    }
+}
+
+macro_rules! extremum_op {
+    ($name: expr, $ordering: expr) => {
+        |_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv| -> Result<Expr, ExprErr> {
+            let numbers = args
+                .iter()
+                .map(real_arg)
+                .collect::<Result<Vec<Number>, ExprErr>>()?;
+            let (first, rest) = numbers.split_first().ok_or(ExprErr::Cause(format!(
+                "{} expects at least one number",
+                $name
+            )))?;
+            let extremum = rest
+                .iter()
+                .fold(*first, |extremum, x| match x.compare(extremum) {
+                    Some(ordering) if ordering == $ordering => *x,
+                    _ => extremum,
+                });
+            Ok(Expr::Number(extremum))
+        }
+    };
+}
+
+pub const MIN: Builtin = extremum_op!("min", Ordering::Less);
+pub const MAX: Builtin = extremum_op!("max", Ordering::Greater);
+
+fn integers(args: &[Expr]) -> Result<Vec<i64>, ExprErr> {
+    args.iter().map(integer_arg).collect()
+}
+
+pub fn integer_overflow() -> ExprErr {
+    ExprErr::Cause("integer overflow".to_string())
+}
+
+// the gcd up to sign, which may only be taken once all arguments are
+// folded in since |i64::MIN| is out of range
+fn gcd_of(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a
+    } else {
+        gcd_of(b, a.wrapping_rem(b))
+    }
+}
+
+pub fn gcd(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let mut integers = integers(args)?.into_iter();
+    let first = integers.next().unwrap_or(0);
+    let gcd = integers.fold(first, gcd_of).checked_abs();
+    gcd.map(integer_expr).ok_or_else(integer_overflow)
+}
+
+pub fn lcm(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let lcm = integers(args)?.into_iter().try_fold(1i64, |a, b| {
+        if a == 0 || b == 0 {
+            Some(0)
+        } else {
+            a.checked_div(gcd_of(a, b))?.checked_mul(b)?.checked_abs()
+        }
+    });
+    lcm.map(integer_expr).ok_or_else(integer_overflow)
+}
+
+// base raised to an integer power by repeated squaring
+fn power_of(base: Complex, power: i64) -> Result<Complex, ExprErr> {
+    let one = (Number::Integer(1), Number::Integer(0));
+    let (mut result, mut square, mut n) = (one, base, power.unsigned_abs());
+    while n > 0 {
+        if n & 1 == 1 {
+            result = product(result, square)?;
+        }
+        n >>= 1;
+        if n > 0 {
+            square = product(square, square)?;
+        }
+    }
+    if power < 0 {
+        quotient(one, result)
+    } else {
+        Ok(result)
+    }
+}
+
+// (expt base power) is exact for a rational base and an integer power
+pub fn expt(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [base, power] = args else {
+        return Err(ExprErr::Cause(
+            "expt expects a base and a power".to_string(),
+        ));
+    };
+    let (base, power) = (complex_arg(base)?, complex_arg(power)?);
+    if is_zero(base) && power.0.compare(Number::Integer(0)) == Some(Ordering::Less) {
+        return Err(division_by_zero());
+    }
+    match (base, power) {
+        ((Number::Float(x), _), (Number::Integer(n), _)) if is_real(base) && is_real(power) => {
+            match i32::try_from(n) {
+                Ok(n) => Ok(float_expr(x.powi(n))),
+                Err(_) => Ok(float_expr(x.powf(n as f64))),
+            }
+        }
+        (_, (Number::Integer(n), _)) if is_real(power) => Ok(number(power_of(base, n)?)),
+        ((x, _), (y, _))
+            if is_real(base)
+                && is_real(power)
+                && x.compare(Number::Integer(0)) != Some(Ordering::Less) =>
+        {
+            Ok(float_expr(x.to_f64().powf(y.to_f64())))
+        }
+        _ if is_zero(base) => Ok(float_expr(0.0)),
+        // a negative or complex base, or a complex power
+        _ => Ok(float_complex(complex_exp(float_product(
+            floats(power),
+            complex_ln(floats(base)),
+        )))),
+    }
+}
+
+// (isqrt natural) is the greatest integer whose square is at most natural
+pub fn isqrt(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let arg = single_arg("isqrt", args)?;
+    let n = integer_arg(arg)?;
+    if n < 0 {
+        return Err(ExprErr::Cause(format!("{} is not natural number", arg)));
+    }
+    let mut root = (n as f64).sqrt() as i64;
+    while root.checked_mul(root).is_none_or(|square| square > n) {
+        root -= 1;
+    }
+    while (root + 1)
+        .checked_mul(root + 1)
+        .is_some_and(|square| square <= n)
+    {
+        root += 1;
+    }
+    Ok(integer_expr(root))
+}
+
+// (log number [base]) is the natural logarithm without a base
+pub fn log(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (x, base) = match args {
+        [x] => (complex_arg(x)?, None),
+        [x, base] => (complex_arg(x)?, Some(complex_arg(base)?)),
+        _ => {
+            return Err(ExprErr::Cause(
+                "log expects a number and a base".to_string(),
+            ))
+        }
+    };
+    if is_zero(x) || base.is_some_and(|base| floats(base) == (1.0, 0.0)) {
+        return Err(division_by_zero());
+    }
+    // negative and complex numbers have complex logarithms
+    let ln = |z: Complex| match floats(z) {
+        (re, im) if is_real(z) && re > 0.0 => (re.ln(), im),
+        z => complex_ln(z),
+    };
+    let logarithm = match base {
+        None => ln(x),
+        Some(base) => float_quotient(ln(x), ln(base)),
+    };
+    match logarithm {
+        (re, im) if im == 0.0 && is_real(x) && base.is_none_or(is_real) => Ok(float_expr(re)),
+        logarithm => Ok(float_complex(logarithm)),
+    }
+}
+
+// (complex real [imaginary])
+// only rational parts with a zero imaginary part make a real
+pub fn complex(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (re, im) = match args {
+        [re] => {
+            let re = real_arg(re)?;
+            (re, re.zero())
+        }
+        [re, im] => (real_arg(re)?, real_arg(im)?),
+        _ => {
+            return Err(ExprErr::Cause(
+                "complex expects a real and an imaginary part".to_string(),
+            ))
+        }
+    };
+    if re.is_float() || im.is_float() {
+        Ok(Expr::Complex(re.float(), im.float()))
+    } else {
+        Ok(complex_expr(re, im))
+    }
+}
+
+pub fn realpart(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (re, _) = complex_arg(single_arg("realpart", args)?)?;
+    Ok(Expr::Number(re))
+}
+
+// the imaginary part of a real is a zero of its kind
+pub fn imagpart(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match complex_arg(single_arg("imagpart", args)?)? {
+        (re, Number::Integer(0)) => Ok(Expr::Number(re.zero())),
+        (_, im) => Ok(Expr::Number(im)),
+    }
+}
+
+pub fn conjugate(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (re, im) = complex_arg(single_arg("conjugate", args)?)?;
+    Ok(complex_expr(re, im.neg()?))
+}
+
+// (phase number) is the angle of the number in the complex plane
+pub fn phase(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (re, im) = floats(complex_arg(single_arg("phase", args)?)?);
+    Ok(float_expr(im.atan2(re)))
+}
+
+macro_rules! number_predicate {
+    ($name: expr, $pattern: pat) => {
+        |_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv| -> Result<Expr, ExprErr> {
+            Ok(bool_expr(matches!(single_arg($name, args)?, $pattern)))
+        }
+    };
+}
+
+pub const NUMBER_PREDICATES: [(&str, Builtin); 6] = [
+    (
+        "NUMBERP",
+        number_predicate!("numberp", Expr::Number(_) | Expr::Complex(..)),
+    ),
+    ("REALP", number_predicate!("realp", Expr::Number(_))),
+    (
+        "RATIONALP",
+        number_predicate!(
+            "rationalp",
+            Expr::Number(Number::Integer(_) | Number::Ratio(..))
+        ),
+    ),
+    (
+        "INTEGERP",
+        number_predicate!("integerp", Expr::Number(Number::Integer(_))),
+    ),
+    (
+        "FLOATP",
+        number_predicate!("floatp", Expr::Number(Number::Float(_))),
+    ),
+    ("COMPLEXP", number_predicate!("complexp", Expr::Complex(..))),
+];
+
+// (float number [prototype]) converts a real to a float
+pub fn float(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [x] | [x, Expr::Number(Number::Float(_))] => Ok(Expr::Number(real_arg(x)?.float())),
+        [_, prototype] => Err(ExprErr::Cause(format!("{} is not float", prototype))),
+        _ => Err(ExprErr::Cause("float expects a number".to_string())),
+    }
+}
+
+fn rational_arg(expr: &Expr) -> Result<(i64, i64), ExprErr> {
+    match expr {
+        Expr::Number(Number::Integer(n)) => Ok((*n, 1)),
+        Expr::Number(Number::Ratio(numerator, denominator)) => Ok((*numerator, *denominator)),
+        _ => Err(ExprErr::Cause(format!("{} is not rational", expr))),
+    }
+}
+
+pub fn numerator(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (numerator, _) = rational_arg(single_arg("numerator", args)?)?;
+    Ok(integer_expr(numerator))
+}
+
+pub fn denominator(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (_, denominator) = rational_arg(single_arg("denominator", args)?)?;
+    Ok(integer_expr(denominator))
+}
+
+// (atan y [x]) is the angle of the point (x, y)
+pub fn atan(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [y] => Ok(float_expr(number_arg(y)?.atan())),
+        [y, x] => Ok(float_expr(number_arg(y)?.atan2(number_arg(x)?))),
+        _ => Err(ExprErr::Cause(
+            "atan expects one or two numbers".to_string(),
+        )),
+    }
+}
+
+macro_rules! bitwise_op {
+    ($identity: expr, $fn: expr) => {
+        |_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv| -> Result<Expr, ExprErr> {
+            Ok(integer_expr(
+                integers(args)?.into_iter().fold($identity, $fn),
+            ))
+        }
+    };
+}
+
+pub const BITWISE_OPS: [(&str, Builtin); 3] = [
+    ("LOGAND", bitwise_op!(-1, |a, b| a & b)),
+    ("LOGIOR", bitwise_op!(0, |a, b| a | b)),
+    ("LOGXOR", bitwise_op!(0, |a, b| a ^ b)),
+];
+
+pub fn lognot(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let n = integer_arg(single_arg("lognot", args)?)?;
+    Ok(integer_expr(!n))
+}
+
+// (ash integer count) shifts left for a positive count, right otherwise
+pub fn ash(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [n, count] = args else {
+        return Err(ExprErr::Cause(
+            "ash expects an integer and a count".to_string(),
+        ));
+    };
+    let (n, count) = (integer_arg(n)?, integer_arg(count)?);
+    if count < 0 {
+        return Ok(integer_expr(n >> (-count).min(63)));
+    }
+    if n == 0 {
+        return Ok(integer_expr(0));
+    }
+    let shifted = (n as i128)
+        .checked_shl(count.min(127) as u32)
+        .filter(|shifted| shifted >> count.min(127) == n as i128);
+    shifted
+        .and_then(|shifted| i64::try_from(shifted).ok())
+        .map(integer_expr)
+        .ok_or_else(integer_overflow)
+}
+
+// (logcount integer) counts the bits that differ from the sign bit
+pub fn logcount(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let n = integer_arg(single_arg("logcount", args)?)?;
+    let bits = if n < 0 { !n } else { n }.count_ones();
+    Ok(integer_expr(bits as i64))
+}
+
+// A random state is a structure whose state is the 48 bits of a linear
+// congruential generator, exactly representable by a number.
+const MULTIPLIER: u64 = 0x5DEECE66D;
+const INCREMENT: u64 = 0xB;
+const STATE_MASK: u64 = (1 << 48) - 1;
+
+thread_local! {
+    static RANDOM_STATE: Rc<StructDef> = define_struct(StructDef {
+        name: Symbol::cl("RANDOM-STATE"),
+        slots: vec![Slot {
+            name: Symbol::cl("STATE"),
+            default: integer_expr(0),
+            typ: Expr::True,
+            read_only: false,
+        }],
+        include: None,
+        list: false,
+        named: false,
+    });
+}
+
+fn random_state(seed: u64) -> Result<Expr, ExprErr> {
+    let state = integer_expr(((seed ^ MULTIPLIER) & STATE_MASK) as i64);
+    RANDOM_STATE.with(|def| make_struct(def, vec![state]))
+}
+
+fn seed_from_time() -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH);
+    now.map(|now| now.as_nanos() as u64).unwrap_or(0)
+}
+
+// define *random-state*, seeded from the clock
+pub fn init_random_state() {
+    let cl = package::common_lisp();
+    let symbol = cl.intern("*RANDOM-STATE*");
+    symbol.proclaim_special();
+    symbol.set_value(random_state(seed_from_time()).ok());
+    _ = cl.export(&symbol);
+    _ = cl.export(&cl.intern("RANDOM-STATE"));
+}
+
+fn random_state_arg(expr: &Expr) -> Result<StructRef, ExprErr> {
+    match expr {
+        Expr::Struct(structure)
+            if RANDOM_STATE.with(|def| Rc::ptr_eq(&structure.borrow().def, def)) =>
+        {
+            Ok(structure.clone())
+        }
+        _ => Err(ExprErr::Cause(format!("{} is not random state", expr))),
+    }
+}
+
+// a number in [0, 1) from the next 53 bits of the generator
+fn next_float(state: &Expr) -> Result<f64, ExprErr> {
+    let state = random_state_arg(state)?;
+    let mut bits = state.borrow().values[0].clone();
+    let mut next = |count: u32| -> Result<u64, ExprErr> {
+        let current = integer_arg(&bits)? as u64;
+        let following = current.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT) & STATE_MASK;
+        bits = integer_expr(following as i64);
+        Ok(following >> (48 - count))
+    };
+    let high = next(26)?;
+    let low = next(27)?;
+    state.borrow_mut().values[0] = bits;
+    Ok(((high << 27) + low) as f64 / (1u64 << 53) as f64)
+}
+
+// (random limit [state]) is an integer below an integer limit, or a
+// float below a float limit
+pub fn random(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (limit, state) = match args {
+        [limit] => (
+            limit,
+            Symbol::cl("*RANDOM-STATE*").value().unwrap_or(Expr::Nil),
+        ),
+        [limit, state] => (limit, state.clone()),
+        _ => {
+            return Err(ExprErr::Cause(
+                "random expects a limit and a random state".to_string(),
+            ))
+        }
+    };
+    match limit {
+        Expr::Number(Number::Integer(n)) if *n > 0 => {
+            Ok(integer_expr((next_float(&state)? * *n as f64) as i64))
+        }
+        Expr::Number(Number::Float(x)) if *x > 0.0 => Ok(float_expr(next_float(&state)? * x)),
+        _ => Err(ExprErr::Cause(format!(
+            "{} is not positive integer or float",
+            limit
+        ))),
+    }
+}
+
+// (make-random-state [state]) copies state, or *random-state* for NIL; T
+// makes a fresh state and an integer a state seeded with it
+pub fn make_random_state(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let state = match args {
+        [] | [Expr::Nil] => Symbol::cl("*RANDOM-STATE*").value().unwrap_or(Expr::Nil),
+        [Expr::True] => return random_state(seed_from_time()),
+        [Expr::Number(Number::Integer(seed))] => return random_state(*seed as u64),
+        [state] => state.clone(),
+        _ => {
+            return Err(ExprErr::Cause(
+                "make-random-state expects a random state".to_string(),
+            ))
+        }
+    };
+    let bits = random_state_arg(&state)?.borrow().values[0].clone();
+    RANDOM_STATE.with(|def| make_struct(def, vec![bits]))
+}
+
+pub fn random_state_p(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let object = single_arg("random-state-p", args)?;
+    Ok(bool_expr(random_state_arg(object).is_ok()))
+}
@@ -0,0 +1,295 @@
+use crate::ast::ExprErr;
+use std::cmp::Ordering;
+
+// A real number: integers and ratios are exact, floats are double floats.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number {
+    Integer(i64),
+    // numerator and denominator in lowest terms, the denominator above one
+    Ratio(i64, i64),
+    Float(f64),
+}
+
+pub fn division_by_zero() -> ExprErr {
+    ExprErr::Cause("division by zero".to_string())
+}
+
+fn integer_overflow() -> ExprErr {
+    ExprErr::Cause("integer overflow".to_string())
+}
+
+// a numerator and a positive denominator, wide enough for the products
+// of two of them
+type Rational = (i128, i128);
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl Number {
+    // the rational numerator/denominator in lowest terms
+    pub fn ratio(numerator: i128, denominator: i128) -> Result<Number, ExprErr> {
+        if denominator == 0 {
+            return Err(division_by_zero());
+        }
+        let divisor = gcd(numerator, denominator) * denominator.signum();
+        let numerator = i64::try_from(numerator / divisor).map_err(|_| integer_overflow())?;
+        let denominator = i64::try_from(denominator / divisor).map_err(|_| integer_overflow())?;
+        if denominator == 1 {
+            Ok(Number::Integer(numerator))
+        } else {
+            Ok(Number::Ratio(numerator, denominator))
+        }
+    }
+
+    // the integer nearest to x as rounded by round
+    pub fn from_float(x: f64, round: fn(f64) -> f64) -> Result<Number, ExprErr> {
+        let x = round(x);
+        if !x.is_finite() || x.abs() >= 9.2e18 {
+            return Err(integer_overflow());
+        }
+        Ok(Number::Integer(x as i64))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Number::Integer(n) => n as f64,
+            Number::Ratio(numerator, denominator) => numerator as f64 / denominator as f64,
+            Number::Float(x) => x,
+        }
+    }
+
+    // the number converted to a float
+    pub fn float(self) -> Number {
+        Number::Float(self.to_f64())
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, Number::Float(_))
+    }
+
+    pub fn is_zero(self) -> bool {
+        match self {
+            Number::Integer(n) => n == 0,
+            Number::Ratio(..) => false,
+            Number::Float(x) => x == 0.0,
+        }
+    }
+
+    // zero of the same kind, as in (* 0 number)
+    pub fn zero(self) -> Number {
+        match self {
+            Number::Float(_) => Number::Float(0.0),
+            _ => Number::Integer(0),
+        }
+    }
+
+    fn rational(self) -> Option<Rational> {
+        match self {
+            Number::Integer(n) => Some((n as i128, 1)),
+            Number::Ratio(numerator, denominator) => Some((numerator as i128, denominator as i128)),
+            Number::Float(_) => None,
+        }
+    }
+
+    // exact for rationals, a float if either number is a float
+    fn combine(
+        self,
+        other: Number,
+        rational: fn(Rational, Rational) -> Rational,
+        float: fn(f64, f64) -> f64,
+    ) -> Result<Number, ExprErr> {
+        match (self.rational(), other.rational()) {
+            (Some(a), Some(b)) => {
+                let (numerator, denominator) = rational(a, b);
+                Number::ratio(numerator, denominator)
+            }
+            _ => Ok(Number::Float(float(self.to_f64(), other.to_f64()))),
+        }
+    }
+
+    pub fn add(self, other: Number) -> Result<Number, ExprErr> {
+        self.combine(other, |(a, b), (c, d)| (a * d + c * b, b * d), |x, y| x + y)
+    }
+
+    pub fn sub(self, other: Number) -> Result<Number, ExprErr> {
+        self.combine(other, |(a, b), (c, d)| (a * d - c * b, b * d), |x, y| x - y)
+    }
+
+    pub fn mul(self, other: Number) -> Result<Number, ExprErr> {
+        self.combine(other, |(a, b), (c, d)| (a * c, b * d), |x, y| x * y)
+    }
+
+    pub fn div(self, other: Number) -> Result<Number, ExprErr> {
+        if other.is_zero() {
+            return Err(division_by_zero());
+        }
+        self.combine(other, |(a, b), (c, d)| (a * d, b * c), |x, y| x / y)
+    }
+
+    // floats flip their sign, so that 0.0 becomes -0.0
+    pub fn neg(self) -> Result<Number, ExprErr> {
+        match self {
+            Number::Float(x) => Ok(Number::Float(-x)),
+            _ => Number::Integer(0).sub(self),
+        }
+    }
+
+    pub fn abs(self) -> Result<Number, ExprErr> {
+        match self.compare(Number::Integer(0)) {
+            Some(Ordering::Less) => self.neg(),
+            _ => Ok(self),
+        }
+    }
+
+    // the numeric order, exact for rationals; None when either is NaN
+    pub fn compare(self, other: Number) -> Option<Ordering> {
+        match (self.rational(), other.rational()) {
+            (Some((a, b)), Some((c, d))) => Some((a * d).cmp(&(c * b))),
+            _ => self.to_f64().partial_cmp(&other.to_f64()),
+        }
+    }
+
+    // the integer quotient of a rational rounded by round, which takes
+    // the numerator and a positive denominator
+    fn round_with(
+        self,
+        rational: fn(i128, i128) -> i128,
+        float: fn(f64) -> f64,
+    ) -> Result<Number, ExprErr> {
+        match self.rational() {
+            Some((numerator, denominator)) => Number::ratio(rational(numerator, denominator), 1),
+            None => Number::from_float(self.to_f64(), float),
+        }
+    }
+
+    pub fn floor(self) -> Result<Number, ExprErr> {
+        self.round_with(i128::div_euclid, f64::floor)
+    }
+
+    pub fn ceiling(self) -> Result<Number, ExprErr> {
+        self.round_with(|n, d| -(-n).div_euclid(d), f64::ceil)
+    }
+
+    pub fn truncate(self) -> Result<Number, ExprErr> {
+        self.round_with(|n, d| n / d, f64::trunc)
+    }
+
+    // to the nearest integer, and to the even one when halfway
+    pub fn round(self) -> Result<Number, ExprErr> {
+        self.round_with(
+            |n, d| {
+                let (floor, twice_remainder) = (n.div_euclid(d), 2 * n.rem_euclid(d));
+                match twice_remainder.cmp(&d) {
+                    Ordering::Less => floor,
+                    Ordering::Greater => floor + 1,
+                    Ordering::Equal => floor + floor.rem_euclid(2),
+                }
+            },
+            f64::round_ties_even,
+        )
+    }
+}
+
+// the float as printed, marked as a double float with d when the
+// *read-default-float-format* is not DOUBLE-FLOAT
+pub fn print_float(x: f64, double_default: bool) -> String {
+    if x.is_nan() {
+        return "#<DOUBLE-FLOAT NaN>".to_string();
+    }
+    if x.is_infinite() {
+        let sign = if x < 0.0 { "-" } else { "+" };
+        return format!("#<DOUBLE-FLOAT {}INFINITY>", sign);
+    }
+    let marker = if double_default { "e" } else { "d" };
+    if x == 0.0 || (1e-3..1e7).contains(&x.abs()) {
+        let mut digits = x.to_string();
+        if !digits.contains('.') {
+            digits.push_str(".0");
+        }
+        return if double_default {
+            digits
+        } else {
+            format!("{}d0", digits)
+        };
+    }
+    // the shortest digits that read back as x, in scientific notation
+    let scientific = format!("{:e}", x);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    if mantissa.contains('.') {
+        format!("{}{}{}", mantissa, marker, exponent)
+    } else {
+        format!("{}.0{}{}", mantissa, marker, exponent)
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Number::Integer(n) => write!(f, "{}", n),
+            Number::Ratio(numerator, denominator) => write!(f, "{}/{}", numerator, denominator),
+            Number::Float(x) => write!(f, "{}", print_float(*x, true)),
+        }
+    }
+}
+
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|ch| ch.is_ascii_digit())
+}
+
+// the number written as s in decimal: an integer with an optional
+// trailing point, a ratio, or a float with an optional exponent marked by
+// e, s, f, d or l; None when s is not number syntax, and an error when the
+// number does not fit
+pub fn parse(s: &str) -> Option<Result<Number, ExprErr>> {
+    let unsigned = s.strip_prefix(['+', '-']).unwrap_or(s);
+    let sign = if s.starts_with('-') { "-" } else { "" };
+    let integer = unsigned.strip_suffix('.').unwrap_or(unsigned);
+    if is_digits(integer) {
+        let n = format!("{}{}", sign, integer).parse::<i64>();
+        return Some(n.map(Number::Integer).map_err(|_| integer_overflow()));
+    }
+    if let Some((numerator, denominator)) = unsigned.split_once('/') {
+        if !is_digits(numerator) || !is_digits(denominator) {
+            return None;
+        }
+        let ratio = |numerator: &str, denominator: &str| -> Result<Number, ExprErr> {
+            let numerator = format!("{}{}", sign, numerator).parse::<i128>();
+            let denominator = denominator.parse::<i128>();
+            match (numerator, denominator) {
+                (Ok(numerator), Ok(denominator)) => Number::ratio(numerator, denominator),
+                _ => Err(integer_overflow()),
+            }
+        };
+        return Some(ratio(numerator, denominator));
+    }
+    let (mantissa, exponent) =
+        match unsigned.split_once(['e', 's', 'f', 'd', 'l', 'E', 'S', 'F', 'D', 'L']) {
+            Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+            None => (unsigned, None),
+        };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits_ok = (whole.is_empty() || is_digits(whole))
+        && (fraction.is_empty() || is_digits(fraction))
+        && !(whole.is_empty() && fraction.is_empty());
+    let exponent_ok = match exponent {
+        Some(exponent) => is_digits(exponent.strip_prefix(['+', '-']).unwrap_or(exponent)),
+        // without an exponent a float needs digits after the point
+        None => !fraction.is_empty(),
+    };
+    if !digits_ok || !exponent_ok {
+        return None;
+    }
+    let float = format!(
+        "{}{}.{}e{}",
+        sign,
+        if whole.is_empty() { "0" } else { whole },
+        if fraction.is_empty() { "0" } else { fraction },
+        exponent.unwrap_or("0")
+    );
+    Some(Ok(Number::Float(float.parse::<f64>().unwrap_or(f64::NAN))))
+}
@@ -0,0 +1,603 @@
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{Evaluator, ExprEnv};
+use crate::symbol::{symbol_of, Symbol, SymbolRef};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+pub type PackageRef = Rc<Package>;
+
+// A package maps names to the symbols present in it. Symbols exported
+// by the packages it uses are accessible too unless a present symbol
+// of the same name shadows them.
+pub struct Package {
+    pub name: String,
+    nicknames: RefCell<Vec<String>>,
+    symbols: RefCell<HashMap<String, SymbolRef>>,
+    external: RefCell<HashSet<String>>,
+    shadowing: RefCell<HashSet<String>>,
+    use_list: RefCell<Vec<PackageRef>>,
+}
+
+pub const COMMON_LISP: &str = "COMMON-LISP";
+pub const COMMON_LISP_USER: &str = "COMMON-LISP-USER";
+pub const KEYWORD: &str = "KEYWORD";
+pub const SYSTEM: &str = "SYSTEM";
+
+// names of the COMMON-LISP package that are not functions
+const SYMBOLS: [&str; 51] = [
+    "T",
+    "NIL",
+    "QUOTE",
+    "FUNCTION",
+    "SETQ",
+    "DEFUN",
+    "LAMBDA",
+    "PROGN",
+    "LET",
+    "LET*",
+    "BLOCK",
+    "RETURN-FROM",
+    "RETURN",
+    "LOOP",
+    "DEFVAR",
+    "DEFPARAMETER",
+    "DEFCONSTANT",
+    "DECLARE",
+    "SPECIAL",
+    "DEFPACKAGE",
+    "IN-PACKAGE",
+    "SETF",
+    "DEFSTRUCT",
+    "PSETF",
+    "INCF",
+    "DECF",
+    "PUSH",
+    "PUSHNEW",
+    "POP",
+    "ROTATEF",
+    "SHIFTF",
+    "DEFSETF",
+    "DEFINE-SETF-EXPANDER",
+    "MULTIPLE-VALUE-LIST",
+    "MULTIPLE-VALUE-BIND",
+    "MULTIPLE-VALUE-CALL",
+    "MULTIPLE-VALUE-PROG1",
+    "NTH-VALUE",
+    "WITH-HASH-TABLE-ITERATOR",
+    "WITH-OUTPUT-TO-STRING",
+    "WITH-INPUT-FROM-STRING",
+    "WITH-OPEN-FILE",
+    "&OPTIONAL",
+    "&REST",
+    "&KEY",
+    "&ALLOW-OTHER-KEYS",
+    "&AUX",
+    "*GENSYM-COUNTER*",
+    "DEFCLASS",
+    "DEFGENERIC",
+    "DEFMETHOD",
+];
+
+// The packages the evaluator itself refers to. They are held here as well
+// as by name so that no package definition can take them away.
+struct Standard {
+    common_lisp: PackageRef,
+    common_lisp_user: PackageRef,
+    keyword: PackageRef,
+    system: PackageRef,
+    package_var: SymbolRef,
+}
+
+thread_local! {
+    static STANDARD: Standard = standard_packages();
+    static PACKAGES: RefCell<HashMap<String, PackageRef>> = RefCell::new(STANDARD.with(|standard| {
+        let mut packages = HashMap::new();
+        for package in [
+            &standard.common_lisp,
+            &standard.common_lisp_user,
+            &standard.keyword,
+            &standard.system,
+        ] {
+            packages.insert(package.name.clone(), package.clone());
+            for nickname in package.nicknames.borrow().iter() {
+                packages.insert(nickname.clone(), package.clone());
+            }
+        }
+        packages
+    }));
+}
+
+// COMMON-LISP, COMMON-LISP-USER using it, KEYWORD, SYSTEM for the
+// internals of generated functions, and *PACKAGE*
+fn standard_packages() -> Standard {
+    let cl = Package::new(COMMON_LISP);
+    let cl_user = Package::new(COMMON_LISP_USER);
+    let keyword = Package::new(KEYWORD);
+    let system = Package::new(SYSTEM);
+    cl.nicknames.borrow_mut().push("CL".to_string());
+    cl_user.nicknames.borrow_mut().push("CL-USER".to_string());
+    system.nicknames.borrow_mut().push("SYS".to_string());
+    cl_user.use_list.borrow_mut().push(cl.clone());
+    system.use_list.borrow_mut().push(cl.clone());
+
+    let package_var = Symbol::new("*PACKAGE*", Some(COMMON_LISP));
+    package_var.proclaim_special();
+    package_var.set_value(Some(Expr::Package(cl_user.clone())));
+    cl.add(&package_var);
+    cl.external.borrow_mut().insert(package_var.name.clone());
+    for name in SYMBOLS {
+        cl.add(&Symbol::new(name, Some(COMMON_LISP)));
+        cl.external.borrow_mut().insert(name.to_string());
+    }
+
+    Standard {
+        common_lisp: cl,
+        common_lisp_user: cl_user,
+        keyword,
+        system,
+        package_var,
+    }
+}
+
+impl Package {
+    fn new(name: &str) -> PackageRef {
+        Rc::new(Package {
+            name: name.to_string(),
+            nicknames: RefCell::new(vec![]),
+            symbols: RefCell::new(HashMap::new()),
+            external: RefCell::new(HashSet::new()),
+            shadowing: RefCell::new(HashSet::new()),
+            use_list: RefCell::new(vec![]),
+        })
+    }
+
+    fn add(&self, symbol: &SymbolRef) {
+        self.symbols
+            .borrow_mut()
+            .insert(symbol.name.clone(), symbol.clone());
+    }
+
+    // symbol present in this package
+    pub fn find_present(&self, name: &str) -> Option<SymbolRef> {
+        self.symbols.borrow().get(name).cloned()
+    }
+
+    pub fn find_external(&self, name: &str) -> Option<SymbolRef> {
+        if self.external.borrow().contains(name) {
+            self.find_present(name)
+        } else {
+            None
+        }
+    }
+
+    // symbol accessible in this package, present or inherited
+    pub fn find(&self, name: &str) -> Option<SymbolRef> {
+        self.find_present(name).or_else(|| {
+            self.use_list
+                .borrow()
+                .iter()
+                .find_map(|package| package.find_external(name))
+        })
+    }
+
+    // the accessible symbol of this name and whether it is :internal,
+    // :external or :inherited
+    pub fn find_status(&self, name: &str) -> Option<(SymbolRef, &'static str)> {
+        match self.find_present(name) {
+            Some(symbol) if self.external.borrow().contains(name) => Some((symbol, "EXTERNAL")),
+            Some(symbol) => Some((symbol, "INTERNAL")),
+            None => self.find(name).map(|symbol| (symbol, "INHERITED")),
+        }
+    }
+
+    pub fn is_external(&self, symbol: &SymbolRef) -> bool {
+        self.find_external(&symbol.name).as_ref() == Some(symbol)
+    }
+
+    // find the accessible symbol or create one present in this package;
+    // keywords are external constants evaluating to themselves
+    pub fn intern(self: &PackageRef, name: &str) -> SymbolRef {
+        if let Some(symbol) = self.find(name) {
+            return symbol;
+        }
+        let symbol = Symbol::new(name, Some(&self.name));
+        if self.name == KEYWORD {
+            symbol.set_value(Some(Expr::Symbol(symbol.clone())));
+            symbol.proclaim_constant();
+            self.external.borrow_mut().insert(name.to_string());
+        }
+        self.add(&symbol);
+        symbol
+    }
+
+    pub fn export(&self, symbol: &SymbolRef) -> Result<(), ExprErr> {
+        match self.find(&symbol.name) {
+            Some(found) if &found == symbol => {
+                self.add(symbol);
+                self.external.borrow_mut().insert(symbol.name.clone());
+                Ok(())
+            }
+            _ => Err(ExprErr::Cause(format!(
+                "symbol {} is not accessible in {}",
+                symbol.name, self.name
+            ))),
+        }
+    }
+
+    pub fn import(&self, symbol: &SymbolRef) -> Result<(), ExprErr> {
+        match self.find(&symbol.name) {
+            Some(found) if &found != symbol => Err(ExprErr::Cause(format!(
+                "name conflict: {} is already accessible in {}",
+                symbol.name, self.name
+            ))),
+            _ => {
+                if symbol.package.borrow().is_none() {
+                    symbol.package.replace(Some(self.name.clone()));
+                }
+                self.add(symbol);
+                Ok(())
+            }
+        }
+    }
+
+    // make a symbol of this name present so that it hides inherited ones
+    pub fn shadow(self: &PackageRef, name: &str) {
+        if self.find_present(name).is_none() {
+            self.add(&Symbol::new(name, Some(&self.name)));
+        }
+        self.shadowing.borrow_mut().insert(name.to_string());
+    }
+
+    pub fn use_package(&self, package: &PackageRef) -> Result<(), ExprErr> {
+        if self
+            .use_list
+            .borrow()
+            .iter()
+            .any(|p| Rc::ptr_eq(p, package))
+        {
+            return Ok(());
+        }
+        for name in package.external.borrow().iter() {
+            if self.shadowing.borrow().contains(name) {
+                continue;
+            }
+            if let (Some(found), Some(inherited)) = (self.find(name), package.find_external(name)) {
+                if found != inherited {
+                    return Err(ExprErr::Cause(format!(
+                        "name conflict: {} is already accessible in {}",
+                        name, self.name
+                    )));
+                }
+            }
+        }
+        self.use_list.borrow_mut().push(package.clone());
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Package {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "#<PACKAGE {}>", self.name)
+    }
+}
+
+pub fn find_package(name: &str) -> Option<PackageRef> {
+    PACKAGES.with(|packages| packages.borrow().get(name).cloned())
+}
+
+fn make_package(name: &str) -> PackageRef {
+    let package = Package::new(name);
+    PACKAGES.with(|packages| {
+        packages
+            .borrow_mut()
+            .insert(name.to_string(), package.clone())
+    });
+    package
+}
+
+pub fn common_lisp() -> PackageRef {
+    STANDARD.with(|standard| standard.common_lisp.clone())
+}
+
+pub fn keyword() -> PackageRef {
+    STANDARD.with(|standard| standard.keyword.clone())
+}
+
+pub fn system() -> PackageRef {
+    STANDARD.with(|standard| standard.system.clone())
+}
+
+fn package_var() -> SymbolRef {
+    STANDARD.with(|standard| standard.package_var.clone())
+}
+
+// the value of *package*
+pub fn current() -> PackageRef {
+    match package_var().value() {
+        Some(Expr::Package(package)) => package,
+        _ => STANDARD.with(|standard| standard.common_lisp_user.clone()),
+    }
+}
+
+// a name or nickname for package must not already name another package
+fn check_name(name: &str, package: Option<&PackageRef>) -> Result<(), ExprErr> {
+    match find_package(name) {
+        Some(other) if package.is_none_or(|package| !Rc::ptr_eq(package, &other)) => {
+            Err(ExprErr::Cause(format!(
+                "package name conflict: {} already names package {}",
+                name, other.name
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+// a string or the name of a symbol
+pub fn string_designator(expr: &Expr) -> Result<String, ExprErr> {
+    match expr {
+        Expr::String(s) => Ok(s.borrow().clone()),
+        _ => Ok(symbol_of(expr)
+            .map_err(|_| ExprErr::Cause(format!("{} is not string designator", expr)))?
+            .name
+            .clone()),
+    }
+}
+
+// a package or the name of an existing one
+pub fn package_designator(expr: &Expr) -> Result<PackageRef, ExprErr> {
+    match expr {
+        Expr::Package(package) => Ok(package.clone()),
+        _ => {
+            let name = string_designator(expr)?;
+            find_package(&name).ok_or(ExprErr::Cause(format!("package not found: {}", name)))
+        }
+    }
+}
+
+// optional package argument defaulting to *package*
+fn package_arg(args: &[Expr], index: usize) -> Result<PackageRef, ExprErr> {
+    match args.get(index) {
+        Some(expr) => package_designator(expr),
+        None => Ok(current()),
+    }
+}
+
+// a symbol or a list of them
+fn symbols_arg(expr: &Expr) -> Result<Vec<SymbolRef>, ExprErr> {
+    match expr {
+        Expr::Cons(_) => expr.to_vec()?.iter().map(symbol_of).collect(),
+        _ => Ok(vec![symbol_of(expr)?]),
+    }
+}
+
+fn designators_arg(expr: &Expr) -> Result<Vec<Expr>, ExprErr> {
+    match expr {
+        Expr::Cons(_) => expr.to_vec(),
+        _ => Ok(vec![expr.clone()]),
+    }
+}
+
+const OPTIONS: [&str; 5] = ["NICKNAMES", "SHADOW", "USE", "IMPORT-FROM", "EXPORT"];
+
+impl Evaluator {
+    // (defpackage name (:nicknames n*) (:use p*) (:shadow s*)
+    //   (:import-from p s*) (:export s*))
+    pub fn eval_defpackage(&mut self, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let (name, options) = args
+            .split_first()
+            .ok_or(ExprErr::Cause("expected package name".to_string()))?;
+        let name = string_designator(name)?;
+        let options = options
+            .iter()
+            .map(|option| {
+                let option = option.to_vec()?;
+                let (head, values) = option
+                    .split_first()
+                    .ok_or(ExprErr::Cause("invalid defpackage option".to_string()))?;
+                let head = string_designator(head)?;
+                if !OPTIONS.contains(&head.as_str()) && head != "DOCUMENTATION" {
+                    return Err(ExprErr::Cause(format!(
+                        "unknown defpackage option: {}",
+                        head
+                    )));
+                }
+                Ok((head, values.to_vec()))
+            })
+            .collect::<Result<Vec<(String, Vec<Expr>)>, ExprErr>>()?;
+        let existing = find_package(&name);
+        for (_, values) in options.iter().filter(|(head, _)| head == "NICKNAMES") {
+            for nickname in values {
+                check_name(&string_designator(nickname)?, existing.as_ref())?;
+            }
+        }
+        let package = existing.unwrap_or_else(|| make_package(&name));
+
+        // shadowing comes before use and export after import so that
+        // the options do not conflict with each other
+        for kind in OPTIONS {
+            for (_, values) in options.iter().filter(|(head, _)| head == kind) {
+                match kind {
+                    "NICKNAMES" => {
+                        for nickname in values {
+                            let nickname = string_designator(nickname)?;
+                            if find_package(&nickname).is_some() {
+                                continue;
+                            }
+                            package.nicknames.borrow_mut().push(nickname.clone());
+                            PACKAGES.with(|packages| {
+                                packages.borrow_mut().insert(nickname, package.clone())
+                            });
+                        }
+                    }
+                    "SHADOW" => {
+                        for value in values {
+                            package.shadow(&string_designator(value)?);
+                        }
+                    }
+                    "USE" => {
+                        for value in values {
+                            package.use_package(&package_designator(value)?)?;
+                        }
+                    }
+                    "IMPORT-FROM" => {
+                        let (from, names) = values.split_first().ok_or(ExprErr::Cause(
+                            "expected package to import from".to_string(),
+                        ))?;
+                        let from = package_designator(from)?;
+                        for value in names {
+                            let name = string_designator(value)?;
+                            let symbol = from.find(&name).ok_or(ExprErr::Cause(format!(
+                                "symbol {} not found in {}",
+                                name, from.name
+                            )))?;
+                            package.import(&symbol)?;
+                        }
+                    }
+                    _ => {
+                        for value in values {
+                            let symbol = package.intern(&string_designator(value)?);
+                            package.export(&symbol)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Expr::Package(package))
+    }
+
+    // (in-package name)
+    pub fn eval_in_package(&mut self, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        match args {
+            [name] => {
+                let package = package_designator(name)?;
+                package_var().set_value(Some(Expr::Package(package.clone())));
+                Ok(Expr::Package(package))
+            }
+            _ => Err(ExprErr::Cause(
+                "in-package expects exactly one arg".to_string(),
+            )),
+        }
+    }
+}
+
+// (find-package name)
+pub fn find_package_fn(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [Expr::Package(package)] => Ok(Expr::Package(package.clone())),
+        [name] => Ok(find_package(&string_designator(name)?)
+            .map(Expr::Package)
+            .unwrap_or(Expr::Nil)),
+        _ => Err(ExprErr::Cause(
+            "find-package expects exactly one arg".to_string(),
+        )),
+    }
+}
+
+pub fn package_name(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [package] => Ok(Expr::string(package_designator(package)?.name.clone())),
+        _ => Err(ExprErr::Cause(
+            "package-name expects exactly one arg".to_string(),
+        )),
+    }
+}
+
+// (use-package packages [package])
+pub fn use_package(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let packages = args
+        .first()
+        .ok_or(ExprErr::Cause("use-package expects packages".to_string()))?;
+    let package = package_arg(args, 1)?;
+    for used in designators_arg(packages)? {
+        package.use_package(&package_designator(&used)?)?;
+    }
+    Ok(Expr::True)
+}
+
+// (export symbols [package])
+pub fn export(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let symbols = args
+        .first()
+        .ok_or(ExprErr::Cause("export expects symbols".to_string()))?;
+    let package = package_arg(args, 1)?;
+    for symbol in symbols_arg(symbols)? {
+        package.export(&symbol)?;
+    }
+    Ok(Expr::True)
+}
+
+// (import symbols [package])
+pub fn import(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let symbols = args
+        .first()
+        .ok_or(ExprErr::Cause("import expects symbols".to_string()))?;
+    let package = package_arg(args, 1)?;
+    for symbol in symbols_arg(symbols)? {
+        package.import(&symbol)?;
+    }
+    Ok(Expr::True)
+}
+
+// (shadow names [package])
+pub fn shadow(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let names = args
+        .first()
+        .ok_or(ExprErr::Cause("shadow expects names".to_string()))?;
+    let package = package_arg(args, 1)?;
+    for name in designators_arg(names)? {
+        package.shadow(&string_designator(&name)?);
+    }
+    Ok(Expr::True)
+}
+
+// the symbol and its status as the values of intern and find-symbol
+fn symbol_values(evaluator: &mut Evaluator, symbol: Expr, status: Option<&str>) -> Expr {
+    let status = status
+        .map(|status| Expr::Symbol(Symbol::keyword(status)))
+        .unwrap_or(Expr::Nil);
+    evaluator.multiple_values(vec![symbol, status])
+}
+
+// (intern "NAME" [package]) returns the symbol and its status, NIL for a
+// new one
+pub fn intern(evaluator: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [Expr::String(name)] | [Expr::String(name), _] => {
+            let package = package_arg(args, 1)?;
+            let name = name.borrow();
+            let (symbol, status) = match package.find_status(&name) {
+                Some((symbol, status)) => (symbol, Some(status)),
+                None => (package.intern(&name), None),
+            };
+            Ok(symbol_values(
+                evaluator,
+                crate::symbol::symbol_expr(symbol),
+                status,
+            ))
+        }
+        _ => Err(ExprErr::Cause("intern expects a string".to_string())),
+    }
+}
+
+// (find-symbol "NAME" [package]) is NIL when no such symbol is accessible
+pub fn find_symbol(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    match args {
+        [Expr::String(name)] | [Expr::String(name), _] => {
+            Ok(match package_arg(args, 1)?.find_status(&name.borrow()) {
+                Some((symbol, status)) => {
+                    symbol_values(evaluator, crate::symbol::symbol_expr(symbol), Some(status))
+                }
+                None => symbol_values(evaluator, Expr::Nil, None),
+            })
+        }
+        _ => Err(ExprErr::Cause("find-symbol expects a string".to_string())),
+    }
+}
@@ -1,54 +1,365 @@
+use super::array::{read_array, Array};
 use super::ast::*;
+use super::eval::{Evaluator, ExprEnv};
 use super::lexer::*;
+use super::math::complex_expr;
+use super::package::find_package;
+use super::pathname::Pathname;
+use super::structure::read_struct;
+use super::symbol::{symbol_expr, Symbol, SymbolRef};
 use super::token::*;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
-pub struct Parser {
+pub struct Parser<'a> {
     lexer: Lexer,
+    // the objects labelled with #n= in the form being read
+    labels: HashMap<usize, Expr>,
+    // whether reading a form excluded by #+ or #-, which is read without
+    // interning symbols and returned as NIL
+    suppress: bool,
+    // evaluates #. forms
+    evaluator: Option<&'a mut Evaluator>,
 }
 
-impl Parser {
-    pub fn new(lexer: Lexer) -> Parser {
-        Self { lexer }
+impl<'a> Parser<'a> {
+    pub fn new(lexer: Lexer) -> Parser<'a> {
+        Self {
+            lexer,
+            labels: HashMap::new(),
+            suppress: false,
+            evaluator: None,
+        }
+    }
+
+    // a parser evaluating #. forms with evaluator
+    pub fn with_evaluator(mut self, evaluator: &'a mut Evaluator) -> Parser<'a> {
+        self.evaluator = Some(evaluator);
+        self
+    }
+
+    pub fn into_lexer(self) -> Lexer {
+        self.lexer
     }
 
     pub fn parse(&mut self) -> Result<Expr, ExprErr> {
-        let token = self.lexer.next_token();
+        Ok(self.read()?.unwrap_or(Expr::Nil))
+    }
+
+    // the next form, or None at the end of the input
+    pub fn read(&mut self) -> Result<Option<Expr>, ExprErr> {
+        self.labels.clear();
+        let token = self.next_token()?;
+
+        match token {
+            Token::Eof => Ok(None),
+            Token::Rparen => Err(ExprErr::Cause("unexpected )".to_string())),
+            _ => self.parse_token(token).map(Some),
+        }
+    }
+
+    // the number of characters of the input read so far
+    pub fn position(&self) -> usize {
+        self.lexer.position()
+    }
 
+    fn parse_token(&mut self, token: Token) -> Result<Expr, ExprErr> {
+        if self.suppress {
+            return self.skip_token(token);
+        }
         match token {
             Token::Number(num) => Ok(Expr::Number(num)),
-            Token::String(s) => Ok(Expr::String(s)),
-            Token::Literal(symbol) => Ok(Expr::Symbol(symbol)),
-            Token::Asterfisk => Ok(Expr::Symbol("*".to_string())),
-            Token::Minus => Ok(Expr::Symbol("-".to_string())),
-            Token::Plus => Ok(Expr::Symbol("+".to_string())),
-            Token::Slash => Ok(Expr::Symbol("/".to_string())),
+            Token::Char(ch) => Ok(Expr::Char(ch)),
+            Token::String(s) => Ok(Expr::string(s)),
+            Token::Literal(symbol) => Ok(Expr::Symbol(Symbol::intern(&symbol))),
+            Token::Keyword(name) => Ok(Expr::Symbol(Symbol::keyword(&name))),
+            Token::Uninterned(name) => Ok(Expr::Symbol(Symbol::uninterned(&name))),
+            Token::Qualified(package, name, internal) => {
+                let package = find_package(&package)
+                    .ok_or(ExprErr::Cause(format!("package not found: {}", package)))?;
+                if internal {
+                    return Ok(symbol_expr(package.intern(&name)));
+                }
+                match package.find_external(&name) {
+                    Some(symbol) => Ok(symbol_expr(symbol)),
+                    None => Err(ExprErr::Cause(format!(
+                        "symbol {} is not external in {}",
+                        name, package.name
+                    ))),
+                }
+            }
+            Token::Asterfisk => Ok(Expr::Symbol(Symbol::intern("*"))),
+            Token::Minus => Ok(Expr::Symbol(Symbol::intern("-"))),
+            Token::Plus => Ok(Expr::Symbol(Symbol::intern("+"))),
+            Token::Slash => Ok(Expr::Symbol(Symbol::intern("/"))),
             Token::True => Ok(Expr::True),
             Token::Nil => Ok(Expr::Nil),
             Token::Illegal(token) => Err(ExprErr::Cause(format!("invalid token: {}", token))),
-            Token::Eof | Token::Rparen => Ok(Expr::Nil),
+            Token::Quote => self.parse_quoted("QUOTE"),
+            Token::Function => self.parse_quoted("FUNCTION"),
+            // #S(name :slot value ...)
+            Token::Struct => match self.next_form_token()? {
+                Token::Lparen => {
+                    let form = self.parse_token(Token::Lparen)?;
+                    read_struct(&form.to_vec()?)
+                }
+                token => Err(ExprErr::Cause(format!("unexpected {}", token))),
+            },
+            // #C(real imag)
+            Token::Complex => match self.next_form_token()? {
+                Token::Lparen => match self.parse_token(Token::Lparen)?.to_vec()?.as_slice() {
+                    [Expr::Number(re), Expr::Number(im)] => Ok(complex_expr(*re, *im)),
+                    _ => Err(ExprErr::Cause(
+                        "#C expects a real and an imaginary part".to_string(),
+                    )),
+                },
+                token => Err(ExprErr::Cause(format!("unexpected {}", token))),
+            },
+            // #P"namestring"
+            Token::Pathname => match self.next_form_token()? {
+                Token::String(namestring) => Ok(Expr::Pathname(Pathname::parse(&namestring))),
+                token => Err(ExprErr::Cause(format!("unexpected {}", token))),
+            },
+            // #(element ...)
+            Token::Vector => {
+                let form = self.parse_token(Token::Lparen)?;
+                Ok(Array::vector(form.to_vec()?))
+            }
+            // #nA followed by nested lists of contents
+            Token::Array(rank) => {
+                let token = self.next_form_token()?;
+                let contents = self.parse_token(token)?;
+                read_array(rank, &contents)
+            }
+            // #n=object, where #n# inside the object refers to a placeholder
+            // until the object is read
+            Token::Label(label) => {
+                if self.labels.contains_key(&label) {
+                    return Err(ExprErr::Cause(format!("label #{}= defined twice", label)));
+                }
+                let placeholder = Symbol::uninterned(&format!("#{}#", label));
+                self.labels.insert(label, Expr::Symbol(placeholder.clone()));
+                let token = self.next_form_token()?;
+                let object = self.parse_token(token)?;
+                if matches!(&object, Expr::Symbol(symbol) if Rc::ptr_eq(symbol, &placeholder)) {
+                    return Err(ExprErr::Cause(format!(
+                        "label #{}= refers only to itself",
+                        label
+                    )));
+                }
+                let object = patch(&object, &placeholder, &object, &mut HashSet::new());
+                self.labels.insert(label, object.clone());
+                Ok(object)
+            }
+            Token::Reference(label) => self
+                .labels
+                .get(&label)
+                .cloned()
+                .ok_or(ExprErr::Cause(format!("undefined label #{}#", label))),
             Token::Lparen => {
                 let mut list = Vec::<Expr>::new();
                 loop {
-                    match self.parse() {
-                        Ok(expr) => {
-                            if expr == Expr::Nil {
-                                return Ok(Expr::List(list));
-                            }
-                            list.push(expr);
-                        }
-                        Err(e) => {
-                            return Err(e);
+                    match self.next_token()? {
+                        Token::Rparen => return Ok(Expr::list(list)),
+                        Token::Eof => return Err(ExprErr::Cause("unexpected EOF".to_string())),
+                        // (a b . tail)
+                        Token::Dot if !list.is_empty() => {
+                            let token = self.next_form_token()?;
+                            let tail = self.parse_token(token)?;
+                            return match self.next_token()? {
+                                Token::Rparen => Ok(Expr::list_with_tail(list, tail)),
+                                token => Err(ExprErr::Cause(format!("unexpected {}", token))),
+                            };
                         }
+                        token => list.push(self.parse_token(token)?),
                     }
                 }
             }
+            // #.form is the value of form, when *read-eval* allows it
+            Token::ReadEval => {
+                let token = self.next_form_token()?;
+                let form = self.parse_token(token)?;
+                if Symbol::cl("*READ-EVAL*")
+                    .value()
+                    .is_none_or(|value| value.is_nil())
+                {
+                    return Err(ExprErr::Cause(
+                        "cannot read #. while *read-eval* is NIL".to_string(),
+                    ));
+                }
+                match self.evaluator.as_deref_mut() {
+                    Some(evaluator) => evaluator.eval(&form, &mut ExprEnv::new()),
+                    None => Err(ExprErr::Cause("cannot evaluate #. here".to_string())),
+                }
+            }
+            Token::Feature(_) | Token::Eof | Token::Rparen | Token::Dot => {
+                Err(ExprErr::Cause(format!("unexpected {}", token)))
+            }
+        }
+    }
+
+    // read past the form starting with token, which is NIL when suppressed
+    fn skip_token(&mut self, token: Token) -> Result<Expr, ExprErr> {
+        match token {
+            Token::Lparen | Token::Vector => loop {
+                match self.next_token()? {
+                    Token::Rparen => break,
+                    Token::Eof => return Err(ExprErr::Cause("unexpected EOF".to_string())),
+                    token => _ = self.skip_token(token)?,
+                }
+            },
+            Token::Quote
+            | Token::Function
+            | Token::Struct
+            | Token::Complex
+            | Token::Pathname
+            | Token::Array(_)
+            | Token::Label(_)
+            | Token::ReadEval => {
+                let token = self.next_form_token()?;
+                self.skip_token(token)?;
+            }
+            Token::Feature(_) | Token::Eof | Token::Rparen => {
+                return Err(ExprErr::Cause(format!("unexpected {}", token)))
+            }
+            _ => {}
+        }
+        Ok(Expr::Nil)
+    }
+
+    // the next token after reader conditionals: #+feature form reads form
+    // only when the feature expression holds, and #-feature form only
+    // when it does not
+    fn next_token(&mut self) -> Result<Token, ExprErr> {
+        loop {
+            let token = self.lexer.next_token();
+            let Token::Feature(wanted) = token else {
+                return Ok(token);
+            };
+            let token = self.lexer.next_token();
+            let feature = self.parse_feature(token)?;
+            if self.suppress || has_feature(&feature)? != wanted {
+                let suppress = std::mem::replace(&mut self.suppress, true);
+                let token = self.next_form_token();
+                let skipped = token.and_then(|token| self.skip_token(token));
+                self.suppress = suppress;
+                skipped?;
+            }
         }
     }
+
+    // 'a => (quote a), #'a => (function a)
+    fn parse_quoted(&mut self, operator: &str) -> Result<Expr, ExprErr> {
+        let token = self.next_form_token()?;
+        let expr = self.parse_token(token)?;
+        Ok(Expr::list(vec![Expr::Symbol(Symbol::cl(operator)), expr]))
+    }
+
+    // a feature expression starting with token: a feature name, read as
+    // a keyword, or a list of AND, OR or NOT and feature expressions
+    fn parse_feature(&mut self, token: Token) -> Result<Expr, ExprErr> {
+        match token {
+            Token::Literal(name) | Token::Keyword(name) => Ok(Expr::Symbol(Symbol::keyword(&name))),
+            Token::Lparen => {
+                let mut list = vec![];
+                loop {
+                    match self.lexer.next_token() {
+                        Token::Rparen => return Ok(Expr::list(list)),
+                        Token::Eof => return Err(ExprErr::Cause("unexpected EOF".to_string())),
+                        token => list.push(self.parse_feature(token)?),
+                    }
+                }
+            }
+            token => Err(ExprErr::Cause(format!(
+                "invalid feature expression: {}",
+                token
+            ))),
+        }
+    }
+
+    fn next_form_token(&mut self) -> Result<Token, ExprErr> {
+        match self.next_token()? {
+            token @ (Token::Eof | Token::Rparen) => {
+                Err(ExprErr::Cause(format!("unexpected {}", token)))
+            }
+            token => Ok(token),
+        }
+    }
+}
+
+// whether the feature expression holds for *features*
+fn has_feature(feature: &Expr) -> Result<bool, ExprErr> {
+    let invalid = || ExprErr::Cause(format!("invalid feature expression: {}", feature));
+    let Expr::Cons(_) = feature else {
+        let features = Symbol::cl("*FEATURES*").value().unwrap_or(Expr::Nil);
+        return Ok(features.to_vec()?.contains(feature));
+    };
+    let list = feature.to_vec()?;
+    let Some((Expr::Symbol(operator), features)) = list.split_first() else {
+        return Err(invalid());
+    };
+    let holds = features
+        .iter()
+        .map(has_feature)
+        .collect::<Result<Vec<bool>, ExprErr>>()?;
+    match (operator.name.as_str(), holds.as_slice()) {
+        ("AND", _) => Ok(holds.iter().all(|holds| *holds)),
+        ("OR", _) => Ok(holds.iter().any(|holds| *holds)),
+        ("NOT", [holds]) => Ok(!holds),
+        _ => Err(invalid()),
+    }
+}
+
+// replace the placeholder of a label by the labelled object, updating
+// conses, vectors and structures in place so that they can refer to
+// themselves
+fn patch(expr: &Expr, placeholder: &SymbolRef, object: &Expr, seen: &mut HashSet<usize>) -> Expr {
+    let patch_all = |exprs: &[Expr], seen: &mut HashSet<usize>| {
+        exprs
+            .iter()
+            .map(|x| patch(x, placeholder, object, seen))
+            .collect::<Vec<Expr>>()
+    };
+    match expr {
+        Expr::Symbol(symbol) if Rc::ptr_eq(symbol, placeholder) => object.clone(),
+        // along the cdrs, up to a cell already patched
+        Expr::Cons(_) => {
+            let mut next = expr.clone();
+            while let Expr::Cons(cell) = next {
+                if !seen.insert(Rc::as_ptr(&cell) as *const () as usize) {
+                    break;
+                }
+                let car = patch(&cell.borrow().car.clone(), placeholder, object, seen);
+                cell.borrow_mut().car = car;
+                next = cell.borrow().cdr.clone();
+                if !matches!(next, Expr::Cons(_)) {
+                    cell.borrow_mut().cdr = patch(&next, placeholder, object, seen);
+                }
+            }
+            expr.clone()
+        }
+        Expr::Array(array) => {
+            if seen.insert(Rc::as_ptr(array) as *const () as usize) {
+                let elements = patch_all(&array.borrow().elements.clone(), seen);
+                array.borrow_mut().elements = elements;
+            }
+            expr.clone()
+        }
+        Expr::Struct(structure) => {
+            if seen.insert(Rc::as_ptr(structure) as *const () as usize) {
+                let values = patch_all(&structure.borrow().values.clone(), seen);
+                structure.borrow_mut().values = values;
+            }
+            expr.clone()
+        }
+        _ => expr.clone(),
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::printer::{write_to_string, PrintOptions};
 
     #[test]
     fn parse() {
@@ -61,6 +372,8 @@ mod test {
             "(+ 1 2 (* 1 3))",
             "t",
             "nil",
+            "(list t nil 1)",
+            "(setq hash-keys (quote (a b)))",
         ];
         for test in tests {
             let l = Lexer::new(String::from(test));
@@ -69,4 +382,24 @@ mod test {
             assert_eq!(expr.to_string(), test.to_uppercase());
         }
     }
+
+    #[test]
+    fn parse_quote() {
+        let tests = vec![
+            ("'a", "(QUOTE A)"),
+            ("'(1 2)", "(QUOTE (1 2))"),
+            ("(list 'a ())", "(LIST (QUOTE A) NIL)"),
+            ("#'car", "(FUNCTION CAR)"),
+            ("'(:a 1 :b 2)", "(QUOTE (:A 1 :B 2))"),
+            ("'#(1 (2) #(3))", "(QUOTE #(1 (2) #(3)))"),
+            ("#2a((1 2) (3 4))", "#2A((1 2) (3 4))"),
+        ];
+        for test in tests {
+            let l = Lexer::new(String::from(test.0));
+            let mut p = Parser::new(l);
+            let expr = p.parse().unwrap();
+            let printed = write_to_string(&expr, &PrintOptions::default()).unwrap();
+            assert_eq!(printed, test.1);
+        }
+    }
 }
@@ -0,0 +1,563 @@
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{keyword_args, Evaluator, ExprEnv};
+use crate::package;
+use crate::stream::Stream;
+use crate::symbol::Symbol;
+use std::fs;
+use std::path::Path;
+
+// A file name split into its directory, name and type. "*" in a
+// component is wild, a "**" directory matches any number of directories,
+// and ".." is the parent directory.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pathname {
+    // None when the directory is not specified
+    pub directory: Option<Directory>,
+    pub name: Option<String>,
+    pub typ: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Directory {
+    pub absolute: bool,
+    pub components: Vec<String>,
+}
+
+impl Pathname {
+    // the pathname written as namestring, such as "/var/log/app.log"
+    pub fn parse(namestring: &str) -> Pathname {
+        let (directory, file) = match namestring.rfind('/') {
+            Some(i) => (Some(&namestring[..=i]), &namestring[i + 1..]),
+            None => (None, namestring),
+        };
+        let mut directory = directory.map(|directory| Directory {
+            absolute: directory.starts_with('/'),
+            components: directory
+                .split('/')
+                .filter(|component| !component.is_empty() && *component != ".")
+                .map(|component| component.to_string())
+                .collect(),
+        });
+        if file == "." || file == ".." {
+            let directory = directory.get_or_insert(Directory {
+                absolute: false,
+                components: vec![],
+            });
+            if file == ".." {
+                directory.components.push(file.to_string());
+            }
+            return Pathname {
+                directory: Some(directory.clone()),
+                ..Pathname::default()
+            };
+        }
+        // a leading dot is part of the name, as in ".bashrc"
+        let (name, typ) = match file.rfind('.') {
+            Some(i) if i > 0 => (&file[..i], Some(file[i + 1..].to_string())),
+            _ => (file, None),
+        };
+        Pathname {
+            directory,
+            name: Some(name.to_string()).filter(|name| !name.is_empty()),
+            typ,
+        }
+    }
+
+    pub fn namestring(&self) -> String {
+        let mut s = String::new();
+        if let Some(directory) = &self.directory {
+            if directory.absolute {
+                s.push('/');
+            }
+            for component in &directory.components {
+                s.push_str(component);
+                s.push('/');
+            }
+        }
+        if let Some(name) = &self.name {
+            s.push_str(name);
+        }
+        if let Some(typ) = &self.typ {
+            s.push('.');
+            s.push_str(typ);
+        }
+        s
+    }
+
+    // the directory part alone
+    pub fn directory_pathname(&self) -> Pathname {
+        Pathname {
+            directory: self.directory.clone(),
+            ..Pathname::default()
+        }
+    }
+
+    // the pathname with its missing parts taken from defaults; a relative
+    // directory is taken relative to the directory of defaults
+    pub fn merge(&self, defaults: &Pathname) -> Pathname {
+        let directory = match (&self.directory, &defaults.directory) {
+            (None, directory) => directory.clone(),
+            (Some(relative), Some(default)) if !relative.absolute => {
+                let mut directory = default.clone();
+                for component in &relative.components {
+                    match directory.components.last() {
+                        Some(last) if component == ".." && last != ".." && last != "**" => {
+                            directory.components.pop();
+                        }
+                        _ => directory.components.push(component.clone()),
+                    }
+                }
+                Some(directory)
+            }
+            (directory, _) => directory.clone(),
+        };
+        Pathname {
+            directory,
+            name: self.name.clone().or(defaults.name.clone()),
+            typ: self.typ.clone().or(defaults.typ.clone()),
+        }
+    }
+
+    fn is_wild(&self) -> bool {
+        let directory = self
+            .directory
+            .iter()
+            .flat_map(|directory| &directory.components);
+        directory
+            .chain(&self.name)
+            .chain(&self.typ)
+            .any(|component| component.contains(['*', '?']))
+    }
+}
+
+// the pathname designated by a string, a pathname or a file stream
+pub fn pathname_arg(expr: &Expr) -> Result<Pathname, ExprErr> {
+    match expr {
+        Expr::String(s) => Ok(Pathname::parse(&s.borrow())),
+        Expr::Pathname(pathname) => Ok(pathname.clone()),
+        Expr::Stream(stream) => match &*stream.borrow() {
+            Stream::File(file) => Ok(file.pathname.clone()),
+            _ => Err(ExprErr::Cause(format!("{} is not file stream", expr))),
+        },
+        _ => Err(ExprErr::Cause(format!(
+            "{} is not pathname designator",
+            expr
+        ))),
+    }
+}
+
+// *default-pathname-defaults* starts as the working directory
+pub fn init_pathname_variables() {
+    let defaults = match std::env::current_dir() {
+        Ok(dir) => Pathname::parse(&format!("{}/", dir.display())),
+        Err(_) => Pathname::default(),
+    };
+    let cl = package::common_lisp();
+    let symbol = cl.intern("*DEFAULT-PATHNAME-DEFAULTS*");
+    symbol.proclaim_special();
+    symbol.set_value(Some(Expr::Pathname(defaults)));
+    _ = cl.export(&symbol);
+}
+
+fn default_pathname() -> Result<Pathname, ExprErr> {
+    match Symbol::cl("*DEFAULT-PATHNAME-DEFAULTS*").value() {
+        Some(defaults) => pathname_arg(&defaults),
+        None => Ok(Pathname::default()),
+    }
+}
+
+// the pathname designated by expr merged with *default-pathname-defaults*
+pub fn merged_pathname(expr: &Expr) -> Result<Pathname, ExprErr> {
+    Ok(pathname_arg(expr)?.merge(&default_pathname()?))
+}
+
+// the name of the file designated by expr, which may not be wild
+pub fn file_name(expr: &Expr) -> Result<String, ExprErr> {
+    let pathname = merged_pathname(expr)?;
+    if pathname.is_wild() {
+        return Err(ExprErr::Cause(format!(
+            "{} is wild pathname",
+            pathname.namestring()
+        )));
+    }
+    Ok(pathname.namestring())
+}
+
+pub fn file_error(action: &str, name: &str, err: std::io::Error) -> ExprErr {
+    ExprErr::Cause(format!("cannot {} {}: {}", action, name, err))
+}
+
+// the truename of an existing file, a directory pathname for directories
+pub fn truename(path: &Path) -> Option<Pathname> {
+    let path = fs::canonicalize(path).ok()?;
+    let namestring = path.to_string_lossy();
+    if path.is_dir() && !namestring.ends_with('/') {
+        Some(Pathname::parse(&format!("{}/", namestring)))
+    } else {
+        Some(Pathname::parse(&namestring))
+    }
+}
+
+// a name or type component: a string, :WILD or NIL
+fn component_expr(component: &Option<String>) -> Expr {
+    match component.as_deref() {
+        None => Expr::Nil,
+        Some("*") => Expr::Symbol(Symbol::keyword("WILD")),
+        Some(component) => Expr::string(component.to_string()),
+    }
+}
+
+fn component_arg(expr: &Expr) -> Result<Option<String>, ExprErr> {
+    match expr {
+        Expr::Nil => Ok(None),
+        Expr::String(s) => Ok(Some(s.borrow().clone())),
+        Expr::Symbol(symbol) if symbol.is_keyword() && symbol.name == "WILD" => {
+            Ok(Some("*".to_string()))
+        }
+        _ => Err(ExprErr::Cause(format!(
+            "{} is not pathname component",
+            expr
+        ))),
+    }
+}
+
+// (:absolute "var" "log") for /var/log/
+fn directory_expr(directory: &Option<Directory>) -> Expr {
+    let Some(directory) = directory else {
+        return Expr::Nil;
+    };
+    let kind = if directory.absolute {
+        "ABSOLUTE"
+    } else {
+        "RELATIVE"
+    };
+    let mut list = vec![Expr::Symbol(Symbol::keyword(kind))];
+    for component in &directory.components {
+        list.push(match component.as_str() {
+            "*" => Expr::Symbol(Symbol::keyword("WILD")),
+            "**" => Expr::Symbol(Symbol::keyword("WILD-INFERIORS")),
+            ".." => Expr::Symbol(Symbol::keyword("UP")),
+            _ => Expr::string(component.clone()),
+        });
+    }
+    Expr::list(list)
+}
+
+fn directory_arg(expr: &Expr) -> Result<Option<Directory>, ExprErr> {
+    let invalid = || ExprErr::Cause(format!("{} is not pathname directory", expr));
+    let list = match expr {
+        Expr::Nil => return Ok(None),
+        Expr::String(_) => vec![Expr::Symbol(Symbol::keyword("ABSOLUTE")), expr.clone()],
+        Expr::Symbol(symbol) if symbol.is_keyword() && symbol.name == "WILD" => vec![
+            Expr::Symbol(Symbol::keyword("ABSOLUTE")),
+            Expr::Symbol(symbol.clone()),
+        ],
+        Expr::Cons(_) => expr.to_vec()?,
+        _ => return Err(invalid()),
+    };
+    let (absolute, components) = match list.split_first() {
+        Some((Expr::Symbol(kind), components)) if kind.is_keyword() => match kind.name.as_str() {
+            "ABSOLUTE" => (true, components),
+            "RELATIVE" => (false, components),
+            _ => return Err(invalid()),
+        },
+        _ => return Err(invalid()),
+    };
+    let components = components
+        .iter()
+        .map(|component| match component {
+            Expr::String(s) => Ok(s.borrow().clone()),
+            Expr::Symbol(symbol) if symbol.is_keyword() => match symbol.name.as_str() {
+                "WILD" => Ok("*".to_string()),
+                "WILD-INFERIORS" => Ok("**".to_string()),
+                "UP" | "BACK" => Ok("..".to_string()),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        })
+        .collect::<Result<Vec<String>, ExprErr>>()?;
+    Ok(Some(Directory {
+        absolute,
+        components,
+    }))
+}
+
+fn single_pathname_arg(name: &str, args: &[Expr]) -> Result<Pathname, ExprErr> {
+    match args {
+        [pathname] => pathname_arg(pathname),
+        _ => Err(ExprErr::Cause(format!("{} expects a pathname", name))),
+    }
+}
+
+// (pathname pathspec)
+pub fn pathname(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    Ok(Expr::Pathname(single_pathname_arg("pathname", args)?))
+}
+
+pub fn pathnamep(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [Expr::Pathname(_)] => Ok(Expr::True),
+        [_] => Ok(Expr::Nil),
+        _ => Err(ExprErr::Cause("pathnamep expects an object".to_string())),
+    }
+}
+
+pub fn namestring(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    Ok(Expr::string(
+        single_pathname_arg("namestring", args)?.namestring(),
+    ))
+}
+
+pub fn pathname_directory(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let pathname = single_pathname_arg("pathname-directory", args)?;
+    Ok(directory_expr(&pathname.directory))
+}
+
+pub fn pathname_name(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    Ok(component_expr(
+        &single_pathname_arg("pathname-name", args)?.name,
+    ))
+}
+
+pub fn pathname_type(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    Ok(component_expr(
+        &single_pathname_arg("pathname-type", args)?.typ,
+    ))
+}
+
+// (make-pathname &key directory name type defaults); the parts not given
+// are those of defaults
+pub fn make_pathname(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let options = keyword_args(
+        "make-pathname",
+        args,
+        &[
+            "HOST",
+            "DEVICE",
+            "DIRECTORY",
+            "NAME",
+            "TYPE",
+            "VERSION",
+            "DEFAULTS",
+        ],
+    )?;
+    let defaults = match options.get("DEFAULTS") {
+        Some(defaults) => pathname_arg(defaults)?,
+        None => Pathname::default(),
+    };
+    Ok(Expr::Pathname(Pathname {
+        directory: match options.get("DIRECTORY") {
+            Some(directory) => directory_arg(directory)?,
+            None => defaults.directory,
+        },
+        name: match options.get("NAME") {
+            Some(name) => component_arg(name)?,
+            None => defaults.name,
+        },
+        typ: match options.get("TYPE") {
+            Some(typ) => component_arg(typ)?,
+            None => defaults.typ,
+        },
+    }))
+}
+
+// (merge-pathnames pathname [defaults [default-version]])
+pub fn merge_pathnames(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (pathname, defaults) = match args {
+        [pathname] => (pathname, default_pathname()?),
+        [pathname, defaults, ..] if args.len() <= 3 => (pathname, pathname_arg(defaults)?),
+        _ => {
+            return Err(ExprErr::Cause(
+                "merge-pathnames expects a pathname and defaults".to_string(),
+            ))
+        }
+    };
+    Ok(Expr::Pathname(pathname_arg(pathname)?.merge(&defaults)))
+}
+
+// (probe-file pathspec) is the truename of the file, or NIL when it does
+// not exist
+pub fn probe_file(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [pathspec] => Ok(truename(Path::new(&file_name(pathspec)?))
+            .map(Expr::Pathname)
+            .unwrap_or(Expr::Nil)),
+        _ => Err(ExprErr::Cause("probe-file expects a pathname".to_string())),
+    }
+}
+
+pub fn delete_file(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [pathspec] => {
+            let name = file_name(pathspec)?;
+            fs::remove_file(&name).map_err(|err| file_error("delete", &name, err))?;
+            Ok(Expr::True)
+        }
+        _ => Err(ExprErr::Cause("delete-file expects a pathname".to_string())),
+    }
+}
+
+// (rename-file file new-name) renames file to new-name merged with file,
+// and returns the new name and the old and new truenames
+pub fn rename_file(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let [file, new_name] = args else {
+        return Err(ExprErr::Cause(
+            "rename-file expects a file and a new name".to_string(),
+        ));
+    };
+    let old = merged_pathname(file)?;
+    let new = pathname_arg(new_name)?.merge(&old);
+    let old_name = file_name(&Expr::Pathname(old))?;
+    let name = file_name(&Expr::Pathname(new.clone()))?;
+    let old_truename = truename(Path::new(&old_name));
+    fs::rename(&old_name, &name).map_err(|err| file_error("rename", &old_name, err))?;
+    let new_truename = truename(Path::new(&name));
+    let truename_expr =
+        |truename: Option<Pathname>| truename.map(Expr::Pathname).unwrap_or(Expr::Nil);
+    Ok(evaluator.multiple_values(vec![
+        Expr::Pathname(new),
+        truename_expr(old_truename),
+        truename_expr(new_truename),
+    ]))
+}
+
+// (ensure-directories-exist pathspec &key verbose) creates the directories
+// of pathspec, and returns pathspec and whether it created any
+pub fn ensure_directories_exist(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let Some((pathspec, options)) = args.split_first() else {
+        return Err(ExprErr::Cause(
+            "ensure-directories-exist expects a pathname".to_string(),
+        ));
+    };
+    let options = keyword_args("ensure-directories-exist", options, &["VERBOSE"])?;
+    let directory = merged_pathname(pathspec)?.directory_pathname();
+    let name = file_name(&Expr::Pathname(directory))?;
+    let created = !name.is_empty() && !Path::new(&name).is_dir();
+    if created {
+        fs::create_dir_all(&name).map_err(|err| file_error("create directory", &name, err))?;
+        if options
+            .get("VERBOSE")
+            .is_some_and(|verbose| !verbose.is_nil())
+        {
+            crate::stream::write_to(None, &format!("creating directory: {}\n", name))?;
+        }
+    }
+    Ok(evaluator.multiple_values(vec![pathspec.clone(), crate::eval::bool_expr(created)]))
+}
+
+// whether name matches pattern, where * matches any characters and ?
+// any one character
+fn wild_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| wild_match(rest, &name[i..])),
+        Some((ch, rest)) => match name.split_first() {
+            Some((first, name)) if *ch == '?' || ch == first => wild_match(rest, name),
+            _ => false,
+        },
+    }
+}
+
+fn component_matches(pattern: &Option<String>, component: &Option<String>) -> bool {
+    match (pattern.as_deref(), component) {
+        (Some("*"), _) => true,
+        (Some(pattern), Some(component)) => wild_match(
+            &pattern.chars().collect::<Vec<char>>(),
+            &component.chars().collect::<Vec<char>>(),
+        ),
+        (pattern, component) => pattern == component.as_deref(),
+    }
+}
+
+fn subdirectories(path: &Path) -> Vec<std::path::PathBuf> {
+    let Ok(entries) = fs::read_dir(path) else {
+        return vec![];
+    };
+    let mut dirs = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs
+}
+
+// the existing directories below path matching the directory components
+fn matching_directories(path: &Path, components: &[String], found: &mut Vec<std::path::PathBuf>) {
+    let Some((component, rest)) = components.split_first() else {
+        if path.is_dir() {
+            found.push(path.to_path_buf());
+        }
+        return;
+    };
+    match component.as_str() {
+        "**" => {
+            matching_directories(path, rest, found);
+            for dir in subdirectories(path) {
+                matching_directories(&dir, components, found);
+            }
+        }
+        _ if component.contains(['*', '?']) => {
+            let pattern = component.chars().collect::<Vec<char>>();
+            for dir in subdirectories(path) {
+                let name = dir.file_name().unwrap_or_default().to_string_lossy();
+                if wild_match(&pattern, &name.chars().collect::<Vec<char>>()) {
+                    matching_directories(&dir, rest, found);
+                }
+            }
+        }
+        _ => matching_directories(&path.join(component), rest, found),
+    }
+}
+
+// (directory pathspec) is the truenames of the files matching pathspec,
+// whose directory, name and type may be wild
+pub fn directory(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let pattern = match args {
+        [pathspec, options @ ..] => {
+            keyword_args("directory", options, &["RESOLVE-SYMLINKS"])?;
+            merged_pathname(pathspec)?
+        }
+        _ => return Err(ExprErr::Cause("directory expects a pathname".to_string())),
+    };
+    let (root, components) = match &pattern.directory {
+        Some(directory) if directory.absolute => ("/", directory.components.as_slice()),
+        Some(directory) => (".", directory.components.as_slice()),
+        None => (".", &[][..]),
+    };
+    let mut dirs = vec![];
+    matching_directories(Path::new(root), components, &mut dirs);
+    let mut found = vec![];
+    for dir in dirs {
+        if pattern.name.is_none() && pattern.typ.is_none() {
+            found.extend(truename(&dir));
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file = Pathname::parse(&entry.file_name().to_string_lossy());
+            if component_matches(&pattern.name, &file.name)
+                && component_matches(&pattern.typ, &file.typ)
+            {
+                found.extend(truename(&entry.path()));
+            }
+        }
+    }
+    found.sort_by_key(|pathname| pathname.namestring());
+    found.dedup();
+    Ok(Expr::list(found.into_iter().map(Expr::Pathname).collect()))
+}
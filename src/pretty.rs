@@ -0,0 +1,250 @@
+use crate::ast::Expr;
+use crate::stream::column_after;
+
+// how the items of a logical block are laid out when it does not fit on
+// the rest of the line
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Style {
+    // as many items on each line as fit, aligned after the prefix
+    Fill,
+    // one item per line, aligned after the prefix
+    Linear,
+    // a function call: the arguments filled, aligned with the first one
+    Call,
+    // the arguments one per line, aligned with the first one, as in cond
+    Aligned,
+    // the operator and that many arguments on the first line, then the
+    // body indented by two, as in defun and let
+    Body(usize),
+}
+
+// a logical block of items, or text printed as is
+pub enum Doc {
+    Text(String),
+    Block {
+        prefix: String,
+        items: Vec<Doc>,
+        suffix: String,
+        style: Style,
+    },
+}
+
+impl Doc {
+    // the text of the document on a single line
+    pub fn flat(&self) -> String {
+        match self {
+            Doc::Text(text) => text.clone(),
+            Doc::Block {
+                prefix,
+                items,
+                suffix,
+                ..
+            } => {
+                let items = items.iter().map(Doc::flat).collect::<Vec<String>>();
+                format!("{}{}{}", prefix, items.join(" "), suffix)
+            }
+        }
+    }
+
+    // the width of the document on a single line
+    fn width(&self) -> usize {
+        match self {
+            Doc::Text(text) => text.chars().count(),
+            Doc::Block {
+                prefix,
+                items,
+                suffix,
+                ..
+            } => {
+                let spaces = items.len().saturating_sub(1);
+                let items = items.iter().map(Doc::width).sum::<usize>();
+                prefix.chars().count() + items + spaces + suffix.chars().count()
+            }
+        }
+    }
+
+    // the width of the start of the document up to its first possible
+    // line break
+    fn head_width(&self) -> usize {
+        match self {
+            Doc::Block { prefix, items, .. } if !items.is_empty() => {
+                prefix.chars().count() + items[0].head_width()
+            }
+            _ => self.width(),
+        }
+    }
+
+    // the text with line breaks wherever a block does not fit within the
+    // right margin, starting at column
+    pub fn layout(&self, column: usize, right_margin: usize) -> String {
+        let mut layout = Layout {
+            out: String::new(),
+            column,
+            right_margin,
+        };
+        layout.doc(self, 0);
+        layout.out
+    }
+}
+
+struct Layout {
+    out: String,
+    column: usize,
+    right_margin: usize,
+}
+
+impl Layout {
+    fn text(&mut self, text: &str) {
+        self.out.push_str(text);
+        self.column = column_after(self.column, text);
+    }
+
+    fn newline(&mut self, indent: usize) {
+        self.out.push('\n');
+        self.out.push_str(&" ".repeat(indent));
+        self.column = indent;
+    }
+
+    fn fits(&self, width: usize) -> bool {
+        self.column + width <= self.right_margin
+    }
+
+    // lay out doc followed by trailing characters on the same line
+    fn doc(&mut self, doc: &Doc, trailing: usize) {
+        let (prefix, items, suffix, style) = match doc {
+            Doc::Block {
+                prefix,
+                items,
+                suffix,
+                style,
+            } if !self.fits(doc.width() + trailing) => (prefix, items, suffix, *style),
+            _ => return self.text(&doc.flat()),
+        };
+        let open = self.column;
+        self.text(prefix);
+        let start = self.column;
+        let last = items.len().saturating_sub(1);
+        // the characters after item i that must stay on its line
+        let after = |i: usize| {
+            if i == last {
+                trailing + suffix.chars().count()
+            } else {
+                0
+            }
+        };
+        // the arguments align with the first one unless it starts a line
+        let mut align = start + items.first().map_or(0, Doc::width) + 1;
+        for (i, item) in items.iter().enumerate() {
+            match (style, i) {
+                (_, 0) => {}
+                (Style::Fill, _) => self.fill(item, after(i), start),
+                (Style::Linear, _) => self.newline(start),
+                (Style::Call | Style::Aligned, 1) => {
+                    if !self.open(item, after(i), start) {
+                        align = start;
+                    }
+                }
+                (Style::Call, _) => self.fill(item, after(i), align),
+                (Style::Aligned, _) => self.newline(align),
+                (Style::Body(distinguished), _) if i <= distinguished => {
+                    self.open(item, after(i), open + 4);
+                }
+                (Style::Body(_), _) => self.newline(open + 2),
+            }
+            self.doc(item, after(i));
+        }
+        self.text(suffix);
+    }
+
+    // a space before the first argument of a form if its start fits on
+    // the line, else a line break; true for the space
+    fn open(&mut self, item: &Doc, trailing: usize, indent: usize) -> bool {
+        let width = match item {
+            Doc::Text(_) => item.width() + trailing,
+            Doc::Block { .. } => item.head_width(),
+        };
+        if self.fits(1 + width) {
+            self.text(" ");
+            true
+        } else {
+            self.newline(indent);
+            false
+        }
+    }
+
+    // a space before the item if it fits on the line, else a line break
+    fn fill(&mut self, item: &Doc, trailing: usize, indent: usize) {
+        if self.fits(1 + item.width() + trailing) {
+            self.text(" ");
+        } else {
+            self.newline(indent);
+        }
+    }
+}
+
+// forms printed with their first two arguments on the first line and the
+// rest as a body
+const TWO_ARGUMENT_FORMS: [&str; 7] = [
+    "DEFUN",
+    "DEFMACRO",
+    "DEFGENERIC",
+    "DESTRUCTURING-BIND",
+    "MULTIPLE-VALUE-BIND",
+    "DO",
+    "DO*",
+];
+
+const ONE_ARGUMENT_FORMS: [&str; 23] = [
+    "LAMBDA",
+    "LET",
+    "LET*",
+    "FLET",
+    "LABELS",
+    "MACROLET",
+    "WHEN",
+    "UNLESS",
+    "DOLIST",
+    "DOTIMES",
+    "BLOCK",
+    "CASE",
+    "ECASE",
+    "TYPECASE",
+    "ETYPECASE",
+    "UNWIND-PROTECT",
+    "HANDLER-CASE",
+    "WITH-OUTPUT-TO-STRING",
+    "WITH-INPUT-FROM-STRING",
+    "WITH-OPEN-FILE",
+    "WITH-HASH-TABLE-ITERATOR",
+    "DEFSTRUCT",
+    "DEFMETHOD",
+];
+
+// forms whose first argument is a list of bindings or definitions
+const BINDING_FORMS: [&str; 7] = ["LET", "LET*", "FLET", "LABELS", "MACROLET", "DO", "DO*"];
+
+// the layout of a list form by its operator
+pub fn list_style(list: &[Expr]) -> Style {
+    let operator = match list.first() {
+        Some(Expr::Symbol(symbol)) => symbol.name.as_str(),
+        _ => return Style::Fill,
+    };
+    match operator {
+        _ if TWO_ARGUMENT_FORMS.contains(&operator) => Style::Body(2),
+        _ if ONE_ARGUMENT_FORMS.contains(&operator) => Style::Body(1),
+        "PROGN" | "TAGBODY" | "LOOP" => Style::Body(0),
+        "COND" | "IF" | "AND" | "OR" => Style::Aligned,
+        _ => Style::Call,
+    }
+}
+
+// the layout of the argument at index of a list form, such as the
+// bindings of let
+pub fn argument_style(list: &[Expr], index: usize) -> Option<Style> {
+    match (list.first(), index) {
+        (Some(Expr::Symbol(symbol)), 1) if BINDING_FORMS.contains(&symbol.name.as_str()) => {
+            Some(Style::Linear)
+        }
+        _ => None,
+    }
+}
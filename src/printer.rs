@@ -0,0 +1,698 @@
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{keyword_args, Evaluator, ExprEnv};
+use crate::lexer::is_constituent;
+use crate::list::index_arg;
+use crate::math::integer_expr;
+use crate::number::{self, print_float, Number};
+use crate::package;
+use crate::pretty::{self, Doc, Style};
+use crate::stream::{column_of, write_to};
+use crate::symbol::{Symbol, SymbolRef};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+// the printer variables and their initial values
+fn print_variables() -> Vec<(&'static str, Expr)> {
+    vec![
+        ("*PRINT-ESCAPE*", Expr::True),
+        ("*PRINT-READABLY*", Expr::Nil),
+        ("*PRINT-BASE*", integer_expr(10)),
+        ("*PRINT-RADIX*", Expr::Nil),
+        ("*PRINT-CASE*", Expr::Symbol(Symbol::keyword("UPCASE"))),
+        ("*PRINT-LENGTH*", Expr::Nil),
+        ("*PRINT-LEVEL*", Expr::Nil),
+        ("*PRINT-PRETTY*", Expr::True),
+        ("*PRINT-RIGHT-MARGIN*", Expr::Nil),
+        ("*PRINT-CIRCLE*", Expr::Nil),
+    ]
+}
+
+// define the printer variables as special variables of COMMON-LISP
+pub fn init_print_variables() {
+    let cl = package::common_lisp();
+    for (name, value) in print_variables() {
+        let symbol = cl.intern(name);
+        symbol.proclaim_special();
+        symbol.set_value(Some(value));
+        _ = cl.export(&symbol);
+    }
+    // all floats are double floats, read and printed without a d exponent
+    // marker unless this is bound to another format
+    let symbol = cl.intern("*READ-DEFAULT-FLOAT-FORMAT*");
+    symbol.proclaim_special();
+    symbol.set_value(Some(Expr::Symbol(Symbol::cl("DOUBLE-FLOAT"))));
+    _ = cl.export(&symbol);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Case {
+    Upcase,
+    Downcase,
+    Capitalize,
+}
+
+// how objects are printed, from the printer variables or the keyword
+// arguments of write
+#[derive(Clone)]
+pub struct PrintOptions {
+    pub escape: bool,
+    pub readably: bool,
+    pub base: u32,
+    pub radix: bool,
+    pub case: Case,
+    pub length: Option<usize>,
+    pub level: Option<usize>,
+    pub pretty: bool,
+    pub right_margin: usize,
+    pub circle: bool,
+    // whether *read-default-float-format* is DOUBLE-FLOAT
+    pub double_float_default: bool,
+}
+
+fn variable(name: &str) -> Expr {
+    Symbol::cl(name).value().unwrap_or(Expr::Nil)
+}
+
+// the initial values of the printer variables, but not printing pretty
+impl Default for PrintOptions {
+    fn default() -> Self {
+        PrintOptions {
+            escape: true,
+            readably: false,
+            base: 10,
+            radix: false,
+            case: Case::Upcase,
+            length: None,
+            level: None,
+            pretty: false,
+            right_margin: 80,
+            circle: false,
+            double_float_default: true,
+        }
+    }
+}
+
+impl PrintOptions {
+    // the options given by the current values of the printer variables
+    pub fn current() -> Result<PrintOptions, ExprErr> {
+        let mut options = PrintOptions::default();
+        for (name, _) in print_variables() {
+            options.set(&name[7..name.len() - 1], &variable(name))?;
+        }
+        options.double_float_default = match variable("*READ-DEFAULT-FLOAT-FORMAT*") {
+            Expr::Symbol(format) => matches!(format.name.as_str(), "DOUBLE-FLOAT" | "LONG-FLOAT"),
+            _ => true,
+        };
+        Ok(options)
+    }
+
+    // set the option named like the keyword argument of write
+    fn set(&mut self, name: &str, value: &Expr) -> Result<(), ExprErr> {
+        let invalid = || {
+            ExprErr::Cause(format!(
+                "invalid *print-{}*: {}",
+                name.to_lowercase(),
+                value
+            ))
+        };
+        match name {
+            "ESCAPE" => self.escape = !value.is_nil(),
+            "READABLY" => self.readably = !value.is_nil(),
+            "BASE" => {
+                self.base = match value {
+                    Expr::Number(Number::Integer(n)) if (2..=36).contains(n) => *n as u32,
+                    _ => return Err(invalid()),
+                }
+            }
+            "RADIX" => self.radix = !value.is_nil(),
+            "CASE" => {
+                self.case = match value {
+                    Expr::Symbol(symbol) if symbol.is_keyword() => match symbol.name.as_str() {
+                        "UPCASE" => Case::Upcase,
+                        "DOWNCASE" => Case::Downcase,
+                        "CAPITALIZE" => Case::Capitalize,
+                        _ => return Err(invalid()),
+                    },
+                    _ => return Err(invalid()),
+                }
+            }
+            "LENGTH" => self.length = limit(value).ok_or_else(invalid)?,
+            "LEVEL" => self.level = limit(value).ok_or_else(invalid)?,
+            "PRETTY" => self.pretty = !value.is_nil(),
+            "CIRCLE" => self.circle = !value.is_nil(),
+            // NIL for the default width of a line
+            "RIGHT-MARGIN" => self.right_margin = limit(value).ok_or_else(invalid)?.unwrap_or(80),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // prin1 prints readably where possible, princ for people
+    pub fn with_escape(mut self, escape: bool) -> PrintOptions {
+        self.escape = escape;
+        if !escape {
+            self.readably = false;
+        }
+        self
+    }
+}
+
+// NIL for no limit, or a non-negative integer
+fn limit(value: &Expr) -> Option<Option<usize>> {
+    match value {
+        Expr::Nil => Some(None),
+        _ => index_arg(value).ok().map(Some),
+    }
+}
+
+// whether a symbol name must be escaped with bars to read back as itself
+fn needs_bars(name: &str) -> bool {
+    name.is_empty()
+        || name.chars().all(|ch| ch == '.')
+        || number::parse(name).is_some()
+        || name
+            .chars()
+            .any(|ch| ch.is_lowercase() || !is_constituent(ch))
+}
+
+fn apply_case(name: &str, case: Case) -> String {
+    match case {
+        Case::Upcase => name.to_string(),
+        Case::Downcase => name.to_lowercase(),
+        Case::Capitalize => {
+            let mut result = String::new();
+            let mut in_word = false;
+            for ch in name.chars() {
+                if !ch.is_alphanumeric() {
+                    result.push(ch);
+                    in_word = false;
+                } else if in_word {
+                    result.extend(ch.to_lowercase());
+                } else {
+                    result.push(ch);
+                    in_word = true;
+                }
+            }
+            result
+        }
+    }
+}
+
+fn symbol_name(name: &str, options: &PrintOptions) -> String {
+    if options.escape && needs_bars(name) {
+        let mut escaped = String::from('|');
+        for ch in name.chars() {
+            if ch == '|' || ch == '\\' {
+                escaped.push('\\');
+            }
+            escaped.push(ch);
+        }
+        escaped.push('|');
+        escaped
+    } else {
+        apply_case(name, options.case)
+    }
+}
+
+fn print_symbol(symbol: &SymbolRef, options: &PrintOptions) -> String {
+    let name = symbol_name(&symbol.name, options);
+    if !options.escape {
+        return name;
+    }
+    let prefix = symbol.package_prefix();
+    if symbol.package.borrow().is_none() {
+        return format!("#:{}", name);
+    }
+    match prefix.strip_suffix("::").or(prefix.strip_suffix(':')) {
+        Some("") | None => format!("{}{}", prefix, name),
+        Some(package) => {
+            let colons = &prefix[package.len()..];
+            format!("{}{}{}", symbol_name(package, options), colons, name)
+        }
+    }
+}
+
+fn digits_in(mut n: u64, base: u32) -> String {
+    if n == 0 {
+        return "0".to_string();
+    }
+    let mut digits = vec![];
+    while n > 0 {
+        let digit = std::char::from_digit((n % base as u64) as u32, base).unwrap_or('?');
+        digits.push(digit.to_ascii_uppercase());
+        n /= base as u64;
+    }
+    digits.iter().rev().collect()
+}
+
+// rationals in *print-base*, marked with their radix if *print-radix*
+fn print_number(num: Number, options: &PrintOptions) -> String {
+    let signed = |n: i64| {
+        format!(
+            "{}{}",
+            if n < 0 { "-" } else { "" },
+            digits_in(n.unsigned_abs(), options.base)
+        )
+    };
+    let digits = match num {
+        Number::Integer(n) => signed(n),
+        Number::Ratio(numerator, denominator) => {
+            format!("{}/{}", signed(numerator), signed(denominator))
+        }
+        Number::Float(x) => return print_float(x, options.double_float_default),
+    };
+    match (options.radix, options.base, num) {
+        (false, _, _) => digits,
+        (true, 2, _) => format!("#b{}", digits),
+        (true, 8, _) => format!("#o{}", digits),
+        (true, 16, _) => format!("#x{}", digits),
+        (true, 10, Number::Integer(_)) => format!("{}.", digits),
+        (true, base, _) => format!("#{}r{}", base, digits),
+    }
+}
+
+fn print_string(s: &str, options: &PrintOptions) -> String {
+    if !options.escape {
+        return s.to_string();
+    }
+    let mut quoted = String::from('"');
+    for ch in s.chars() {
+        if ch == '"' || ch == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
+    }
+    quoted.push('"');
+    quoted
+}
+
+// the elements of a list or vector, abbreviated after *print-length*
+fn element_docs(
+    elements: &[Expr],
+    options: &PrintOptions,
+    depth: usize,
+    circle: &Circle,
+) -> Result<Vec<Doc>, ExprErr> {
+    let mut items = vec![];
+    for (i, element) in elements.iter().enumerate() {
+        if options.length.is_some_and(|length| i >= length) {
+            items.push(Doc::Text("...".to_string()));
+            break;
+        }
+        items.push(document(element, options, depth + 1, circle)?);
+    }
+    Ok(items)
+}
+
+fn block(prefix: &str, items: Vec<Doc>, suffix: &str, style: Style) -> Doc {
+    Doc::Block {
+        prefix: prefix.to_string(),
+        items,
+        suffix: suffix.to_string(),
+        style,
+    }
+}
+
+// 'x and #'x for (quote x) and (function x) when printing pretty
+fn quoted<'a>(list: &'a [Expr], options: &PrintOptions) -> Option<(&'static str, &'a Expr)> {
+    match list {
+        [Expr::Symbol(operator), object] if options.pretty => match operator.name.as_str() {
+            "QUOTE" => Some(("'", object)),
+            "FUNCTION" => Some(("#'", object)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// the elements of a list up to a tail that is not a list or is labelled,
+// printed after a dot
+fn list_document(
+    expr: &Expr,
+    options: &PrintOptions,
+    depth: usize,
+    circle: &Circle,
+) -> Result<Doc, ExprErr> {
+    let mut list = vec![];
+    let mut tail = expr.clone();
+    while let Expr::Cons(cell) = tail {
+        if options.length.is_some_and(|length| list.len() > length) {
+            tail = Expr::Nil;
+            break;
+        }
+        list.push(cell.borrow().car.clone());
+        tail = cell.borrow().cdr.clone();
+        if identity(&tail).is_some_and(|id| circle.shared.contains(&id)) {
+            break;
+        }
+    }
+    if tail.is_nil() {
+        if let Some((prefix, object)) = quoted(&list, options) {
+            let items = vec![document(object, options, depth, circle)?];
+            return Ok(block(prefix, items, "", Style::Fill));
+        }
+    }
+    let mut items = element_docs(&list, options, depth, circle)?;
+    if !tail.is_nil() {
+        items.push(Doc::Text(".".to_string()));
+        items.push(document(&tail, options, depth, circle)?);
+    }
+    for (i, item) in items.iter_mut().enumerate() {
+        if let (
+            Some(style),
+            Doc::Block {
+                style: item_style, ..
+            },
+        ) = (pretty::argument_style(&list, i), &mut *item)
+        {
+            *item_style = style;
+        }
+    }
+    Ok(block("(", items, ")", pretty::list_style(&list)))
+}
+
+// the identity of an object that can be shared or circular
+fn identity(expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Cons(cell) => Some(Rc::as_ptr(cell) as *const () as usize),
+        Expr::Array(array) => Some(Rc::as_ptr(array) as *const () as usize),
+        Expr::Struct(structure) => Some(Rc::as_ptr(structure) as *const () as usize),
+        _ => None,
+    }
+}
+
+// the objects to label when printing: those reached more than once with
+// *print-circle*, and those containing themselves in any case so that
+// printing them ends
+#[derive(Default)]
+struct Circle {
+    shared: HashSet<usize>,
+    labels: RefCell<HashMap<usize, usize>>,
+}
+
+impl Circle {
+    fn new(expr: &Expr, options: &PrintOptions) -> Circle {
+        let mut circle = Circle::default();
+        circle.find_shared(
+            expr,
+            options.circle,
+            &mut HashSet::new(),
+            &mut HashSet::new(),
+        );
+        circle
+    }
+
+    // the cells of a list are walked along the cdrs rather than by
+    // recursion, all of them staying on the path until its end
+    fn find_shared(
+        &mut self,
+        expr: &Expr,
+        all: bool,
+        seen: &mut HashSet<usize>,
+        path: &mut HashSet<usize>,
+    ) {
+        let mut next = expr.clone();
+        let mut entered = vec![];
+        loop {
+            if let Some(id) = identity(&next) {
+                if path.contains(&id) || (all && seen.contains(&id)) {
+                    self.shared.insert(id);
+                    break;
+                }
+                if !seen.insert(id) {
+                    break;
+                }
+                path.insert(id);
+                entered.push(id);
+            }
+            let (elements, cdr) = match &next {
+                Expr::Cons(cell) => {
+                    let cell = cell.borrow();
+                    (vec![cell.car.clone()], Some(cell.cdr.clone()))
+                }
+                Expr::Array(array) => (array.borrow().elements.clone(), None),
+                Expr::Struct(structure) => (structure.borrow().values.clone(), None),
+                _ => (vec![], None),
+            };
+            for element in &elements {
+                self.find_shared(element, all, seen, path);
+            }
+            match cdr {
+                Some(cdr) => next = cdr,
+                None => break,
+            }
+        }
+        for id in entered {
+            path.remove(&id);
+        }
+    }
+}
+
+// #n= before the first occurrence of a shared object, #n# for the others
+fn document(
+    expr: &Expr,
+    options: &PrintOptions,
+    depth: usize,
+    circle: &Circle,
+) -> Result<Doc, ExprErr> {
+    let id = match identity(expr) {
+        Some(id) if circle.shared.contains(&id) => id,
+        _ => return object_document(expr, options, depth, circle),
+    };
+    let label = {
+        let mut labels = circle.labels.borrow_mut();
+        if let Some(label) = labels.get(&id) {
+            return Ok(Doc::Text(format!("#{}#", label)));
+        }
+        let label = labels.len() + 1;
+        labels.insert(id, label);
+        label
+    };
+    let doc = object_document(expr, options, depth, circle)?;
+    Ok(block(&format!("#{}=", label), vec![doc], "", Style::Fill))
+}
+
+// the logical blocks of an object for the pretty printer, printed on a
+// single line otherwise
+fn object_document(
+    expr: &Expr,
+    options: &PrintOptions,
+    depth: usize,
+    circle: &Circle,
+) -> Result<Doc, ExprErr> {
+    let nested = matches!(expr, Expr::Cons(_) | Expr::Array(_) | Expr::Struct(_));
+    if nested && options.level.is_some_and(|level| depth >= level) {
+        return Ok(Doc::Text("#".to_string()));
+    }
+    let printed = match expr {
+        Expr::Number(num) => print_number(*num, options),
+        Expr::Complex(re, im) => format!(
+            "#C({} {})",
+            print_number(*re, options),
+            print_number(*im, options)
+        ),
+        Expr::Char(ch) if !options.escape => ch.to_string(),
+        Expr::String(s) => print_string(&s.borrow(), options),
+        Expr::Pathname(pathname) if options.escape => {
+            format!("#P{}", print_string(&pathname.namestring(), options))
+        }
+        Expr::Symbol(symbol) => print_symbol(symbol, options),
+        Expr::Nil => symbol_name("NIL", options),
+        Expr::True => symbol_name("T", options),
+        Expr::Cons(_) => return list_document(expr, options, depth, circle),
+        Expr::Array(array) => {
+            let array = array.borrow();
+            if array.dimensions.len() == 1 {
+                let items = element_docs(array.active(), options, depth, circle)?;
+                return Ok(block("#(", items, ")", Style::Fill));
+            }
+            // elements of multidimensional arrays are not abbreviated
+            let error = RefCell::new(None);
+            let printed = array.print(&|x| {
+                print(x, options, depth + 1, circle).unwrap_or_else(|e| {
+                    error.replace(Some(e));
+                    String::new()
+                })
+            });
+            match error.into_inner() {
+                Some(error) => return Err(error),
+                None => printed,
+            }
+        }
+        Expr::Struct(structure) => {
+            let structure = structure.borrow();
+            let mut items = vec![Doc::Text(print_symbol(&structure.def.name, options))];
+            for (slot, value) in structure.def.slots.iter().zip(&structure.values) {
+                let key = Expr::Symbol(Symbol::keyword(&slot.name.name));
+                items.push(document(&key, options, depth + 1, circle)?);
+                items.push(document(value, options, depth + 1, circle)?);
+            }
+            return Ok(block("#S(", items, ")", Style::Fill));
+        }
+        Expr::Char(_) => expr.to_string(),
+        _ if options.readably => {
+            return Err(ExprErr::Cause(format!("cannot print {} readably", expr)))
+        }
+        _ => expr.to_string(),
+    };
+    Ok(Doc::Text(printed))
+}
+
+fn print(
+    expr: &Expr,
+    options: &PrintOptions,
+    depth: usize,
+    circle: &Circle,
+) -> Result<String, ExprErr> {
+    Ok(document(expr, options, depth, circle)?.flat())
+}
+
+// the printed representation of an object starting at column, laid
+// out within the right margin when printing pretty
+pub fn write_at(expr: &Expr, options: &PrintOptions, column: usize) -> Result<String, ExprErr> {
+    let mut options = options.clone();
+    if options.readably {
+        options.escape = true;
+        options.length = None;
+        options.level = None;
+    }
+    let doc = document(expr, &options, 0, &Circle::new(expr, &options))?;
+    if options.pretty {
+        Ok(doc.layout(column, options.right_margin))
+    } else {
+        Ok(doc.flat())
+    }
+}
+
+// the printed representation of an object
+pub fn write_to_string(expr: &Expr, options: &PrintOptions) -> Result<String, ExprErr> {
+    write_at(expr, options, 0)
+}
+
+// print an object at the current column of stream
+fn print_to(stream: Option<&Expr>, expr: &Expr, options: &PrintOptions) -> Result<(), ExprErr> {
+    let printed = write_at(expr, options, column_of(stream)?)?;
+    write_to(stream, &printed)
+}
+
+// printed with the printer variables, with or without escaping like
+// prin1 and princ
+pub fn print_object(expr: &Expr, escape: bool) -> Result<String, ExprErr> {
+    write_to_string(expr, &PrintOptions::current()?.with_escape(escape))
+}
+
+// the object and optional stream of prin1, princ and print
+fn object_and_stream<'a>(
+    name: &str,
+    args: &'a [Expr],
+) -> Result<(&'a Expr, Option<&'a Expr>), ExprErr> {
+    match args {
+        [object] => Ok((object, None)),
+        [object, stream] => Ok((object, Some(stream))),
+        _ => Err(ExprErr::Cause(format!(
+            "{} expects an object and a stream",
+            name
+        ))),
+    }
+}
+
+pub fn prin1(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (object, stream) = object_and_stream("prin1", args)?;
+    print_to(stream, object, &PrintOptions::current()?)?;
+    Ok(object.clone())
+}
+
+pub fn princ(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (object, stream) = object_and_stream("princ", args)?;
+    print_to(stream, object, &PrintOptions::current()?.with_escape(false))?;
+    Ok(object.clone())
+}
+
+// (print object [stream]) is prin1 on a new line followed by a space
+pub fn print_fn(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (object, stream) = object_and_stream("print", args)?;
+    write_to(stream, "\n")?;
+    print_to(stream, object, &PrintOptions::current()?)?;
+    write_to(stream, " ")?;
+    Ok(object.clone())
+}
+
+// (pprint object [stream]) is print without the space, printing pretty,
+// and returns no values
+pub fn pprint(evaluator: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (object, stream) = object_and_stream("pprint", args)?;
+    let mut options = PrintOptions::current()?;
+    options.pretty = true;
+    write_to(stream, "\n")?;
+    print_to(stream, object, &options)?;
+    Ok(evaluator.multiple_values(vec![]))
+}
+
+pub fn prin1_to_string(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (object, _) = object_and_stream("prin1-to-string", args)?;
+    Ok(Expr::string(print_object(object, true)?))
+}
+
+pub fn princ_to_string(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (object, _) = object_and_stream("princ-to-string", args)?;
+    Ok(Expr::string(print_object(object, false)?))
+}
+
+const WRITE_KEYS: [&str; 10] = [
+    "ESCAPE",
+    "READABLY",
+    "BASE",
+    "RADIX",
+    "CASE",
+    "LENGTH",
+    "LEVEL",
+    "PRETTY",
+    "RIGHT-MARGIN",
+    "CIRCLE",
+];
+
+// the printer variables overridden by the keyword arguments of write
+fn write_options(options: &HashMap<String, &Expr>) -> Result<PrintOptions, ExprErr> {
+    let mut print_options = PrintOptions::current()?;
+    for name in WRITE_KEYS {
+        if let Some(value) = options.get(name) {
+            print_options.set(name, value)?;
+        }
+    }
+    Ok(print_options)
+}
+
+// (write object &key stream escape readably base radix case length level
+//   pretty right-margin circle)
+pub fn write(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (object, options) = args
+        .split_first()
+        .ok_or(ExprErr::Cause("write expects an object".to_string()))?;
+    let mut allowed = WRITE_KEYS.to_vec();
+    allowed.push("STREAM");
+    let options = keyword_args("write", options, &allowed)?;
+    print_to(
+        options.get("STREAM").copied(),
+        object,
+        &write_options(&options)?,
+    )?;
+    Ok(object.clone())
+}
+
+// (write-to-string object &key escape readably base radix case length
+//   level pretty right-margin circle)
+pub fn write_to_string_fn(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (object, options) = args.split_first().ok_or(ExprErr::Cause(
+        "write-to-string expects an object".to_string(),
+    ))?;
+    let options = keyword_args("write-to-string", options, &WRITE_KEYS)?;
+    Ok(Expr::string(write_to_string(
+        object,
+        &write_options(&options)?,
+    )?))
+}
@@ -0,0 +1,452 @@
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{keyword_args, Evaluator, ExprEnv, Shadowed};
+use crate::lexer::Lexer;
+use crate::number::Number;
+use crate::package;
+use crate::parser::Parser;
+use crate::pathname::{file_error, file_name, merged_pathname, pathname_arg, truename, Pathname};
+use crate::printer::print_object;
+use crate::stream::{input_stream, write_to, ReadArgs, Stream, StreamRef};
+use crate::string::string_arg;
+use crate::symbol::Symbol;
+use std::fs;
+use std::path::Path;
+
+// the features of this implementation for #+ and #-
+fn features() -> Expr {
+    let mut features = vec!["RISP", "COMMON-LISP"];
+    if cfg!(unix) {
+        features.push("UNIX");
+    }
+    if cfg!(target_os = "linux") {
+        features.push("LINUX");
+    }
+    if cfg!(target_os = "macos") {
+        features.push("DARWIN");
+    }
+    if cfg!(windows) {
+        features.push("WIN32");
+    }
+    Expr::list(
+        features
+            .into_iter()
+            .map(|feature| Expr::Symbol(Symbol::keyword(feature)))
+            .collect(),
+    )
+}
+
+// define the variables of the reader and of load
+pub fn init_reader_variables() {
+    let cl = package::common_lisp();
+    let variables = [
+        ("*FEATURES*", features()),
+        ("*READ-EVAL*", Expr::True),
+        ("*LOAD-PATHNAME*", Expr::Nil),
+        ("*LOAD-TRUENAME*", Expr::Nil),
+        ("*LOAD-VERBOSE*", Expr::Nil),
+        ("*LOAD-PRINT*", Expr::Nil),
+    ];
+    for (name, value) in variables {
+        let symbol = cl.intern(name);
+        symbol.proclaim_special();
+        symbol.set_value(Some(value));
+        _ = cl.export(&symbol);
+    }
+}
+
+// the end of an object read from chars up to position: the whitespace
+// ending a token is read with it unless preserving whitespace
+fn end_of_object(chars: &[char], position: usize, preserve_whitespace: bool) -> usize {
+    match (
+        position.checked_sub(1).map(|i| chars[i]),
+        chars.get(position),
+    ) {
+        (Some(last), Some(next)) if !preserve_whitespace && next.is_whitespace() => {
+            if last == ')' || last == '"' {
+                position
+            } else {
+                position + 1
+            }
+        }
+        _ => position,
+    }
+}
+
+// Tracks whether the text read so far can end a form: outside strings,
+// |names| and comments, with every parenthesis closed. Scanning each
+// character once keeps reading a long form linear.
+#[derive(Default)]
+struct Balance {
+    depth: usize,
+    string: bool,
+    bar: bool,
+    // the next character is escaped
+    escape: bool,
+    line_comment: bool,
+    block_comments: usize,
+    last: char,
+}
+
+impl Balance {
+    fn scan(&mut self, ch: char) {
+        let last = std::mem::replace(&mut self.last, ch);
+        if self.escape {
+            self.escape = false;
+            self.last = '\0';
+        } else if self.line_comment {
+            self.line_comment = ch != '\n';
+        } else if self.block_comments > 0 {
+            match (last, ch) {
+                ('|', '#') => self.block_comments -= 1,
+                ('#', '|') => self.block_comments += 1,
+                _ => return,
+            }
+            self.last = '\0';
+        } else if self.string || self.bar {
+            match ch {
+                '\\' => self.escape = true,
+                '"' if self.string => self.string = false,
+                '|' if self.bar => self.bar = false,
+                _ => {}
+            }
+        } else {
+            match (last, ch) {
+                ('#', '|') => {
+                    self.block_comments = 1;
+                    self.last = '\0';
+                }
+                // #\c and \c
+                (_, '\\') => self.escape = true,
+                (_, '"') => self.string = true,
+                (_, '|') => self.bar = true,
+                (_, ';') => self.line_comment = true,
+                (_, '(') => self.depth += 1,
+                (_, ')') => self.depth = self.depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+
+    fn is_balanced(&self) -> bool {
+        self.depth == 0 && !self.string && !self.bar && !self.escape && self.block_comments == 0
+    }
+}
+
+// the next form from stream, or None at end of file; the stream is read a
+// line at a time until the form is complete, and what follows the form is
+// put back. The text is parsed only when it is balanced at the end of a
+// line, so that #. forms inside a form are evaluated once.
+fn read_form(evaluator: &mut Evaluator, stream: &StreamRef) -> Result<Option<Expr>, ExprErr> {
+    let mut text = String::new();
+    let mut balance = Balance::default();
+    loop {
+        let mut eof = true;
+        {
+            let mut stream = stream.borrow_mut();
+            while let Some(ch) = stream.read_char()? {
+                text.push(ch);
+                balance.scan(ch);
+                if ch == '\n' {
+                    eof = false;
+                    break;
+                }
+            }
+        }
+        if !eof && !balance.is_balanced() {
+            continue;
+        }
+        let mut parser = Parser::new(Lexer::new(text.clone())).with_evaluator(evaluator);
+        let result = parser.read();
+        let position = parser.position();
+        let chars = text.chars().collect::<Vec<char>>();
+        match result {
+            Ok(Some(form)) => {
+                let end = end_of_object(&chars, position, false);
+                let rest = chars[end..].iter().collect::<String>();
+                stream.borrow_mut().unread_str(&rest);
+                return Ok(Some(form));
+            }
+            Ok(None) if eof => return Ok(None),
+            // an error before the end of the text is not for want of input
+            Err(err) if eof || position < chars.len() => return Err(err),
+            _ => {}
+        }
+    }
+}
+
+// (read [stream [eof-error-p [eof-value [recursive-p]]]])
+pub fn read(evaluator: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let read = ReadArgs::parse("read", args)?;
+    match read_form(evaluator, &read.stream)? {
+        Some(form) => Ok(form),
+        None => read.eof(),
+    }
+}
+
+// (read-from-string string [eof-error-p [eof-value]] &key start end
+// preserve-whitespace) is the object read and the index after it
+pub fn read_from_string(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let Some(string) = args.first() else {
+        return Err(ExprErr::Cause(
+            "read-from-string expects a string".to_string(),
+        ));
+    };
+    let options = keyword_args(
+        "read-from-string",
+        args.get(3..).unwrap_or_default(),
+        &["START", "END", "PRESERVE-WHITESPACE"],
+    )?;
+    let chars = string_arg(string)?.chars().collect::<Vec<char>>();
+    let (start, end) = crate::seq::bounds(&options, "START", "END", chars.len())?;
+    let preserve_whitespace = options
+        .get("PRESERVE-WHITESPACE")
+        .is_some_and(|preserve| !preserve.is_nil());
+    let chars = &chars[start..end];
+    let mut parser = Parser::new(Lexer::new(chars.iter().collect())).with_evaluator(evaluator);
+    let read = parser.read()?;
+    let position = parser.position();
+    let object = match read {
+        Some(object) => object,
+        None if matches!(args.get(1), Some(Expr::Nil)) => args.get(2).cloned().unwrap_or(Expr::Nil),
+        None => {
+            return Err(ExprErr::Cause(
+                "end of file on #<STRING-INPUT-STREAM>".to_string(),
+            ))
+        }
+    };
+    let position = start + end_of_object(chars, position, preserve_whitespace);
+    Ok(evaluator.multiple_values(vec![object, Expr::Number(Number::Integer(position as i64))]))
+}
+
+// (eval form) in the global environment
+pub fn eval(evaluator: &mut Evaluator, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [form] => {
+            let primary = evaluator.eval(form, &mut env.global())?;
+            let values = evaluator.take_values(primary);
+            Ok(evaluator.multiple_values(values))
+        }
+        _ => Err(ExprErr::Cause("eval expects a form".to_string())),
+    }
+}
+
+// the source file designated by filespec, with type "lisp" when it has
+// no type and does not exist without one
+fn source_pathname(filespec: &Expr) -> Result<Pathname, ExprErr> {
+    let pathname = merged_pathname(filespec)?;
+    let name = file_name(&Expr::Pathname(pathname.clone()))?;
+    if pathname.typ.is_some() || Path::new(&name).exists() {
+        return Ok(pathname);
+    }
+    let source = Pathname {
+        typ: Some("lisp".to_string()),
+        ..pathname.clone()
+    };
+    let exists = Path::new(&source.namestring()).exists();
+    Ok(if exists { source } else { pathname })
+}
+
+fn option_set(value: Option<&&Expr>, default: &str) -> bool {
+    match value {
+        Some(value) => !value.is_nil(),
+        None => Symbol::cl(default)
+            .value()
+            .is_some_and(|value| !value.is_nil()),
+    }
+}
+
+impl Evaluator {
+    // evaluate the forms read by next at top level, printing their values
+    // when print
+    fn load_forms(
+        &mut self,
+        next: &mut dyn FnMut(&mut Evaluator) -> Result<Option<Expr>, ExprErr>,
+        print: bool,
+        env: &mut ExprEnv,
+    ) -> Result<(), ExprErr> {
+        while let Some(form) = next(self)? {
+            let value = self.eval(&form, env)?;
+            if print {
+                write_to(None, &format!("{}\n", print_object(&value, true)?))?;
+            }
+        }
+        Ok(())
+    }
+
+    // load the file at pathname, with *load-pathname* and *load-truename*
+    // bound to it
+    fn load_file(
+        &mut self,
+        pathname: Pathname,
+        verbose: bool,
+        print: bool,
+        env: &mut ExprEnv,
+    ) -> Result<(), ExprErr> {
+        let name = file_name(&Expr::Pathname(pathname.clone()))?;
+        let text = fs::read_to_string(&name).map_err(|err| file_error("load", &name, err))?;
+        if verbose {
+            write_to(
+                None,
+                &format!(
+                    "; loading {}\n",
+                    print_object(&Expr::Pathname(pathname.clone()), true)?
+                ),
+            )?;
+        }
+        let truename = truename(Path::new(&name)).map_or(Expr::Nil, Expr::Pathname);
+        let mut shadowed = Shadowed::new();
+        let bindings = [
+            ("*LOAD-PATHNAME*", Expr::Pathname(pathname)),
+            ("*LOAD-TRUENAME*", truename),
+            ("*PACKAGE*", Expr::Package(package::current())),
+        ];
+        for (var, value) in bindings {
+            self.bind(env, &Symbol::cl(var), value, true, &mut shadowed)?;
+        }
+        let mut lexer = Some(Lexer::new(text));
+        let mut next = |evaluator: &mut Evaluator| {
+            let Some(source) = lexer.take() else {
+                return Ok(None);
+            };
+            let mut parser = Parser::new(source).with_evaluator(evaluator);
+            let form = parser.read();
+            lexer = Some(parser.into_lexer());
+            form
+        };
+        let result = self.load_forms(&mut next, print, env);
+        self.unbind(shadowed);
+        result
+    }
+}
+
+// (load filespec &key verbose print if-does-not-exist external-format)
+// evaluates the forms of a file, or of an input stream; a relative file
+// name is taken relative to the file being loaded
+pub fn load(evaluator: &mut Evaluator, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let Some((filespec, options)) = args.split_first() else {
+        return Err(ExprErr::Cause("load expects a pathname".to_string()));
+    };
+    let options = keyword_args(
+        "load",
+        options,
+        &["VERBOSE", "PRINT", "IF-DOES-NOT-EXIST", "EXTERNAL-FORMAT"],
+    )?;
+    let verbose = option_set(options.get("VERBOSE"), "*LOAD-VERBOSE*");
+    let print = option_set(options.get("PRINT"), "*LOAD-PRINT*");
+    let mut env = env.global();
+    if let Expr::Stream(stream) = filespec {
+        if !matches!(&*stream.borrow(), Stream::File(_)) {
+            let stream = input_stream(Some(filespec))?;
+            let mut next = |evaluator: &mut Evaluator| read_form(evaluator, &stream);
+            evaluator.load_forms(&mut next, print, &mut env)?;
+            return Ok(Expr::True);
+        }
+    }
+    let mut pathname = pathname_arg(filespec)?;
+    if let Some(Expr::Pathname(loading)) = Symbol::cl("*LOAD-PATHNAME*").value() {
+        pathname = pathname.merge(&loading.directory_pathname());
+    }
+    let pathname = source_pathname(&Expr::Pathname(pathname))?;
+    let exists = Path::new(&file_name(&Expr::Pathname(pathname.clone()))?).exists();
+    if !exists
+        && options
+            .get("IF-DOES-NOT-EXIST")
+            .is_some_and(|value| value.is_nil())
+    {
+        return Ok(Expr::Nil);
+    }
+    evaluator.load_file(pathname, verbose, print, &mut env)?;
+    Ok(Expr::True)
+}
+
+// (compile name [definition]) defines name as the function of definition,
+// which is already as compiled as it gets, and returns name or the
+// function with no warnings and no failure
+pub fn compile(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (name, definition) = match args {
+        [name] => (name, None),
+        [name, definition] => (name, Some(definition)),
+        _ => {
+            return Err(ExprErr::Cause(
+                "compile expects a name and a definition".to_string(),
+            ))
+        }
+    };
+    let function = match (name, definition) {
+        (_, Some(function @ (Expr::Func(_) | Expr::Lambda(_) | Expr::Generic(_)))) => {
+            function.clone()
+        }
+        (_, Some(lambda)) => evaluator.eval(lambda, &mut env.global())?,
+        (Expr::Symbol(symbol), None) => symbol.function().ok_or(ExprErr::Cause(format!(
+            "undefined function: {}",
+            symbol.name
+        )))?,
+        _ => return Err(ExprErr::Cause(format!("{} is not function name", name))),
+    };
+    let result = match name {
+        Expr::Nil => function,
+        Expr::Symbol(symbol) => {
+            if definition.is_some() {
+                symbol.set_function(Some(function));
+            }
+            name.clone()
+        }
+        _ => return Err(ExprErr::Cause(format!("{} is not function name", name))),
+    };
+    Ok(evaluator.multiple_values(vec![result, Expr::Nil, Expr::Nil]))
+}
+
+// (compile-file input-file &key output-file verbose print external-format)
+// checks that the forms of the source file can be read, and writes them to
+// the output file, of type "fasl" by default, for load; it returns the
+// truename of the output with no warnings and no failure
+pub fn compile_file(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let Some((input, options)) = args.split_first() else {
+        return Err(ExprErr::Cause(
+            "compile-file expects a pathname".to_string(),
+        ));
+    };
+    let options = keyword_args(
+        "compile-file",
+        options,
+        &["OUTPUT-FILE", "VERBOSE", "PRINT", "EXTERNAL-FORMAT"],
+    )?;
+    let input = source_pathname(input)?;
+    let input_name = file_name(&Expr::Pathname(input.clone()))?;
+    let text =
+        fs::read_to_string(&input_name).map_err(|err| file_error("compile", &input_name, err))?;
+    if option_set(options.get("VERBOSE"), "*COMPILE-VERBOSE*") {
+        write_to(
+            None,
+            &format!(
+                "; compiling {}\n",
+                print_object(&Expr::Pathname(input.clone()), true)?
+            ),
+        )?;
+    }
+    let mut parser = Parser::new(Lexer::new(text.clone())).with_evaluator(evaluator);
+    while parser.read()?.is_some() {}
+    let output = match options.get("OUTPUT-FILE") {
+        Some(output) => pathname_arg(output)?.merge(&input),
+        None => Pathname {
+            typ: Some("fasl".to_string()),
+            ..input
+        },
+    };
+    let output_name = file_name(&Expr::Pathname(output))?;
+    fs::write(&output_name, text).map_err(|err| file_error("write", &output_name, err))?;
+    let truename = truename(Path::new(&output_name)).map_or(Expr::Nil, Expr::Pathname);
+    Ok(evaluator.multiple_values(vec![truename, Expr::Nil, Expr::Nil]))
+}
@@ -0,0 +1,910 @@
+use crate::array::{Array, ArrayRef};
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{keyword_args, Evaluator, ExprEnv};
+use crate::hash::eql;
+use crate::list::{self, index_arg, ConsRef};
+use crate::math::integer_expr;
+use crate::number::Number;
+use crate::symbol::symbol_of;
+use std::collections::HashMap;
+
+// what a sequence result is made of
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    List,
+    Vector,
+    String,
+}
+
+// the kind and elements of a list, vector or string; vectors give the
+// elements before their fill pointer
+pub fn elements(expr: &Expr) -> Result<(Kind, Vec<Expr>), ExprErr> {
+    match expr {
+        Expr::Nil | Expr::Cons(_) => Ok((Kind::List, expr.to_vec()?)),
+        Expr::String(s) => Ok((Kind::String, s.borrow().chars().map(Expr::Char).collect())),
+        Expr::Array(array) if array.borrow().dimensions.len() == 1 => {
+            Ok((Kind::Vector, array.borrow().active().to_vec()))
+        }
+        _ => Err(ExprErr::Cause(format!("{} is not sequence", expr))),
+    }
+}
+
+pub fn make_sequence(kind: Kind, elements: Vec<Expr>) -> Result<Expr, ExprErr> {
+    match kind {
+        Kind::List => Ok(Expr::list(elements)),
+        Kind::Vector => Ok(Array::vector(elements)),
+        Kind::String => chars_of(&elements).map(Expr::string),
+    }
+}
+
+fn chars_of(elements: &[Expr]) -> Result<String, ExprErr> {
+    elements
+        .iter()
+        .map(|element| match element {
+            Expr::Char(ch) => Ok(*ch),
+            _ => Err(ExprErr::Cause(format!("{} is not character", element))),
+        })
+        .collect()
+}
+
+// a vector whose elements are modified in place by destructive functions
+fn vector_of(expr: &Expr) -> Option<ArrayRef> {
+    match expr {
+        Expr::Array(array) if array.borrow().dimensions.len() == 1 => Some(array.clone()),
+        _ => None,
+    }
+}
+
+// store `elements` as the contents of `sequence`: into the cells of a
+// list, ending it after the last one stored, or into a vector or string
+fn update(sequence: &Expr, kind: Kind, elements: Vec<Expr>) -> Result<Expr, ExprErr> {
+    if let Expr::Cons(_) = sequence {
+        let cells = list::cells(sequence)
+            .take(elements.len())
+            .collect::<Vec<ConsRef>>();
+        let Some(last) = cells.last() else {
+            return Ok(Expr::Nil);
+        };
+        last.borrow_mut().cdr = Expr::Nil;
+        for (cell, element) in cells.iter().zip(elements) {
+            cell.borrow_mut().car = element;
+        }
+        return Ok(sequence.clone());
+    }
+    if let Expr::String(string) = sequence {
+        let stored = chars_of(&elements)?;
+        let mut string = string.borrow_mut();
+        let rest = string.chars().skip(elements.len()).collect::<String>();
+        *string = stored + &rest;
+        return Ok(sequence.clone());
+    }
+    match vector_of(sequence) {
+        Some(vector) => {
+            let mut vector = vector.borrow_mut();
+            let len = elements.len();
+            vector.elements[..len].clone_from_slice(&elements);
+            Ok(sequence.clone())
+        }
+        None => make_sequence(kind, elements),
+    }
+}
+
+// sequence type of concatenate, map and coerce; NIL for no result
+pub fn result_kind(typ: &Expr) -> Result<Option<Kind>, ExprErr> {
+    let name = match typ {
+        Expr::Nil => return Ok(None),
+        Expr::Cons(_) => symbol_of(&typ.car()?)?,
+        _ => symbol_of(typ)?,
+    };
+    match name.name.as_str() {
+        "LIST" | "CONS" => Ok(Some(Kind::List)),
+        "VECTOR" | "SIMPLE-VECTOR" | "ARRAY" | "SIMPLE-ARRAY" => Ok(Some(Kind::Vector)),
+        "STRING" | "SIMPLE-STRING" | "BASE-STRING" => Ok(Some(Kind::String)),
+        _ => Err(ExprErr::Cause(format!("invalid sequence type: {}", typ))),
+    }
+}
+
+// :start and :end (or :start1 and :end1, ...) within a sequence of `len`
+pub fn bounds(
+    options: &HashMap<String, &Expr>,
+    start: &str,
+    end: &str,
+    len: usize,
+) -> Result<(usize, usize), ExprErr> {
+    let start = match options.get(start) {
+        Some(start) => index_arg(start)?,
+        None => 0,
+    };
+    let end = match options.get(end) {
+        None | Some(Expr::Nil) => len,
+        Some(end) => index_arg(end)?,
+    };
+    if start > end || end > len {
+        return Err(ExprErr::Cause(format!(
+            "bounding indices {} and {} are bad for a sequence of length {}",
+            start, end, len
+        )));
+    }
+    Ok((start, end))
+}
+
+fn is_set(options: &HashMap<String, &Expr>, name: &str) -> bool {
+    options.get(name).is_some_and(|value| !value.is_nil())
+}
+
+// :count nil is no limit; a negative count is zero
+fn count_limit(options: &HashMap<String, &Expr>) -> Result<Option<usize>, ExprErr> {
+    match options.get("COUNT") {
+        None | Some(Expr::Nil) => Ok(None),
+        Some(Expr::Number(Number::Integer(n))) if *n < 0 => Ok(Some(0)),
+        Some(count) => Ok(Some(index_arg(count)?)),
+    }
+}
+
+// :key and :test or :test-not of a sequence function. With an item the
+// test compares it with each key; otherwise it is a predicate of the key.
+struct Matcher {
+    item: Option<Expr>,
+    test: Option<Expr>,
+    negate: bool,
+    key: Option<Expr>,
+}
+
+impl Matcher {
+    fn key(&self, evaluator: &mut Evaluator, x: &Expr, env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        match &self.key {
+            Some(key) => evaluator.apply(key, std::slice::from_ref(x), env),
+            None => Ok(x.clone()),
+        }
+    }
+
+    fn test(
+        &self,
+        evaluator: &mut Evaluator,
+        a: &Expr,
+        b: &Expr,
+        env: &mut ExprEnv,
+    ) -> Result<bool, ExprErr> {
+        let result = match &self.test {
+            Some(test) => !evaluator
+                .apply(test, &[a.clone(), b.clone()], env)?
+                .is_nil(),
+            None => eql(a, b),
+        };
+        Ok(result != self.negate)
+    }
+
+    fn satisfies(
+        &self,
+        evaluator: &mut Evaluator,
+        element: &Expr,
+        env: &mut ExprEnv,
+    ) -> Result<bool, ExprErr> {
+        let key = self.key(evaluator, element, env)?;
+        match (&self.item, &self.test) {
+            (Some(item), _) => self.test(evaluator, item, &key, env),
+            (None, Some(predicate)) => {
+                let result = !evaluator.apply(predicate, &[key], env)?.is_nil();
+                Ok(result != self.negate)
+            }
+            (None, None) => unreachable!("matchers without an item have a predicate"),
+        }
+    }
+
+    // indices in [start, end) of the elements satisfying the matcher, at
+    // most `limit` of them, taken from the end if `from_end`
+    fn indices(
+        &self,
+        evaluator: &mut Evaluator,
+        elements: &[Expr],
+        (start, end): (usize, usize),
+        from_end: bool,
+        limit: Option<usize>,
+        env: &mut ExprEnv,
+    ) -> Result<Vec<usize>, ExprErr> {
+        let range: Box<dyn Iterator<Item = usize>> = if from_end {
+            Box::new((start..end).rev())
+        } else {
+            Box::new(start..end)
+        };
+        let mut indices = vec![];
+        for i in range {
+            if limit.is_some_and(|limit| indices.len() >= limit) {
+                break;
+            }
+            if self.satisfies(evaluator, &elements[i], env)? {
+                indices.push(i);
+            }
+        }
+        Ok(indices)
+    }
+}
+
+impl Evaluator {
+    // :test or :test-not and :key, against `item` or with a predicate
+    fn matcher(
+        &mut self,
+        item: Option<&Expr>,
+        predicate: Option<(&Expr, bool)>,
+        options: &HashMap<String, &Expr>,
+    ) -> Result<Matcher, ExprErr> {
+        let key = match options.get("KEY") {
+            None | Some(Expr::Nil) => None,
+            Some(key) => Some(self.function_designator(key)?),
+        };
+        let (test, negate) = match (predicate, options.get("TEST"), options.get("TEST-NOT")) {
+            (Some((predicate, negate)), _, _) => {
+                (Some(self.function_designator(predicate)?), negate)
+            }
+            (None, Some(_), Some(_)) => {
+                return Err(ExprErr::Cause(
+                    "cannot supply both :test and :test-not".to_string(),
+                ))
+            }
+            (None, Some(test), None) => (Some(self.function_designator(test)?), false),
+            (None, None, Some(test)) => (Some(self.function_designator(test)?), true),
+            (None, None, None) => (None, false),
+        };
+        Ok(Matcher {
+            item: item.cloned(),
+            test,
+            negate,
+            key,
+        })
+    }
+
+    // stable merge sort with a predicate which may fail
+    fn merge_sort(
+        &mut self,
+        mut items: Vec<(Expr, Expr)>,
+        predicate: &Expr,
+        env: &mut ExprEnv,
+    ) -> Result<Vec<(Expr, Expr)>, ExprErr> {
+        if items.len() <= 1 {
+            return Ok(items);
+        }
+        let right = items.split_off(items.len() / 2);
+        let mut left = self
+            .merge_sort(items, predicate, env)?
+            .into_iter()
+            .peekable();
+        let mut right = self
+            .merge_sort(right, predicate, env)?
+            .into_iter()
+            .peekable();
+        let mut merged = vec![];
+        while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+            // the right element goes first only if it is strictly less
+            let less = self.apply(predicate, &[b.0.clone(), a.0.clone()], env)?;
+            let next = if less.is_nil() {
+                left.next()
+            } else {
+                right.next()
+            };
+            merged.extend(next);
+        }
+        merged.extend(left);
+        merged.extend(right);
+        Ok(merged)
+    }
+}
+
+// what the item functions (find, position, count, remove, substitute)
+// look for: an item, or elements satisfying or not satisfying a predicate
+#[derive(Clone, Copy, PartialEq)]
+enum Search {
+    Item,
+    If,
+    IfNot,
+}
+
+// the item or predicate, sequence and keyword options of an item function
+fn search_args<'a>(
+    name: &str,
+    args: &'a [Expr],
+    search: Search,
+    counted: bool,
+) -> Result<(&'a Expr, &'a Expr, HashMap<String, &'a Expr>), ExprErr> {
+    let mut allowed = vec!["FROM-END", "START", "END", "KEY"];
+    if search == Search::Item {
+        allowed.extend(["TEST", "TEST-NOT"]);
+    }
+    if counted {
+        allowed.push("COUNT");
+    }
+    match args {
+        [item, sequence, options @ ..] => {
+            Ok((item, sequence, keyword_args(name, options, &allowed)?))
+        }
+        _ => Err(ExprErr::Cause(format!(
+            "{} expects an item and a sequence",
+            name
+        ))),
+    }
+}
+
+// the kind, elements and matching indices of an item function's sequence
+fn search_indices(
+    evaluator: &mut Evaluator,
+    search: Search,
+    item: &Expr,
+    sequence: &Expr,
+    options: &HashMap<String, &Expr>,
+    limit: Option<usize>,
+    env: &mut ExprEnv,
+) -> Result<(Kind, Vec<Expr>, Vec<usize>), ExprErr> {
+    let matcher = match search {
+        Search::Item => evaluator.matcher(Some(item), None, options)?,
+        Search::If => evaluator.matcher(None, Some((item, false)), options)?,
+        Search::IfNot => evaluator.matcher(None, Some((item, true)), options)?,
+    };
+    let (kind, elements) = elements(sequence)?;
+    let bounds = bounds(options, "START", "END", elements.len())?;
+    let from_end = is_set(options, "FROM-END");
+    let indices = matcher.indices(evaluator, &elements, bounds, from_end, limit, env)?;
+    Ok((kind, elements, indices))
+}
+
+fn find_with(
+    evaluator: &mut Evaluator,
+    name: &str,
+    args: &[Expr],
+    search: Search,
+    position: bool,
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (item, sequence, options) = search_args(name, args, search, false)?;
+    let (_, elements, indices) =
+        search_indices(evaluator, search, item, sequence, &options, Some(1), env)?;
+    Ok(match indices.first() {
+        Some(i) if position => integer_expr(*i as i64),
+        Some(i) => elements[*i].clone(),
+        None => Expr::Nil,
+    })
+}
+
+fn count_with(
+    evaluator: &mut Evaluator,
+    name: &str,
+    args: &[Expr],
+    search: Search,
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (item, sequence, options) = search_args(name, args, search, false)?;
+    let (_, _, indices) = search_indices(evaluator, search, item, sequence, &options, None, env)?;
+    Ok(integer_expr(indices.len() as i64))
+}
+
+// a destructive removal keeps the remaining elements in the cells of a
+// list
+fn remove_with(
+    evaluator: &mut Evaluator,
+    name: &str,
+    args: &[Expr],
+    search: Search,
+    destructive: bool,
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (item, sequence, options) = search_args(name, args, search, true)?;
+    let limit = count_limit(&options)?;
+    let (kind, elements, indices) =
+        search_indices(evaluator, search, item, sequence, &options, limit, env)?;
+    let kept = elements
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !indices.contains(i))
+        .map(|(_, element)| element)
+        .collect();
+    match sequence {
+        Expr::Cons(_) if destructive => update(sequence, kind, kept),
+        _ => make_sequence(kind, kept),
+    }
+}
+
+fn substitute_with(
+    evaluator: &mut Evaluator,
+    name: &str,
+    args: &[Expr],
+    search: Search,
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (new, args) = args
+        .split_first()
+        .ok_or(ExprErr::Cause(format!("{} expects a new item", name)))?;
+    let (item, sequence, options) = search_args(name, args, search, true)?;
+    let limit = count_limit(&options)?;
+    let (kind, mut elements, indices) =
+        search_indices(evaluator, search, item, sequence, &options, limit, env)?;
+    for i in indices {
+        elements[i] = new.clone();
+    }
+    make_sequence(kind, elements)
+}
+
+pub fn length(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [sequence] => Ok(integer_expr(elements(sequence)?.1.len() as i64)),
+        _ => Err(ExprErr::Cause("length expects exactly one arg".to_string())),
+    }
+}
+
+// (elt sequence index)
+pub fn elt(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [sequence, index] => {
+            let (_, elements) = elements(sequence)?;
+            let index = index_arg(index)?;
+            elements.get(index).cloned().ok_or(ExprErr::Cause(format!(
+                "index {} is out of bounds for {}",
+                index, sequence
+            )))
+        }
+        _ => Err(ExprErr::Cause("elt expects exactly two args".to_string())),
+    }
+}
+
+// (subseq sequence start [end])
+pub fn subseq(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (sequence, start, end) = match args {
+        [sequence, start] => (sequence, start, &Expr::Nil),
+        [sequence, start, end] => (sequence, start, end),
+        _ => return Err(ExprErr::Cause("subseq expects 2 or 3 args".to_string())),
+    };
+    let (kind, elements) = elements(sequence)?;
+    let options = HashMap::from([("START".to_string(), start), ("END".to_string(), end)]);
+    let (start, end) = bounds(&options, "START", "END", elements.len())?;
+    make_sequence(kind, elements[start..end].to_vec())
+}
+
+// (concatenate result-type sequence*)
+pub fn concatenate(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (typ, sequences) = args.split_first().ok_or(ExprErr::Cause(
+        "concatenate expects a result type".to_string(),
+    ))?;
+    let kind =
+        result_kind(typ)?.ok_or(ExprErr::Cause(format!("invalid sequence type: {}", typ)))?;
+    let mut result = vec![];
+    for sequence in sequences {
+        result.extend(elements(sequence)?.1);
+    }
+    make_sequence(kind, result)
+}
+
+// (map result-type function sequence+) stops at the shortest sequence
+pub fn map(evaluator: &mut Evaluator, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [typ, function, sequences @ ..] = args else {
+        return Err(ExprErr::Cause(
+            "map expects a result type and a function".to_string(),
+        ));
+    };
+    if sequences.is_empty() {
+        return Err(ExprErr::Cause("map expects a sequence".to_string()));
+    }
+    let kind = result_kind(typ)?;
+    let function = evaluator.function_designator(function)?;
+    let sequences = sequences
+        .iter()
+        .map(|sequence| Ok(elements(sequence)?.1))
+        .collect::<Result<Vec<Vec<Expr>>, ExprErr>>()?;
+    let len = sequences.iter().map(Vec::len).min().unwrap_or(0);
+
+    let mut result = vec![];
+    for i in 0..len {
+        let args = sequences
+            .iter()
+            .map(|sequence| sequence[i].clone())
+            .collect::<Vec<Expr>>();
+        result.push(evaluator.apply(&function, &args, env)?);
+    }
+    match kind {
+        Some(kind) => make_sequence(kind, result),
+        None => Ok(Expr::Nil),
+    }
+}
+
+// (reduce function sequence &key key from-end start end initial-value)
+pub fn reduce(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let [function, sequence, options @ ..] = args else {
+        return Err(ExprErr::Cause(
+            "reduce expects a function and a sequence".to_string(),
+        ));
+    };
+    let options = keyword_args(
+        "reduce",
+        options,
+        &["KEY", "FROM-END", "START", "END", "INITIAL-VALUE"],
+    )?;
+    let function = evaluator.function_designator(function)?;
+    let matcher = evaluator.matcher(None, None, &options)?;
+    let (_, elements) = elements(sequence)?;
+    let (start, end) = bounds(&options, "START", "END", elements.len())?;
+    let from_end = is_set(&options, "FROM-END");
+
+    let mut values = vec![];
+    for element in &elements[start..end] {
+        values.push(matcher.key(evaluator, element, env)?);
+    }
+    if from_end {
+        values.reverse();
+    }
+    let mut values = values.into_iter();
+    let mut acc = match options.get("INITIAL-VALUE") {
+        Some(initial) => (*initial).clone(),
+        None => match values.next() {
+            Some(first) => first,
+            None => return evaluator.apply(&function, &[], env),
+        },
+    };
+    for value in values {
+        let args = if from_end { [value, acc] } else { [acc, value] };
+        acc = evaluator.apply(&function, &args, env)?;
+    }
+    Ok(acc)
+}
+
+pub fn find(evaluator: &mut Evaluator, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    find_with(evaluator, "find", args, Search::Item, false, env)
+}
+
+pub fn find_if(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    find_with(evaluator, "find-if", args, Search::If, false, env)
+}
+
+pub fn find_if_not(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    find_with(evaluator, "find-if-not", args, Search::IfNot, false, env)
+}
+
+pub fn position(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    find_with(evaluator, "position", args, Search::Item, true, env)
+}
+
+pub fn position_if(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    find_with(evaluator, "position-if", args, Search::If, true, env)
+}
+
+pub fn position_if_not(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    find_with(evaluator, "position-if-not", args, Search::IfNot, true, env)
+}
+
+pub fn count(evaluator: &mut Evaluator, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    count_with(evaluator, "count", args, Search::Item, env)
+}
+
+pub fn count_if(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    count_with(evaluator, "count-if", args, Search::If, env)
+}
+
+pub fn count_if_not(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    count_with(evaluator, "count-if-not", args, Search::IfNot, env)
+}
+
+// remove and delete return a new sequence; delete may not modify its
+// argument, as lists and strings are values
+pub fn remove(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    remove_with(evaluator, "remove", args, Search::Item, false, env)
+}
+
+pub fn remove_if(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    remove_with(evaluator, "remove-if", args, Search::If, false, env)
+}
+
+pub fn remove_if_not(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    remove_with(evaluator, "remove-if-not", args, Search::IfNot, false, env)
+}
+
+pub fn delete(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    remove_with(evaluator, "delete", args, Search::Item, true, env)
+}
+
+pub fn delete_if(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    remove_with(evaluator, "delete-if", args, Search::If, true, env)
+}
+
+pub fn substitute(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    substitute_with(evaluator, "substitute", args, Search::Item, env)
+}
+
+pub fn substitute_if(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    substitute_with(evaluator, "substitute-if", args, Search::If, env)
+}
+
+// two sequences compared element by element with :test and :key
+struct Comparison {
+    matcher: Matcher,
+    a: Vec<Expr>,
+    b: Vec<Expr>,
+    a_bounds: (usize, usize),
+    b_bounds: (usize, usize),
+    from_end: bool,
+}
+
+fn comparison(evaluator: &mut Evaluator, name: &str, args: &[Expr]) -> Result<Comparison, ExprErr> {
+    let [a, b, options @ ..] = args else {
+        return Err(ExprErr::Cause(format!("{} expects two sequences", name)));
+    };
+    let options = keyword_args(
+        name,
+        options,
+        &[
+            "FROM-END", "TEST", "TEST-NOT", "KEY", "START1", "END1", "START2", "END2",
+        ],
+    )?;
+    let matcher = evaluator.matcher(None, None, &options)?;
+    let (_, a) = elements(a)?;
+    let (_, b) = elements(b)?;
+    let a_bounds = bounds(&options, "START1", "END1", a.len())?;
+    let b_bounds = bounds(&options, "START2", "END2", b.len())?;
+    Ok(Comparison {
+        matcher,
+        a,
+        b,
+        a_bounds,
+        b_bounds,
+        from_end: is_set(&options, "FROM-END"),
+    })
+}
+
+impl Comparison {
+    fn same(
+        &self,
+        evaluator: &mut Evaluator,
+        i: usize,
+        j: usize,
+        env: &mut ExprEnv,
+    ) -> Result<bool, ExprErr> {
+        let a = self.matcher.key(evaluator, &self.a[i], env)?;
+        let b = self.matcher.key(evaluator, &self.b[j], env)?;
+        self.matcher.test(evaluator, &a, &b, env)
+    }
+}
+
+// (search sequence1 sequence2 ...) is the index in sequence2 where
+// sequence1 occurs
+pub fn search(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let comparison = comparison(evaluator, "search", args)?;
+    let (start1, end1) = comparison.a_bounds;
+    let (start2, end2) = comparison.b_bounds;
+    let len = end1 - start1;
+    if len > end2 - start2 {
+        return Ok(Expr::Nil);
+    }
+    let candidates: Box<dyn Iterator<Item = usize>> = if comparison.from_end {
+        Box::new((start2..=end2 - len).rev())
+    } else {
+        Box::new(start2..=end2 - len)
+    };
+    'candidates: for j in candidates {
+        for i in 0..len {
+            if !comparison.same(evaluator, start1 + i, j + i, env)? {
+                continue 'candidates;
+            }
+        }
+        return Ok(integer_expr(j as i64));
+    }
+    Ok(Expr::Nil)
+}
+
+// (mismatch sequence1 sequence2 ...) is the index in sequence1 where they
+// first differ, or NIL; from the end it is one past the last difference
+pub fn mismatch(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let comparison = comparison(evaluator, "mismatch", args)?;
+    let (start1, end1) = comparison.a_bounds;
+    let (start2, end2) = comparison.b_bounds;
+    let common = (end1 - start1).min(end2 - start2);
+    let same_length = end1 - start1 == end2 - start2;
+    for k in 0..common {
+        let (i, j) = if comparison.from_end {
+            (end1 - 1 - k, end2 - 1 - k)
+        } else {
+            (start1 + k, start2 + k)
+        };
+        if !comparison.same(evaluator, i, j, env)? {
+            let index = if comparison.from_end { i + 1 } else { i };
+            return Ok(integer_expr(index as i64));
+        }
+    }
+    if same_length {
+        return Ok(Expr::Nil);
+    }
+    let index = if comparison.from_end {
+        end1 - common
+    } else {
+        start1 + common
+    };
+    Ok(integer_expr(index as i64))
+}
+
+// (sort sequence predicate &key key) is stable, like stable-sort; vectors
+// are sorted in place
+pub fn sort(evaluator: &mut Evaluator, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [sequence, predicate, options @ ..] = args else {
+        return Err(ExprErr::Cause(
+            "sort expects a sequence and a predicate".to_string(),
+        ));
+    };
+    let options = keyword_args("sort", options, &["KEY"])?;
+    let predicate = evaluator.function_designator(predicate)?;
+    let matcher = evaluator.matcher(None, None, &options)?;
+    let (kind, elements) = elements(sequence)?;
+    let mut items = vec![];
+    for element in elements {
+        items.push((matcher.key(evaluator, &element, env)?, element));
+    }
+    let sorted = evaluator.merge_sort(items, &predicate, env)?;
+    update(sequence, kind, sorted.into_iter().map(|(_, x)| x).collect())
+}
+
+pub fn reverse(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [sequence] => {
+            let (kind, mut elements) = elements(sequence)?;
+            elements.reverse();
+            make_sequence(kind, elements)
+        }
+        _ => Err(ExprErr::Cause(
+            "reverse expects exactly one arg".to_string(),
+        )),
+    }
+}
+
+// (nreverse sequence) reverses a list or vector in place
+pub fn nreverse(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [sequence] => {
+            let (kind, mut elements) = elements(sequence)?;
+            elements.reverse();
+            update(sequence, kind, elements)
+        }
+        _ => Err(ExprErr::Cause(
+            "nreverse expects exactly one arg".to_string(),
+        )),
+    }
+}
+
+// (fill sequence item &key start end)
+pub fn fill(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [sequence, item, options @ ..] = args else {
+        return Err(ExprErr::Cause(
+            "fill expects a sequence and an item".to_string(),
+        ));
+    };
+    let options = keyword_args("fill", options, &["START", "END"])?;
+    let (kind, mut elements) = elements(sequence)?;
+    let (start, end) = bounds(&options, "START", "END", elements.len())?;
+    for element in &mut elements[start..end] {
+        *element = item.clone();
+    }
+    update(sequence, kind, elements)
+}
+
+// (replace sequence1 sequence2 &key start1 end1 start2 end2) copies the
+// elements of sequence2 into sequence1
+pub fn replace(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [target, source, options @ ..] = args else {
+        return Err(ExprErr::Cause("replace expects two sequences".to_string()));
+    };
+    let options = keyword_args("replace", options, &["START1", "END1", "START2", "END2"])?;
+    let (_, source) = elements(source)?;
+    let (kind, mut elements) = elements(target)?;
+    let (start1, end1) = bounds(&options, "START1", "END1", elements.len())?;
+    let (start2, end2) = bounds(&options, "START2", "END2", source.len())?;
+    let len = (end1 - start1).min(end2 - start2);
+    elements[start1..start1 + len].clone_from_slice(&source[start2..start2 + len]);
+    update(target, kind, elements)
+}
+
+// (remove-duplicates sequence &key test test-not key start end from-end)
+// keeps the last of equal elements, or the first with :from-end
+pub fn remove_duplicates(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let [sequence, options @ ..] = args else {
+        return Err(ExprErr::Cause(
+            "remove-duplicates expects a sequence".to_string(),
+        ));
+    };
+    let options = keyword_args(
+        "remove-duplicates",
+        options,
+        &["TEST", "TEST-NOT", "KEY", "START", "END", "FROM-END"],
+    )?;
+    let matcher = evaluator.matcher(None, None, &options)?;
+    let (kind, elements) = elements(sequence)?;
+    let (start, end) = bounds(&options, "START", "END", elements.len())?;
+    let from_end = is_set(&options, "FROM-END");
+
+    let mut keys = vec![];
+    for element in &elements[start..end] {
+        keys.push(matcher.key(evaluator, element, env)?);
+    }
+    let mut duplicate = vec![false; keys.len()];
+    for i in 0..keys.len() {
+        for j in i + 1..keys.len() {
+            if matcher.test(evaluator, &keys[i], &keys[j], env)? {
+                if from_end {
+                    duplicate[j] = true;
+                } else {
+                    duplicate[i] = true;
+                    break;
+                }
+            }
+        }
+    }
+    let kept = elements
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| *i < start || *i >= end || !duplicate[i - start])
+        .map(|(_, element)| element)
+        .collect();
+    make_sequence(kind, kept)
+}
@@ -0,0 +1,649 @@
+use crate::array::{array_arg, set_fill_pointer, ArrayRef};
+use crate::ast::{Expr, ExprErr, Lambda, StringRef};
+use crate::clos;
+use crate::eval::{keyword_args, make_lambda, Evaluator, ExprEnv};
+use crate::hash::{hash_table_arg, HashTableRef};
+use crate::list::{self, index_arg, nth_cell, ConsRef};
+use crate::math::integer_expr;
+use crate::number::Number;
+use crate::seq;
+use crate::string::char_arg;
+use crate::structure::{set_slot, struct_arg, StructDef, StructRef};
+use crate::symbol::{plist_get, symbol_of, Symbol, SymbolRef};
+use std::rc::Rc;
+
+// How (setf (name args...) value) stores into a user-defined place.
+#[derive(Clone)]
+pub enum SetfExpander {
+    // (defsetf name update-fn)
+    Update(SymbolRef),
+    // (defsetf name lambda-list (store) body*) returns the store form
+    Long(Lambda, SymbolRef),
+    // (define-setf-expander name lambda-list body*) returns the five
+    // values temps, vals, stores, store form and access form
+    Expander(Lambda),
+    // slot accessor of a defstruct
+    Slot(Rc<StructDef>, usize),
+    // (defun (setf name) ...) or a generic function of that name, called
+    // with the new value and the arguments of the place
+    Function(Expr),
+}
+
+// the accessor of a (setf name) function name
+pub fn setf_function_name(expr: &Expr) -> Option<SymbolRef> {
+    match expr {
+        Expr::Cons(_) => match expr.to_vec().ok()?.as_slice() {
+            [Expr::Symbol(setf), Expr::Symbol(name)] if setf.name == "SETF" => Some(name.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// A place whose subforms have been evaluated. Elements of lists are
+// stored into their cons cells and characters into their string.
+enum Place {
+    Var(SymbolRef),
+    SymbolValue(SymbolRef),
+    Get(SymbolRef, Expr, Expr),
+    Car(ConsRef),
+    Cdr(ConsRef),
+    // character of a string; list and vector elements are Car and Aref
+    // places
+    Char(StringRef, usize),
+    Getf(Box<Place>, Expr, Expr),
+    Gethash(Expr, HashTableRef, Expr),
+    Slot(StructRef, usize),
+    SlotValue(Expr, SymbolRef),
+    Aref(ArrayRef, usize),
+    FillPointer(ArrayRef),
+    Call {
+        reader: SymbolRef,
+        writer: Expr,
+        args: Vec<Expr>,
+    },
+    // temporaries bound in `env`, `store` evaluated with the value bound
+    // to the store variable
+    Expansion {
+        env: ExprEnv,
+        store_var: SymbolRef,
+        store: Expr,
+        access: Expr,
+    },
+}
+
+fn place_args<'a>(
+    name: &str,
+    args: &'a [Expr],
+    min: usize,
+    max: usize,
+) -> Result<&'a [Expr], ExprErr> {
+    if args.len() < min || args.len() > max {
+        return Err(ExprErr::Cause(format!(
+            "invalid {} place",
+            name.to_lowercase()
+        )));
+    }
+    Ok(args)
+}
+
+impl Evaluator {
+    // evaluate the subforms of a place from left to right
+    fn resolve_place(&mut self, place: &Expr, env: &mut ExprEnv) -> Result<Place, ExprErr> {
+        let list;
+        let (head, args) = match place {
+            Expr::Symbol(symbol) => return Ok(Place::Var(symbol.clone())),
+            Expr::Cons(_) => {
+                list = place.to_vec()?;
+                match list.split_first() {
+                    Some((Expr::Symbol(head), args)) => (head.clone(), args),
+                    _ => return Err(ExprErr::Cause(format!("invalid place: {}", place))),
+                }
+            }
+            _ => return Err(ExprErr::Cause(format!("invalid place: {}", place))),
+        };
+
+        match self.setf_expanders.get(&head).cloned() {
+            Some(SetfExpander::Slot(def, index)) => {
+                let args = place_args(&head.name, args, 1, 1)?;
+                // list structures are stored into their list
+                if def.list {
+                    let list = self.eval(&args[0], env)?;
+                    return nth_place(&list, def.list_index(index));
+                }
+                let structure = struct_arg(&self.eval(&args[0], env)?, &def)?;
+                return Ok(Place::Slot(structure, index));
+            }
+            Some(SetfExpander::Function(writer)) => {
+                let args = self.eval_args(args, env)?;
+                return Ok(Place::Call {
+                    reader: head,
+                    writer,
+                    args,
+                });
+            }
+            Some(expander) => return self.expand_place(&head, expander, args, env),
+            None => {}
+        }
+        match head.name.as_str() {
+            "CAR" | "FIRST" => {
+                let args = place_args(&head.name, args, 1, 1)?;
+                match self.eval(&args[0], env)? {
+                    Expr::Cons(cell) => Ok(Place::Car(cell)),
+                    Expr::Nil => Err(ExprErr::Cause("cannot set car of NIL".to_string())),
+                    list => Err(ExprErr::Cause(format!("{} is not list", list))),
+                }
+            }
+            "CDR" | "REST" => {
+                let args = place_args(&head.name, args, 1, 1)?;
+                match self.eval(&args[0], env)? {
+                    Expr::Cons(cell) => Ok(Place::Cdr(cell)),
+                    Expr::Nil => Err(ExprErr::Cause("cannot set cdr of NIL".to_string())),
+                    list => Err(ExprErr::Cause(format!("{} is not list", list))),
+                }
+            }
+            "NTH" => {
+                let args = place_args(&head.name, args, 2, 2)?;
+                let index = index_arg(&self.eval(&args[0], env)?)?;
+                nth_place(&self.eval(&args[1], env)?, index)
+            }
+            "ELT" | "CHAR" | "SCHAR" => {
+                let args = place_args(&head.name, args, 2, 2)?;
+                let sequence = self.eval(&args[0], env)?;
+                let index = index_arg(&self.eval(&args[1], env)?)?;
+                if index >= seq::elements(&sequence)?.1.len() {
+                    return Err(ExprErr::Cause(format!(
+                        "index {} is out of bounds for {}",
+                        index, sequence
+                    )));
+                }
+                match sequence {
+                    Expr::Array(array) => Ok(Place::Aref(array, index)),
+                    Expr::String(string) => Ok(Place::Char(string, index)),
+                    _ => nth_place(&sequence, index),
+                }
+            }
+            "GETF" => {
+                let args = place_args(&head.name, args, 2, 3)?;
+                let plist = self.resolve_place(&args[0], env)?;
+                let indicator = self.eval(&args[1], env)?;
+                let default = match args.get(2) {
+                    Some(form) => self.eval(form, env)?,
+                    None => Expr::Nil,
+                };
+                Ok(Place::Getf(Box::new(plist), indicator, default))
+            }
+            "GETHASH" => {
+                let args = place_args(&head.name, args, 2, 3)?;
+                let key = self.eval(&args[0], env)?;
+                let table = hash_table_arg(&self.eval(&args[1], env)?)?;
+                let default = match args.get(2) {
+                    Some(form) => self.eval(form, env)?,
+                    None => Expr::Nil,
+                };
+                Ok(Place::Gethash(key, table, default))
+            }
+            "AREF" => {
+                let (array, subscripts) = args
+                    .split_first()
+                    .ok_or(ExprErr::Cause("invalid aref place".to_string()))?;
+                let array = array_arg(&self.eval(array, env)?)?;
+                let subscripts = self.eval_args(subscripts, env)?;
+                let index = array.borrow().row_major_index(&subscripts)?;
+                Ok(Place::Aref(array, index))
+            }
+            "FILL-POINTER" => {
+                let args = place_args(&head.name, args, 1, 1)?;
+                Ok(Place::FillPointer(array_arg(&self.eval(&args[0], env)?)?))
+            }
+            "SLOT-VALUE" => {
+                let args = place_args(&head.name, args, 2, 2)?;
+                let object = self.eval(&args[0], env)?;
+                let name = symbol_of(&self.eval(&args[1], env)?)?;
+                Ok(Place::SlotValue(object, name))
+            }
+            "SYMBOL-VALUE" => {
+                let args = place_args(&head.name, args, 1, 1)?;
+                Ok(Place::SymbolValue(symbol_of(&self.eval(&args[0], env)?)?))
+            }
+            "GET" => {
+                let args = place_args(&head.name, args, 2, 3)?;
+                let symbol = symbol_of(&self.eval(&args[0], env)?)?;
+                let indicator = self.eval(&args[1], env)?;
+                let default = match args.get(2) {
+                    Some(form) => self.eval(form, env)?,
+                    None => Expr::Nil,
+                };
+                Ok(Place::Get(symbol, indicator, default))
+            }
+            _ => Err(ExprErr::Cause(format!("invalid place: {}", place))),
+        }
+    }
+
+    fn expand_place(
+        &mut self,
+        head: &SymbolRef,
+        expander: SetfExpander,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Place, ExprErr> {
+        let mut place_env = env.extend();
+        let store_var = Symbol::uninterned("STORE");
+
+        // the arguments of defsetf places are evaluated into temporaries
+        let mut temps = vec![];
+        if !matches!(expander, SetfExpander::Expander(_)) {
+            for arg in args {
+                let temp = Symbol::uninterned("TEMP");
+                let value = self.eval(arg, env)?;
+                place_env.define(&temp, value);
+                temps.push(Expr::Symbol(temp));
+            }
+        }
+        let mut access = vec![Expr::Symbol(head.clone())];
+        access.extend(temps.iter().cloned());
+
+        let (store_var, store, access) = match expander {
+            SetfExpander::Update(update) => {
+                let mut store = vec![Expr::Symbol(update)];
+                store.extend(temps);
+                store.push(Expr::Symbol(store_var.clone()));
+                (store_var, Expr::list(store), Expr::list(access))
+            }
+            SetfExpander::Long(mut lambda, store_param) => {
+                lambda.env = lambda.env.extend();
+                lambda
+                    .env
+                    .define(&store_param, Expr::Symbol(store_var.clone()));
+                let store = self.eval_lambda(lambda, &temps)?;
+                (store_var, store, Expr::list(access))
+            }
+            SetfExpander::Slot(..) | SetfExpander::Function(_) => {
+                unreachable!("resolved directly")
+            }
+            SetfExpander::Expander(lambda) => {
+                let primary = self.eval_lambda(lambda, args)?;
+                let values = self.take_values(primary);
+                let [temps, vals, stores, store, access] = values.as_slice() else {
+                    return Err(ExprErr::Cause(format!(
+                        "setf expander for {} must return five values",
+                        head.name
+                    )));
+                };
+                for (temp, val) in temps.to_vec()?.iter().zip(vals.to_vec()?) {
+                    let value = self.eval(&val, &mut place_env)?;
+                    place_env.define(&symbol_of(temp)?, value);
+                }
+                let store_var = match stores.to_vec()?.as_slice() {
+                    [store_var] => symbol_of(store_var)?,
+                    _ => {
+                        return Err(ExprErr::Cause(
+                            "expected exactly one store variable".to_string(),
+                        ))
+                    }
+                };
+                (store_var, store.clone(), access.clone())
+            }
+        };
+        Ok(Place::Expansion {
+            env: place_env,
+            store_var,
+            store,
+            access,
+        })
+    }
+
+    fn read_place(&mut self, place: &Place, env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        match place {
+            Place::Var(symbol) => self.eval(&Expr::Symbol(symbol.clone()), env),
+            Place::SymbolValue(symbol) => symbol
+                .value()
+                .ok_or(ExprErr::Cause(format!("unbound variable: {}", symbol.name))),
+            Place::Get(symbol, indicator, default) => {
+                Ok(plist_get(&symbol.plist(), indicator)?.unwrap_or(default.clone()))
+            }
+            Place::Car(cell) => Ok(cell.borrow().car.clone()),
+            Place::Cdr(cell) => Ok(cell.borrow().cdr.clone()),
+            Place::Char(string, index) => string
+                .borrow()
+                .chars()
+                .nth(*index)
+                .map(Expr::Char)
+                .ok_or(ExprErr::Cause(format!("index {} is out of range", index))),
+            Place::Getf(plist, indicator, default) => {
+                let plist = self.read_place(plist, env)?;
+                Ok(plist_get(&plist, indicator)?.unwrap_or(default.clone()))
+            }
+            Place::Gethash(key, table, default) => {
+                Ok(table.borrow().get(key).unwrap_or(default.clone()))
+            }
+            Place::Slot(structure, index) => Ok(structure.borrow().values[*index].clone()),
+            Place::SlotValue(object, name) => clos::slot_value(object, name),
+            Place::Aref(array, index) => Ok(array.borrow().elements[*index].clone()),
+            Place::FillPointer(array) => {
+                let fill_pointer = array.borrow().fill_pointer;
+                fill_pointer
+                    .map(|fill_pointer| integer_expr(fill_pointer as i64))
+                    .ok_or(ExprErr::Cause(format!(
+                        "{} has no fill pointer",
+                        Expr::Array(array.clone())
+                    )))
+            }
+            Place::Call { reader, args, .. } => {
+                let function = self.eval_function(&Expr::Symbol(reader.clone()), env)?;
+                self.apply(&function, args, env)
+            }
+            Place::Expansion { env, access, .. } => self.eval(access, &mut env.clone()),
+        }
+    }
+
+    fn write_place(
+        &mut self,
+        place: &Place,
+        value: Expr,
+        env: &mut ExprEnv,
+    ) -> Result<(), ExprErr> {
+        match place {
+            Place::Var(symbol) => self.set_var(env, symbol, value),
+            Place::SymbolValue(symbol) => {
+                if symbol.is_constant() {
+                    return Err(ExprErr::Cause(format!(
+                        "cannot set constant: {}",
+                        symbol.name
+                    )));
+                }
+                symbol.set_value(Some(value));
+                Ok(())
+            }
+            Place::Get(symbol, indicator, _) => {
+                symbol.set_plist(plist_put(&symbol.plist(), indicator, value)?);
+                Ok(())
+            }
+            Place::Car(cell) => {
+                cell.borrow_mut().car = value;
+                Ok(())
+            }
+            Place::Cdr(cell) => {
+                cell.borrow_mut().cdr = value;
+                Ok(())
+            }
+            Place::Char(string, index) => {
+                let ch = char_arg(&value)?;
+                let mut string = string.borrow_mut();
+                let (offset, old) = string
+                    .char_indices()
+                    .nth(*index)
+                    .ok_or(ExprErr::Cause(format!("index {} is out of range", index)))?;
+                string.replace_range(offset..offset + old.len_utf8(), &ch.to_string());
+                Ok(())
+            }
+            Place::Getf(plist, indicator, _) => {
+                let updated = plist_put(&self.read_place(plist, env)?, indicator, value)?;
+                self.write_place(plist, updated, env)
+            }
+            Place::Gethash(key, table, _) => {
+                table.borrow_mut().insert(key, value);
+                Ok(())
+            }
+            Place::Slot(structure, index) => set_slot(structure, *index, value),
+            Place::SlotValue(object, name) => clos::set_slot_value(object, name, value),
+            Place::Aref(array, index) => {
+                array.borrow_mut().elements[*index] = value;
+                Ok(())
+            }
+            Place::FillPointer(array) => set_fill_pointer(array, &value),
+            Place::Call { writer, args, .. } => {
+                let mut call = vec![value];
+                call.extend(args.iter().cloned());
+                self.apply(writer, &call, env)?;
+                Ok(())
+            }
+            Place::Expansion {
+                env,
+                store_var,
+                store,
+                ..
+            } => {
+                let mut env = env.extend();
+                env.define(store_var, value);
+                self.eval(store, &mut env)?;
+                Ok(())
+            }
+        }
+    }
+
+    // (setf place value ...)
+    pub fn eval_setf(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        if !args.len().is_multiple_of(2) {
+            return Err(ExprErr::Cause(
+                "expected pairs of place and value".to_string(),
+            ));
+        }
+
+        let mut value = Expr::Nil;
+        for pair in args.chunks(2) {
+            let place = self.resolve_place(&pair[0], env)?;
+            value = self.eval(&pair[1], env)?;
+            self.write_place(&place, value.clone(), env)?;
+        }
+        Ok(value)
+    }
+
+    // (psetf place value ...) stores after all values are computed
+    pub fn eval_psetf(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        if !args.len().is_multiple_of(2) {
+            return Err(ExprErr::Cause(
+                "expected pairs of place and value".to_string(),
+            ));
+        }
+
+        let mut stores = vec![];
+        for pair in args.chunks(2) {
+            let place = self.resolve_place(&pair[0], env)?;
+            stores.push((place, self.eval(&pair[1], env)?));
+        }
+        for (place, value) in stores {
+            self.write_place(&place, value, env)?;
+        }
+        Ok(Expr::Nil)
+    }
+
+    // (incf place [delta]), (decf place [delta])
+    pub fn eval_incf(
+        &mut self,
+        args: &[Expr],
+        env: &mut ExprEnv,
+        operation: fn(Number, Number) -> Result<Number, ExprErr>,
+    ) -> Result<Expr, ExprErr> {
+        let (place, delta) = match args {
+            [place] => (place, None),
+            [place, delta] => (place, Some(delta)),
+            _ => return Err(ExprErr::Cause("expected place and delta".to_string())),
+        };
+        let place = self.resolve_place(place, env)?;
+        let old = self.read_place(&place, env)?;
+        let delta = match delta {
+            Some(delta) => self.eval(delta, env)?,
+            None => integer_expr(1),
+        };
+        let value = match (&old, &delta) {
+            (Expr::Number(old), Expr::Number(delta)) => Expr::Number(operation(*old, *delta)?),
+            (Expr::Number(_), _) => return Err(ExprErr::Cause(format!("{} is not number", delta))),
+            _ => return Err(ExprErr::Cause(format!("{} is not number", old))),
+        };
+        self.write_place(&place, value.clone(), env)?;
+        Ok(value)
+    }
+
+    // (push item place)
+    pub fn eval_push(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let [item, place] = args else {
+            return Err(ExprErr::Cause("push expects item and place".to_string()));
+        };
+        let item = self.eval(item, env)?;
+        let place = self.resolve_place(place, env)?;
+        let list = Expr::cons(item, self.read_place(&place, env)?);
+        self.write_place(&place, list.clone(), env)?;
+        Ok(list)
+    }
+
+    // (pushnew item place &key test key) pushes unless already present
+    pub fn eval_pushnew(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let [item, place, options @ ..] = args else {
+            return Err(ExprErr::Cause("pushnew expects item and place".to_string()));
+        };
+        let item = self.eval(item, env)?;
+        let place = self.resolve_place(place, env)?;
+        let options = self.eval_args(options, env)?;
+        let options = keyword_args("pushnew", &options, &["TEST", "KEY"])?;
+        let test = match options.get("TEST") {
+            Some(test) => self.function_designator(test)?,
+            None => Symbol::cl("EQL").function().unwrap_or(Expr::Nil),
+        };
+        let key = match options.get("KEY") {
+            Some(key) if !key.is_nil() => Some(self.function_designator(key)?),
+            _ => None,
+        };
+
+        let list = self.read_place(&place, env)?;
+        let item_key = match &key {
+            Some(key) => self.apply(key, std::slice::from_ref(&item), env)?,
+            None => item.clone(),
+        };
+        for element in list.to_vec()? {
+            let element_key = match &key {
+                Some(key) => self.apply(key, &[element], env)?,
+                None => element,
+            };
+            if !self
+                .apply(&test, &[item_key.clone(), element_key], env)?
+                .is_nil()
+            {
+                return Ok(list);
+            }
+        }
+
+        let list = Expr::cons(item, list);
+        self.write_place(&place, list.clone(), env)?;
+        Ok(list)
+    }
+
+    // (pop place) removes and returns the first element
+    pub fn eval_pop(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let [place] = args else {
+            return Err(ExprErr::Cause("pop expects a place".to_string()));
+        };
+        let place = self.resolve_place(place, env)?;
+        let list = self.read_place(&place, env)?;
+        self.write_place(&place, list.cdr()?, env)?;
+        list.car()
+    }
+
+    // (rotatef place*) moves each value one place to the left
+    pub fn eval_rotatef(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let places = args
+            .iter()
+            .map(|place| self.resolve_place(place, env))
+            .collect::<Result<Vec<Place>, ExprErr>>()?;
+        let mut values = places
+            .iter()
+            .map(|place| self.read_place(place, env))
+            .collect::<Result<Vec<Expr>, ExprErr>>()?;
+        if !values.is_empty() {
+            values.rotate_left(1);
+        }
+        for (place, value) in places.iter().zip(values) {
+            self.write_place(place, value, env)?;
+        }
+        Ok(Expr::Nil)
+    }
+
+    // (shiftf place* value) shifts values to the left, returning the first
+    pub fn eval_shiftf(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let (value, places) = match args.split_last() {
+            Some((value, places)) if !places.is_empty() => (value, places),
+            _ => {
+                return Err(ExprErr::Cause(
+                    "shiftf expects places and a value".to_string(),
+                ))
+            }
+        };
+        let places = places
+            .iter()
+            .map(|place| self.resolve_place(place, env))
+            .collect::<Result<Vec<Place>, ExprErr>>()?;
+        let mut values = places
+            .iter()
+            .map(|place| self.read_place(place, env))
+            .collect::<Result<Vec<Expr>, ExprErr>>()?;
+        values.push(self.eval(value, env)?);
+        let first = values.remove(0);
+        for (place, value) in places.iter().zip(values) {
+            self.write_place(place, value, env)?;
+        }
+        Ok(first)
+    }
+
+    // (defsetf name update-fn) or (defsetf name lambda-list (store) body*)
+    pub fn eval_defsetf(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let expander = match args {
+            [Expr::Symbol(_), Expr::Symbol(update)] => SetfExpander::Update(update.clone()),
+            [Expr::Symbol(_), params, stores, body @ ..] => {
+                let store = match stores.to_vec()?.as_slice() {
+                    [Expr::Symbol(store)] => store.clone(),
+                    _ => {
+                        return Err(ExprErr::Cause(
+                            "expected exactly one store variable".to_string(),
+                        ))
+                    }
+                };
+                let mut form = vec![params.clone()];
+                form.extend(body.iter().cloned());
+                SetfExpander::Long(make_lambda(&form, env)?, store)
+            }
+            _ => return Err(ExprErr::Cause("invalid defsetf".to_string())),
+        };
+        let name = symbol_of(&args[0])?;
+        self.setf_expanders.insert(name.clone(), expander);
+        Ok(Expr::Symbol(name))
+    }
+
+    // (define-setf-expander name lambda-list body*)
+    pub fn eval_define_setf_expander(
+        &mut self,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        let [Expr::Symbol(name), rest @ ..] = args else {
+            return Err(ExprErr::Cause("invalid define-setf-expander".to_string()));
+        };
+        let lambda = make_lambda(rest, env)?;
+        self.setf_expanders
+            .insert(name.clone(), SetfExpander::Expander(lambda));
+        Ok(Expr::Symbol(name.clone()))
+    }
+}
+
+// the element of a list at index as a place
+fn nth_place(list: &Expr, index: usize) -> Result<Place, ExprErr> {
+    match nth_cell(list, index)? {
+        Some(cell) => Ok(Place::Car(cell)),
+        None => Err(ExprErr::Cause(format!("index {} is out of range", index))),
+    }
+}
+
+// plist with the value under `indicator` replaced in its cell, or added
+// in front
+fn plist_put(plist: &Expr, indicator: &Expr, value: Expr) -> Result<Expr, ExprErr> {
+    let mut cells = list::cells(plist);
+    while let (Some(key), Some(cell)) = (cells.next(), cells.next()) {
+        if &key.borrow().car == indicator {
+            cell.borrow_mut().car = value;
+            return Ok(plist.clone());
+        }
+    }
+    Ok(Expr::cons(
+        indicator.clone(),
+        Expr::cons(value, plist.clone()),
+    ))
+}
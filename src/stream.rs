@@ -0,0 +1,535 @@
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{keyword_args, Evaluator, ExprEnv, Shadowed};
+use crate::file::FileStream;
+use crate::list::index_arg;
+use crate::package;
+use crate::seq;
+use crate::string::{char_arg, string_arg};
+use crate::symbol::{Symbol, SymbolRef};
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+pub enum Stream {
+    // collects the characters written to it
+    StringOutput(String),
+    // reads the characters of a string from index
+    StringInput {
+        chars: Vec<char>,
+        index: usize,
+    },
+    // writes to standard output, and reads standard input a line at a time
+    Terminal {
+        line: Vec<char>,
+        index: usize,
+        column: usize,
+    },
+    // writes to standard error
+    ErrorOutput {
+        column: usize,
+    },
+    File(FileStream),
+}
+
+pub type StreamRef = Rc<RefCell<Stream>>;
+
+impl Stream {
+    pub fn write_str(&mut self, s: &str) -> Result<(), ExprErr> {
+        match self {
+            Stream::StringOutput(buffer) => buffer.push_str(s),
+            Stream::Terminal { column, .. } => {
+                print!("{}", s);
+                _ = std::io::stdout().flush();
+                *column = column_after(*column, s);
+            }
+            Stream::ErrorOutput { column } => {
+                eprint!("{}", s);
+                *column = column_after(*column, s);
+            }
+            Stream::File(file) => file.write_str(s)?,
+            Stream::StringInput { .. } => {
+                return Err(ExprErr::Cause(format!("{:?} is not output stream", self)))
+            }
+        }
+        Ok(())
+    }
+
+    // the column of the next character written
+    pub fn column(&self) -> usize {
+        match self {
+            Stream::StringOutput(buffer) => column_after(0, buffer),
+            Stream::Terminal { column, .. } | Stream::ErrorOutput { column } => *column,
+            Stream::StringInput { .. } => 0,
+            Stream::File(file) => file.column(),
+        }
+    }
+
+    // the next character, or None at end of file
+    pub fn read_char(&mut self) -> Result<Option<char>, ExprErr> {
+        match self {
+            Stream::StringInput { chars, index }
+            | Stream::Terminal {
+                line: chars, index, ..
+            } if *index < chars.len() => {
+                *index += 1;
+                Ok(Some(chars[*index - 1]))
+            }
+            Stream::StringInput { .. } => Ok(None),
+            Stream::Terminal {
+                line,
+                index,
+                column,
+            } => {
+                let mut input = String::new();
+                if std::io::stdin().read_line(&mut input).is_err() || input.is_empty() {
+                    return Ok(None);
+                }
+                // the line was echoed with its newline
+                *column = 0;
+                *line = input.chars().collect();
+                *index = 1;
+                Ok(Some(line[0]))
+            }
+            Stream::File(file) => file.read_char(),
+            _ => Err(ExprErr::Cause(format!("{:?} is not input stream", self))),
+        }
+    }
+
+    // put back text, the characters last read
+    pub fn unread_str(&mut self, text: &str) {
+        match self {
+            Stream::StringInput { index, .. } | Stream::Terminal { index, .. } => {
+                *index = index.saturating_sub(text.chars().count())
+            }
+            Stream::File(file) => file.unread_str(text),
+            _ => {}
+        }
+    }
+
+    // put back the character last read
+    pub fn unread_char(&mut self) {
+        match self {
+            Stream::StringInput { index, .. } | Stream::Terminal { index, .. } => {
+                *index = index.saturating_sub(1)
+            }
+            Stream::File(file) => file.unread_char(),
+            _ => {}
+        }
+    }
+}
+
+// the column after writing `s` at `column`
+pub fn column_after(column: usize, s: &str) -> usize {
+    match s.rfind('\n') {
+        Some(i) => s[i + 1..].chars().count(),
+        None => column + s.chars().count(),
+    }
+}
+
+impl std::fmt::Debug for Stream {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Stream::StringOutput(_) => write!(f, "#<STRING-OUTPUT-STREAM>"),
+            Stream::StringInput { .. } => write!(f, "#<STRING-INPUT-STREAM>"),
+            Stream::Terminal { .. } => write!(f, "#<TERMINAL-STREAM>"),
+            Stream::ErrorOutput { .. } => write!(f, "#<ERROR-OUTPUT-STREAM>"),
+            Stream::File(file) => write!(f, "#<FILE-STREAM \"{}\">", file.pathname.namestring()),
+        }
+    }
+}
+
+pub fn string_output_stream() -> StreamRef {
+    Rc::new(RefCell::new(Stream::StringOutput(String::new())))
+}
+
+fn string_input_stream(chars: Vec<char>) -> StreamRef {
+    Rc::new(RefCell::new(Stream::StringInput { chars, index: 0 }))
+}
+
+// define the standard streams: *terminal-io*, *standard-output* and
+// *standard-input* are the terminal, *error-output* is standard error
+pub fn init_streams() {
+    let terminal = Expr::Stream(Rc::new(RefCell::new(Stream::Terminal {
+        line: vec![],
+        index: 0,
+        column: 0,
+    })));
+    let error = Expr::Stream(Rc::new(RefCell::new(Stream::ErrorOutput { column: 0 })));
+    let cl = package::common_lisp();
+    for (name, stream) in [
+        ("*TERMINAL-IO*", &terminal),
+        ("*STANDARD-OUTPUT*", &terminal),
+        ("*STANDARD-INPUT*", &terminal),
+        ("*ERROR-OUTPUT*", &error),
+    ] {
+        let symbol = cl.intern(name);
+        symbol.proclaim_special();
+        symbol.set_value(Some(stream.clone()));
+        _ = cl.export(&symbol);
+    }
+}
+
+fn standard_stream(name: &str) -> Result<StreamRef, ExprErr> {
+    match Symbol::cl(name).value() {
+        Some(Expr::Stream(stream)) => Ok(stream),
+        value => Err(ExprErr::Cause(format!(
+            "{} is not stream",
+            value.unwrap_or(Expr::Nil)
+        ))),
+    }
+}
+
+// the stream designated by a stream argument: NIL or no argument for the
+// standard stream, T for the terminal
+fn designated(stream: Option<&Expr>, standard: &str) -> Result<StreamRef, ExprErr> {
+    match stream {
+        None | Some(Expr::Nil) => standard_stream(standard),
+        Some(Expr::True) => standard_stream("*TERMINAL-IO*"),
+        Some(Expr::Stream(stream)) => Ok(stream.clone()),
+        Some(stream) => Err(ExprErr::Cause(format!("{} is not stream", stream))),
+    }
+}
+
+pub fn output_stream(stream: Option<&Expr>) -> Result<StreamRef, ExprErr> {
+    designated(stream, "*STANDARD-OUTPUT*")
+}
+
+pub fn input_stream(stream: Option<&Expr>) -> Result<StreamRef, ExprErr> {
+    designated(stream, "*STANDARD-INPUT*")
+}
+
+pub fn write_to(stream: Option<&Expr>, s: &str) -> Result<(), ExprErr> {
+    output_stream(stream)?.borrow_mut().write_str(s)
+}
+
+pub fn column_of(stream: Option<&Expr>) -> Result<usize, ExprErr> {
+    Ok(output_stream(stream)?.borrow().column())
+}
+
+// print a result of the REPL on a fresh line of the terminal
+pub fn echo(result: &str) {
+    if let Ok(terminal) = standard_stream("*TERMINAL-IO*") {
+        let mut terminal = terminal.borrow_mut();
+        let fresh = if terminal.column() > 0 { "\n" } else { "" };
+        _ = terminal.write_str(&format!("{}{}\n", fresh, result));
+    }
+}
+
+// (write-string string [stream] &key start end)
+pub fn write_string(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (string, stream, options) = match args {
+        [string] => (string, None, &[][..]),
+        [string, stream, options @ ..] => (string, Some(stream), options),
+        _ => return Err(ExprErr::Cause("write-string expects a string".to_string())),
+    };
+    let options = keyword_args("write-string", options, &["START", "END"])?;
+    let chars = string_arg(string)?.chars().collect::<Vec<char>>();
+    let (start, end) = seq::bounds(&options, "START", "END", chars.len())?;
+    write_to(stream, &chars[start..end].iter().collect::<String>())?;
+    Ok(string.clone())
+}
+
+// (write-char char [stream])
+pub fn write_char(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [ch] | [ch, _] => {
+            write_to(args.get(1), &char_arg(ch)?.to_string())?;
+            Ok(ch.clone())
+        }
+        _ => Err(ExprErr::Cause(
+            "write-char expects a character and a stream".to_string(),
+        )),
+    }
+}
+
+// (write-line string [stream] &key start end)
+pub fn write_line(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    env: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let string = write_string(evaluator, args, env)?;
+    write_to(args.get(1), "\n")?;
+    Ok(string)
+}
+
+// (terpri [stream])
+pub fn terpri(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    write_to(args.first(), "\n")?;
+    Ok(Expr::Nil)
+}
+
+// (fresh-line [stream]) is T when it started a new line
+pub fn fresh_line(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let stream = output_stream(args.first())?;
+    let mut stream = stream.borrow_mut();
+    if stream.column() == 0 {
+        return Ok(Expr::Nil);
+    }
+    stream.write_str("\n")?;
+    Ok(Expr::True)
+}
+
+// the input stream and eof handling of
+// (read-xxx [stream [eof-error-p [eof-value [recursive-p]]]])
+pub struct ReadArgs {
+    pub stream: StreamRef,
+    eof_error: bool,
+    eof_value: Expr,
+}
+
+impl ReadArgs {
+    pub fn parse(name: &str, args: &[Expr]) -> Result<ReadArgs, ExprErr> {
+        if args.len() > 4 {
+            return Err(ExprErr::Cause(format!("too many args to {}", name)));
+        }
+        Ok(ReadArgs {
+            stream: input_stream(args.first())?,
+            eof_error: !matches!(args.get(1), Some(Expr::Nil)),
+            eof_value: args.get(2).cloned().unwrap_or(Expr::Nil),
+        })
+    }
+
+    // the eof-value, or an error when eof-error-p
+    pub fn eof(&self) -> Result<Expr, ExprErr> {
+        if self.eof_error {
+            Err(ExprErr::Cause(format!(
+                "end of file on {:?}",
+                self.stream.borrow()
+            )))
+        } else {
+            Ok(self.eof_value.clone())
+        }
+    }
+}
+
+// (read-line [stream [eof-error-p [eof-value [recursive-p]]]]) is the line
+// and whether it ended at end of file instead of a newline
+pub fn read_line(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let read = ReadArgs::parse("read-line", args)?;
+    let mut stream = read.stream.borrow_mut();
+    let mut line = String::new();
+    loop {
+        match stream.read_char()? {
+            Some('\n') => return Ok(evaluator.multiple_values(vec![Expr::string(line), Expr::Nil])),
+            Some(ch) => line.push(ch),
+            None if line.is_empty() => {
+                drop(stream);
+                let eof = read.eof()?;
+                return Ok(evaluator.multiple_values(vec![eof, Expr::True]));
+            }
+            None => return Ok(evaluator.multiple_values(vec![Expr::string(line), Expr::True])),
+        }
+    }
+}
+
+// (read-char [stream [eof-error-p [eof-value [recursive-p]]]])
+pub fn read_char(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let read = ReadArgs::parse("read-char", args)?;
+    let ch = read.stream.borrow_mut().read_char()?;
+    match ch {
+        Some(ch) => Ok(Expr::Char(ch)),
+        None => read.eof(),
+    }
+}
+
+// (peek-char [peek-type [stream [eof-error-p [eof-value [recursive-p]]]]])
+// is the next character without reading it; a peek-type of T skips
+// whitespace first, and a character skips up to that character
+pub fn peek_char(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let peek_type = args.first().unwrap_or(&Expr::Nil);
+    let read = ReadArgs::parse("peek-char", args.get(1..).unwrap_or_default())?;
+    let mut stream = read.stream.borrow_mut();
+    loop {
+        let Some(ch) = stream.read_char()? else {
+            drop(stream);
+            return read.eof();
+        };
+        let skip = match peek_type {
+            Expr::Nil => false,
+            Expr::True => ch.is_whitespace(),
+            Expr::Char(target) => ch != *target,
+            _ => return Err(ExprErr::Cause(format!("{} is not peek type", peek_type))),
+        };
+        if !skip {
+            stream.unread_char();
+            return Ok(Expr::Char(ch));
+        }
+    }
+}
+
+// (unread-char char [stream])
+pub fn unread_char(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [ch] | [ch, _] => {
+            char_arg(ch)?;
+            input_stream(args.get(1))?.borrow_mut().unread_char();
+            Ok(Expr::Nil)
+        }
+        _ => Err(ExprErr::Cause(
+            "unread-char expects a character and a stream".to_string(),
+        )),
+    }
+}
+
+// the characters of string from start below end
+fn substring_chars(
+    name: &str,
+    string: &Expr,
+    start: Option<&Expr>,
+    end: Option<&Expr>,
+) -> Result<Vec<char>, ExprErr> {
+    let chars = string_arg(string)?.chars().collect::<Vec<char>>();
+    let start = match start {
+        None | Some(Expr::Nil) => 0,
+        Some(start) => index_arg(start)?,
+    };
+    let end = match end {
+        None | Some(Expr::Nil) => chars.len(),
+        Some(end) => index_arg(end)?,
+    };
+    if start > end || end > chars.len() {
+        return Err(ExprErr::Cause(format!(
+            "{}: bounds {} and {} are invalid for {}",
+            name, start, end, string
+        )));
+    }
+    Ok(chars[start..end].to_vec())
+}
+
+// (make-string-input-stream string [start [end]])
+pub fn make_string_input_stream(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    match args {
+        [string, ..] if args.len() <= 3 => {
+            let chars =
+                substring_chars("make-string-input-stream", string, args.get(1), args.get(2))?;
+            Ok(Expr::Stream(string_input_stream(chars)))
+        }
+        _ => Err(ExprErr::Cause(
+            "make-string-input-stream expects a string".to_string(),
+        )),
+    }
+}
+
+// (make-string-output-stream)
+pub fn make_string_output_stream(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    if !args.is_empty() {
+        return Err(ExprErr::Cause(
+            "make-string-output-stream expects no args".to_string(),
+        ));
+    }
+    Ok(Expr::Stream(string_output_stream()))
+}
+
+// (get-output-stream-string stream) is the output so far, and clears it
+pub fn get_output_stream_string(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    match args {
+        [Expr::Stream(stream)] => match &mut *stream.borrow_mut() {
+            Stream::StringOutput(buffer) => Ok(Expr::string(std::mem::take(buffer))),
+            stream => Err(ExprErr::Cause(format!(
+                "{:?} is not string output stream",
+                stream
+            ))),
+        },
+        _ => Err(ExprErr::Cause(
+            "get-output-stream-string expects a string output stream".to_string(),
+        )),
+    }
+}
+
+impl Evaluator {
+    // (with-output-to-string (var) body*) is the output written to var
+    pub fn eval_with_output_to_string(
+        &mut self,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        let (spec, body) = args
+            .split_first()
+            .ok_or(ExprErr::Cause("expected stream variable".to_string()))?;
+        let var: SymbolRef = match spec.to_vec()?.as_slice() {
+            [Expr::Symbol(var)] => var.clone(),
+            _ => {
+                return Err(ExprErr::Cause(format!(
+                    "invalid stream variable spec: {}",
+                    spec
+                )))
+            }
+        };
+        let stream = string_output_stream();
+        self.eval_with_stream(&var, Expr::Stream(stream.clone()), body, env)?;
+        let output = match &*stream.borrow() {
+            Stream::StringOutput(buffer) => buffer.clone(),
+            _ => String::new(),
+        };
+        Ok(Expr::string(output))
+    }
+
+    // (with-input-from-string (var string &key start end) body*) reads
+    // var from the characters of string
+    pub fn eval_with_input_from_string(
+        &mut self,
+        args: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        let (spec, body) = args
+            .split_first()
+            .ok_or(ExprErr::Cause("expected stream variable".to_string()))?;
+        let (var, string, options) = match spec.to_vec()?.as_slice() {
+            [Expr::Symbol(var), string, options @ ..] => {
+                (var.clone(), self.eval(string, env)?, options.to_vec())
+            }
+            _ => {
+                return Err(ExprErr::Cause(format!(
+                    "invalid stream variable spec: {}",
+                    spec
+                )))
+            }
+        };
+        let options = options
+            .iter()
+            .map(|option| self.eval(option, env))
+            .collect::<Result<Vec<Expr>, ExprErr>>()?;
+        let options = keyword_args("with-input-from-string", &options, &["START", "END"])?;
+        let chars = substring_chars(
+            "with-input-from-string",
+            &string,
+            options.get("START").copied(),
+            options.get("END").copied(),
+        )?;
+        self.eval_with_stream(&var, Expr::Stream(string_input_stream(chars)), body, env)
+    }
+
+    // body evaluated with var bound to stream, dynamically when var is
+    // special such as *standard-output*
+    pub fn eval_with_stream(
+        &mut self,
+        var: &SymbolRef,
+        stream: Expr,
+        body: &[Expr],
+        env: &mut ExprEnv,
+    ) -> Result<Expr, ExprErr> {
+        let mut local_env = env.extend();
+        let mut shadowed = Shadowed::new();
+        self.bind(&local_env, var, stream, false, &mut shadowed)?;
+        let result = self.eval_progn(body, &mut local_env);
+        self.unbind(shadowed);
+        result
+    }
+}
@@ -0,0 +1,550 @@
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{bool_expr, keyword_args, single_arg, Builtin, Evaluator, ExprEnv};
+use crate::list::index_arg;
+use crate::math::{integer_expr, integer_overflow};
+use crate::number::Number;
+use crate::package::string_designator;
+use crate::seq;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+// a string, symbol name or character
+pub fn string_arg(expr: &Expr) -> Result<String, ExprErr> {
+    match expr {
+        Expr::Char(ch) => Ok(ch.to_string()),
+        _ => string_designator(expr),
+    }
+}
+
+pub fn char_arg(expr: &Expr) -> Result<char, ExprErr> {
+    match expr {
+        Expr::Char(ch) => Ok(*ch),
+        _ => Err(ExprErr::Cause(format!("{} is not character", expr))),
+    }
+}
+
+fn chars_of(expr: &Expr) -> Result<Vec<char>, ExprErr> {
+    Ok(string_arg(expr)?.chars().collect())
+}
+
+fn fold(ch: char, fold_case: bool) -> char {
+    if fold_case {
+        ch.to_lowercase().next().unwrap_or(ch)
+    } else {
+        ch
+    }
+}
+
+// order of two strings, and the index in the first where they differ
+fn compare(name: &str, args: &[Expr], fold_case: bool) -> Result<(Ordering, usize), ExprErr> {
+    let [a, b, options @ ..] = args else {
+        return Err(ExprErr::Cause(format!("{} expects two strings", name)));
+    };
+    let options = keyword_args(name, options, &["START1", "END1", "START2", "END2"])?;
+    let a = chars_of(a)?;
+    let b = chars_of(b)?;
+    let (start1, end1) = seq::bounds(&options, "START1", "END1", a.len())?;
+    let (start2, end2) = seq::bounds(&options, "START2", "END2", b.len())?;
+    let a = &a[start1..end1];
+    let b = &b[start2..end2];
+    for (i, (x, y)) in a.iter().zip(b).enumerate() {
+        match fold(*x, fold_case).cmp(&fold(*y, fold_case)) {
+            Ordering::Equal => continue,
+            ordering => return Ok((ordering, start1 + i)),
+        }
+    }
+    let common = a.len().min(b.len());
+    Ok((a.len().cmp(&b.len()), start1 + common))
+}
+
+// string= and string-equal are true or false; the other comparisons are
+// the index where the strings differ
+macro_rules! string_compare {
+    ($name: expr, $fold: expr, $accept: expr) => {
+        |_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv| -> Result<Expr, ExprErr> {
+            let (ordering, index) = compare($name, args, $fold)?;
+            Ok(match $accept(ordering) {
+                true => integer_expr(index as i64),
+                false => Expr::Nil,
+            })
+        }
+    };
+}
+
+macro_rules! string_equal {
+    ($name: expr, $fold: expr) => {
+        |_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv| -> Result<Expr, ExprErr> {
+            let (ordering, _) = compare($name, args, $fold)?;
+            Ok(bool_expr(ordering.is_eq()))
+        }
+    };
+}
+
+pub const STRING_COMPARISONS: [(&str, Builtin); 12] = [
+    ("STRING=", string_equal!("string=", false)),
+    (
+        "STRING/=",
+        string_compare!("string/=", false, Ordering::is_ne),
+    ),
+    (
+        "STRING<",
+        string_compare!("string<", false, Ordering::is_lt),
+    ),
+    (
+        "STRING>",
+        string_compare!("string>", false, Ordering::is_gt),
+    ),
+    (
+        "STRING<=",
+        string_compare!("string<=", false, Ordering::is_le),
+    ),
+    (
+        "STRING>=",
+        string_compare!("string>=", false, Ordering::is_ge),
+    ),
+    ("STRING-EQUAL", string_equal!("string-equal", true)),
+    (
+        "STRING-NOT-EQUAL",
+        string_compare!("string-not-equal", true, Ordering::is_ne),
+    ),
+    (
+        "STRING-LESSP",
+        string_compare!("string-lessp", true, Ordering::is_lt),
+    ),
+    (
+        "STRING-GREATERP",
+        string_compare!("string-greaterp", true, Ordering::is_gt),
+    ),
+    (
+        "STRING-NOT-GREATERP",
+        string_compare!("string-not-greaterp", true, Ordering::is_le),
+    ),
+    (
+        "STRING-NOT-LESSP",
+        string_compare!("string-not-lessp", true, Ordering::is_ge),
+    ),
+];
+
+// char= and char-equal hold if all characters are the same, char/= if
+// all differ, the others if the characters are ordered
+macro_rules! char_compare {
+    ($fold: expr, $accept: expr) => {
+        |_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv| -> Result<Expr, ExprErr> {
+            let chars = args
+                .iter()
+                .map(|arg| Ok(fold(char_arg(arg)?, $fold)))
+                .collect::<Result<Vec<char>, ExprErr>>()?;
+            if chars.is_empty() {
+                return Err(ExprErr::Cause(
+                    "expected at least one character".to_string(),
+                ));
+            }
+            Ok(bool_expr(
+                chars.windows(2).all(|w| $accept(w[0].cmp(&w[1]))),
+            ))
+        }
+    };
+}
+
+fn char_not_equal(fold_case: bool, args: &[Expr]) -> Result<Expr, ExprErr> {
+    let chars = args
+        .iter()
+        .map(|arg| Ok(fold(char_arg(arg)?, fold_case)))
+        .collect::<Result<Vec<char>, ExprErr>>()?;
+    let distinct = chars
+        .iter()
+        .enumerate()
+        .all(|(i, ch)| !chars[i + 1..].contains(ch));
+    Ok(bool_expr(distinct))
+}
+
+pub const CHAR_COMPARISONS: [(&str, Builtin); 12] = [
+    ("CHAR=", char_compare!(false, Ordering::is_eq)),
+    ("CHAR/=", |_, args, _| char_not_equal(false, args)),
+    ("CHAR<", char_compare!(false, Ordering::is_lt)),
+    ("CHAR>", char_compare!(false, Ordering::is_gt)),
+    ("CHAR<=", char_compare!(false, Ordering::is_le)),
+    ("CHAR>=", char_compare!(false, Ordering::is_ge)),
+    ("CHAR-EQUAL", char_compare!(true, Ordering::is_eq)),
+    ("CHAR-NOT-EQUAL", |_, args, _| char_not_equal(true, args)),
+    ("CHAR-LESSP", char_compare!(true, Ordering::is_lt)),
+    ("CHAR-GREATERP", char_compare!(true, Ordering::is_gt)),
+    ("CHAR-NOT-GREATERP", char_compare!(true, Ordering::is_le)),
+    ("CHAR-NOT-LESSP", char_compare!(true, Ordering::is_ge)),
+];
+
+pub fn characterp(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    Ok(bool_expr(matches!(
+        single_arg("characterp", args)?,
+        Expr::Char(_)
+    )))
+}
+
+pub fn stringp(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    Ok(bool_expr(matches!(
+        single_arg("stringp", args)?,
+        Expr::String(_)
+    )))
+}
+
+pub fn char_code(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let ch = char_arg(single_arg("char-code", args)?)?;
+    Ok(integer_expr(ch as u32 as i64))
+}
+
+pub fn code_char(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let code = index_arg(single_arg("code-char", args)?)?;
+    Ok(u32::try_from(code)
+        .ok()
+        .and_then(char::from_u32)
+        .map_or(Expr::Nil, Expr::Char))
+}
+
+pub fn char_upcase(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let ch = char_arg(single_arg("char-upcase", args)?)?;
+    Ok(Expr::Char(ch.to_uppercase().next().unwrap_or(ch)))
+}
+
+pub fn char_downcase(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let ch = char_arg(single_arg("char-downcase", args)?)?;
+    Ok(Expr::Char(fold(ch, true)))
+}
+
+macro_rules! char_predicate {
+    ($name: expr, $fn: expr) => {
+        |_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv| -> Result<Expr, ExprErr> {
+            let ch = char_arg(single_arg($name, args)?)?;
+            Ok(bool_expr($fn(ch)))
+        }
+    };
+}
+
+pub const CHAR_PREDICATES: [(&str, Builtin); 4] = [
+    (
+        "UPPER-CASE-P",
+        char_predicate!("upper-case-p", char::is_uppercase),
+    ),
+    (
+        "LOWER-CASE-P",
+        char_predicate!("lower-case-p", char::is_lowercase),
+    ),
+    (
+        "ALPHA-CHAR-P",
+        char_predicate!("alpha-char-p", char::is_alphabetic),
+    ),
+    (
+        "ALPHANUMERICP",
+        char_predicate!("alphanumericp", char::is_alphanumeric),
+    ),
+];
+
+fn radix_arg(expr: Option<&&Expr>) -> Result<u32, ExprErr> {
+    match expr {
+        None | Some(Expr::Nil) => Ok(10),
+        Some(radix) => match index_arg(radix)? {
+            radix @ 2..=36 => Ok(radix as u32),
+            radix => Err(ExprErr::Cause(format!("invalid radix: {}", radix))),
+        },
+    }
+}
+
+// (digit-char-p char [radix]) is the weight of a digit
+pub fn digit_char_p(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (ch, radix) = match args {
+        [ch] => (ch, None),
+        [ch, radix] => (ch, Some(radix)),
+        _ => {
+            return Err(ExprErr::Cause(
+                "digit-char-p expects 1 or 2 args".to_string(),
+            ))
+        }
+    };
+    let radix = radix_arg(radix.as_ref())?;
+    Ok(char_arg(ch)?
+        .to_digit(radix)
+        .map_or(Expr::Nil, |digit| integer_expr(digit as i64)))
+}
+
+// (string x) of a string, symbol or character
+pub fn string(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    Ok(Expr::string(string_arg(single_arg("string", args)?)?))
+}
+
+// (char string index) and schar
+pub fn char(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match args {
+        [Expr::String(s), index] => {
+            let index = index_arg(index)?;
+            s.borrow()
+                .chars()
+                .nth(index)
+                .map(Expr::Char)
+                .ok_or(ExprErr::Cause(format!(
+                    "index {} is out of bounds for {}",
+                    index, args[0]
+                )))
+        }
+        [string, _] => Err(ExprErr::Cause(format!("{} is not string", string))),
+        _ => Err(ExprErr::Cause("char expects exactly two args".to_string())),
+    }
+}
+
+// (make-string size &key initial-element element-type)
+pub fn make_string(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (size, options) = args
+        .split_first()
+        .ok_or(ExprErr::Cause("make-string expects a size".to_string()))?;
+    let options = keyword_args("make-string", options, &["INITIAL-ELEMENT", "ELEMENT-TYPE"])?;
+    let ch = match options.get("INITIAL-ELEMENT") {
+        Some(ch) => char_arg(ch)?,
+        None => ' ',
+    };
+    Ok(Expr::string(ch.to_string().repeat(index_arg(size)?)))
+}
+
+// the characters of a string argument and the bounds of its :start and :end
+fn case_args(name: &str, args: &[Expr]) -> Result<(Vec<char>, usize, usize), ExprErr> {
+    let (string, options) = args
+        .split_first()
+        .ok_or(ExprErr::Cause(format!("{} expects a string", name)))?;
+    let options = keyword_args(name, options, &["START", "END"])?;
+    let chars = chars_of(string)?;
+    let (start, end) = seq::bounds(&options, "START", "END", chars.len())?;
+    Ok((chars, start, end))
+}
+
+pub fn string_upcase(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (chars, start, end) = case_args("string-upcase", args)?;
+    let mut result = chars[..start].iter().collect::<String>();
+    result.extend(chars[start..end].iter().flat_map(|ch| ch.to_uppercase()));
+    result.extend(&chars[end..]);
+    Ok(Expr::string(result))
+}
+
+pub fn string_downcase(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (chars, start, end) = case_args("string-downcase", args)?;
+    let mut result = chars[..start].iter().collect::<String>();
+    result.extend(chars[start..end].iter().flat_map(|ch| ch.to_lowercase()));
+    result.extend(&chars[end..]);
+    Ok(Expr::string(result))
+}
+
+// words are runs of alphanumeric characters; each starts upper case and
+// continues lower case
+pub fn string_capitalize(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (chars, start, end) = case_args("string-capitalize", args)?;
+    let mut result = chars[..start].iter().collect::<String>();
+    let mut in_word = false;
+    for ch in &chars[start..end] {
+        if !ch.is_alphanumeric() {
+            result.push(*ch);
+            in_word = false;
+        } else if in_word {
+            result.extend(ch.to_lowercase());
+        } else {
+            result.extend(ch.to_uppercase());
+            in_word = true;
+        }
+    }
+    result.extend(&chars[end..]);
+    Ok(Expr::string(result))
+}
+
+fn trim_args(name: &str, args: &[Expr]) -> Result<(Vec<char>, String), ExprErr> {
+    match args {
+        [bag, string] => {
+            let bag = seq::elements(bag)?
+                .1
+                .iter()
+                .map(char_arg)
+                .collect::<Result<Vec<char>, ExprErr>>()?;
+            Ok((bag, string_arg(string)?))
+        }
+        _ => Err(ExprErr::Cause(format!(
+            "{} expects a character bag and a string",
+            name
+        ))),
+    }
+}
+
+// (string-trim bag string) removes characters in bag from both ends
+pub fn string_trim(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (bag, s) = trim_args("string-trim", args)?;
+    Ok(Expr::string(s.trim_matches(bag.as_slice()).to_string()))
+}
+
+pub fn string_left_trim(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (bag, s) = trim_args("string-left-trim", args)?;
+    Ok(Expr::string(
+        s.trim_start_matches(bag.as_slice()).to_string(),
+    ))
+}
+
+pub fn string_right_trim(
+    _: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (bag, s) = trim_args("string-right-trim", args)?;
+    Ok(Expr::string(s.trim_end_matches(bag.as_slice()).to_string()))
+}
+
+// (string-split string [separators]) splits at each of the separator
+// characters, a character or a string of them, by default a space; empty
+// fields are kept
+pub fn string_split(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (s, separators) = match args {
+        [s] => (string_arg(s)?, vec![' ']),
+        [s, separators] => (string_arg(s)?, chars_of(separators)?),
+        _ => {
+            return Err(ExprErr::Cause(
+                "string-split expects 1 or 2 args".to_string(),
+            ))
+        }
+    };
+    Ok(Expr::list(
+        s.split(separators.as_slice())
+            .map(|field| Expr::string(field.to_string()))
+            .collect(),
+    ))
+}
+
+// the characters, bounds and :junk-allowed of a number to parse
+fn parse_args(
+    name: &str,
+    args: &[Expr],
+    allowed: &[&str],
+) -> Result<(Vec<char>, usize, usize, bool, u32), ExprErr> {
+    let (string, options) = args
+        .split_first()
+        .ok_or(ExprErr::Cause(format!("{} expects a string", name)))?;
+    let options: HashMap<String, &Expr> = keyword_args(name, options, allowed)?;
+    let chars = match string {
+        Expr::String(s) => s.borrow().chars().collect::<Vec<char>>(),
+        _ => return Err(ExprErr::Cause(format!("{} is not string", string))),
+    };
+    let (start, end) = seq::bounds(&options, "START", "END", chars.len())?;
+    let junk_allowed = options.get("JUNK-ALLOWED").is_some_and(|x| !x.is_nil());
+    let radix = radix_arg(options.get("RADIX"))?;
+    Ok((chars, start, end, junk_allowed, radix))
+}
+
+// the end of a number's digits starting at `i`
+fn skip_digits(chars: &[char], mut i: usize, end: usize, radix: u32) -> usize {
+    while i < end && chars[i].is_digit(radix) {
+        i += 1;
+    }
+    i
+}
+
+fn skip_whitespace(chars: &[char], mut i: usize, end: usize) -> usize {
+    while i < end && chars[i].is_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+fn skip_sign(chars: &[char], i: usize, end: usize) -> usize {
+    if i < end && matches!(chars[i], '+' | '-') {
+        i + 1
+    } else {
+        i
+    }
+}
+
+// the number and the index after it: surrounding whitespace is skipped
+// and anything else is junk
+fn parse_number(
+    evaluator: &mut Evaluator,
+    chars: &[char],
+    (start, end): (usize, usize),
+    junk_allowed: bool,
+    scan: impl Fn(usize) -> Result<(usize, Option<Number>), ExprErr>,
+) -> Result<Expr, ExprErr> {
+    let begin = skip_whitespace(chars, start, end);
+    let (after, value) = scan(begin)?;
+    let rest = skip_whitespace(chars, after, end);
+    let value = match value {
+        Some(value) if rest == end || junk_allowed => Expr::Number(value),
+        _ if junk_allowed => Expr::Nil,
+        _ => {
+            return Err(ExprErr::Cause(format!(
+                "junk in string {:?}",
+                chars[start..end].iter().collect::<String>()
+            )))
+        }
+    };
+    let index = if junk_allowed { after } else { rest };
+    Ok(evaluator.multiple_values(vec![value, integer_expr(index as i64)]))
+}
+
+// (parse-integer string &key start end radix junk-allowed) is the integer
+// and the index where parsing stopped
+pub fn parse_integer(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (chars, start, end, junk_allowed, radix) = parse_args(
+        "parse-integer",
+        args,
+        &["START", "END", "RADIX", "JUNK-ALLOWED"],
+    )?;
+    let scan = |begin: usize| {
+        let digits = skip_sign(&chars, begin, end);
+        let after = skip_digits(&chars, digits, end, radix);
+        if after == digits {
+            return Ok((begin, None));
+        }
+        // the digits are valid, so only a value too large fails
+        let text = chars[begin..after].iter().collect::<String>();
+        let value = i64::from_str_radix(&text, radix).map_err(|_| integer_overflow())?;
+        Ok((after, Some(Number::Integer(value))))
+    };
+    parse_number(evaluator, &chars, (start, end), junk_allowed, scan)
+}
+
+// (parse-float string &key start end junk-allowed) reads a decimal number
+// such as -1.5, .5, 2 or 1.0e3, where the exponent marker may also be
+// s, f, d or l
+pub fn parse_float(
+    evaluator: &mut Evaluator,
+    args: &[Expr],
+    _: &mut ExprEnv,
+) -> Result<Expr, ExprErr> {
+    let (chars, start, end, junk_allowed, _) =
+        parse_args("parse-float", args, &["START", "END", "JUNK-ALLOWED"])?;
+    let scan = |begin: usize| {
+        let int_start = skip_sign(&chars, begin, end);
+        let mut after = skip_digits(&chars, int_start, end, 10);
+        let mut digits = after - int_start;
+        if after < end && chars[after] == '.' {
+            let frac_end = skip_digits(&chars, after + 1, end, 10);
+            digits += frac_end - after - 1;
+            after = frac_end;
+        }
+        if digits == 0 {
+            return Ok((begin, None));
+        }
+        let mut text = chars[begin..after].iter().collect::<String>();
+        if after < end && "eEsSfFdDlL".contains(chars[after]) {
+            let exp_start = skip_sign(&chars, after + 1, end);
+            let exp_end = skip_digits(&chars, exp_start, end, 10);
+            if exp_end > exp_start {
+                text.push('e');
+                text.extend(&chars[after + 1..exp_end]);
+                after = exp_end;
+            }
+        }
+        Ok((after, text.parse::<f64>().ok().map(Number::Float)))
+    };
+    parse_number(evaluator, &chars, (start, end), junk_allowed, scan)
+}
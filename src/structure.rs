@@ -0,0 +1,591 @@
+use crate::ast::{Expr, ExprErr};
+use crate::clos;
+use crate::eval::{bool_expr, make_lambda, Evaluator, ExprEnv};
+use crate::list::index_arg;
+use crate::math::integer_expr;
+use crate::number::Number;
+use crate::package::string_designator;
+use crate::setf::SetfExpander;
+use crate::symbol::{symbol_of, Symbol, SymbolRef};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+pub struct Slot {
+    pub name: SymbolRef,
+    pub default: Expr,
+    pub typ: Expr,
+    pub read_only: bool,
+}
+
+// A structure type. Slots of an included type come first.
+pub struct StructDef {
+    pub name: SymbolRef,
+    pub slots: Vec<Slot>,
+    pub include: Option<Rc<StructDef>>,
+    // (:type list) structures are plain lists, headed by the name if :named
+    pub list: bool,
+    pub named: bool,
+}
+
+impl StructDef {
+    // this type or one including it
+    fn is_subtype_of(&self, name: &SymbolRef) -> bool {
+        &self.name == name
+            || self
+                .include
+                .as_ref()
+                .is_some_and(|include| include.is_subtype_of(name))
+    }
+
+    // position of a slot in the list representation
+    pub fn list_index(&self, index: usize) -> usize {
+        if self.named {
+            index + 1
+        } else {
+            index
+        }
+    }
+}
+
+pub struct Structure {
+    pub def: Rc<StructDef>,
+    pub values: Vec<Expr>,
+}
+
+pub type StructRef = Rc<RefCell<Structure>>;
+
+impl std::fmt::Display for Structure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "#S({}", Expr::Symbol(self.def.name.clone()))?;
+        for (slot, value) in self.def.slots.iter().zip(&self.values) {
+            write!(f, " :{} {}", slot.name.name, value)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::fmt::Debug for Structure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+thread_local! {
+    static STRUCTS: RefCell<HashMap<SymbolRef, Rc<StructDef>>> = RefCell::new(HashMap::new());
+}
+
+pub fn find_struct(name: &SymbolRef) -> Option<Rc<StructDef>> {
+    STRUCTS.with(|structs| structs.borrow().get(name).cloned())
+}
+
+// register a structure type under its name
+pub fn define_struct(def: StructDef) -> Rc<StructDef> {
+    let def = Rc::new(def);
+    STRUCTS.with(|structs| structs.borrow_mut().insert(def.name.clone(), def.clone()));
+    def
+}
+
+fn struct_def_arg(expr: &Expr) -> Result<Rc<StructDef>, ExprErr> {
+    let name = symbol_of(expr)?;
+    find_struct(&name).ok_or(ExprErr::Cause(format!(
+        "undefined structure type: {}",
+        name.name
+    )))
+}
+
+// whether `value` is of the type named `typ`; types we do not know
+// are not checked
+pub fn typep(value: &Expr, typ: &Expr) -> bool {
+    let name = match typ {
+        Expr::True => return true,
+        Expr::Nil => return false,
+        Expr::Symbol(name) => name,
+        _ => return true,
+    };
+    match name.name.as_str() {
+        "NUMBER" => matches!(value, Expr::Number(_) | Expr::Complex(..)),
+        "REAL" => matches!(value, Expr::Number(_)),
+        "RATIONAL" => matches!(value, Expr::Number(Number::Integer(_) | Number::Ratio(..))),
+        "RATIO" => matches!(value, Expr::Number(Number::Ratio(..))),
+        "FLOAT" | "DOUBLE-FLOAT" => matches!(value, Expr::Number(Number::Float(_))),
+        "COMPLEX" => matches!(value, Expr::Complex(..)),
+        "INTEGER" | "FIXNUM" => matches!(value, Expr::Number(Number::Integer(_))),
+        "CHARACTER" => matches!(value, Expr::Char(_)),
+        "STRING" => matches!(value, Expr::String(_)),
+        "ARRAY" => matches!(value, Expr::Array(_) | Expr::String(_)),
+        "VECTOR" => match value {
+            Expr::Array(array) => array.borrow().dimensions.len() == 1,
+            _ => matches!(value, Expr::String(_)),
+        },
+        "SYMBOL" => matches!(value, Expr::Symbol(_) | Expr::Nil | Expr::True),
+        "KEYWORD" => matches!(value, Expr::Symbol(symbol) if symbol.is_keyword()),
+        "LIST" => matches!(value, Expr::Cons(_) | Expr::Nil),
+        "CONS" => matches!(value, Expr::Cons(_)),
+        "NULL" => value.is_nil(),
+        "HASH-TABLE" => matches!(value, Expr::HashTable(_)),
+        "FUNCTION" => matches!(value, Expr::Func(_) | Expr::Lambda(_) | Expr::Generic(_)),
+        "PACKAGE" => matches!(value, Expr::Package(_)),
+        _ => match (find_struct(name), value) {
+            (Some(_), Expr::Struct(structure)) => structure.borrow().def.is_subtype_of(name),
+            (Some(_), _) => false,
+            (None, _) => match clos::find_class(name) {
+                Some(class) => clos::class_of(value).is_subclass_of(&class),
+                None => true,
+            },
+        },
+    }
+}
+
+fn check_slot_type(slot: &Slot, value: &Expr) -> Result<(), ExprErr> {
+    if typep(value, &slot.typ) {
+        Ok(())
+    } else {
+        Err(ExprErr::Cause(format!(
+            "{} is not of type {} for slot {}",
+            value, slot.typ, slot.name.name
+        )))
+    }
+}
+
+// a structure of `def` whose slots hold `values` in slot order
+pub fn make_struct(def: &Rc<StructDef>, values: Vec<Expr>) -> Result<Expr, ExprErr> {
+    for (slot, value) in def.slots.iter().zip(&values) {
+        check_slot_type(slot, value)?;
+    }
+    if def.list {
+        let mut list = vec![];
+        if def.named {
+            list.push(Expr::Symbol(def.name.clone()));
+        }
+        list.extend(values);
+        return Ok(Expr::list(list));
+    }
+    Ok(Expr::Struct(Rc::new(RefCell::new(Structure {
+        def: def.clone(),
+        values,
+    }))))
+}
+
+// structure of type `def` or a subtype
+pub fn struct_arg(expr: &Expr, def: &StructDef) -> Result<StructRef, ExprErr> {
+    match expr {
+        Expr::Struct(structure) if structure.borrow().def.is_subtype_of(&def.name) => {
+            Ok(structure.clone())
+        }
+        _ => Err(ExprErr::Cause(format!("{} is not {}", expr, def.name.name))),
+    }
+}
+
+// #S(name :slot value ...) as read; slots not given take their default
+// when it is a constant
+pub fn read_struct(form: &[Expr]) -> Result<Expr, ExprErr> {
+    let (name, pairs) = form
+        .split_first()
+        .ok_or(ExprErr::Cause("expected structure name".to_string()))?;
+    let def = struct_def_arg(name)?;
+    if !pairs.len().is_multiple_of(2) {
+        return Err(ExprErr::Cause(format!(
+            "invalid structure: {}",
+            Expr::list(form.to_vec())
+        )));
+    }
+    let mut values = def
+        .slots
+        .iter()
+        .map(|slot| match &slot.default {
+            Expr::Number(_) | Expr::String(_) | Expr::True | Expr::Nil => slot.default.clone(),
+            Expr::Symbol(symbol) if symbol.is_keyword() => slot.default.clone(),
+            _ => Expr::Nil,
+        })
+        .collect::<Vec<Expr>>();
+    for pair in pairs.chunks(2) {
+        let name = string_designator(&pair[0])?;
+        let index = def
+            .slots
+            .iter()
+            .position(|slot| slot.name.name == name)
+            .ok_or(ExprErr::Cause(format!(
+                "unknown slot {} of {}",
+                name, def.name.name
+            )))?;
+        values[index] = pair[1].clone();
+    }
+    make_struct(&def, values)
+}
+
+// (sys::%make-struct 'name value*)
+pub fn make_struct_fn(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (name, values) = args
+        .split_first()
+        .ok_or(ExprErr::Cause("expected structure name".to_string()))?;
+    make_struct(&struct_def_arg(name)?, values.to_vec())
+}
+
+// (sys::%struct-ref object 'name index)
+pub fn struct_ref(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [object, name, index] = args else {
+        return Err(ExprErr::Cause("%struct-ref expects three args".to_string()));
+    };
+    let def = struct_def_arg(name)?;
+    let index = index_arg(index)?;
+    if def.list {
+        return Ok(object
+            .to_vec()?
+            .get(def.list_index(index))
+            .cloned()
+            .unwrap_or(Expr::Nil));
+    }
+    let structure = struct_arg(object, &def)?;
+    let value = structure.borrow().values[index].clone();
+    Ok(value)
+}
+
+// (sys::%struct-typep object 'name)
+pub fn struct_typep(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [object, name] = args else {
+        return Err(ExprErr::Cause("%struct-typep expects two args".to_string()));
+    };
+    let def = struct_def_arg(name)?;
+    if def.list {
+        let named = matches!(object.to_vec().ok().as_deref(), Some([head, ..]) if head == &Expr::Symbol(def.name.clone()));
+        return Ok(bool_expr(named));
+    }
+    Ok(bool_expr(typep(object, name)))
+}
+
+// (sys::%copy-struct object 'name) copies the slots, not their values
+pub fn copy_struct(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let [object, name] = args else {
+        return Err(ExprErr::Cause("%copy-struct expects two args".to_string()));
+    };
+    let def = struct_def_arg(name)?;
+    if def.list {
+        return Ok(object.clone());
+    }
+    let structure = struct_arg(object, &def)?;
+    let copy = Structure {
+        def: structure.borrow().def.clone(),
+        values: structure.borrow().values.clone(),
+    };
+    Ok(Expr::Struct(Rc::new(RefCell::new(copy))))
+}
+
+// store into slot `index` of a structure, honoring :read-only and :type
+pub fn set_slot(structure: &StructRef, index: usize, value: Expr) -> Result<(), ExprErr> {
+    let def = structure.borrow().def.clone();
+    let slot = &def.slots[index];
+    if slot.read_only {
+        return Err(ExprErr::Cause(format!(
+            "slot {} of {} is read-only",
+            slot.name.name, def.name.name
+        )));
+    }
+    check_slot_type(slot, &value)?;
+    structure.borrow_mut().values[index] = value;
+    Ok(())
+}
+
+// name or (name default :type type :read-only flag)
+fn parse_slot(desc: &Expr) -> Result<Slot, ExprErr> {
+    let list;
+    let (name, rest) = match desc {
+        Expr::Cons(_) => {
+            list = desc.to_vec()?;
+            (&list[0], &list[1..])
+        }
+        _ => (desc, &[][..]),
+    };
+    let name = match name {
+        Expr::Symbol(name) => name.clone(),
+        _ => return Err(ExprErr::Cause(format!("invalid slot: {}", desc))),
+    };
+    let (default, options) = match rest.split_first() {
+        Some((default, options)) => (default.clone(), options),
+        None => (Expr::Nil, rest),
+    };
+    if !options.len().is_multiple_of(2) {
+        return Err(ExprErr::Cause(format!("invalid slot: {}", desc)));
+    }
+    let mut slot = Slot {
+        name,
+        default,
+        typ: Expr::True,
+        read_only: false,
+    };
+    for pair in options.chunks(2) {
+        match string_designator(&pair[0])?.as_str() {
+            "TYPE" => slot.typ = pair[1].clone(),
+            "READ-ONLY" => slot.read_only = !pair[1].is_nil(),
+            option => return Err(ExprErr::Cause(format!("unknown slot option: {}", option))),
+        }
+    }
+    Ok(slot)
+}
+
+fn sys(name: &str) -> Expr {
+    Expr::Symbol(Symbol::sys(name))
+}
+
+fn quote(expr: Expr) -> Expr {
+    Expr::list(vec![Expr::Symbol(Symbol::cl("QUOTE")), expr])
+}
+
+// The parameters and body of a "by order of arguments" constructor such
+// as (:constructor make-point (x &optional (y 2))). Its parameters
+// initialize the slots of the same name; optional and key parameters
+// without a default take the default of their slot, and the slots not
+// named in the lambda list take theirs.
+fn boa_constructor(
+    def: &StructDef,
+    name: &Expr,
+    lambda_list: &[Expr],
+) -> Result<(Vec<Expr>, Expr), ExprErr> {
+    let slot_default = |var: &SymbolRef| {
+        def.slots
+            .iter()
+            .find(|slot| slot.name.name == var.name)
+            .map(|slot| slot.default.clone())
+    };
+    let mut params = vec![];
+    let mut vars = vec![];
+    let mut defaulted = false;
+    for param in lambda_list {
+        match param {
+            Expr::Symbol(keyword) if keyword.name.starts_with('&') => {
+                defaulted = matches!(keyword.name.as_str(), "&OPTIONAL" | "&KEY");
+                params.push(param.clone());
+            }
+            Expr::Symbol(var) => {
+                match slot_default(var) {
+                    Some(default) if defaulted => {
+                        params.push(Expr::list(vec![param.clone(), default]))
+                    }
+                    _ => params.push(param.clone()),
+                }
+                vars.push(var.clone());
+            }
+            // (var [default [supplied-p]]) or ((keyword var) ...)
+            Expr::Cons(_) => {
+                let var = match param.car()? {
+                    spec @ Expr::Cons(_) => spec.cdr()?.car()?,
+                    var => var,
+                };
+                vars.push(symbol_of(&var)?);
+                params.push(param.clone());
+            }
+            _ => {
+                return Err(ExprErr::Cause(format!(
+                    "invalid constructor parameter: {}",
+                    param
+                )))
+            }
+        }
+    }
+    let mut body = vec![sys("%MAKE-STRUCT"), name.clone()];
+    for slot in &def.slots {
+        match vars.iter().find(|var| var.name == slot.name.name) {
+            Some(var) => body.push(Expr::Symbol(var.clone())),
+            None => body.push(slot.default.clone()),
+        }
+    }
+    Ok((params, Expr::list(body)))
+}
+
+impl Evaluator {
+    // (defstruct name-and-options [doc] slot*)
+    pub fn eval_defstruct(&mut self, args: &[Expr], env: &mut ExprEnv) -> Result<Expr, ExprErr> {
+        let (head, mut slots) = args
+            .split_first()
+            .ok_or(ExprErr::Cause("expected structure name".to_string()))?;
+        if let [Expr::String(_), rest @ ..] = slots {
+            slots = rest;
+        }
+        let list;
+        let (name, options) = match head {
+            Expr::Symbol(name) => (name.clone(), &[][..]),
+            Expr::Cons(_) => {
+                list = head.to_vec()?;
+                match list.split_first() {
+                    Some((Expr::Symbol(name), options)) => (name.clone(), options),
+                    _ => return Err(ExprErr::Cause(format!("invalid structure name: {}", head))),
+                }
+            }
+            _ => return Err(ExprErr::Cause(format!("invalid structure name: {}", head))),
+        };
+
+        let mut conc_name = format!("{}-", name.name);
+        // each constructor with its lambda list if it is a BOA constructor
+        let mut constructors = vec![(Symbol::intern(&format!("MAKE-{}", name.name)), None)];
+        let mut copier = Some(Symbol::intern(&format!("COPY-{}", name.name)));
+        let mut predicate = Some(Symbol::intern(&format!("{}-P", name.name)));
+        let mut include = None;
+        let mut overrides = vec![];
+        let mut list = false;
+        let mut named = false;
+        let mut default_constructor = true;
+        for option in options {
+            let (option, values) = match option {
+                Expr::Cons(_) => {
+                    let list = option.to_vec()?;
+                    (string_designator(&list[0])?, list[1..].to_vec())
+                }
+                _ => (string_designator(option)?, vec![]),
+            };
+            match (option.as_str(), values.as_slice()) {
+                ("CONC-NAME", []) | ("CONC-NAME", [Expr::Nil]) => conc_name = String::new(),
+                ("CONC-NAME", [prefix]) => conc_name = string_designator(prefix)?,
+                ("CONSTRUCTOR", []) => {}
+                ("CONSTRUCTOR", [Expr::Nil]) => default_constructor = false,
+                ("CONSTRUCTOR", [constructor]) | ("CONSTRUCTOR", [constructor, _]) => {
+                    if default_constructor {
+                        constructors.clear();
+                        default_constructor = false;
+                    }
+                    let lambda_list = match values.get(1) {
+                        Some(lambda_list) => Some(lambda_list.to_vec()?),
+                        None => None,
+                    };
+                    constructors.push((symbol_of(constructor)?, lambda_list));
+                }
+                ("COPIER", [Expr::Nil]) => copier = None,
+                ("COPIER", [copy]) => copier = Some(symbol_of(copy)?),
+                ("PREDICATE", [Expr::Nil]) => predicate = None,
+                ("PREDICATE", [pred]) => predicate = Some(symbol_of(pred)?),
+                ("INCLUDE", [parent, slots @ ..]) => {
+                    include = Some(struct_def_arg(parent)?);
+                    overrides = slots.to_vec();
+                }
+                ("TYPE", [typ]) => match string_designator(typ)?.as_str() {
+                    "LIST" => list = true,
+                    typ => {
+                        return Err(ExprErr::Cause(format!(
+                            "unsupported structure type: {}",
+                            typ
+                        )))
+                    }
+                },
+                ("NAMED", []) => named = true,
+                _ => {
+                    return Err(ExprErr::Cause(format!(
+                        "invalid defstruct option: {}",
+                        option
+                    )))
+                }
+            }
+        }
+        if list && !named {
+            predicate = None;
+        }
+
+        // included slots come first; :include may give them new defaults
+        let mut all_slots = vec![];
+        if let Some(parent) = &include {
+            if parent.list != list {
+                return Err(ExprErr::Cause(format!(
+                    "cannot include {} of a different representation",
+                    parent.name.name
+                )));
+            }
+            for slot in &parent.slots {
+                all_slots.push(Slot {
+                    name: slot.name.clone(),
+                    default: slot.default.clone(),
+                    typ: slot.typ.clone(),
+                    read_only: slot.read_only,
+                });
+            }
+            for desc in &overrides {
+                let slot = parse_slot(desc)?;
+                match all_slots.iter_mut().find(|s| s.name == slot.name) {
+                    Some(inherited) => inherited.default = slot.default,
+                    None => {
+                        return Err(ExprErr::Cause(format!(
+                            "{} has no slot {}",
+                            parent.name.name, slot.name.name
+                        )))
+                    }
+                }
+            }
+        }
+        for desc in slots {
+            let slot = parse_slot(desc)?;
+            if all_slots.iter().any(|s| s.name.name == slot.name.name) {
+                return Err(ExprErr::Cause(format!(
+                    "duplicate slot: {}",
+                    slot.name.name
+                )));
+            }
+            all_slots.push(slot);
+        }
+
+        let def = define_struct(StructDef {
+            name: name.clone(),
+            slots: all_slots,
+            include,
+            list,
+            named,
+        });
+        self.define_struct_functions(&def, &conc_name, &constructors, copier, predicate, env)?;
+
+        Ok(Expr::Symbol(name))
+    }
+
+    fn define_struct_functions(
+        &mut self,
+        def: &Rc<StructDef>,
+        conc_name: &str,
+        constructors: &[(SymbolRef, Option<Vec<Expr>>)],
+        copier: Option<SymbolRef>,
+        predicate: Option<SymbolRef>,
+        env: &mut ExprEnv,
+    ) -> Result<(), ExprErr> {
+        let name = quote(Expr::Symbol(def.name.clone()));
+        let object = Expr::Symbol(Symbol::uninterned("OBJECT"));
+        let define = |function: &SymbolRef, params: Vec<Expr>, body: Expr| -> Result<(), ExprErr> {
+            let lambda = make_lambda(&[Expr::list(params), body], env)?;
+            function.set_function(Some(Expr::Lambda(lambda)));
+            Ok(())
+        };
+
+        // (make-name &key (slot default) ...)
+        let mut params = vec![Expr::Symbol(Symbol::cl("&KEY"))];
+        let mut body = vec![sys("%MAKE-STRUCT"), name.clone()];
+        for slot in &def.slots {
+            params.push(Expr::list(vec![
+                Expr::Symbol(slot.name.clone()),
+                slot.default.clone(),
+            ]));
+            body.push(Expr::Symbol(slot.name.clone()));
+        }
+        for (constructor, lambda_list) in constructors {
+            match lambda_list {
+                Some(lambda_list) => {
+                    let (params, body) = boa_constructor(def, &name, lambda_list)?;
+                    define(constructor, params, body)?;
+                }
+                None => define(constructor, params.clone(), Expr::list(body.clone()))?,
+            }
+        }
+
+        if let Some(copier) = copier {
+            let body = vec![sys("%COPY-STRUCT"), object.clone(), name.clone()];
+            define(&copier, vec![object.clone()], Expr::list(body))?;
+        }
+        if let Some(predicate) = predicate {
+            let body = vec![sys("%STRUCT-TYPEP"), object.clone(), name.clone()];
+            define(&predicate, vec![object.clone()], Expr::list(body))?;
+        }
+        for (index, slot) in def.slots.iter().enumerate() {
+            let accessor = Symbol::intern(&format!("{}{}", conc_name, slot.name.name));
+            let body = vec![
+                sys("%STRUCT-REF"),
+                object.clone(),
+                name.clone(),
+                integer_expr(index as i64),
+            ];
+            define(&accessor, vec![object.clone()], Expr::list(body))?;
+            self.setf_expanders
+                .insert(accessor, SetfExpander::Slot(def.clone(), index));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+use crate::ast::{Expr, ExprErr};
+use crate::eval::{bool_expr, single_arg, Evaluator, ExprEnv};
+use crate::math::integer_expr;
+use crate::number::Number;
+use crate::package;
+use std::{
+    cell::{Cell, RefCell},
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+pub type SymbolRef = Rc<Symbol>;
+
+// Symbols are interned once per name and compared by identity. The global
+// value, the global function and the property list live in the symbol.
+pub struct Symbol {
+    pub name: String,
+    // name of the home package, None for uninterned symbols
+    pub package: RefCell<Option<String>>,
+    value: RefCell<Option<Expr>>,
+    function: RefCell<Option<Expr>>,
+    plist: RefCell<Expr>,
+    special: Cell<bool>,
+    constant: Cell<bool>,
+}
+
+impl Symbol {
+    pub fn new(name: &str, package: Option<&str>) -> SymbolRef {
+        Rc::new(Symbol {
+            name: name.to_string(),
+            package: RefCell::new(package.map(|p| p.to_string())),
+            value: RefCell::new(None),
+            function: RefCell::new(None),
+            plist: RefCell::new(Expr::Nil),
+            special: Cell::new(false),
+            constant: Cell::new(false),
+        })
+    }
+
+    // intern in the current package
+    pub fn intern(name: &str) -> SymbolRef {
+        package::current().intern(name)
+    }
+
+    // symbol of the COMMON-LISP package, for names the evaluator refers to
+    pub fn cl(name: &str) -> SymbolRef {
+        package::common_lisp().intern(name)
+    }
+
+    // symbol of the SYSTEM package, for internals of generated code
+    pub fn sys(name: &str) -> SymbolRef {
+        package::system().intern(name)
+    }
+
+    // keywords are constants whose value is the keyword itself
+    pub fn keyword(name: &str) -> SymbolRef {
+        package::keyword().intern(name)
+    }
+
+    pub fn is_keyword(&self) -> bool {
+        self.package.borrow().as_deref() == Some(package::KEYWORD)
+    }
+
+    pub fn uninterned(name: &str) -> SymbolRef {
+        Symbol::new(name, None)
+    }
+
+    // name as printed in the current package: symbols that are not
+    // accessible there are qualified with their home package
+    pub fn qualified_name(self: &SymbolRef) -> String {
+        format!("{}{}", self.package_prefix(), self.name)
+    }
+
+    // ":" for keywords, "PKG:" or "PKG::" for symbols not accessible in the
+    // current package, and nothing otherwise
+    pub fn package_prefix(self: &SymbolRef) -> String {
+        if self.is_keyword() {
+            return ":".to_string();
+        }
+        let home = match self
+            .package
+            .borrow()
+            .as_deref()
+            .and_then(package::find_package)
+        {
+            Some(home) => home,
+            None => return String::new(),
+        };
+        if package::current().find(&self.name).as_ref() == Some(self) {
+            String::new()
+        } else if home.is_external(self) {
+            format!("{}:", home.name)
+        } else {
+            format!("{}::", home.name)
+        }
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        self.value.borrow().clone()
+    }
+
+    // returns the previous value
+    pub fn set_value(&self, value: Option<Expr>) -> Option<Expr> {
+        self.value.replace(value)
+    }
+
+    pub fn function(&self) -> Option<Expr> {
+        self.function.borrow().clone()
+    }
+
+    pub fn set_function(&self, function: Option<Expr>) {
+        self.function.replace(function);
+    }
+
+    pub fn plist(&self) -> Expr {
+        self.plist.borrow().clone()
+    }
+
+    pub fn set_plist(&self, plist: Expr) {
+        self.plist.replace(plist);
+    }
+
+    pub fn is_special(&self) -> bool {
+        self.special.get()
+    }
+
+    pub fn proclaim_special(&self) {
+        self.special.set(true);
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.constant.get()
+    }
+
+    pub fn proclaim_constant(&self) {
+        self.special.set(true);
+        self.constant.set(true);
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::ptr::hash(self, state)
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// the interned NIL and T are represented by Expr::Nil and Expr::True
+pub fn symbol_expr(symbol: SymbolRef) -> Expr {
+    if symbol.package.borrow().as_deref() == Some(package::COMMON_LISP) {
+        match symbol.name.as_str() {
+            "NIL" => return Expr::Nil,
+            "T" => return Expr::True,
+            _ => {}
+        }
+    }
+    Expr::Symbol(symbol)
+}
+
+// symbol of a symbol argument, including T and NIL
+pub fn symbol_of(expr: &Expr) -> Result<SymbolRef, ExprErr> {
+    match expr {
+        Expr::Symbol(symbol) => Ok(symbol.clone()),
+        Expr::Nil => Ok(Symbol::cl("NIL")),
+        Expr::True => Ok(Symbol::cl("T")),
+        _ => Err(ExprErr::Cause(format!("{} is not symbol", expr))),
+    }
+}
+
+fn string_arg(name: &str, args: &[Expr]) -> Result<String, ExprErr> {
+    match single_arg(name, args)? {
+        Expr::String(s) => Ok(s.borrow().clone()),
+        arg => Err(ExprErr::Cause(format!("{} is not string", arg))),
+    }
+}
+
+pub fn symbol_function(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let symbol = symbol_of(single_arg("symbol-function", args)?)?;
+    symbol.function().ok_or(ExprErr::Cause(format!(
+        "undefined function: {}",
+        symbol.name
+    )))
+}
+
+pub fn fboundp(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let symbol = symbol_of(single_arg("fboundp", args)?)?;
+    Ok(bool_expr(symbol.function().is_some()))
+}
+
+pub fn fmakunbound(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let arg = single_arg("fmakunbound", args)?;
+    symbol_of(arg)?.set_function(None);
+    Ok(arg.clone())
+}
+
+pub fn symbol_value(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let arg = single_arg("symbol-value", args)?;
+    match arg {
+        Expr::Nil | Expr::True => Ok(arg.clone()),
+        _ => {
+            let symbol = symbol_of(arg)?;
+            symbol
+                .value()
+                .ok_or(ExprErr::Cause(format!("unbound variable: {}", symbol.name)))
+        }
+    }
+}
+
+pub fn boundp(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let arg = single_arg("boundp", args)?;
+    match arg {
+        Expr::Nil | Expr::True => Ok(Expr::True),
+        _ => Ok(bool_expr(symbol_of(arg)?.value().is_some())),
+    }
+}
+
+pub fn makunbound(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let arg = single_arg("makunbound", args)?;
+    let symbol = symbol_of(arg)?;
+    if symbol.is_constant() {
+        return Err(ExprErr::Cause(format!(
+            "cannot unbind constant: {}",
+            symbol.name
+        )));
+    }
+    symbol.set_value(None);
+    Ok(arg.clone())
+}
+
+pub fn symbol_name(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let symbol = symbol_of(single_arg("symbol-name", args)?)?;
+    Ok(Expr::string(symbol.name.clone()))
+}
+
+pub fn symbol_package(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let symbol = symbol_of(single_arg("symbol-package", args)?)?;
+    let package = symbol.package.borrow().clone();
+    Ok(package
+        .and_then(|name| package::find_package(&name))
+        .map(Expr::Package)
+        .unwrap_or(Expr::Nil))
+}
+
+pub fn symbol_plist(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let symbol = symbol_of(single_arg("symbol-plist", args)?)?;
+    Ok(symbol.plist())
+}
+
+// value following `indicator` in a property list
+pub fn plist_get(plist: &Expr, indicator: &Expr) -> Result<Option<Expr>, ExprErr> {
+    Ok(plist
+        .to_vec()?
+        .chunks(2)
+        .find(|pair| &pair[0] == indicator)
+        .and_then(|pair| pair.get(1).cloned()))
+}
+
+// (get symbol indicator [default])
+pub fn get(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (symbol, indicator, default) = match args {
+        [symbol, indicator] => (symbol, indicator, Expr::Nil),
+        [symbol, indicator, default] => (symbol, indicator, default.clone()),
+        _ => return Err(ExprErr::Cause("get expects 2 or 3 args".to_string())),
+    };
+    Ok(plist_get(&symbol_of(symbol)?.plist(), indicator)?.unwrap_or(default))
+}
+
+// (getf plist indicator [default])
+pub fn getf(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let (plist, indicator, default) = match args {
+        [plist, indicator] => (plist, indicator, Expr::Nil),
+        [plist, indicator, default] => (plist, indicator, default.clone()),
+        _ => return Err(ExprErr::Cause("getf expects 2 or 3 args".to_string())),
+    };
+    Ok(plist_get(plist, indicator)?.unwrap_or(default))
+}
+
+pub fn keywordp(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    match single_arg("keywordp", args)? {
+        Expr::Symbol(symbol) => Ok(bool_expr(symbol.is_keyword())),
+        _ => Ok(Expr::Nil),
+    }
+}
+
+// (make-symbol "NAME") creates a fresh uninterned symbol
+pub fn make_symbol(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let name = string_arg("make-symbol", args)?;
+    Ok(Expr::Symbol(Symbol::uninterned(&name)))
+}
+
+// (gensym [prefix]) names the symbol after *gensym-counter*
+pub fn gensym(_: &mut Evaluator, args: &[Expr], _: &mut ExprEnv) -> Result<Expr, ExprErr> {
+    let counter = Symbol::cl("*GENSYM-COUNTER*");
+    let count = match counter.value() {
+        Some(Expr::Number(Number::Integer(n))) => n,
+        _ => 1,
+    };
+    let (prefix, count) = match args {
+        [] => ("G".to_string(), count),
+        [Expr::String(prefix)] => (prefix.borrow().clone(), count),
+        [Expr::Number(Number::Integer(n))] => ("G".to_string(), *n),
+        _ => return Err(ExprErr::Cause("invalid gensym argument".to_string())),
+    };
+    if args.len() != 1 || matches!(args[0], Expr::String(_)) {
+        counter.set_value(Some(integer_expr(count + 1)));
+    }
+    Ok(Expr::Symbol(Symbol::uninterned(&format!(
+        "{}{}",
+        prefix, count
+    ))))
+}
@@ -1,3 +1,5 @@
+use crate::number::Number;
+
 #[derive(Debug, PartialEq)]
 pub enum Token {
     Plus,
@@ -6,13 +8,35 @@ pub enum Token {
     Slash,
     Lparen,
     Rparen,
+    // the dot of a dotted list
+    Dot,
+    Quote,
+    Function,
+    Struct,
+    Complex,
+    Pathname,
+    // #+ or #- followed by a feature expression
+    Feature(bool),
+    ReadEval,
+    Vector,
+    // #nA with the rank
+    Array(usize),
+    // #n= labelling the next object and #n# referring to it
+    Label(usize),
+    Reference(usize),
     Eof,
     True,
     Nil,
     Illegal(String),
-    Number(f64),
+    Number(Number),
+    Char(char),
     String(String),
     Literal(String),
+    Keyword(String),
+    // #:name
+    Uninterned(String),
+    // package, name, and whether written with :: for an internal symbol
+    Qualified(String, String, bool),
 }
 
 impl std::fmt::Display for Token {
@@ -24,13 +48,31 @@ impl std::fmt::Display for Token {
             Self::Slash => "/".to_string(),
             Self::Lparen => "(".to_string(),
             Self::Rparen => ")".to_string(),
+            Self::Dot => ".".to_string(),
+            Self::Quote => "'".to_string(),
+            Self::Function => "#'".to_string(),
+            Self::Struct => "#S".to_string(),
+            Self::Complex => "#C".to_string(),
+            Self::Pathname => "#P".to_string(),
+            Self::Feature(true) => "#+".to_string(),
+            Self::Feature(false) => "#-".to_string(),
+            Self::ReadEval => "#.".to_string(),
+            Self::Vector => "#(".to_string(),
+            Self::Array(rank) => format!("#{}A", rank),
+            Self::Label(label) => format!("#{}=", label),
+            Self::Reference(label) => format!("#{}#", label),
             Self::Eof => "EOF".to_string(),
             Self::True => "T".to_string(),
             Self::Nil => "NIL".to_string(),
             Self::Illegal(s) => format!("ILLEGAL({})", s),
             Self::Number(num) => num.to_string(),
+            Self::Char(ch) => format!("#\\{}", ch),
             Self::String(s) => String::from(s),
             Self::Literal(s) => String::from(s),
+            Self::Keyword(s) => format!(":{}", s),
+            Self::Uninterned(s) => format!("#:{}", s),
+            Self::Qualified(package, name, true) => format!("{}::{}", package, name),
+            Self::Qualified(package, name, false) => format!("{}:{}", package, name),
         };
 
         write!(f, "{}", s)